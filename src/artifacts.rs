@@ -0,0 +1,127 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use tracing::{info, warn};
+
+use crate::config::ArtifactConfig;
+
+/// Copies each `[[artifacts]]` entry's matches out of the dev container into
+/// its host directory, returning how many paths were copied. Runs at
+/// teardown while the container is still up; a failing entry is logged and
+/// the rest are still copied.
+pub fn collect(container_id: &str, artifacts: &[ArtifactConfig], project_dir: &Path) -> usize {
+    let mut copied = 0;
+    for artifact in artifacts {
+        match collect_one(container_id, artifact, project_dir) {
+            Ok(0) => warn!(from = %artifact.from, "no files matched artifact"),
+            Ok(n) => copied += n,
+            Err(e) => warn!(from = %artifact.from, error = %e, "failed to copy artifact"),
+        }
+    }
+    copied
+}
+
+fn collect_one(container_id: &str, artifact: &ArtifactConfig, project_dir: &Path) -> Result<usize> {
+    let matches = list_matches(container_id, &artifact.from)?;
+    if matches.is_empty() {
+        return Ok(0);
+    }
+
+    let host_dir = artifact.host_dir(project_dir);
+    std::fs::create_dir_all(&host_dir)
+        .with_context(|| format!("failed to create {}", host_dir.display()))?;
+    for path in &matches {
+        let output = Command::new("docker")
+            .arg("cp")
+            .arg(format!("{container_id}:{path}"))
+            .arg(&host_dir)
+            .stdin(Stdio::null())
+            .output()
+            .context("failed to spawn docker cp")?;
+        if !output.status.success() {
+            bail!(
+                "docker cp {path}: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        info!(path, to = %host_dir.display(), "copied artifact");
+    }
+    Ok(matches.len())
+}
+
+/// Expands `pattern` with the container's `sh`, returning absolute paths
+/// (`docker cp` resolves relative ones against `/`, not the working
+/// directory).
+fn list_matches(container_id: &str, pattern: &str) -> Result<Vec<String>> {
+    let output = Command::new("docker")
+        .args(["exec", container_id, "sh", "-c", &list_script(pattern)])
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to spawn docker exec")?;
+    if !output.status.success() {
+        bail!(
+            "failed to expand `{pattern}`: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Shell script printing each existing path `pattern` matches, one per line,
+/// made absolute against the working directory. An unmatched glob stays
+/// literal in `sh`, so the existence check drops it.
+fn list_script(pattern: &str) -> String {
+    format!(
+        "for f in {pattern}; do\n\
+         \x20 [ -e \"$f\" ] || continue\n\
+         \x20 case \"$f\" in /*) printf '%s\\n' \"$f\" ;; *) printf '%s\\n' \"$PWD/$f\" ;; esac\n\
+         done\n"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_script(dir: &Path, pattern: &str) -> Vec<String> {
+        let output = Command::new("sh")
+            .args(["-c", &list_script(pattern)])
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn list_script_expands_globs_to_absolute_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_path = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(dir_path.join("coverage")).unwrap();
+        std::fs::write(dir_path.join("coverage/a.xml"), "").unwrap();
+        std::fs::write(dir_path.join("coverage/b.xml"), "").unwrap();
+        std::fs::write(dir_path.join("coverage/c.txt"), "").unwrap();
+
+        let base = dir_path.display();
+        assert_eq!(
+            run_script(&dir_path, "coverage/*.xml"),
+            vec![
+                format!("{base}/coverage/a.xml"),
+                format!("{base}/coverage/b.xml")
+            ]
+        );
+        assert_eq!(
+            run_script(&dir_path, &format!("{base}/coverage")),
+            vec![format!("{base}/coverage")]
+        );
+        assert!(run_script(&dir_path, "reports/*.html").is_empty());
+    }
+}
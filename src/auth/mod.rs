@@ -1,40 +1,187 @@
 #[cfg(target_os = "macos")]
 pub mod keychain;
 
-use anyhow::Result;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, anyhow};
 use serde_json::{Map, Value};
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
+
+use crate::config::{AuthConfig, AuthProvider};
+use crate::error::Error;
+use crate::redact;
 
 const ENV_VAR_NAME: &str = "CLAUDE_CODE_OAUTH_TOKEN";
 
-/// Resolves the Claude Code OAuth token using platform-specific strategies.
-///
-/// Resolution order:
-/// 1. Check host environment variable `CLAUDE_CODE_OAUTH_TOKEN`
-/// 2. On macOS, attempt to extract from the Keychain
+/// Cheapest authenticated endpoint: lists models, costs no tokens.
+const VERIFY_URL: &str = "https://api.anthropic.com/v1/models?limit=1";
+
+/// Seconds `verify_oauth_token` waits for the API.
+const VERIFY_TIMEOUT_SECS: &str = "10";
+
+/// Resolves the Claude Code OAuth token from the providers in
+/// `auth.providers`, in order (by default the host environment variable
+/// `CLAUDE_CODE_OAUTH_TOKEN`, then the macOS Keychain). The Keychain is
+/// skipped unless `use_keychain`; in CI its prompt would block forever.
 ///
-/// Returns `Ok(None)` if no token is available (warning logged, not an error).
-pub fn resolve_oauth_token() -> Result<Option<String>> {
-    // Strategy 1: Check environment variable
-    if let Ok(token) = std::env::var(ENV_VAR_NAME) {
-        if !token.is_empty() {
-            info!("OAuth token found in environment variable");
-            return Ok(Some(token));
+/// Logs which provider supplied the token, never the token itself. Returns
+/// `Ok(None)` if no token is available (warning logged, not an error).
+pub fn resolve_oauth_token(auth: &AuthConfig, use_keychain: bool) -> Result<Option<String>> {
+    for provider in auth.providers() {
+        let token = match provider {
+            AuthProvider::Env => std::env::var(ENV_VAR_NAME).ok(),
+            AuthProvider::Keychain => from_keychain(use_keychain)?,
+            AuthProvider::OnePassword => from_onepassword(auth.onepassword.as_deref()),
+            AuthProvider::File => credentials_path().and_then(|path| from_file(&path)),
+        };
+        match token.filter(|t| !t.is_empty()) {
+            Some(token) => {
+                info!(provider = provider.name(), "OAuth token resolved");
+                redact::register(&token);
+                return Ok(Some(token));
+            }
+            None => debug!(provider = provider.name(), "no OAuth token from provider"),
         }
     }
 
-    // Strategy 2: macOS Keychain
-    #[cfg(target_os = "macos")]
-    {
-        if let Some(token) = keychain::get_oauth_token()? {
-            return Ok(Some(token));
-        }
+    warn!("no OAuth token found — Claude Code authentication may fail inside the container");
+    Ok(None)
+}
+
+#[cfg(target_os = "macos")]
+fn from_keychain(use_keychain: bool) -> Result<Option<String>> {
+    if !use_keychain {
+        return Ok(None);
     }
+    keychain::get_oauth_token()
+}
 
-    warn!("no OAuth token found — Claude Code authentication may fail inside the container");
+#[cfg(not(target_os = "macos"))]
+fn from_keychain(_use_keychain: bool) -> Result<Option<String>> {
     Ok(None)
 }
 
+/// Reads `reference` with the 1Password CLI, which prompts to unlock the
+/// app or uses `OP_SERVICE_ACCOUNT_TOKEN`.
+fn from_onepassword(reference: Option<&str>) -> Option<String> {
+    let Some(reference) = reference else {
+        warn!("auth.providers lists 1password but auth.onepassword is not set");
+        return None;
+    };
+    match Command::new("op").args(["read", reference]).output() {
+        Ok(out) if out.status.success() => {
+            let token = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            Some(token)
+        }
+        Ok(out) => {
+            let stderr = String::from_utf8_lossy(&out.stderr);
+            warn!("op read {reference} failed: {}", stderr.trim());
+            None
+        }
+        Err(e) => {
+            warn!("failed to run the 1Password CLI (op): {e}");
+            None
+        }
+    }
+}
+
+/// `~/.config/bubble-bot/credentials` (`~/Library/Application
+/// Support/bubble-bot/credentials` on macOS), read by the `file` provider.
+pub fn credentials_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("bubble-bot").join("credentials"))
+}
+
+/// The token in a credentials file: its first line that is neither blank
+/// nor a `#` comment. Warns if others can read the file, as `ssh` does for
+/// keys.
+fn from_file(path: &Path) -> Option<String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("failed to read {}: {e}", path.display());
+            return None;
+        }
+    };
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if metadata.permissions().mode() & 0o077 != 0 {
+            warn!(
+                "{} is readable by other users; run `chmod 600` on it",
+                path.display()
+            );
+        }
+    }
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+}
+
+/// Checks `token` against the Anthropic API (`auth.verify`), failing with
+/// [`Error::AuthMissing`] if the API rejects it, so an expired token shows
+/// up before the session starts rather than inside it. When the API can't
+/// be reached or answers with anything else, only a warning is logged.
+pub fn verify_oauth_token(token: &str) -> Result<()> {
+    match api_status(token) {
+        Ok(status) => verdict(status),
+        Err(e) => {
+            warn!(error = %e, "couldn't check the OAuth token; continuing");
+            Ok(())
+        }
+    }
+}
+
+/// What a status from [`VERIFY_URL`] says about the token.
+fn verdict(status: u16) -> Result<()> {
+    match status {
+        200..=299 => {
+            info!("OAuth token accepted by the API");
+            Ok(())
+        }
+        401 | 403 => Err(Error::AuthMissing(anyhow!(
+            "the API rejected the OAuth token (HTTP {status}); re-authenticate on the host with `claude setup-token` or `claude /login`"
+        ))
+        .into()),
+        _ => {
+            warn!(status, "couldn't check the OAuth token; continuing");
+            Ok(())
+        }
+    }
+}
+
+/// The HTTP status of a GET of [`VERIFY_URL`] with `token`. Headers go to
+/// `curl` on stdin, keeping the token out of its arguments.
+fn api_status(token: &str) -> Result<u16> {
+    let mut child = Command::new("curl")
+        .args(["-sS", "-m", VERIFY_TIMEOUT_SECS, "-o", "/dev/null"])
+        .args(["-w", "%{http_code}", "-H", "@-", VERIFY_URL])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run curl")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        write!(
+            stdin,
+            "Authorization: Bearer {token}\nanthropic-version: 2023-06-01\nanthropic-beta: oauth-2025-04-20\n"
+        )?;
+    }
+    let output = child
+        .wait_with_output()
+        .context("failed to wait for curl")?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let code = String::from_utf8_lossy(&output.stdout);
+    code.trim()
+        .parse()
+        .with_context(|| format!("unexpected curl output `{}`", code.trim()))
+}
+
 /// Builds the `.claude.json` config to write into the container.
 ///
 /// Reads `~/.claude.json` from the host to extract `oauthAccount`.
@@ -77,7 +224,38 @@ mod tests {
     #[test]
     fn resolve_returns_ok() {
         // Should never panic or return Err, regardless of environment state
-        let result = resolve_oauth_token();
+        let result = resolve_oauth_token(&AuthConfig::default(), true);
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn only_a_rejection_fails_the_check() {
+        assert!(verdict(200).is_ok());
+        let err = verdict(401).unwrap_err();
+        assert_eq!(crate::error::exit_code(&err), 84);
+        assert!(
+            err.to_string().contains("re-authenticate on the host"),
+            "{err}"
+        );
+        // Outages and rate limits say nothing about the token
+        assert!(verdict(429).is_ok());
+        assert!(verdict(529).is_ok());
+    }
+
+    #[test]
+    fn credentials_file_holds_the_token_on_its_first_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials");
+        assert_eq!(from_file(&path), None);
+
+        std::fs::write(
+            &path,
+            "# from `claude setup-token`\n\n  sk-ant-oat01-abc  \n",
+        )
+        .unwrap();
+        assert_eq!(from_file(&path).as_deref(), Some("sk-ant-oat01-abc"));
+
+        // Without a reference the 1password provider has nothing to read
+        assert_eq!(from_onepassword(None), None);
+    }
 }
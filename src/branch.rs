@@ -0,0 +1,87 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+/// Branch name for an agent run with `agent.auto_branch` and no `--branch`:
+/// `bubble-bot/<agent>-<unix seconds>`, unique per run.
+pub fn auto_name(agent: &str, now: SystemTime) -> String {
+    let secs = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    format!("bubble-bot/{agent}-{secs}")
+}
+
+/// Creates branch `name` at `HEAD` of the repository at `repo` and checks it
+/// out. Uncommitted changes carry over to the new branch. Fails if the
+/// branch already exists, so a run never reuses (and edits) an existing one.
+pub fn checkout_new(repo: &Path, name: &str) -> Result<()> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["checkout", "-q", "-b", name])
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to spawn git")?;
+    if !output.status.success() {
+        bail!(
+            "failed to create branch {name}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    info!(branch = name, repo = %repo.display(), "checked out agent branch");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn git(repo: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    #[test]
+    fn auto_names_are_namespaced_by_agent() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_760_000_000);
+        assert_eq!(auto_name("claude", now), "bubble-bot/claude-1760000000");
+        assert_eq!(auto_name("chief", now), "bubble-bot/chief-1760000000");
+    }
+
+    #[test]
+    fn checks_out_a_new_branch_only_once() {
+        let dir = tempfile::tempdir().unwrap();
+        git(dir.path(), &["init", "-q"]);
+        git(
+            dir.path(),
+            &["commit", "-q", "--allow-empty", "-m", "initial"],
+        );
+
+        checkout_new(dir.path(), "agent/fix-tests").unwrap();
+        assert_eq!(
+            git(dir.path(), &["rev-parse", "--abbrev-ref", "HEAD"]),
+            "agent/fix-tests"
+        );
+
+        let err = checkout_new(dir.path(), "agent/fix-tests")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("failed to create branch agent/fix-tests"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+
+use crate::config::ArtifactConfig;
+
+/// Where `chief --tasks` mounts the task file's directory, read-only. Kept
+/// out of the home directory, where Docker would create the parent
+/// directories root-owned.
+pub const TASKS_DIR: &str = "/workspace-tasks";
+
+/// Container directory whose contents `chief --results` copies back to the
+/// host. Exported to Chief as `BUBBLE_BOT_RESULTS`, so a task file can tell
+/// it where to write reports.
+pub const RESULTS_DIR: &str = "/tmp/bubble-bot-results";
+
+/// A task file on the host and its path in the container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskFile {
+    /// Bind of the file's directory, as `host:container:ro`. The directory
+    /// is mounted rather than the file so files it references come along.
+    pub bind: String,
+    pub container_path: String,
+}
+
+impl TaskFile {
+    /// Resolves `path` (relative to the current directory) to a bind.
+    pub fn resolve(path: &Path) -> Result<Self> {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("task file {} not found", path.display()))?;
+        if !path.is_file() {
+            bail!("task file {} is not a file", path.display());
+        }
+        let dir = path.parent().context("task file has no parent directory")?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        Ok(Self {
+            bind: format!("{}:{TASKS_DIR}:ro", dir.display()),
+            container_path: format!("{TASKS_DIR}/{name}"),
+        })
+    }
+}
+
+/// The Chief command line: `chief [--tasks PATH] ARGS...`, started by a
+/// wrapper that creates and exports [`RESULTS_DIR`].
+pub fn command(tasks: Option<&TaskFile>, args: &[String]) -> Vec<String> {
+    let mut cmd = vec![
+        "env".to_string(),
+        format!("BUBBLE_BOT_RESULTS={RESULTS_DIR}"),
+        "sh".to_string(),
+        "-c".to_string(),
+        "mkdir -p \"$BUBBLE_BOT_RESULTS\" && exec chief \"$@\"".to_string(),
+        "chief".to_string(),
+    ];
+    if let Some(tasks) = tasks {
+        cmd.push("--tasks".to_string());
+        cmd.push(tasks.container_path.clone());
+    }
+    cmd.extend(args.iter().cloned());
+    cmd
+}
+
+/// The artifact copying everything in [`RESULTS_DIR`] into `dir` at
+/// teardown, whether Chief succeeded or not.
+pub fn results_artifact(dir: &Path) -> ArtifactConfig {
+    ArtifactConfig {
+        from: format!("{RESULTS_DIR}/*"),
+        to: dir.to_string_lossy().to_string(),
+    }
+}
+
+/// Absolute form of `--results`, relative to the current directory like
+/// `--tasks` (artifact `to` paths are otherwise under the project root).
+pub fn results_dir(dir: &Path) -> Result<PathBuf> {
+    if dir.is_absolute() {
+        return Ok(dir.to_path_buf());
+    }
+    Ok(std::env::current_dir()?.join(dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_file_directory_is_mounted_read_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let host = dir.path().canonicalize().unwrap();
+        std::fs::write(host.join("tasks.yaml"), "tasks: []\n").unwrap();
+
+        let tasks = TaskFile::resolve(&host.join("tasks.yaml")).unwrap();
+        assert_eq!(
+            tasks.bind,
+            format!("{}:/workspace-tasks:ro", host.display())
+        );
+        assert_eq!(tasks.container_path, "/workspace-tasks/tasks.yaml");
+        assert_eq!(
+            command(Some(&tasks), &["--verbose".to_string()]),
+            vec![
+                "env",
+                "BUBBLE_BOT_RESULTS=/tmp/bubble-bot-results",
+                "sh",
+                "-c",
+                "mkdir -p \"$BUBBLE_BOT_RESULTS\" && exec chief \"$@\"",
+                "chief",
+                "--tasks",
+                "/workspace-tasks/tasks.yaml",
+                "--verbose",
+            ]
+        );
+
+        let err = TaskFile::resolve(&host.join("missing.yaml")).unwrap_err();
+        assert!(err.to_string().contains("not found"), "{err}");
+        let err = TaskFile::resolve(&host).unwrap_err();
+        assert!(err.to_string().contains("not a file"), "{err}");
+    }
+
+    #[test]
+    fn results_are_copied_from_the_results_dir() {
+        let artifact = results_artifact(Path::new("/tmp/out"));
+        assert_eq!(artifact.from, "/tmp/bubble-bot-results/*");
+        assert_eq!(artifact.to, "/tmp/out");
+    }
+}
@@ -0,0 +1,47 @@
+/// Whether bubble-bot is running under a CI system.
+pub fn detect() -> bool {
+    detect_from(|name| std::env::var(name).ok())
+}
+
+/// Detects CI from environment lookups: `CI=true` (set by most CI systems,
+/// including GitLab, CircleCI, and Buildkite) or GitHub Actions' own vars.
+fn detect_from(var: impl Fn(&str) -> Option<String>) -> bool {
+    let truthy = |name: &str| {
+        var(name).is_some_and(|value| value.eq_ignore_ascii_case("true") || value == "1")
+    };
+    truthy("CI") || truthy("GITHUB_ACTIONS")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn detect_with(vars: &[(&str, &str)]) -> bool {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        detect_from(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn ci_true_is_detected() {
+        assert!(detect_with(&[("CI", "true")]));
+        assert!(detect_with(&[("CI", "1")]));
+        assert!(detect_with(&[("CI", "TRUE")]));
+    }
+
+    #[test]
+    fn github_actions_is_detected() {
+        assert!(detect_with(&[("GITHUB_ACTIONS", "true")]));
+    }
+
+    #[test]
+    fn local_shell_is_not_ci() {
+        assert!(!detect_with(&[]));
+        assert!(!detect_with(&[("CI", "false")]));
+        assert!(!detect_with(&[("CI", "")]));
+    }
+}
@@ -1,11 +1,47 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
 use clap::{Args, Parser, Subcommand};
 
+use crate::output::ColorChoice;
+use crate::plan::PlanFormat;
+
 #[derive(Debug, Parser)]
 #[command(name = "bubble-bot", about = "Ephemeral Docker dev containers")]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Command>,
 
+    /// Use this config file instead of `.bubble-bot.toml` in the current directory
+    #[arg(long, env = "BUBBLE_BOT_CONFIG", value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Project directory to mount and name the session after, instead of
+    /// the one found from the current directory
+    #[arg(long, env = "BUBBLE_BOT_PROJECT_DIR", value_name = "PATH")]
+    pub project_dir: Option<PathBuf>,
+
+    /// Print passwords, tokens, and keys in `config`, `--dry-run`, and logs
+    /// instead of masking them
+    #[arg(long, global = true)]
+    pub show_secrets: bool,
+
+    /// When to color output: `auto` (on a terminal, unless `NO_COLOR` is
+    /// set), `always`, or `never`
+    #[arg(long, global = true, value_name = "WHEN", default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// Export spans for each phase (config load, render, build, network,
+    /// services, session, cleanup) to this OTLP/HTTP collector, e.g.
+    /// `http://localhost:4318`
+    #[arg(
+        long,
+        global = true,
+        env = "BUBBLE_BOT_OTEL_ENDPOINT",
+        value_name = "URL"
+    )]
+    pub otel_endpoint: Option<String>,
+
     #[command(flatten)]
     pub runtime: RuntimeFlags,
 
@@ -30,6 +66,19 @@ pub enum Command {
 
     /// Run Claude Code inside the container
     Claude {
+        /// Stop the agent and clean up after this long (e.g. `30m`, `1h30m`)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        max_duration: Option<Duration>,
+
+        /// Run headless: write Claude Code's stdout to this host file instead
+        /// of the terminal and exit with its exit code
+        #[arg(long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Check out a new git branch with this name before the agent starts
+        #[arg(long, value_name = "NAME")]
+        branch: Option<String>,
+
         /// Arguments passed to Claude Code
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -37,6 +86,23 @@ pub enum Command {
 
     /// Run Chief (autonomous Claude Code task runner) inside the container
     Chief {
+        /// Stop the agent and clean up after this long (e.g. `30m`, `1h30m`)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+        max_duration: Option<Duration>,
+
+        /// Check out a new git branch with this name before the agent starts
+        #[arg(long, value_name = "NAME")]
+        branch: Option<String>,
+
+        /// Mount this task file read-only and pass it to Chief as `--tasks`
+        #[arg(long, value_name = "FILE")]
+        tasks: Option<PathBuf>,
+
+        /// Copy what Chief writes to `$BUBBLE_BOT_RESULTS` into this host
+        /// directory when the session ends
+        #[arg(long, value_name = "DIR")]
+        results: Option<PathBuf>,
+
         /// Arguments passed to Chief
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
@@ -44,16 +110,84 @@ pub enum Command {
 
     /// Run a command inside the container and exit
     Exec {
+        /// Run the command in this directory; relative paths are under the
+        /// container's working directory
+        #[arg(short = 'w', long, value_name = "PATH")]
+        workdir: Option<String>,
+
+        /// Set an environment variable for the command (repeatable)
+        #[arg(short = 'e', long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+        env: Vec<String>,
+
+        /// Command and arguments to run
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        cmd: Vec<String>,
+    },
+
+    /// Run a command for CI: no TTY, no host config files, plain log output,
+    /// and the command's exit code
+    Run {
         /// Command and arguments to run
         #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
         cmd: Vec<String>,
     },
 
+    /// Open a database client in a running service container
+    Db {
+        /// Service to connect to; optional when only one is enabled
+        #[arg(value_parser = ["mysql", "postgres", "redis"])]
+        service: Option<String>,
+    },
+
+    /// Show how to connect to the running services: hostname, port,
+    /// database, username, and password (masked without --show-secrets)
+    Info,
+
+    /// List running sessions across all projects
+    Ps,
+
     /// Build the container image without starting a container
     Build,
 
+    /// Compare what a session would run with what runs now, listing the
+    /// image, network, and containers it would create, replace, or remove
+    Plan,
+
     /// Show the resolved configuration
-    Config,
+    Config {
+        /// Validate the config and exit non-zero if any problems are found
+        #[arg(long, conflicts_with = "origin")]
+        check: bool,
+
+        /// Annotate each resolved value with the layer that set it
+        #[arg(long)]
+        origin: bool,
+
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
+
+    /// List Bubble Bot images with the project and config that built them
+    Images,
+
+    /// List, remove, back up, and restore Bubble Bot's named volumes
+    Volumes {
+        #[command(subcommand)]
+        action: Option<VolumesAction>,
+    },
+
+    /// Save the project's image to a compressed tarball, for machines that
+    /// can't build or pull it
+    Export {
+        #[command(subcommand)]
+        action: ExportAction,
+    },
+
+    /// Load images from a tarball written by `export`
+    Import {
+        #[command(subcommand)]
+        action: ImportAction,
+    },
 
     /// Remove Bubble Bot images, networks, and optionally volumes
     Clean {
@@ -61,6 +195,127 @@ pub enum Command {
         #[arg(long)]
         volumes: bool,
     },
+
+    /// Replace this binary with the latest GitHub release
+    SelfUpdate {
+        /// Only check: exit 0 if up to date, 10 if a newer release exists
+        #[arg(long)]
+        check: bool,
+    },
+}
+
+impl Command {
+    /// The subcommand as typed, e.g. `shell`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Shell => "shell",
+            Command::Claude { .. } => "claude",
+            Command::Chief { .. } => "chief",
+            Command::Exec { .. } => "exec",
+            Command::Run { .. } => "run",
+            Command::Db { .. } => "db",
+            Command::Info => "info",
+            Command::Ps => "ps",
+            Command::Build => "build",
+            Command::Plan => "plan",
+            Command::Config { .. } => "config",
+            Command::Images => "images",
+            Command::Volumes { .. } => "volumes",
+            Command::Export { .. } => "export",
+            Command::Import { .. } => "import",
+            Command::Clean { .. } => "clean",
+            Command::SelfUpdate { .. } => "self-update",
+        }
+    }
+}
+
+/// Edits to a config file, preserving its comments and formatting.
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigAction {
+    /// Set a value, e.g. `config set runtimes.php 8.3`
+    Set {
+        /// Dotted key path (e.g. `services.mysql.version`)
+        key: String,
+
+        /// Value; parsed as TOML (`true`, `["a", "b"]`) when the key expects it
+        value: String,
+
+        /// Edit the global config instead of the project config
+        #[arg(long)]
+        global: bool,
+    },
+
+    /// Remove a value
+    Unset {
+        /// Dotted key path (e.g. `services.mysql.version`)
+        key: String,
+
+        /// Edit the global config instead of the project config
+        #[arg(long)]
+        global: bool,
+    },
+}
+
+/// `bubble-bot volumes` actions; `list` is the default.
+#[derive(Debug, Clone, Subcommand)]
+pub enum VolumesAction {
+    /// List volumes with the project and contents each belongs to
+    List,
+
+    /// Remove a volume
+    Rm {
+        /// Volume name, as shown by `volumes list`
+        name: String,
+    },
+
+    /// Archive a database's data volume into a tarball
+    Backup {
+        #[arg(value_parser = ["mysql", "postgres"])]
+        service: String,
+
+        /// File to write (a `.tar.gz`)
+        file: PathBuf,
+    },
+
+    /// Replace a database's data volume with a tarball from `backup`; the
+    /// next session starts with it
+    Restore {
+        #[arg(value_parser = ["mysql", "postgres"])]
+        service: String,
+
+        /// Tarball written by `volumes backup`
+        file: PathBuf,
+    },
+}
+
+/// `bubble-bot export` actions.
+#[derive(Debug, Clone, Subcommand)]
+pub enum ExportAction {
+    /// Save the image a session would use (it must be built)
+    Image {
+        /// File to write; `.tar.zst`, `.tar.gz`, or `.tar` picks the
+        /// compression
+        #[arg(short, long, value_name = "FILE")]
+        output: PathBuf,
+
+        /// Also save the enabled services' images (and the readiness probe's)
+        #[arg(long)]
+        services: bool,
+
+        /// Save the image `chief` sessions use, which adds Chief
+        #[arg(long)]
+        chief: bool,
+    },
+}
+
+/// `bubble-bot import` actions.
+#[derive(Debug, Clone, Subcommand)]
+pub enum ImportAction {
+    /// Load the images in a tarball from `export image`
+    Image {
+        /// Tarball written by `export image` (compression is detected)
+        file: PathBuf,
+    },
 }
 
 #[derive(Debug, Clone, Args)]
@@ -95,6 +350,10 @@ pub struct ServiceFlags {
     /// Start a PostgreSQL service container (optional version, default 16)
     #[arg(long = "with-postgres", value_name = "VERSION", num_args = 0..=1, default_missing_value = "16")]
     pub postgres: Option<String>,
+
+    /// Open the session without waiting for services to be ready
+    #[arg(long)]
+    pub no_wait: bool,
 }
 
 #[derive(Debug, Clone, Args)]
@@ -111,6 +370,11 @@ pub struct ContainerFlags {
     #[arg(long, default_value = "bash")]
     pub shell: String,
 
+    /// Build and run the dev container for this platform (e.g. linux/amd64),
+    /// emulated if it isn't the host's
+    #[arg(long)]
+    pub platform: Option<String>,
+
     /// Force rebuild ignoring cache
     #[arg(long)]
     pub no_cache: bool,
@@ -118,10 +382,69 @@ pub struct ContainerFlags {
     /// Show what would be run without executing
     #[arg(long)]
     pub dry_run: bool,
+
+    /// Format of the `--dry-run` plan: `text` or `json`
+    #[arg(long, value_name = "FORMAT", requires = "dry_run")]
+    pub format: Option<PlanFormat>,
+
+    /// Isolate the session network from the internet (services stay reachable)
+    #[arg(long)]
+    pub no_internet: bool,
+
+    /// Recreate the dev container and re-run post_start hooks if it dies mid-session
+    #[arg(long)]
+    pub auto_restart: bool,
+
+    /// Write the full image build output to this file (default: a
+    /// timestamped file under ~/.local/share/bubble-bot/logs/)
+    #[arg(long, value_name = "PATH")]
+    pub build_log: Option<String>,
+
+    /// Leave the containers and network running on exit, to reattach to
+    /// (shell, claude, chief)
+    #[arg(long)]
+    pub keep: bool,
+}
+
+/// Checks a `KEY=VALUE` environment variable.
+fn parse_env_var(value: &str) -> Result<String, String> {
+    match value.split_once('=') {
+        Some((key, _)) if !key.is_empty() => Ok(value.to_string()),
+        _ => Err(format!("invalid env var `{value}` (expected KEY=VALUE)")),
+    }
+}
+
+/// Parses a duration such as `90s`, `30m`, `2h`, or `1h30m`.
+pub(crate) fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration `{value}` (expected e.g. `30m`, `1h30m`, `90s`)");
+
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in value.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            _ => return Err(invalid()),
+        };
+        let amount: u64 = digits.parse().map_err(|_| invalid())?;
+        total += amount * unit;
+        digits.clear();
+    }
+    if !digits.is_empty() || total == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(total))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
     use clap::Parser;
 
     use super::*;
@@ -143,7 +466,7 @@ mod tests {
     fn claude_subcommand_with_trailing_args() {
         let cli = Cli::parse_from(["bubble-bot", "claude", "--", "-p", "fix bug"]);
         match cli.command() {
-            Command::Claude { args } => {
+            Command::Claude { args, .. } => {
                 assert_eq!(args, vec!["-p", "fix bug"]);
             }
             _ => panic!("expected Claude subcommand"),
@@ -154,34 +477,326 @@ mod tests {
     fn chief_subcommand_with_trailing_args() {
         let cli = Cli::parse_from(["bubble-bot", "chief", "--", "--task", "deploy"]);
         match cli.command() {
-            Command::Chief { args } => {
+            Command::Chief { args, .. } => {
                 assert_eq!(args, vec!["--task", "deploy"]);
             }
             _ => panic!("expected Chief subcommand"),
         }
     }
 
+    #[test]
+    fn chief_tasks_and_results() {
+        let cli = Cli::parse_from([
+            "bubble-bot",
+            "chief",
+            "--tasks",
+            "tasks.yaml",
+            "--results",
+            "out",
+            "--resume",
+        ]);
+        match cli.command() {
+            Command::Chief {
+                tasks,
+                results,
+                args,
+                ..
+            } => {
+                assert_eq!(tasks, Some(PathBuf::from("tasks.yaml")));
+                assert_eq!(results, Some(PathBuf::from("out")));
+                assert_eq!(args, vec!["--resume"]);
+            }
+            _ => panic!("expected Chief subcommand"),
+        }
+    }
+
+    #[test]
+    fn claude_max_duration_before_trailing_args() {
+        let cli = Cli::parse_from(["bubble-bot", "claude", "--max-duration", "30m", "-p", "fix"]);
+        match cli.command() {
+            Command::Claude {
+                max_duration, args, ..
+            } => {
+                assert_eq!(max_duration, Some(Duration::from_secs(1800)));
+                assert_eq!(args, vec!["-p", "fix"]);
+            }
+            _ => panic!("expected Claude subcommand"),
+        }
+    }
+
+    #[test]
+    fn claude_output_before_trailing_args() {
+        let cli = Cli::parse_from([
+            "bubble-bot",
+            "claude",
+            "--output",
+            "result.json",
+            "--",
+            "-p",
+            "fix",
+            "--output-format",
+            "json",
+        ]);
+        match cli.command() {
+            Command::Claude { output, args, .. } => {
+                assert_eq!(output, Some(PathBuf::from("result.json")));
+                assert_eq!(args, vec!["-p", "fix", "--output-format", "json"]);
+            }
+            _ => panic!("expected Claude subcommand"),
+        }
+    }
+
+    #[test]
+    fn agent_branch_flag() {
+        let cli = Cli::parse_from([
+            "bubble-bot",
+            "chief",
+            "--branch",
+            "agent/deploy",
+            "--",
+            "run",
+        ]);
+        match cli.command() {
+            Command::Chief { branch, args, .. } => {
+                assert_eq!(branch.as_deref(), Some("agent/deploy"));
+                assert_eq!(args, vec!["run"]);
+            }
+            _ => panic!("expected Chief subcommand"),
+        }
+    }
+
+    #[test]
+    fn exec_workdir_and_env_before_command() {
+        let cli = Cli::parse_from([
+            "bubble-bot",
+            "exec",
+            "--workdir",
+            "packages/api",
+            "-e",
+            "APP_ENV=testing",
+            "-e",
+            "DEBUG=",
+            "php",
+            "artisan",
+            "test",
+        ]);
+        match cli.command() {
+            Command::Exec { workdir, env, cmd } => {
+                assert_eq!(workdir.as_deref(), Some("packages/api"));
+                assert_eq!(env, vec!["APP_ENV=testing", "DEBUG="]);
+                assert_eq!(cmd, vec!["php", "artisan", "test"]);
+            }
+            _ => panic!("expected Exec subcommand"),
+        }
+
+        assert!(Cli::try_parse_from(["bubble-bot", "exec", "-e", "APP_ENV", "php"]).is_err());
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid() {
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("5d").is_err());
+    }
+
     #[test]
     fn exec_subcommand_requires_cmd() {
         let cli = Cli::parse_from(["bubble-bot", "exec", "--", "ls", "-la"]);
         match cli.command() {
-            Command::Exec { cmd } => {
+            Command::Exec { cmd, .. } => {
                 assert_eq!(cmd, vec!["ls", "-la"]);
             }
             _ => panic!("expected Exec subcommand"),
         }
     }
 
+    #[test]
+    fn run_subcommand_takes_trailing_cmd() {
+        let cli = Cli::parse_from(["bubble-bot", "run", "--", "cargo", "test", "--workspace"]);
+        match cli.command() {
+            Command::Run { cmd } => {
+                assert_eq!(cmd, vec!["cargo", "test", "--workspace"]);
+            }
+            _ => panic!("expected Run subcommand"),
+        }
+    }
+
+    #[test]
+    fn db_subcommand_service_is_optional() {
+        let cli = Cli::parse_from(["bubble-bot", "db"]);
+        assert!(matches!(cli.command(), Command::Db { service: None }));
+
+        let cli = Cli::parse_from(["bubble-bot", "db", "postgres"]);
+        assert!(matches!(cli.command(), Command::Db { service: Some(s) } if s == "postgres"));
+
+        assert!(Cli::try_parse_from(["bubble-bot", "db", "mongo"]).is_err());
+    }
+
+    #[test]
+    fn run_subcommand_requires_cmd() {
+        assert!(Cli::try_parse_from(["bubble-bot", "run"]).is_err());
+    }
+
     #[test]
     fn build_subcommand() {
         let cli = Cli::parse_from(["bubble-bot", "build"]);
         assert!(matches!(cli.command(), Command::Build));
     }
 
+    #[test]
+    fn ps_subcommand() {
+        let cli = Cli::parse_from(["bubble-bot", "ps"]);
+        assert!(matches!(cli.command(), Command::Ps));
+    }
+
+    #[test]
+    fn plan_format_needs_dry_run() {
+        let cli = Cli::parse_from(["bubble-bot", "--dry-run", "--format", "json", "build"]);
+        assert_eq!(cli.container.format, Some(PlanFormat::Json));
+        assert!(Cli::try_parse_from(["bubble-bot", "--format", "json", "build"]).is_err());
+    }
+
+    #[test]
+    fn color_flag_is_global() {
+        let cli = Cli::parse_from(["bubble-bot", "images", "--color", "never"]);
+        assert_eq!(cli.color, ColorChoice::Never);
+        assert_eq!(Cli::parse_from(["bubble-bot"]).color, ColorChoice::Auto);
+        assert!(Cli::try_parse_from(["bubble-bot", "--color", "sometimes"]).is_err());
+    }
+
+    #[test]
+    fn info_subcommand_takes_show_secrets() {
+        let cli = Cli::parse_from(["bubble-bot", "info", "--show-secrets"]);
+        assert!(matches!(cli.command(), Command::Info));
+        assert!(cli.show_secrets);
+    }
+
     #[test]
     fn config_subcommand() {
         let cli = Cli::parse_from(["bubble-bot", "config"]);
-        assert!(matches!(cli.command(), Command::Config));
+        assert!(matches!(
+            cli.command(),
+            Command::Config {
+                check: false,
+                origin: false,
+                action: None
+            }
+        ));
+    }
+
+    #[test]
+    fn config_check_flag() {
+        let cli = Cli::parse_from(["bubble-bot", "config", "--check"]);
+        assert!(matches!(cli.command(), Command::Config { check: true, .. }));
+    }
+
+    #[test]
+    fn config_set_subcommand() {
+        let cli = Cli::parse_from(["bubble-bot", "config", "set", "runtimes.php", "8.3"]);
+        match cli.command() {
+            Command::Config {
+                action: Some(ConfigAction::Set { key, value, global }),
+                ..
+            } => {
+                assert_eq!(key, "runtimes.php");
+                assert_eq!(value, "8.3");
+                assert!(!global);
+            }
+            _ => panic!("expected config set"),
+        }
+    }
+
+    #[test]
+    fn config_unset_global() {
+        let cli = Cli::parse_from(["bubble-bot", "config", "unset", "runtimes.php", "--global"]);
+        assert!(matches!(
+            cli.command(),
+            Command::Config {
+                action: Some(ConfigAction::Unset { global: true, .. }),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn volumes_subcommands() {
+        let cli = Cli::parse_from(["bubble-bot", "volumes"]);
+        assert!(matches!(cli.command(), Command::Volumes { action: None }));
+
+        let cli = Cli::parse_from(["bubble-bot", "volumes", "backup", "mysql", "db.tar.gz"]);
+        match cli.command() {
+            Command::Volumes {
+                action: Some(VolumesAction::Backup { service, file }),
+            } => {
+                assert_eq!(service, "mysql");
+                assert_eq!(file, PathBuf::from("db.tar.gz"));
+            }
+            _ => panic!("expected volumes backup"),
+        }
+        assert!(Cli::try_parse_from(["bubble-bot", "volumes", "restore", "redis", "x"]).is_err());
+    }
+
+    #[test]
+    fn export_and_import_image() {
+        let cli = Cli::parse_from([
+            "bubble-bot",
+            "export",
+            "image",
+            "--output",
+            "bubble.tar.zst",
+            "--services",
+        ]);
+        match cli.command() {
+            Command::Export {
+                action:
+                    ExportAction::Image {
+                        output,
+                        services,
+                        chief,
+                    },
+            } => {
+                assert_eq!(output, PathBuf::from("bubble.tar.zst"));
+                assert!(services);
+                assert!(!chief);
+            }
+            _ => panic!("expected export image"),
+        }
+        assert!(Cli::try_parse_from(["bubble-bot", "export", "image"]).is_err());
+
+        let cli = Cli::parse_from(["bubble-bot", "import", "image", "bubble.tar.zst"]);
+        assert!(matches!(
+            cli.command(),
+            Command::Import {
+                action: ImportAction::Image { .. }
+            }
+        ));
+    }
+
+    #[test]
+    fn config_origin_flag() {
+        let cli = Cli::parse_from(["bubble-bot", "config", "--origin"]);
+        assert!(matches!(
+            cli.command(),
+            Command::Config { origin: true, .. }
+        ));
+        assert!(Cli::try_parse_from(["bubble-bot", "config", "--check", "--origin"]).is_err());
+    }
+
+    #[test]
+    fn self_update_subcommand() {
+        let cli = Cli::parse_from(["bubble-bot", "self-update", "--check"]);
+        match cli.command() {
+            Command::SelfUpdate { check } => assert!(check),
+            _ => panic!("expected SelfUpdate subcommand"),
+        }
     }
 
     #[test]
@@ -252,12 +867,30 @@ mod tests {
             "bash",
             "--no-cache",
             "--dry-run",
+            "--no-internet",
+            "--auto-restart",
+            "--keep",
         ]);
         assert_eq!(cli.container.network.as_deref(), Some("mynet"));
         assert_eq!(cli.container.name.as_deref(), Some("mycontainer"));
         assert_eq!(cli.container.shell, "bash");
         assert!(cli.container.no_cache);
         assert!(cli.container.dry_run);
+        assert!(cli.container.no_internet);
+        assert!(cli.container.auto_restart);
+        assert!(cli.container.keep);
+    }
+
+    #[test]
+    fn config_flag() {
+        let cli = Cli::parse_from(["bubble-bot", "--config", "envs/ci.toml", "build"]);
+        assert_eq!(cli.config.as_deref(), Some(Path::new("envs/ci.toml")));
+    }
+
+    #[test]
+    fn project_dir_flag() {
+        let cli = Cli::parse_from(["bubble-bot", "--project-dir", "../api", "exec", "--", "ls"]);
+        assert_eq!(cli.project_dir.as_deref(), Some(Path::new("../api")));
     }
 
     #[test]
@@ -288,7 +921,7 @@ mod tests {
         assert!(cli.service.redis);
         assert!(cli.container.dry_run);
         match cli.command() {
-            Command::Claude { args } => {
+            Command::Claude { args, .. } => {
                 assert_eq!(args, vec!["-p", "help me"]);
             }
             _ => panic!("expected Claude subcommand"),
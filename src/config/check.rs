@@ -0,0 +1,632 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value as JsonValue;
+
+use super::{AuthProvider, Config, ConfigFile, ShellFramework, expand_home, project_dir};
+use crate::runtime::{go::GoRuntime, node::NodeRuntime, php::PhpRuntime};
+use crate::services::{collect_services, env_collisions};
+
+/// Minimum Jaro-Winkler similarity for a known key to be suggested.
+const SUGGESTION_THRESHOLD: f64 = 0.8;
+
+/// A problem found by `config --check`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// Config file the issue was found in, or `None` for the merged config.
+    pub source: Option<PathBuf>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(path) => write!(f, "{}: {}", path.display(), self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Validates each config file in `files`, then the config `load` merges
+/// from them. The files are checked on their own first, so a wrong-typed
+/// value is reported with its file and key rather than as a failed load.
+/// Returns every issue found; an empty list means the config is valid.
+pub fn check(files: &[ConfigFile], load: impl FnOnce() -> anyhow::Result<Config>) -> Vec<Issue> {
+    let mut issues: Vec<Issue> = files.iter().flat_map(|f| check_file(&f.path)).collect();
+    let merged = match load() {
+        Ok(config) => check_merged(&config),
+        // Already reported against the file that broke it
+        Err(_) if !issues.is_empty() => Vec::new(),
+        Err(e) => vec![format!("{e:#}")],
+    };
+    issues.extend(merged.into_iter().map(|message| Issue {
+        source: None,
+        message,
+    }));
+    issues
+}
+
+/// Checks a single config file for syntax errors and unknown keys.
+/// Missing files are not an error.
+fn check_file(path: &Path) -> Vec<Issue> {
+    let issue = |message: String| Issue {
+        source: Some(path.to_path_buf()),
+        message,
+    };
+
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => return vec![issue(e.to_string())],
+    };
+
+    match unknown_keys(&contents) {
+        Ok(messages) => messages.into_iter().map(issue).collect(),
+        Err(message) => vec![issue(message)],
+    }
+}
+
+/// Returns an error message per key in `contents` that `Config` doesn't know.
+///
+/// The schema is taken from the parsed config itself: serializing it to JSON
+/// keeps unset `Option` fields (as `null`) and expands every section present
+/// in the file, so it lists all keys the file could legally contain.
+pub(super) fn unknown_keys(contents: &str) -> Result<Vec<String>, String> {
+    let raw: toml::Table = toml::from_str(contents).map_err(|e| describe(contents, &e))?;
+    let config: Config = toml::from_str(contents).map_err(|e| describe(contents, &e))?;
+    let schema = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+
+    let mut messages = Vec::new();
+    walk_keys(&raw, &schema, "", &mut messages);
+    Ok(messages)
+}
+
+/// The parse error `e` in `contents`, located by line and, when it's about
+/// a value, by the dotted key it was given for.
+fn describe(contents: &str, e: &toml::de::Error) -> String {
+    let Some(span) = e.span() else {
+        return e.message().to_string();
+    };
+    let before = &contents[..span.start.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    match key_at(before) {
+        Some(key) => format!("`{key}` (line {line}): {}", e.message()),
+        None => format!("line {line}: {}", e.message()),
+    }
+}
+
+/// The dotted key of a value starting at the end of `before`: the key on
+/// its line under the last `[table]` header.
+fn key_at(before: &str) -> Option<String> {
+    let (earlier, line) = before.rsplit_once('\n').unwrap_or(("", before));
+    let key = line.split_once('=')?.0.trim();
+    if key.is_empty() {
+        return None;
+    }
+    let table = earlier
+        .lines()
+        .rev()
+        .map(str::trim)
+        .find(|line| line.starts_with('['))
+        .map(|header| header.trim_matches(|c| c == '[' || c == ']').trim());
+    Some(match table {
+        Some(table) => format!("{table}.{key}"),
+        None => key.to_string(),
+    })
+}
+
+fn walk_keys(raw: &toml::Table, schema: &JsonValue, prefix: &str, out: &mut Vec<String>) {
+    let Some(known) = schema.as_object() else {
+        return;
+    };
+
+    for (key, value) in raw {
+        let path = format!("{prefix}{key}");
+        let Some(sub_schema) = known.get(key) else {
+            let candidates = known.keys().map(String::as_str);
+            let message = match suggest(key, candidates) {
+                Some(suggestion) => {
+                    format!("unknown key `{path}` (did you mean `{prefix}{suggestion}`?)")
+                }
+                None => format!("unknown key `{path}`"),
+            };
+            out.push(message);
+            continue;
+        };
+
+        match (value, sub_schema) {
+            (toml::Value::Table(table), _) => {
+                walk_keys(table, sub_schema, &format!("{path}."), out);
+            }
+            (toml::Value::Array(items), JsonValue::Array(schemas)) => {
+                for (item, item_schema) in items.iter().zip(schemas) {
+                    if let toml::Value::Table(table) = item {
+                        walk_keys(table, item_schema, &format!("{path}."), out);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the candidate most similar to `key`, if any is close enough.
+fn suggest<'a>(key: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .map(|c| (c, strsim::jaro_winkler(key, c)))
+        .filter(|(_, score)| *score >= SUGGESTION_THRESHOLD)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(c, _)| c)
+}
+
+/// Checks the merged config for problems that span sections or files.
+fn check_merged(config: &Config) -> Vec<String> {
+    let mut messages = Vec::new();
+
+    // Unsupported runtime versions
+    let runtimes = [
+        config.runtimes.php.as_deref().map(|v| {
+            PhpRuntime::new(v)
+                .and_then(|rt| rt.with_extensions(&config.runtimes.php_extensions))
+                .map(drop)
+        }),
+        config
+            .runtimes
+            .node
+            .as_deref()
+            .map(|v| NodeRuntime::new(v).map(drop)),
+        config
+            .runtimes
+            .go
+            .as_deref()
+            .map(|v| GoRuntime::new(v).map(drop)),
+    ];
+    for result in runtimes.into_iter().flatten() {
+        if let Err(e) = result {
+            messages.push(e.to_string());
+        }
+    }
+
+    let php_settings =
+        !config.runtimes.php_ini.is_empty() || !config.runtimes.php_extensions.is_empty();
+    if php_settings && config.runtimes.php.is_none() {
+        messages.push(
+            "runtimes.php_ini and runtimes.php_extensions have no effect without runtimes.php"
+                .to_string(),
+        );
+    }
+
+    let shell = config.container.shell.as_deref().unwrap_or("bash");
+    if config.shell.framework == Some(ShellFramework::Ohmyzsh) && shell != "zsh" {
+        messages.push(format!(
+            "shell.framework = \"ohmyzsh\" has no effect with container.shell = \"{shell}\"; set it to \"zsh\""
+        ));
+    }
+
+    if let Err(e) =
+        crate::shell::dotfile_entries(&config.shell.dotfiles, &config.shell.dotfiles_exclude)
+    {
+        messages.push(e.to_string());
+    }
+    if config.shell.dotfiles_install.is_some() && config.shell.dotfiles_repo.is_none() {
+        messages
+            .push("shell.dotfiles_install has no effect without shell.dotfiles_repo".to_string());
+    }
+
+    // Container settings parsed only at launch
+    if let Err(e) = config.container.tmpfs_mounts() {
+        messages.push(e.to_string());
+    }
+    if let Err(e) = config.container.device_mappings() {
+        messages.push(e.to_string());
+    }
+    if let Some(Err(e)) = config
+        .container
+        .platform
+        .as_deref()
+        .map(crate::docker::platform::platform_arch)
+    {
+        messages.push(e.to_string());
+    }
+    if let Err(e) = config.container.shm_size_bytes() {
+        messages.push(e.to_string());
+    }
+    if let Err(e) = config.build.timeout_duration() {
+        messages.push(e.to_string());
+    }
+    if let Err(e) = config.auth.refresh_interval() {
+        messages.push(e.to_string());
+    }
+    if let Err(e) = config.workspace.workdir_path() {
+        messages.push(e.to_string());
+    }
+    if let Err(e) = config.workspace.extra_binds() {
+        messages.push(e.to_string());
+    }
+    if let Err(e) = config.mount.exclude_volumes("check") {
+        messages.push(e.to_string());
+    }
+    if let Err(e) = config.notify.webhook_url() {
+        messages.push(e.to_string());
+    }
+    if let Err(e) = config.telemetry.endpoint_url() {
+        messages.push(e.to_string());
+    }
+    if config.auth.providers().contains(&AuthProvider::OnePassword)
+        && config.auth.onepassword.is_none()
+    {
+        messages.push(
+            "auth.providers lists 1password but auth.onepassword (an `op://` reference) is not set"
+                .to_string(),
+        );
+    }
+    for artifact in &config.artifacts {
+        if artifact.from.trim().is_empty() || artifact.to.trim().is_empty() {
+            messages.push("artifacts entries need both `from` and `to`".to_string());
+        }
+    }
+
+    for (name, custom) in &config.services.custom {
+        if custom.image.trim().is_empty() {
+            messages.push(format!("services.custom.{name} needs an `image`"));
+        }
+        let builtin = match name.as_str() {
+            "mysql" => config.services.mysql.is_some(),
+            "postgres" => config.services.postgres.is_some(),
+            "redis" => config.services.redis == Some(true),
+            "egress" => !config.network.allow.is_empty(),
+            _ => false,
+        };
+        if builtin {
+            messages.push(format!(
+                "services.custom.{name} has the same name as the built-in {name} service"
+            ));
+        }
+    }
+
+    // Services claiming the same dev container env vars
+    for (owners, keys) in env_collisions(&collect_services(config, "check")) {
+        messages.push(format!(
+            "services {} set the same dev container env vars ({}); set `env_prefix` on one of them",
+            owners.join(", "),
+            keys.join(", ")
+        ));
+    }
+    let prefixes = [
+        (
+            "mysql",
+            config.services.mysql.as_ref().map(|c| &c.env_prefix),
+        ),
+        (
+            "postgres",
+            config.services.postgres.as_ref().map(|c| &c.env_prefix),
+        ),
+    ];
+    for (name, prefix) in prefixes {
+        let Some(Some(prefix)) = prefix else { continue };
+        let valid = prefix
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+        if prefix.is_empty() || !valid {
+            messages.push(format!(
+                "services.{name}.env_prefix `{prefix}` must be uppercase letters, digits, or `_`"
+            ));
+        }
+    }
+
+    // Host paths that must exist
+    for path in &config.tls.ca_certs {
+        if !expand_home(path).exists() {
+            messages.push(format!("tls.ca_certs: `{path}` does not exist"));
+        }
+    }
+    if let Some(file) = &config.services.from_compose {
+        if !project_dir().join(expand_home(file)).is_file() {
+            messages.push(format!("services.from_compose: `{file}` does not exist"));
+        }
+    }
+    if let Some(mysql) = &config.services.mysql {
+        for (script, path) in mysql.init_scripts.iter().zip(mysql.init_script_paths()) {
+            if !path.is_file() {
+                messages.push(format!(
+                    "services.mysql.init_scripts: `{script}` does not exist"
+                ));
+            }
+        }
+    }
+    for file in &config.build.files {
+        if !file.host_path().exists() {
+            messages.push(format!("build.files: `{}` does not exist", file.source));
+        }
+    }
+
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MysqlConfig, PostgresConfig};
+
+    #[test]
+    fn valid_file_has_no_unknown_keys() {
+        let messages = unknown_keys(
+            r#"
+            [runtimes]
+            php = "8.3"
+
+            [services.mysql]
+            version = "8.4"
+
+            [network]
+            allow = ["github.com"]
+
+            [template.vars]
+            anything = { nested = [1, 2] }
+            "#,
+        )
+        .unwrap();
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn unknown_top_level_key_suggests_section() {
+        let messages = unknown_keys("[servics]\nredis = true\n").unwrap();
+        assert_eq!(
+            messages,
+            vec!["unknown key `servics` (did you mean `services`?)"]
+        );
+    }
+
+    #[test]
+    fn unknown_nested_key_suggests_field() {
+        let messages = unknown_keys("[services.mysql]\nversoin = \"8.4\"\n").unwrap();
+        assert_eq!(
+            messages,
+            vec!["unknown key `services.mysql.versoin` (did you mean `services.mysql.version`?)"]
+        );
+    }
+
+    #[test]
+    fn unset_option_fields_are_known() {
+        let messages = unknown_keys("[runtimes]\nphpp = \"8.3\"\n").unwrap();
+        assert_eq!(
+            messages,
+            vec!["unknown key `runtimes.phpp` (did you mean `runtimes.php`?)"]
+        );
+    }
+
+    #[test]
+    fn unknown_key_without_close_match() {
+        let messages = unknown_keys("[container]\nzzz = 1\n").unwrap();
+        assert_eq!(messages, vec!["unknown key `container.zzz`"]);
+    }
+
+    #[test]
+    fn type_errors_are_reported() {
+        assert!(unknown_keys("[runtimes]\nrust = \"yes\"\n").is_err());
+    }
+
+    #[test]
+    fn missing_file_is_not_an_issue() {
+        assert!(check_file(Path::new("/nonexistent/.bubble-bot.toml")).is_empty());
+    }
+
+    #[test]
+    fn file_issues_carry_their_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".bubble-bot.toml");
+        std::fs::write(&path, "[hook]\npost_start = []\n").unwrap();
+
+        let issues = check_file(&path);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].source.as_deref(), Some(path.as_path()));
+        assert!(issues[0].to_string().contains("did you mean `hooks`?"));
+    }
+
+    #[test]
+    fn unsupported_runtime_versions_are_all_reported() {
+        let mut config = Config::default();
+        config.runtimes.php = Some("7.4".to_string());
+        config.runtimes.go = Some("1.0".to_string());
+        let messages = check_merged(&config);
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].contains("unsupported PHP version"));
+        assert!(messages[1].contains("Go"));
+    }
+
+    #[test]
+    fn invalid_container_settings_are_reported() {
+        let mut config = Config::default();
+        config.container.tmpfs = vec!["tmp".to_string()];
+        config.container.shm_size = Some("huge".to_string());
+        config.container.devices = vec!["kvm".to_string()];
+        let messages = check_merged(&config);
+        assert_eq!(messages.len(), 3, "{messages:?}");
+        assert!(messages[0].contains("absolute"), "{messages:?}");
+        assert!(messages[1].contains("container.devices"), "{messages:?}");
+        assert!(messages[2].contains("container.shm_size"), "{messages:?}");
+    }
+
+    #[test]
+    fn php_settings_need_php_and_valid_extensions() {
+        let mut config = Config::default();
+        config.runtimes.php_extensions = vec!["xdebug".to_string()];
+        assert_eq!(
+            check_merged(&config),
+            vec![
+                "runtimes.php_ini and runtimes.php_extensions have no effect without runtimes.php"
+            ]
+        );
+
+        config.runtimes.php = Some("8.3".to_string());
+        assert!(check_merged(&config).is_empty());
+        config.runtimes.php_extensions = vec!["Xdebug!".to_string()];
+        let messages = check_merged(&config);
+        assert!(
+            messages[0].contains("invalid PHP extension"),
+            "{messages:?}"
+        );
+    }
+
+    #[test]
+    fn ohmyzsh_needs_zsh() {
+        let mut config = Config::default();
+        config.shell.framework = Some(ShellFramework::Ohmyzsh);
+        let messages = check_merged(&config);
+        assert!(
+            messages[0].contains("container.shell = \"bash\""),
+            "{messages:?}"
+        );
+
+        config.container.shell = Some("zsh".to_string());
+        assert!(check_merged(&config).is_empty());
+    }
+
+    #[test]
+    fn dotfiles_install_needs_repo() {
+        let mut config = Config::default();
+        config.shell.dotfiles_install = Some("make".to_string());
+        assert_eq!(
+            check_merged(&config),
+            vec!["shell.dotfiles_install has no effect without shell.dotfiles_repo"]
+        );
+        config.shell.dotfiles_repo = Some("git@github.com:me/dotfiles".to_string());
+        assert!(check_merged(&config).is_empty());
+    }
+
+    #[test]
+    fn onepassword_provider_needs_reference() {
+        let mut config = Config::default();
+        config.auth.providers = vec![AuthProvider::Env, AuthProvider::OnePassword];
+        assert_eq!(
+            check_merged(&config),
+            vec![
+                "auth.providers lists 1password but auth.onepassword (an `op://` reference) is not set"
+            ]
+        );
+        config.auth.onepassword = Some("op://Private/Claude/token".to_string());
+        assert!(check_merged(&config).is_empty());
+    }
+
+    #[test]
+    fn mysql_and_postgres_conflict_on_db_vars() {
+        let mut config = Config::default();
+        config.services.mysql = Some(MysqlConfig::default());
+        config.services.postgres = Some(PostgresConfig::default());
+        config.services.redis = Some(true);
+        let messages = check_merged(&config);
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with(
+            "services mysql, postgres set the same dev container env vars (DATABASE_URL, DB_DATABASE,"
+        ));
+        assert!(!messages[0].contains("REDIS"));
+    }
+
+    #[test]
+    fn env_prefix_resolves_conflict_and_is_validated() {
+        let mut config = Config::default();
+        config.services.mysql = Some(MysqlConfig::default());
+        config.services.postgres = Some(PostgresConfig {
+            env_prefix: Some("PG".to_string()),
+            ..Default::default()
+        });
+        assert!(check_merged(&config).is_empty());
+
+        config.services.mysql = Some(MysqlConfig {
+            env_prefix: Some("my-db".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            check_merged(&config),
+            vec!["services.mysql.env_prefix `my-db` must be uppercase letters, digits, or `_`"]
+        );
+    }
+
+    #[test]
+    fn missing_ca_cert_is_reported() {
+        let mut config = Config::default();
+        config.tls.ca_certs = vec!["/nonexistent/corp.pem".to_string()];
+        assert_eq!(
+            check_merged(&config),
+            vec!["tls.ca_certs: `/nonexistent/corp.pem` does not exist"]
+        );
+    }
+
+    #[test]
+    fn missing_mysql_init_script_is_reported() {
+        let mut config = Config::default();
+        config.services.mysql = Some(MysqlConfig {
+            init_scripts: vec!["/nonexistent/seed.sql".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(
+            check_merged(&config),
+            vec!["services.mysql.init_scripts: `/nonexistent/seed.sql` does not exist"]
+        );
+    }
+
+    #[test]
+    fn custom_service_needs_image_and_unique_name() {
+        let mut config = Config::default();
+        config.services.redis = Some(true);
+        config
+            .services
+            .custom
+            .insert("redis".to_string(), Default::default());
+        assert_eq!(
+            check_merged(&config),
+            vec![
+                "services.custom.redis needs an `image`",
+                "services.custom.redis has the same name as the built-in redis service",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_compose_file_is_reported() {
+        let mut config = Config::default();
+        config.services.from_compose = Some("/nonexistent/compose.yml".to_string());
+        assert_eq!(
+            check_merged(&config),
+            vec!["services.from_compose: `/nonexistent/compose.yml` does not exist"]
+        );
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(check(&[], || Ok(Config::default())).is_empty());
+    }
+
+    #[test]
+    fn wrong_typed_values_are_reported_with_file_and_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".bubble-bot.toml");
+        std::fs::write(
+            &path,
+            "[runtimes]\nphp = \"8.3\"\n\n[services.mysql]\nversion = 8\n",
+        )
+        .unwrap();
+        let files = [ConfigFile {
+            path: path.clone(),
+            layer: crate::config::Layer::Project,
+        }];
+
+        let issues = check(&files, || anyhow::bail!("failed to parse config"));
+        assert_eq!(issues.len(), 1, "{issues:?}");
+        assert_eq!(issues[0].source.as_deref(), Some(path.as_path()));
+        assert!(
+            issues[0]
+                .message
+                .starts_with("`services.mysql.version` (line 5): invalid type: integer `8`"),
+            "{}",
+            issues[0].message
+        );
+    }
+
+    #[test]
+    fn load_errors_without_a_file_issue_are_reported() {
+        let issues = check(&[], || anyhow::bail!("compose file unreadable"));
+        assert_eq!(issues[0].to_string(), "compose file unreadable");
+    }
+}
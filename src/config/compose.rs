@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use tracing::warn;
+
+use super::{CustomServiceConfig, Readiness};
+
+/// The parts of `docker compose config --format json` output that map onto
+/// a custom service. Compose normalizes the file first: variables are
+/// interpolated, `extends` resolved, and bind sources made absolute.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ComposeFile {
+    services: BTreeMap<String, ComposeService>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ComposeService {
+    image: Option<String>,
+    /// `null` for a variable listed without a value and unset on the host.
+    environment: BTreeMap<String, Option<String>>,
+    volumes: Vec<ComposeVolume>,
+    healthcheck: Option<Healthcheck>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ComposeVolume {
+    #[serde(rename = "type")]
+    kind: String,
+    source: Option<String>,
+    target: String,
+    #[serde(default)]
+    read_only: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Healthcheck {
+    test: Vec<String>,
+    disable: bool,
+}
+
+/// Custom services for the services in the Compose file at `path`: those
+/// named in `select`, or all with an `image` if it is empty.
+pub fn import(path: &Path, select: &[String]) -> Result<BTreeMap<String, CustomServiceConfig>> {
+    if !path.is_file() {
+        bail!("compose file {} does not exist", path.display());
+    }
+    let output = Command::new("docker")
+        .arg("compose")
+        .arg("-f")
+        .arg(path)
+        .args(["config", "--format", "json"])
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run `docker compose config`")?;
+    if !output.status.success() {
+        bail!(
+            "`docker compose config` failed for {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    services_from_json(&String::from_utf8_lossy(&output.stdout), select)
+}
+
+fn services_from_json(
+    json: &str,
+    select: &[String],
+) -> Result<BTreeMap<String, CustomServiceConfig>> {
+    let file: ComposeFile =
+        serde_json::from_str(json).context("failed to parse `docker compose config` output")?;
+
+    for name in select {
+        if !file.services.contains_key(name) {
+            bail!("services.compose_services: `{name}` is not in the compose file");
+        }
+    }
+
+    let mut services = BTreeMap::new();
+    for (name, service) in file.services {
+        if !select.is_empty() && !select.contains(&name) {
+            continue;
+        }
+        let Some(image) = service.image else {
+            // Built services are the application itself, as a rule
+            if !select.is_empty() {
+                warn!(service = %name, "compose service has no image; skipping");
+            }
+            continue;
+        };
+        services.insert(
+            name.clone(),
+            CustomServiceConfig {
+                image,
+                env: service
+                    .environment
+                    .into_iter()
+                    .filter_map(|(key, value)| Some((key, value?)))
+                    .collect(),
+                readiness: service.healthcheck.and_then(readiness),
+                ..mounts(&name, service.volumes)
+            },
+        );
+    }
+    Ok(services)
+}
+
+/// The service's first named volume becomes its data volume and bind mounts
+/// carry over; other mounts have no bubble-bot equivalent and are skipped.
+fn mounts(name: &str, volumes: Vec<ComposeVolume>) -> CustomServiceConfig {
+    let mut config = CustomServiceConfig::default();
+    for volume in volumes {
+        match (volume.kind.as_str(), volume.source) {
+            ("volume", Some(_)) if config.volume.is_none() => {
+                config.volume = Some(volume.target);
+            }
+            ("bind", Some(source)) => {
+                let mode = if volume.read_only { ":ro" } else { "" };
+                config
+                    .binds
+                    .push(format!("{source}:{}{mode}", volume.target));
+            }
+            (kind, _) => {
+                warn!(service = name, kind, target = %volume.target, "skipping compose mount");
+            }
+        }
+    }
+    config
+}
+
+/// A Compose healthcheck as a readiness command: `CMD` runs its arguments
+/// as is, `CMD-SHELL` through `sh -c`.
+fn readiness(healthcheck: Healthcheck) -> Option<Readiness> {
+    if healthcheck.disable {
+        return None;
+    }
+    let (kind, args) = healthcheck.test.split_first()?;
+    match kind.as_str() {
+        "CMD" if !args.is_empty() => Some(Readiness::Cmd(args.to_vec())),
+        "CMD-SHELL" if !args.is_empty() => Some(Readiness::Cmd(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            args.join(" "),
+        ])),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMPOSE: &str = r#"{
+      "name": "shop",
+      "services": {
+        "app": { "build": { "context": "." } },
+        "db": {
+          "image": "postgres:16",
+          "environment": { "POSTGRES_PASSWORD": "secret", "PGTZ": null },
+          "volumes": [
+            { "type": "volume", "source": "pgdata", "target": "/var/lib/postgresql/data", "volume": {} },
+            { "type": "bind", "source": "/src/shop/db/init", "target": "/docker-entrypoint-initdb.d", "read_only": true },
+            { "type": "tmpfs", "target": "/tmp" }
+          ],
+          "healthcheck": { "test": ["CMD-SHELL", "pg_isready -U postgres"], "interval": "5s" }
+        },
+        "mail": {
+          "image": "axllent/mailpit",
+          "healthcheck": { "test": ["NONE"] }
+        }
+      },
+      "volumes": { "pgdata": {} }
+    }"#;
+
+    #[test]
+    fn services_with_images_are_imported() {
+        let services = services_from_json(COMPOSE, &[]).unwrap();
+        assert_eq!(services.keys().collect::<Vec<_>>(), vec!["db", "mail"]);
+
+        let db = &services["db"];
+        assert_eq!(db.image, "postgres:16");
+        assert_eq!(
+            db.env,
+            BTreeMap::from([("POSTGRES_PASSWORD".to_string(), "secret".to_string())])
+        );
+        assert_eq!(db.volume.as_deref(), Some("/var/lib/postgresql/data"));
+        assert_eq!(
+            db.binds,
+            vec!["/src/shop/db/init:/docker-entrypoint-initdb.d:ro"]
+        );
+        assert_eq!(
+            db.readiness,
+            Some(Readiness::Cmd(vec![
+                "sh".into(),
+                "-c".into(),
+                "pg_isready -U postgres".into()
+            ]))
+        );
+        assert_eq!(services["mail"].readiness, None);
+    }
+
+    #[test]
+    fn selection_limits_and_validates_services() {
+        let services = services_from_json(COMPOSE, &["mail".to_string()]).unwrap();
+        assert_eq!(services.keys().collect::<Vec<_>>(), vec!["mail"]);
+
+        let err = services_from_json(COMPOSE, &["cache".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("`cache`"), "{err}");
+    }
+}
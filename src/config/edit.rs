@@ -0,0 +1,255 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+use super::check::unknown_keys;
+
+/// Sets `key` (a dotted path like `runtimes.php`) to `raw` in the config file
+/// at `path`, creating the file if needed. Comments and formatting elsewhere
+/// in the file are preserved.
+pub fn set(path: &Path, key: &str, raw: &str) -> Result<()> {
+    let contents = read_or_empty(path)?;
+    let updated = set_in(&contents, key, raw)?;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    std::fs::write(path, updated).with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Removes `key` from the config file at `path`. Returns false if it was not set.
+pub fn unset(path: &Path, key: &str) -> Result<bool> {
+    let contents = read_or_empty(path)?;
+    match unset_in(&contents, key)? {
+        Some(updated) => {
+            std::fs::write(path, updated)
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+fn read_or_empty(path: &Path) -> Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Returns `contents` with `key` set to `raw`.
+///
+/// `raw` is parsed as a TOML value (`true`, `["a", "b"]`, `512`) when that
+/// matches the config schema, and is otherwise stored as a string, so that
+/// `runtimes.php 8.3` stays `"8.3"` rather than becoming a float.
+fn set_in(contents: &str, key: &str, raw: &str) -> Result<String> {
+    let doc: DocumentMut = contents.parse().context("failed to parse config")?;
+    let segments = split_key(key)?;
+    let existing = unknown_keys(contents).unwrap_or_default();
+
+    let mut candidates = Vec::new();
+    if let Ok(value) = raw.parse::<Value>() {
+        if !value.is_str() {
+            candidates.push(value);
+        }
+    }
+    candidates.push(Value::from(raw));
+
+    let mut last_error = None;
+    for value in candidates {
+        let mut doc = doc.clone();
+        insert(doc.as_table_mut(), &segments, value);
+        let updated = doc.to_string();
+
+        match unknown_keys(&updated) {
+            Ok(unknown) => {
+                if let Some(message) = unknown.into_iter().find(|m| !existing.contains(m)) {
+                    bail!("{message}");
+                }
+                return Ok(updated);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    bail!(
+        "invalid value for `{key}`: {}",
+        last_error.unwrap_or_default()
+    )
+}
+
+/// Returns `contents` without `key`, or `None` if it was not set. Tables left
+/// empty by the removal are removed too.
+fn unset_in(contents: &str, key: &str) -> Result<Option<String>> {
+    let mut doc: DocumentMut = contents.parse().context("failed to parse config")?;
+    let segments = split_key(key)?;
+
+    if remove(doc.as_table_mut(), &segments) {
+        Ok(Some(doc.to_string()))
+    } else {
+        Ok(None)
+    }
+}
+
+fn split_key(key: &str) -> Result<Vec<&str>> {
+    let segments: Vec<&str> = key.split('.').collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        bail!("invalid config key `{key}`");
+    }
+    Ok(segments)
+}
+
+fn insert(table: &mut Table, segments: &[&str], value: Value) {
+    let (last, parents) = segments.split_last().expect("key has segments");
+    let mut table = table;
+    for segment in parents {
+        let item = table.entry(segment).or_insert_with(|| {
+            let mut child = Table::new();
+            child.set_implicit(true);
+            Item::Table(child)
+        });
+        // A scalar in the way (e.g., `redis = true` when setting
+        // `redis.version`) is replaced; validation rejects the result if the
+        // schema disagrees
+        if !item.is_table() {
+            *item = Item::Table(Table::new());
+        }
+        table = item.as_table_mut().expect("item is a table");
+    }
+    // Replace an existing value in place so its key comments and trailing
+    // comment survive
+    match table.get_mut(last).and_then(Item::as_value_mut) {
+        Some(existing) => {
+            let decor = existing.decor().clone();
+            *existing = value;
+            *existing.decor_mut() = decor;
+        }
+        None => {
+            table.insert(last, Item::Value(value));
+        }
+    }
+}
+
+fn remove(table: &mut Table, segments: &[&str]) -> bool {
+    match segments {
+        [] => false,
+        [last] => table.remove(last).is_some(),
+        [first, rest @ ..] => {
+            let Some(child) = table.get_mut(first).and_then(Item::as_table_mut) else {
+                return false;
+            };
+            let removed = remove(child, rest);
+            if removed && child.is_empty() {
+                table.remove(first);
+            }
+            removed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_creates_section() {
+        let updated = set_in("", "runtimes.php", "8.3").unwrap();
+        assert_eq!(updated, "[runtimes]\nphp = \"8.3\"\n");
+    }
+
+    #[test]
+    fn set_preserves_comments() {
+        let contents =
+            "# Project config\n[runtimes]\n# pinned for prod parity\nphp = \"8.1\" # LTS\n";
+        let updated = set_in(contents, "runtimes.php", "8.3").unwrap();
+        assert_eq!(
+            updated,
+            "# Project config\n[runtimes]\n# pinned for prod parity\nphp = \"8.3\" # LTS\n"
+        );
+    }
+
+    #[test]
+    fn set_parses_typed_values() {
+        let updated = set_in("", "runtimes.rust", "true").unwrap();
+        assert!(updated.contains("rust = true"));
+
+        let updated = set_in("", "network.allow", r#"["github.com", ".npmjs.org"]"#).unwrap();
+        assert!(updated.contains(r#"allow = ["github.com", ".npmjs.org"]"#));
+    }
+
+    #[test]
+    fn set_keeps_version_numbers_as_strings() {
+        let updated = set_in("", "runtimes.node", "22").unwrap();
+        assert!(updated.contains("node = \"22\""));
+    }
+
+    #[test]
+    fn set_nested_service_field() {
+        let updated = set_in("", "services.mysql.version", "8.4").unwrap();
+        assert_eq!(updated, "[services.mysql]\nversion = \"8.4\"\n");
+    }
+
+    #[test]
+    fn set_rejects_unknown_key_with_suggestion() {
+        let err = set_in("", "runtimes.phpp", "8.3").unwrap_err();
+        assert!(err.to_string().contains("did you mean `runtimes.php`?"));
+    }
+
+    #[test]
+    fn set_ignores_preexisting_unknown_keys() {
+        let updated = set_in("[legacy]\nx = 1\n", "runtimes.go", "1.23").unwrap();
+        assert!(updated.contains("go = \"1.23\""));
+    }
+
+    #[test]
+    fn set_rejects_invalid_value() {
+        let err = set_in("", "network.internal", "maybe").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("invalid value for `network.internal`")
+        );
+    }
+
+    #[test]
+    fn set_rejects_empty_segments() {
+        assert!(set_in("", "runtimes..php", "8.3").is_err());
+    }
+
+    #[test]
+    fn unset_removes_key_and_empty_table() {
+        let contents = "[runtimes]\nphp = \"8.3\"\n\n[services]\nredis = true\n";
+        let updated = unset_in(contents, "runtimes.php").unwrap().unwrap();
+        assert_eq!(updated.trim_start(), "[services]\nredis = true\n");
+    }
+
+    #[test]
+    fn unset_keeps_remaining_keys() {
+        let contents = "[runtimes]\nphp = \"8.3\"\nnode = \"22\"\n";
+        let updated = unset_in(contents, "runtimes.php").unwrap().unwrap();
+        assert_eq!(updated, "[runtimes]\nnode = \"22\"\n");
+    }
+
+    #[test]
+    fn unset_missing_key_is_none() {
+        assert!(unset_in("[runtimes]\n", "runtimes.php").unwrap().is_none());
+        assert!(unset_in("", "services.mysql.version").unwrap().is_none());
+    }
+
+    #[test]
+    fn set_and_unset_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/config.toml");
+
+        set(&path, "services.redis", "true").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "[services]\nredis = true\n"
+        );
+
+        assert!(unset(&path, "services.redis").unwrap());
+        assert!(!unset(&path, "services.redis").unwrap());
+    }
+}
@@ -0,0 +1,2918 @@
+pub mod check;
+pub mod compose;
+pub mod edit;
+pub mod origin;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::cli::{Cli, ContainerFlags, RuntimeFlags, ServiceFlags, parse_duration};
+
+// -- Top-level config --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub runtimes: RuntimeConfig,
+    pub services: ServiceConfig,
+    pub hooks: HookConfig,
+    pub container: ContainerConfig,
+    pub shell: ShellConfig,
+    pub workspace: WorkspaceConfig,
+    pub mount: MountConfig,
+    pub network: NetworkConfig,
+    pub tls: TlsConfig,
+    pub base: BaseConfig,
+    pub build: BuildConfig,
+    pub docker: DockerConfig,
+    pub agent: AgentConfig,
+    pub claude: ClaudeConfig,
+    pub auth: AuthConfig,
+    pub notify: NotifyConfig,
+    pub telemetry: TelemetryConfig,
+    pub mirrors: MirrorConfig,
+    pub template: TemplateConfig,
+    /// Private registry credentials keyed by host (e.g., `ghcr.io`).
+    pub registry: BTreeMap<String, RegistryConfig>,
+    pub laravel: LaravelConfig,
+    /// Long-running processes supervised in the dev container.
+    pub workers: Vec<WorkerConfig>,
+    /// Files copied out of the dev container when the session ends.
+    pub artifacts: Vec<ArtifactConfig>,
+    /// Commands baked into the image after the runtime layers (global tools).
+    pub setup: Vec<String>,
+}
+
+// -- Runtimes --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    pub php: Option<String>,
+    pub node: Option<String>,
+    pub rust: Option<bool>,
+    pub go: Option<String>,
+    /// php.ini settings baked into the PHP layer (`memory_limit = "1G"`).
+    pub php_ini: BTreeMap<String, toml::Value>,
+    /// Extra PHP extensions installed as `php<version>-<name>` packages.
+    pub php_extensions: Vec<String>,
+}
+
+// -- Services --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ServiceConfig {
+    pub mysql: Option<MysqlConfig>,
+    pub redis: Option<bool>,
+    pub postgres: Option<PostgresConfig>,
+    /// When to pull service images; defaults to `missing`.
+    pub pull_policy: Option<PullPolicy>,
+    /// Whether the main command waits for services to be ready; defaults to
+    /// `true`. When `false`, readiness is checked in the background.
+    pub wait: Option<bool>,
+    /// Services from any image, keyed by name (which is also the hostname).
+    pub custom: BTreeMap<String, CustomServiceConfig>,
+    /// Compose file (relative to the project root) whose services are
+    /// imported as custom services; `custom` entries of the same name win.
+    pub from_compose: Option<String>,
+    /// Compose services to import; all with an `image` if empty.
+    pub compose_services: Vec<String>,
+}
+
+impl ServiceConfig {
+    pub fn wait(&self) -> bool {
+        self.wait.unwrap_or(true)
+    }
+}
+
+/// When service images are pulled before their containers start.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PullPolicy {
+    /// Pull only if the image is not present locally.
+    #[default]
+    Missing,
+    /// Always pull, picking up updates to moving tags like `latest`.
+    Always,
+    /// Never pull; fail if the image is not present locally.
+    Never,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MysqlConfig {
+    pub version: String,
+    pub database: String,
+    pub username: String,
+    pub password: String,
+    /// Prefix of the connection vars in the dev container; `DB` by default
+    /// (`DB_HOST`, `DB_PORT`, ...).
+    pub env_prefix: Option<String>,
+    /// Name of the connection URL var; `DATABASE_URL`, or `<env_prefix>_URL`
+    /// when a prefix is set.
+    pub url_env: Option<String>,
+    /// Server options written to a `[mysqld]` section (`max_connections = 500`).
+    pub settings: BTreeMap<String, toml::Value>,
+    /// SQL or shell scripts (relative to the project root) mounted into
+    /// `/docker-entrypoint-initdb.d/`, run in order when the volume is new.
+    pub init_scripts: Vec<String>,
+}
+
+impl Default for MysqlConfig {
+    fn default() -> Self {
+        Self {
+            version: "8.0".to_string(),
+            database: "app".to_string(),
+            username: "root".to_string(),
+            password: "password".to_string(),
+            env_prefix: None,
+            url_env: None,
+            settings: BTreeMap::new(),
+            init_scripts: Vec::new(),
+        }
+    }
+}
+
+impl MysqlConfig {
+    /// Host paths of `init_scripts`, `~` expanded.
+    pub fn init_script_paths(&self) -> Vec<PathBuf> {
+        self.init_scripts
+            .iter()
+            .map(|script| project_dir().join(expand_home(script)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PostgresConfig {
+    pub version: String,
+    pub database: String,
+    pub username: String,
+    pub password: String,
+    /// Prefix of the connection vars in the dev container; `DB` by default.
+    pub env_prefix: Option<String>,
+    /// Name of the connection URL var; `DATABASE_URL`, or `<env_prefix>_URL`
+    /// when a prefix is set.
+    pub url_env: Option<String>,
+}
+
+impl Default for PostgresConfig {
+    fn default() -> Self {
+        Self {
+            version: "16".to_string(),
+            database: "app".to_string(),
+            username: "postgres".to_string(),
+            password: "password".to_string(),
+            env_prefix: None,
+            url_env: None,
+        }
+    }
+}
+
+/// A service container from an arbitrary image (`[services.custom.<name>]`).
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CustomServiceConfig {
+    pub image: String,
+    /// Env of the service container.
+    pub env: BTreeMap<String, String>,
+    /// Env injected into the dev container to reach the service
+    /// (`ELASTICSEARCH_URL = "http://elasticsearch:9200"`).
+    pub dev_env: BTreeMap<String, String>,
+    /// Container path kept in the `bubble-bot-<project-id>-<name>-data` volume.
+    pub volume: Option<String>,
+    /// Host paths (relative to the project root) bind-mounted as
+    /// `host:container[:ro]`.
+    pub binds: Vec<String>,
+    /// How to tell the service is ready; without it, the service counts as
+    /// ready once its container runs.
+    pub readiness: Option<Readiness>,
+}
+
+impl CustomServiceConfig {
+    /// Host paths of `binds`, `~` expanded and relative to the project root.
+    pub fn bind_mounts(&self) -> Vec<String> {
+        self.binds
+            .iter()
+            .map(|bind| match bind.split_once(':') {
+                Some((host, rest)) => {
+                    format!("{}:{rest}", project_dir().join(expand_home(host)).display())
+                }
+                None => bind.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A service readiness check, retried until it passes.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Readiness {
+    /// Command run in the service container with `docker exec`; ready once
+    /// it exits 0 (`{ cmd = ["pg_isready"] }`).
+    Cmd(Vec<String>),
+    /// Port on the service's localhost that accepts TCP connections
+    /// (`{ tcp = 9200 }`).
+    Tcp(u16),
+    /// URL, as seen from the service container, that answers with a 2xx
+    /// status (`{ http = "http://localhost:9200/_cluster/health" }`).
+    Http(String),
+}
+
+// -- Hooks --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HookConfig {
+    pub post_start: Vec<String>,
+    pub pre_stop: Vec<String>,
+}
+
+// -- Workers --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WorkerConfig {
+    /// Log prefix; defaults to the command's program name.
+    pub name: Option<String>,
+    /// Shell command run via `sh -c` in the dev container.
+    pub cmd: String,
+    pub restart: RestartPolicy,
+    /// Only start the worker when this service is enabled.
+    pub depends_on_service: Option<String>,
+}
+
+impl WorkerConfig {
+    /// Name used to prefix the worker's log lines.
+    pub fn display_name(&self) -> &str {
+        self.name
+            .as_deref()
+            .or_else(|| self.cmd.split_whitespace().next())
+            .unwrap_or("worker")
+    }
+}
+
+// -- Artifacts --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ArtifactConfig {
+    /// Container path or `sh` glob (`coverage/*.xml`); relative paths are
+    /// under the container's working directory.
+    pub from: String,
+    /// Host directory the matches are copied into; relative paths are under
+    /// the project root.
+    pub to: String,
+}
+
+impl ArtifactConfig {
+    /// The host directory for this artifact's matches.
+    pub fn host_dir(&self, project_dir: &Path) -> PathBuf {
+        project_dir.join(expand_home(&self.to))
+    }
+}
+
+/// When a worker is restarted after it exits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    Never,
+    /// Restart only after a non-zero exit.
+    #[default]
+    OnFailure,
+    Always,
+}
+
+// -- Container --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ContainerConfig {
+    pub network: Option<String>,
+    pub name: Option<String>,
+    pub shell: Option<String>,
+    /// tmpfs mounts for the dev container, as `path` or `path:options`
+    /// (e.g. `/tmp:size=512m`).
+    pub tmpfs: Vec<String>,
+    /// Size of `/dev/shm`, e.g. `"1g"`. Docker's 64MB default is too small
+    /// for headless Chrome.
+    pub shm_size: Option<String>,
+    /// Extra groups for the container user, by host group name or GID
+    /// (e.g. `docker` for a mounted socket).
+    pub groups: Vec<String>,
+    /// Host devices exposed to the container, as `host[:container][:perms]`
+    /// (e.g. `/dev/kvm`).
+    pub devices: Vec<String>,
+    /// Platform of the dev image and container (e.g. `linux/amd64`), for
+    /// amd64-only dependencies on arm64 hosts.
+    pub platform: Option<String>,
+}
+
+/// A host device exposed to the dev container.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    pub host: String,
+    pub container: String,
+    /// cgroup permissions, some of `rwm`.
+    pub permissions: String,
+}
+
+impl ContainerConfig {
+    /// tmpfs mounts keyed by container path, in the form `HostConfig` takes.
+    pub fn tmpfs_mounts(&self) -> Result<BTreeMap<String, String>> {
+        let mut mounts = BTreeMap::new();
+        for entry in &self.tmpfs {
+            let (path, options) = entry.split_once(':').unwrap_or((entry, ""));
+            if !path.starts_with('/') {
+                bail!("container.tmpfs entry `{entry}` must be an absolute container path");
+            }
+            mounts.insert(path.to_string(), options.to_string());
+        }
+        Ok(mounts)
+    }
+
+    /// Parsed `devices`, in `docker run --device` syntax: the container path
+    /// defaults to the host path and permissions to `rwm`.
+    pub fn device_mappings(&self) -> Result<Vec<Device>> {
+        self.devices
+            .iter()
+            .map(|entry| {
+                let parts: Vec<&str> = entry.split(':').collect();
+                let is_perms = |p: &str| !p.is_empty() && p.chars().all(|c| "rwm".contains(c));
+                let (host, container, permissions) = match parts[..] {
+                    [host] => (host, host, "rwm"),
+                    [host, perms] if is_perms(perms) => (host, host, perms),
+                    [host, container] => (host, container, "rwm"),
+                    [host, container, perms] if is_perms(perms) => (host, container, perms),
+                    _ => bail!(
+                        "invalid container.devices entry `{entry}` (expected `host[:container][:perms]`)"
+                    ),
+                };
+                if !host.starts_with('/') || !container.starts_with('/') {
+                    bail!("container.devices entry `{entry}` must use absolute device paths");
+                }
+                Ok(Device {
+                    host: host.to_string(),
+                    container: container.to_string(),
+                    permissions: permissions.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// `shm_size` in bytes.
+    pub fn shm_size_bytes(&self) -> Result<Option<i64>> {
+        self.shm_size
+            .as_deref()
+            .map(|size| {
+                parse_size(size).with_context(|| {
+                    format!("invalid container.shm_size `{size}` (expected e.g. `512m`, `1g`)")
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Parses a byte size such as `1g`, `512m`, or `65536`, with Docker's
+/// binary units.
+fn parse_size(value: &str) -> Result<i64> {
+    let value = value.trim().to_ascii_lowercase();
+    let value = value.strip_suffix('b').unwrap_or(&value);
+    let (digits, unit) = match value.char_indices().last() {
+        Some((i, 'k')) => (&value[..i], 1 << 10),
+        Some((i, 'm')) => (&value[..i], 1 << 20),
+        Some((i, 'g')) => (&value[..i], 1 << 30),
+        _ => (value, 1),
+    };
+    let amount: i64 = digits.parse().context("not a number")?;
+    match amount.checked_mul(unit) {
+        Some(bytes) if bytes > 0 => Ok(bytes),
+        _ => bail!("size must be positive"),
+    }
+}
+
+// -- Shell --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ShellConfig {
+    /// Keeps shell and Claude prompt history in a per-project volume.
+    pub persist_history: Option<bool>,
+    /// Prompt framework baked into the image, with default rc files.
+    pub framework: Option<ShellFramework>,
+    /// Dotfiles repo cloned on the host and applied at container start.
+    pub dotfiles_repo: Option<String>,
+    /// Command run in the dotfiles checkout instead of the default install
+    /// script lookup.
+    pub dotfiles_install: Option<String>,
+    /// Home-relative files or directories mounted read-only in addition to
+    /// the default dotfiles.
+    pub dotfiles: Vec<String>,
+    /// Default dotfiles not to mount.
+    pub dotfiles_exclude: Vec<String>,
+    /// Mounts `~/.ssh/known_hosts` and a sanitized `~/.ssh/config`, and
+    /// trusts GitHub and GitLab host keys in the image.
+    pub mount_ssh: Option<bool>,
+    /// Writes a summary of the environment into the container, printed by
+    /// interactive shells and imported into Claude Code's memory. On by
+    /// default.
+    pub motd: Option<bool>,
+}
+
+/// Shell prompt framework installed in the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellFramework {
+    /// Oh My Zsh, with zsh itself.
+    Ohmyzsh,
+    /// The starship prompt, for bash and zsh.
+    Starship,
+    None,
+}
+
+// -- Workspace --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WorkspaceConfig {
+    /// Which directory is mounted at `/workspace`; the project root by default.
+    pub root: Option<WorkspaceRoot>,
+    /// Container working directory (and where hooks run), relative to the
+    /// mounted root. Follows the current directory when unset.
+    pub workdir: Option<String>,
+    /// Other directories (sibling repositories) mounted next to the workspace.
+    pub extra: Vec<WorkspaceMount>,
+    /// At teardown, give files under `/workspace` that tools running as
+    /// another user (root) created back to the container user.
+    pub fix_ownership: Option<bool>,
+}
+
+/// A host directory mounted into the dev container besides the workspace.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WorkspaceMount {
+    /// Host directory (`~` expanded; relative paths are under the project root).
+    pub source: String,
+    /// Container path; defaults to `/workspace-libs/<directory name>`.
+    pub target: Option<String>,
+    /// Mount it read-only.
+    pub read_only: Option<bool>,
+}
+
+impl WorkspaceConfig {
+    /// The host directory mounted at `/workspace` for the project at
+    /// `project_dir`.
+    pub fn mount_root(&self, project_dir: &Path) -> PathBuf {
+        match self.root.unwrap_or_default() {
+            WorkspaceRoot::Project => project_dir.to_path_buf(),
+            WorkspaceRoot::AutoGit => {
+                git_root(project_dir).unwrap_or_else(|| project_dir.to_path_buf())
+            }
+        }
+    }
+
+    /// Binds for `extra`, as `host:container[:ro]`.
+    pub fn extra_binds(&self) -> Result<Vec<String>> {
+        self.extra_binds_under(&project_dir())
+    }
+
+    fn extra_binds_under(&self, project_dir: &Path) -> Result<Vec<String>> {
+        let mut binds = Vec::new();
+        let mut targets = Vec::new();
+        for mount in &self.extra {
+            let source = project_dir.join(expand_home(&mount.source));
+            let source = source
+                .canonicalize()
+                .with_context(|| format!("workspace.extra source `{}` not found", mount.source))?;
+            if !source.is_dir() {
+                bail!(
+                    "workspace.extra source `{}` is not a directory",
+                    mount.source
+                );
+            }
+            let target = match &mount.target {
+                Some(target) => target.trim_end_matches('/').to_string(),
+                None => {
+                    let name = source.file_name().unwrap_or_default().to_string_lossy();
+                    format!("/workspace-libs/{name}")
+                }
+            };
+            let path = Path::new(&target);
+            let normal = path
+                .components()
+                .skip(1)
+                .all(|c| matches!(c, std::path::Component::Normal(_)));
+            if !path.is_absolute() || !normal || path.starts_with("/workspace") {
+                bail!(
+                    "workspace.extra target `{target}` must be an absolute path outside /workspace"
+                );
+            }
+            if targets.contains(&target) {
+                bail!("workspace.extra mounts two directories at `{target}`; set `target` on one");
+            }
+            let mode = if mount.read_only == Some(true) {
+                ":ro"
+            } else {
+                ""
+            };
+            binds.push(format!("{}:{target}{mode}", source.display()));
+            targets.push(target);
+        }
+        Ok(binds)
+    }
+
+    /// `workdir` as a path relative to the mounted root.
+    pub fn workdir_path(&self) -> Result<Option<&Path>> {
+        let Some(workdir) = self.workdir.as_deref() else {
+            return Ok(None);
+        };
+        let path = Path::new(workdir.trim_end_matches('/'));
+        let normal = path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)));
+        if workdir.is_empty() || !normal {
+            bail!("workspace.workdir `{workdir}` must be a relative path inside the workspace");
+        }
+        Ok(Some(path))
+    }
+}
+
+/// The directory mounted at `/workspace`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WorkspaceRoot {
+    /// The project root.
+    #[default]
+    Project,
+    /// The root of the git repository containing the project, so a monorepo
+    /// package sees its siblings at the same relative paths.
+    AutoGit,
+}
+
+// -- Mounts --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MountConfig {
+    /// SELinux relabeling for the project and dotfile bind mounts, needed on
+    /// enforcing hosts (Fedora/RHEL) or the container gets `EACCES`.
+    pub selinux_label: Option<SelinuxLabel>,
+    /// Project paths (`node_modules`, `vendor`) overlaid with named volumes,
+    /// so heavy dependency trees skip the slow bind mount.
+    pub exclude: Vec<String>,
+}
+
+impl MountConfig {
+    /// Volumes for `exclude`, as `(volume name, container path)`. Volumes are
+    /// per project and path, so dependencies persist across sessions.
+    pub fn exclude_volumes(&self, project: &str) -> Result<Vec<(String, String)>> {
+        let mut volumes = Vec::new();
+        for entry in &self.exclude {
+            let path = entry.trim_end_matches('/');
+            let normal = Path::new(path)
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)));
+            if path.is_empty() || !normal {
+                bail!("mount.exclude entry `{entry}` must be a relative path inside the project");
+            }
+            let slug = path.replace('/', "-");
+            volumes.push((
+                format!("bubble-bot-{project}-mount-{slug}"),
+                format!("/workspace/{path}"),
+            ));
+        }
+        Ok(volumes)
+    }
+}
+
+/// How Docker relabels a bind mount's SELinux context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelinuxLabel {
+    /// `:z`, shared between containers.
+    Shared,
+    /// `:Z`, private to the dev container.
+    Private,
+}
+
+impl SelinuxLabel {
+    /// The bind mount option.
+    pub fn option(self) -> &'static str {
+        match self {
+            Self::Shared => "z",
+            Self::Private => "Z",
+        }
+    }
+
+    /// Appends the relabel option to a `host:container[:options]` bind.
+    pub fn apply(self, bind: &str) -> String {
+        let separator = if bind.split(':').count() > 2 {
+            ','
+        } else {
+            ':'
+        };
+        format!("{bind}{separator}{}", self.option())
+    }
+}
+
+// -- Network --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// Creates the session network as internal (no internet egress).
+    pub internal: Option<bool>,
+    /// Domains the dev container may reach through the egress proxy. A leading
+    /// dot (`.github.com`) also matches subdomains.
+    pub allow: Vec<String>,
+    /// DNS servers for the dev container.
+    pub dns: Vec<String>,
+    /// Extra `/etc/hosts` entries for the dev container in `host:ip` format.
+    pub extra_hosts: Vec<String>,
+    /// HTTP(S) proxy URL, overriding the host's `HTTP_PROXY`/`HTTPS_PROXY`.
+    pub proxy: Option<String>,
+    /// Comma-separated proxy bypass list, overriding the host's `NO_PROXY`.
+    pub no_proxy: Option<String>,
+}
+
+impl NetworkConfig {
+    /// Whether the session network is internal. An egress allowlist implies
+    /// an internal network, since the proxy must be the only way out.
+    pub fn is_internal(&self) -> bool {
+        self.internal.unwrap_or(false) || !self.allow.is_empty()
+    }
+}
+
+// -- TLS --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// PEM certificates (host paths, `~` expanded) trusted inside the image.
+    pub ca_certs: Vec<String>,
+}
+
+// -- Base image --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BaseConfig {
+    /// Leaves the compiler toolchain (`build-essential`) and other heavy
+    /// defaults out of the base layer, for projects that never compile
+    /// anything natively.
+    pub minimal: Option<bool>,
+}
+
+impl BaseConfig {
+    pub fn minimal(&self) -> bool {
+        self.minimal.unwrap_or(false)
+    }
+}
+
+// -- Build context --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BuildConfig {
+    /// Build with BuildKit through the docker CLI, with package-manager
+    /// cache mounts in the Dockerfile.
+    pub buildkit: Option<bool>,
+    /// Longest an image build may run (e.g., `20m`) before it is aborted;
+    /// unlimited when unset.
+    pub timeout: Option<String>,
+    /// File the full build output is written to (`~` is expanded); a
+    /// timestamped file in the data directory when unset.
+    pub log: Option<String>,
+    /// Host files added to the image build context.
+    pub files: Vec<BuildFile>,
+}
+
+impl BuildConfig {
+    /// The expanded `log` path.
+    pub fn log_path(&self) -> Option<PathBuf> {
+        self.log.as_deref().map(expand_home)
+    }
+
+    /// The parsed `timeout`.
+    pub fn timeout_duration(&self) -> Result<Option<Duration>> {
+        self.timeout
+            .as_deref()
+            .map(|timeout| {
+                parse_duration(timeout).map_err(|_| {
+                    anyhow::anyhow!(
+                        "invalid build.timeout `{timeout}` (expected e.g. `20m`, `1h30m`)"
+                    )
+                })
+            })
+            .transpose()
+    }
+}
+
+/// A host file copied into the build context (and optionally the image).
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BuildFile {
+    /// Host path (`~` expanded; relative paths are under the project root).
+    pub source: String,
+    /// Path in the build context; defaults to the source's file name.
+    pub path: Option<String>,
+    /// Where to `COPY` the file in the image, before the setup commands.
+    pub dest: Option<String>,
+    /// File mode in the build context; defaults to `0o644`.
+    pub mode: Option<u32>,
+}
+
+impl BuildFile {
+    /// The file's path in the build context.
+    pub fn context_path(&self) -> String {
+        self.path.clone().unwrap_or_else(|| {
+            Path::new(&self.source)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| self.source.clone())
+        })
+    }
+
+    /// The resolved host path.
+    pub fn host_path(&self) -> PathBuf {
+        project_dir().join(expand_home(&self.source))
+    }
+}
+
+// -- Docker API --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DockerConfig {
+    /// Retries of container and network operations that fail transiently;
+    /// defaults to [`crate::docker::retry::DEFAULT_RETRIES`].
+    pub retries: Option<u32>,
+}
+
+impl DockerConfig {
+    pub fn retries(&self) -> u32 {
+        self.retries
+            .unwrap_or(crate::docker::retry::DEFAULT_RETRIES)
+    }
+}
+
+// -- Agents --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    /// Starts `claude` and `chief` on a fresh git branch even without
+    /// `--branch`, so their edits never land on the current branch.
+    pub auto_branch: Option<bool>,
+}
+
+impl AgentConfig {
+    pub fn auto_branch(&self) -> bool {
+        self.auto_branch.unwrap_or(false)
+    }
+}
+
+// -- Claude Code --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ClaudeConfig {
+    /// Keeps a block describing the bubble's services and restrictions in
+    /// the project's `CLAUDE.local.md`, so Claude Code reaches MySQL or
+    /// Redis by hostname instead of guessing `localhost`.
+    pub write_env_context: Option<bool>,
+    /// Keeps `~/.claude/projects` in a per-project volume, so
+    /// `claude --resume` and `--continue` find sessions from earlier
+    /// containers.
+    pub persist_sessions: Option<bool>,
+}
+
+impl ClaudeConfig {
+    pub fn write_env_context(&self) -> bool {
+        self.write_env_context.unwrap_or(false)
+    }
+
+    pub fn persist_sessions(&self) -> bool {
+        self.persist_sessions.unwrap_or(false)
+    }
+}
+
+// -- Auth --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Where the Claude Code OAuth token is looked up, in order; the first
+    /// provider with a token wins.
+    pub providers: Vec<AuthProvider>,
+    /// 1Password secret reference (`op://vault/item/field`) read by the
+    /// `1password` provider.
+    pub onepassword: Option<String>,
+    /// Checks the token against the Anthropic API before the session starts,
+    /// failing if it was rejected.
+    pub verify: Option<bool>,
+    /// How often the token is resolved again while a session runs, e.g.
+    /// `30m`; a changed token is written into the dev container.
+    pub refresh: Option<String>,
+}
+
+impl AuthConfig {
+    /// `providers`, defaulting to the environment, then the Keychain.
+    pub fn providers(&self) -> Vec<AuthProvider> {
+        if self.providers.is_empty() {
+            vec![AuthProvider::Env, AuthProvider::Keychain]
+        } else {
+            self.providers.clone()
+        }
+    }
+
+    pub fn verify(&self) -> bool {
+        self.verify.unwrap_or(false)
+    }
+
+    /// The parsed `refresh`.
+    pub fn refresh_interval(&self) -> Result<Option<Duration>> {
+        self.refresh
+            .as_deref()
+            .map(|refresh| {
+                parse_duration(refresh).map_err(|_| {
+                    anyhow::anyhow!("invalid auth.refresh `{refresh}` (expected e.g. `30m`, `1h`)")
+                })
+            })
+            .transpose()
+    }
+}
+
+/// A source of the Claude Code OAuth token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AuthProvider {
+    /// `CLAUDE_CODE_OAUTH_TOKEN` on the host.
+    #[serde(rename = "env")]
+    Env,
+    /// The macOS Keychain entry Claude Code writes.
+    #[serde(rename = "keychain")]
+    Keychain,
+    /// `op read` of `auth.onepassword`.
+    #[serde(rename = "1password")]
+    OnePassword,
+    /// `~/.config/bubble-bot/credentials`.
+    #[serde(rename = "file")]
+    File,
+}
+
+impl AuthProvider {
+    /// The name used in `auth.providers`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Env => "env",
+            Self::Keychain => "keychain",
+            Self::OnePassword => "1password",
+            Self::File => "file",
+        }
+    }
+}
+
+// -- Notifications --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    /// Shows a desktop notification when a `claude` or `chief` session ends.
+    pub desktop: Option<bool>,
+    /// URL that gets a JSON summary POSTed when a `claude` or `chief`
+    /// session ends (e.g., a Slack incoming webhook).
+    pub webhook: Option<String>,
+}
+
+impl NotifyConfig {
+    pub fn desktop(&self) -> bool {
+        self.desktop.unwrap_or(false)
+    }
+
+    /// The webhook URL, checked to be `http(s)://`.
+    pub fn webhook_url(&self) -> Result<Option<&str>> {
+        let Some(url) = self.webhook.as_deref() else {
+            return Ok(None);
+        };
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            bail!("invalid notify.webhook `{url}` (expected an http:// or https:// URL)");
+        }
+        Ok(Some(url))
+    }
+}
+
+// -- Telemetry --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// Where anonymized session metadata is published after every session:
+    /// an `http(s)://` URL (JSON POST) or `s3://bucket/prefix`. Only read
+    /// from the global config.
+    pub endpoint: Option<String>,
+}
+
+impl TelemetryConfig {
+    /// The endpoint, checked to be `http(s)://` or `s3://`.
+    pub fn endpoint_url(&self) -> Result<Option<&str>> {
+        let Some(url) = self.endpoint.as_deref() else {
+            return Ok(None);
+        };
+        if !["https://", "http://", "s3://"]
+            .iter()
+            .any(|scheme| url.starts_with(scheme))
+        {
+            bail!(
+                "invalid telemetry.endpoint `{url}` (expected an http://, https://, or s3:// URL)"
+            );
+        }
+        Ok(Some(url))
+    }
+}
+
+// -- Package mirrors --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MirrorConfig {
+    /// Ubuntu archive mirror URL (replaces archive/security.ubuntu.com).
+    pub apt: Option<String>,
+    /// Ubuntu ports mirror URL (replaces ports.ubuntu.com, used by arm64 and
+    /// other non-x86 images). Separate from `apt`: the archive doesn't carry
+    /// those architectures.
+    pub apt_ports: Option<String>,
+    /// npm registry URL.
+    pub npm: Option<String>,
+    /// Composer repository URL (replaces packagist.org).
+    pub composer: Option<String>,
+    /// Cargo registry replacing crates.io (e.g., `sparse+https://mirror/index/`).
+    pub crates: Option<String>,
+    /// Go module proxy (`GOPROXY`).
+    pub go: Option<String>,
+}
+
+// -- Templates --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TemplateConfig {
+    /// Project values available to every Dockerfile template as `vars.<name>`.
+    pub vars: BTreeMap<String, toml::Value>,
+}
+
+// -- Registries --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RegistryConfig {
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Host env var holding the password, so secrets stay out of config files.
+    pub password_env: Option<String>,
+}
+
+// -- Laravel --
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LaravelConfig {
+    /// Writes service `DB_*`/`REDIS_*`/`MAIL_*` values into the project's
+    /// `.env` for the session, restoring the original values on exit.
+    pub manage_env: Option<bool>,
+}
+
+// -- Merge logic --
+
+impl Config {
+    /// Loads and merges config from all sources:
+    /// defaults -> global config -> project config -> local config -> CLI flags.
+    /// `--config` replaces the project and local configs with a single file.
+    pub fn load(cli: &Cli) -> Result<Self> {
+        let mut config = Config::default();
+
+        // Layers 1-3: global config, project config, local overlay (or --config)
+        for (file, mut file_config) in load_files(cli.config.as_deref())? {
+            // A cloned repository mustn't opt its users into publishing
+            if file.layer != Layer::Global && file_config.telemetry.endpoint.take().is_some() {
+                warn!(
+                    path = %file.path.display(),
+                    "telemetry.endpoint is only read from the global config; ignored"
+                );
+            }
+            config.merge(file_config);
+        }
+
+        // Layer 4: CLI flags
+        config.apply_cli(cli);
+
+        // A broken compose file mustn't block `bubble-bot config` from
+        // fixing it, so it only costs the imported services
+        if let Err(e) = config.import_compose() {
+            warn!(error = %format_args!("{e:#}"), "compose services not imported");
+        }
+
+        Ok(config)
+    }
+
+    /// Adds the services of `services.from_compose` to the custom services,
+    /// keeping any already defined under the same name.
+    fn import_compose(&mut self) -> Result<()> {
+        let Some(file) = &self.services.from_compose else {
+            return Ok(());
+        };
+        let path = project_dir().join(expand_home(file));
+        for (name, service) in compose::import(&path, &self.services.compose_services)? {
+            self.services.custom.entry(name).or_insert(service);
+        }
+        Ok(())
+    }
+
+    /// Merges another config on top of self. Non-None / non-empty values
+    /// in `other` take precedence.
+    fn merge(&mut self, other: Config) {
+        // Runtimes
+        if other.runtimes.php.is_some() {
+            self.runtimes.php = other.runtimes.php;
+        }
+        if other.runtimes.node.is_some() {
+            self.runtimes.node = other.runtimes.node;
+        }
+        if other.runtimes.rust.is_some() {
+            self.runtimes.rust = other.runtimes.rust;
+        }
+        if other.runtimes.go.is_some() {
+            self.runtimes.go = other.runtimes.go;
+        }
+        self.runtimes.php_ini.extend(other.runtimes.php_ini);
+        if !other.runtimes.php_extensions.is_empty() {
+            self.runtimes.php_extensions = other.runtimes.php_extensions;
+        }
+
+        // Services
+        if other.services.mysql.is_some() {
+            self.services.mysql = other.services.mysql;
+        }
+        if other.services.redis.is_some() {
+            self.services.redis = other.services.redis;
+        }
+        if other.services.postgres.is_some() {
+            self.services.postgres = other.services.postgres;
+        }
+        if other.services.pull_policy.is_some() {
+            self.services.pull_policy = other.services.pull_policy;
+        }
+        if other.services.wait.is_some() {
+            self.services.wait = other.services.wait;
+        }
+        // A custom service is replaced whole by a later layer's definition
+        self.services.custom.extend(other.services.custom);
+        if other.services.from_compose.is_some() {
+            self.services.from_compose = other.services.from_compose;
+        }
+        if !other.services.compose_services.is_empty() {
+            self.services.compose_services = other.services.compose_services;
+        }
+
+        // Hooks (non-empty overrides)
+        if !other.hooks.post_start.is_empty() {
+            self.hooks.post_start = other.hooks.post_start;
+        }
+        if !other.hooks.pre_stop.is_empty() {
+            self.hooks.pre_stop = other.hooks.pre_stop;
+        }
+
+        // Container
+        if other.container.network.is_some() {
+            self.container.network = other.container.network;
+        }
+        if other.container.name.is_some() {
+            self.container.name = other.container.name;
+        }
+        if other.container.shell.is_some() {
+            self.container.shell = other.container.shell;
+        }
+        if !other.container.tmpfs.is_empty() {
+            self.container.tmpfs = other.container.tmpfs;
+        }
+        if other.container.shm_size.is_some() {
+            self.container.shm_size = other.container.shm_size;
+        }
+        if !other.container.groups.is_empty() {
+            self.container.groups = other.container.groups;
+        }
+        if !other.container.devices.is_empty() {
+            self.container.devices = other.container.devices;
+        }
+        if other.container.platform.is_some() {
+            self.container.platform = other.container.platform;
+        }
+
+        // Shell
+        if other.shell.persist_history.is_some() {
+            self.shell.persist_history = other.shell.persist_history;
+        }
+        if other.shell.framework.is_some() {
+            self.shell.framework = other.shell.framework;
+        }
+        if other.shell.dotfiles_repo.is_some() {
+            self.shell.dotfiles_repo = other.shell.dotfiles_repo;
+        }
+        if other.shell.dotfiles_install.is_some() {
+            self.shell.dotfiles_install = other.shell.dotfiles_install;
+        }
+        if !other.shell.dotfiles.is_empty() {
+            self.shell.dotfiles = other.shell.dotfiles;
+        }
+        if !other.shell.dotfiles_exclude.is_empty() {
+            self.shell.dotfiles_exclude = other.shell.dotfiles_exclude;
+        }
+        if other.shell.mount_ssh.is_some() {
+            self.shell.mount_ssh = other.shell.mount_ssh;
+        }
+        if other.shell.motd.is_some() {
+            self.shell.motd = other.shell.motd;
+        }
+
+        // Workspace
+        if other.workspace.root.is_some() {
+            self.workspace.root = other.workspace.root;
+        }
+        if other.workspace.workdir.is_some() {
+            self.workspace.workdir = other.workspace.workdir;
+        }
+        if !other.workspace.extra.is_empty() {
+            self.workspace.extra = other.workspace.extra;
+        }
+        if other.workspace.fix_ownership.is_some() {
+            self.workspace.fix_ownership = other.workspace.fix_ownership;
+        }
+
+        // Mounts
+        if other.mount.selinux_label.is_some() {
+            self.mount.selinux_label = other.mount.selinux_label;
+        }
+        if !other.mount.exclude.is_empty() {
+            self.mount.exclude = other.mount.exclude;
+        }
+
+        // Network
+        if other.network.internal.is_some() {
+            self.network.internal = other.network.internal;
+        }
+        if !other.network.allow.is_empty() {
+            self.network.allow = other.network.allow;
+        }
+        if !other.network.dns.is_empty() {
+            self.network.dns = other.network.dns;
+        }
+        if !other.network.extra_hosts.is_empty() {
+            self.network.extra_hosts = other.network.extra_hosts;
+        }
+        if other.network.proxy.is_some() {
+            self.network.proxy = other.network.proxy;
+        }
+        if other.network.no_proxy.is_some() {
+            self.network.no_proxy = other.network.no_proxy;
+        }
+
+        // TLS
+        if !other.tls.ca_certs.is_empty() {
+            self.tls.ca_certs = other.tls.ca_certs;
+        }
+
+        // Base image
+        if other.base.minimal.is_some() {
+            self.base.minimal = other.base.minimal;
+        }
+
+        // Build
+        if other.build.buildkit.is_some() {
+            self.build.buildkit = other.build.buildkit;
+        }
+        if other.build.timeout.is_some() {
+            self.build.timeout = other.build.timeout;
+        }
+        if other.build.log.is_some() {
+            self.build.log = other.build.log;
+        }
+        if !other.build.files.is_empty() {
+            self.build.files = other.build.files;
+        }
+
+        // Docker API
+        if other.docker.retries.is_some() {
+            self.docker.retries = other.docker.retries;
+        }
+
+        // Agents
+        if other.agent.auto_branch.is_some() {
+            self.agent.auto_branch = other.agent.auto_branch;
+        }
+
+        // Claude Code
+        if other.claude.write_env_context.is_some() {
+            self.claude.write_env_context = other.claude.write_env_context;
+        }
+        if other.claude.persist_sessions.is_some() {
+            self.claude.persist_sessions = other.claude.persist_sessions;
+        }
+
+        // Auth
+        if !other.auth.providers.is_empty() {
+            self.auth.providers = other.auth.providers;
+        }
+        if other.auth.onepassword.is_some() {
+            self.auth.onepassword = other.auth.onepassword;
+        }
+        if other.auth.verify.is_some() {
+            self.auth.verify = other.auth.verify;
+        }
+        if other.auth.refresh.is_some() {
+            self.auth.refresh = other.auth.refresh;
+        }
+
+        // Notifications
+        if other.notify.desktop.is_some() {
+            self.notify.desktop = other.notify.desktop;
+        }
+        if other.notify.webhook.is_some() {
+            self.notify.webhook = other.notify.webhook;
+        }
+
+        // Telemetry
+        if other.telemetry.endpoint.is_some() {
+            self.telemetry.endpoint = other.telemetry.endpoint;
+        }
+
+        // Mirrors
+        if other.mirrors.apt.is_some() {
+            self.mirrors.apt = other.mirrors.apt;
+        }
+        if other.mirrors.apt_ports.is_some() {
+            self.mirrors.apt_ports = other.mirrors.apt_ports;
+        }
+        if other.mirrors.npm.is_some() {
+            self.mirrors.npm = other.mirrors.npm;
+        }
+        if other.mirrors.composer.is_some() {
+            self.mirrors.composer = other.mirrors.composer;
+        }
+        if other.mirrors.crates.is_some() {
+            self.mirrors.crates = other.mirrors.crates;
+        }
+        if other.mirrors.go.is_some() {
+            self.mirrors.go = other.mirrors.go;
+        }
+
+        // Template vars (per key)
+        self.template.vars.extend(other.template.vars);
+
+        // Registries (per host)
+        self.registry.extend(other.registry);
+
+        // Workers (non-empty overrides)
+        if !other.workers.is_empty() {
+            self.workers = other.workers;
+        }
+
+        // Artifacts (non-empty overrides)
+        if !other.artifacts.is_empty() {
+            self.artifacts = other.artifacts;
+        }
+
+        // Setup commands (non-empty overrides)
+        if !other.setup.is_empty() {
+            self.setup = other.setup;
+        }
+
+        // Laravel
+        if other.laravel.manage_env.is_some() {
+            self.laravel.manage_env = other.laravel.manage_env;
+        }
+    }
+
+    /// Applies CLI flags on top of the current config. CLI flags always win
+    /// when they are explicitly set.
+    fn apply_cli(&mut self, cli: &Cli) {
+        self.apply_runtime_flags(&cli.runtime);
+        self.apply_service_flags(&cli.service);
+        self.apply_container_flags(&cli.container);
+    }
+
+    fn apply_runtime_flags(&mut self, flags: &RuntimeFlags) {
+        if flags.php.is_some() {
+            self.runtimes.php.clone_from(&flags.php);
+        }
+        if flags.node.is_some() {
+            self.runtimes.node.clone_from(&flags.node);
+        }
+        if flags.rust {
+            self.runtimes.rust = Some(true);
+        }
+        if flags.go.is_some() {
+            self.runtimes.go.clone_from(&flags.go);
+        }
+    }
+
+    fn apply_service_flags(&mut self, flags: &ServiceFlags) {
+        if let Some(ref version) = flags.mysql {
+            let mut mysql = self.services.mysql.clone().unwrap_or_default();
+            mysql.version = version.clone();
+            self.services.mysql = Some(mysql);
+        }
+        if flags.redis {
+            self.services.redis = Some(true);
+        }
+        if let Some(ref version) = flags.postgres {
+            let mut pg = self.services.postgres.clone().unwrap_or_default();
+            pg.version = version.clone();
+            self.services.postgres = Some(pg);
+        }
+        if flags.no_wait {
+            self.services.wait = Some(false);
+        }
+    }
+
+    fn apply_container_flags(&mut self, flags: &ContainerFlags) {
+        if flags.network.is_some() {
+            self.container.network.clone_from(&flags.network);
+        }
+        if flags.name.is_some() {
+            self.container.name.clone_from(&flags.name);
+        }
+        if flags.platform.is_some() {
+            self.container.platform.clone_from(&flags.platform);
+        }
+        // shell always has a value from clap default, but we only override
+        // if it differs from the default "zsh" (meaning user explicitly set it)
+        // or if no config file set a shell.
+        if flags.shell != "bash" || self.container.shell.is_none() {
+            self.container.shell = Some(flags.shell.clone());
+        }
+        if flags.no_internet {
+            self.network.internal = Some(true);
+        }
+        if flags.build_log.is_some() {
+            self.build.log.clone_from(&flags.build_log);
+        }
+    }
+}
+
+// -- File loading --
+
+/// Expands a leading `~/` to the user's home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()
+            .map(|home| home.join(rest))
+            .unwrap_or_else(|| PathBuf::from(path)),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Project config file name, discovered upward from the current directory.
+const PROJECT_CONFIG: &str = ".bubble-bot.toml";
+
+/// Untracked local overlay, read from the same directory as the project config.
+const LOCAL_CONFIG: &str = ".bubble-bot.local.toml";
+
+/// Finds the nearest directory at or above `start` that contains a project or
+/// local config. The search stops at the git root so configs outside the
+/// repository are never picked up.
+pub fn find_project_dir(start: &Path) -> Option<PathBuf> {
+    for dir in start.ancestors() {
+        if dir.join(PROJECT_CONFIG).is_file() || dir.join(LOCAL_CONFIG).is_file() {
+            return Some(dir.to_path_buf());
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+/// The root of the git repository containing `dir`, if any.
+pub fn git_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|d| d.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Project root given with `--project-dir`, replacing discovery.
+static PROJECT_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// The project root: the `--project-dir`, else the nearest directory with a
+/// config, falling back to the current directory. It is mounted as
+/// `/workspace` and, through [`project_id`], names the session's containers
+/// and network.
+pub fn project_dir() -> PathBuf {
+    if let Some(dir) = PROJECT_DIR.get() {
+        return dir.clone();
+    }
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    find_project_dir(&cwd).unwrap_or(cwd)
+}
+
+/// Names the project's containers and network: the project directory's
+/// name and a hash of its full path, so two checkouts with the same name
+/// (two `api` repos) don't share, or remove, each other's containers.
+pub fn project_id() -> String {
+    project_id_for(&project_dir())
+}
+
+fn project_id_for(dir: &Path) -> String {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let name = dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "project".to_string());
+    let digest = Sha256::digest(dir.to_string_lossy().as_bytes());
+    let hex: String = digest.iter().take(4).map(|b| format!("{b:02x}")).collect();
+    format!("{name}-{hex}")
+}
+
+/// Uses `dir` as the project root for the rest of the process, instead of
+/// discovering it from the current directory. Call before loading config.
+pub fn set_project_dir(dir: &Path) -> Result<()> {
+    let dir = resolve_project_dir(dir)?;
+    debug!(dir = %dir.display(), "project directory set");
+    // Only the first call takes effect
+    let _ = PROJECT_DIR.set(dir);
+    Ok(())
+}
+
+fn resolve_project_dir(dir: &Path) -> Result<PathBuf> {
+    let resolved = dir
+        .canonicalize()
+        .with_context(|| format!("project directory `{}` not found", dir.display()))?;
+    if !resolved.is_dir() {
+        bail!("project directory `{}` is not a directory", dir.display());
+    }
+    Ok(resolved)
+}
+
+fn global_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|d| d.join("bubble-bot").join("config.toml"))
+}
+
+/// The layer a config file belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    Global,
+    Project,
+    /// Untracked per-developer overlay on top of the project config.
+    Local,
+    /// File given by `--config` / `BUBBLE_BOT_CONFIG`, replacing the project
+    /// and local configs.
+    Explicit,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layer::Global => write!(f, "global config"),
+            Layer::Project => write!(f, "project config"),
+            Layer::Local => write!(f, "local config"),
+            Layer::Explicit => write!(f, "explicit config"),
+        }
+    }
+}
+
+/// A config file location and the layer it is merged as.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigFile {
+    pub layer: Layer,
+    pub path: PathBuf,
+}
+
+/// Config files in merge order (lowest precedence first). Files that don't
+/// exist are included; callers skip them. An `explicit` file replaces the
+/// project and local configs in the current directory.
+pub fn config_files(explicit: Option<&Path>) -> Vec<ConfigFile> {
+    let mut files: Vec<ConfigFile> = global_config_path()
+        .map(|path| ConfigFile {
+            layer: Layer::Global,
+            path,
+        })
+        .into_iter()
+        .collect();
+    if let Some(path) = explicit {
+        files.push(ConfigFile {
+            layer: Layer::Explicit,
+            path: path.to_path_buf(),
+        });
+        return files;
+    }
+    let dir = project_dir();
+    files.push(ConfigFile {
+        layer: Layer::Project,
+        path: dir.join(PROJECT_CONFIG),
+    });
+    files.push(ConfigFile {
+        layer: Layer::Local,
+        path: dir.join(LOCAL_CONFIG),
+    });
+    files
+}
+
+/// The file `config set`/`config unset` edit: the global config with
+/// `global`, otherwise the explicit config if given, else the project config.
+pub fn edit_target(global: bool, explicit: Option<&Path>) -> Result<PathBuf> {
+    if global {
+        return global_config_path().context("could not determine the global config directory");
+    }
+    Ok(explicit
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| project_dir().join(PROJECT_CONFIG)))
+}
+
+/// Loads every config file that exists, in merge order. An explicit config
+/// file must exist.
+fn load_files(explicit: Option<&Path>) -> Result<Vec<(ConfigFile, Config)>> {
+    let mut loaded = Vec::new();
+    for file in config_files(explicit) {
+        match load_from_file(&file.path)? {
+            Some(file_config) => {
+                debug!("loaded {} from {}", file.layer, file.path.display());
+                loaded.push((file, file_config));
+            }
+            None if file.layer == Layer::Explicit => {
+                bail!("config file {} not found", file.path.display());
+            }
+            None => {}
+        }
+    }
+    Ok(loaded)
+}
+
+fn load_from_file(path: &Path) -> Result<Option<Config>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => {
+            let config: Config = toml::from_str(&contents)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+            Ok(Some(config))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    /// Helper to parse a TOML string into Config.
+    fn parse_toml(s: &str) -> Config {
+        toml::from_str(s).expect("valid TOML")
+    }
+
+    #[test]
+    fn default_config_has_empty_values() {
+        let config = Config::default();
+        assert!(config.runtimes.php.is_none());
+        assert!(config.runtimes.node.is_none());
+        assert!(config.runtimes.rust.is_none());
+        assert!(config.runtimes.go.is_none());
+        assert!(config.services.mysql.is_none());
+        assert!(config.services.redis.is_none());
+        assert!(config.services.postgres.is_none());
+        assert!(config.hooks.post_start.is_empty());
+        assert!(config.hooks.pre_stop.is_empty());
+    }
+
+    #[test]
+    fn parse_full_toml() {
+        let config = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.3"
+            node = "22"
+            rust = true
+            go = "1.23"
+
+            [services.mysql]
+            version = "8.4"
+            database = "mydb"
+            username = "admin"
+            password = "secret"
+
+            [services]
+            redis = true
+
+            [services.postgres]
+            version = "15"
+            database = "pgdb"
+            username = "pguser"
+            password = "pgpass"
+
+            [hooks]
+            post_start = ["composer install", "npm ci"]
+            pre_stop = ["echo bye"]
+
+            [container]
+            network = "custom-net"
+            name = "my-container"
+            shell = "bash"
+            "#,
+        );
+
+        assert_eq!(config.runtimes.php.as_deref(), Some("8.3"));
+        assert_eq!(config.runtimes.node.as_deref(), Some("22"));
+        assert_eq!(config.runtimes.rust, Some(true));
+        assert_eq!(config.runtimes.go.as_deref(), Some("1.23"));
+
+        let mysql = config.services.mysql.unwrap();
+        assert_eq!(mysql.version, "8.4");
+        assert_eq!(mysql.database, "mydb");
+        assert_eq!(mysql.username, "admin");
+        assert_eq!(mysql.password, "secret");
+
+        assert_eq!(config.services.redis, Some(true));
+
+        let pg = config.services.postgres.unwrap();
+        assert_eq!(pg.version, "15");
+        assert_eq!(pg.database, "pgdb");
+        assert_eq!(pg.username, "pguser");
+        assert_eq!(pg.password, "pgpass");
+
+        assert_eq!(config.hooks.post_start, vec!["composer install", "npm ci"]);
+        assert_eq!(config.hooks.pre_stop, vec!["echo bye"]);
+
+        assert_eq!(config.container.network.as_deref(), Some("custom-net"));
+        assert_eq!(config.container.name.as_deref(), Some("my-container"));
+        assert_eq!(config.container.shell.as_deref(), Some("bash"));
+    }
+
+    #[test]
+    fn parse_partial_toml_uses_defaults() {
+        let config = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.2"
+            "#,
+        );
+        assert_eq!(config.runtimes.php.as_deref(), Some("8.2"));
+        assert!(config.runtimes.node.is_none());
+        assert!(config.services.mysql.is_none());
+        assert!(config.hooks.post_start.is_empty());
+    }
+
+    #[test]
+    fn empty_toml_parses_to_defaults() {
+        let config = parse_toml("");
+        assert!(config.runtimes.php.is_none());
+        assert!(config.services.mysql.is_none());
+    }
+
+    #[test]
+    fn merge_overrides_set_values() {
+        let mut base = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.2"
+            node = "20"
+            "#,
+        );
+        let overlay = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.3"
+            "#,
+        );
+
+        base.merge(overlay);
+
+        // php overridden
+        assert_eq!(base.runtimes.php.as_deref(), Some("8.3"));
+        // node preserved from base
+        assert_eq!(base.runtimes.node.as_deref(), Some("20"));
+    }
+
+    #[test]
+    fn template_vars_merge_per_key() {
+        let mut base = parse_toml(
+            r#"
+            [template.vars]
+            apt_extra = "postgresql-client"
+            workers = 2
+            "#,
+        );
+        let overlay = parse_toml(
+            r#"
+            [template.vars]
+            workers = 4
+            "#,
+        );
+
+        base.merge(overlay);
+
+        assert_eq!(
+            base.template.vars.get("apt_extra"),
+            Some(&toml::Value::from("postgresql-client"))
+        );
+        assert_eq!(
+            base.template.vars.get("workers"),
+            Some(&toml::Value::from(4))
+        );
+    }
+
+    #[test]
+    fn merge_does_not_clear_values() {
+        let mut base = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.2"
+
+            [services.mysql]
+            version = "8.0"
+            database = "mydb"
+            username = "root"
+            password = "secret"
+
+            [hooks]
+            post_start = ["npm ci"]
+            "#,
+        );
+        // Overlay has no runtimes, no mysql, no hooks
+        let overlay = parse_toml("");
+
+        base.merge(overlay);
+
+        assert_eq!(base.runtimes.php.as_deref(), Some("8.2"));
+        assert!(base.services.mysql.is_some());
+        assert_eq!(base.hooks.post_start, vec!["npm ci"]);
+    }
+
+    #[test]
+    fn merge_three_layers() {
+        // Simulates: defaults -> global -> project
+        let mut config = Config::default();
+
+        let global = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.2"
+            "#,
+        );
+        config.merge(global);
+
+        let project = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.3"
+            node = "22"
+            "#,
+        );
+        config.merge(project);
+
+        // php overridden by project
+        assert_eq!(config.runtimes.php.as_deref(), Some("8.3"));
+        // node from project
+        assert_eq!(config.runtimes.node.as_deref(), Some("22"));
+    }
+
+    #[test]
+    fn cli_flags_override_config() {
+        let mut config = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.2"
+            node = "20"
+
+            [container]
+            name = "config-name"
+            "#,
+        );
+
+        let cli = Cli::parse_from(["bubble-bot", "--with-php", "8.3", "--name", "cli-name"]);
+        config.apply_cli(&cli);
+
+        // CLI overrides php
+        assert_eq!(config.runtimes.php.as_deref(), Some("8.3"));
+        // node preserved from config (CLI didn't set it)
+        assert_eq!(config.runtimes.node.as_deref(), Some("20"));
+        // name overridden by CLI
+        assert_eq!(config.container.name.as_deref(), Some("cli-name"));
+    }
+
+    #[test]
+    fn cli_service_flags_merge_with_config() {
+        let mut config = parse_toml(
+            r#"
+            [services.mysql]
+            database = "mydb"
+            username = "admin"
+            password = "secret"
+            "#,
+        );
+
+        // CLI specifies --with-mysql 8.4 (overrides version only)
+        let cli = Cli::parse_from(["bubble-bot", "--with-mysql", "8.4"]);
+        config.apply_cli(&cli);
+
+        let mysql = config.services.mysql.unwrap();
+        assert_eq!(mysql.version, "8.4");
+        // Config values preserved
+        assert_eq!(mysql.database, "mydb");
+        assert_eq!(mysql.username, "admin");
+        assert_eq!(mysql.password, "secret");
+    }
+
+    #[test]
+    fn full_merge_precedence() {
+        // defaults -> global -> project -> CLI
+        let mut config = Config::default();
+
+        // Global sets php 8.1
+        let global = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.1"
+
+            [hooks]
+            post_start = ["global-hook"]
+            "#,
+        );
+        config.merge(global);
+
+        // Project overrides php to 8.2 and adds node
+        let project = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.2"
+            node = "20"
+
+            [hooks]
+            post_start = ["project-hook"]
+            "#,
+        );
+        config.merge(project);
+
+        // CLI overrides php to 8.3
+        let cli = Cli::parse_from(["bubble-bot", "--with-php", "8.3"]);
+        config.apply_cli(&cli);
+
+        // Final results
+        assert_eq!(config.runtimes.php.as_deref(), Some("8.3")); // CLI wins
+        assert_eq!(config.runtimes.node.as_deref(), Some("20")); // project
+        assert_eq!(config.hooks.post_start, vec!["project-hook"]); // project overrides global
+    }
+
+    #[test]
+    fn config_files_in_merge_order() {
+        let files = config_files(None);
+        let layers: Vec<Layer> = files.iter().map(|f| f.layer).collect();
+        assert!(layers.ends_with(&[Layer::Project, Layer::Local]));
+        assert!(
+            files
+                .last()
+                .unwrap()
+                .path
+                .ends_with(".bubble-bot.local.toml")
+        );
+    }
+
+    #[test]
+    fn find_project_dir_walks_up_to_nearest_config() {
+        let root = tempfile::tempdir().unwrap();
+        let nested = root.path().join("backend/src");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(root.path().join(".bubble-bot.toml"), "").unwrap();
+
+        assert_eq!(find_project_dir(&nested).unwrap(), root.path());
+    }
+
+    #[test]
+    fn find_project_dir_prefers_nearest() {
+        let root = tempfile::tempdir().unwrap();
+        let backend = root.path().join("backend");
+        std::fs::create_dir_all(&backend).unwrap();
+        std::fs::write(root.path().join(".bubble-bot.toml"), "").unwrap();
+        std::fs::write(backend.join(".bubble-bot.local.toml"), "").unwrap();
+
+        assert_eq!(find_project_dir(&backend).unwrap(), backend);
+    }
+
+    #[test]
+    fn project_dir_must_be_an_existing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            resolve_project_dir(&dir.path().join(".")).unwrap(),
+            dir.path().canonicalize().unwrap()
+        );
+
+        let err = resolve_project_dir(&dir.path().join("missing")).unwrap_err();
+        assert!(err.to_string().contains("not found"), "{err}");
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        let err = resolve_project_dir(&file).unwrap_err();
+        assert!(err.to_string().contains("is not a directory"), "{err}");
+    }
+
+    #[test]
+    fn project_id_tells_same_named_checkouts_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("work/api");
+        let second = dir.path().join("clients/api");
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+
+        let id = project_id_for(&first);
+        let (name, hash) = id.rsplit_once('-').unwrap();
+        assert_eq!(name, "api");
+        assert_eq!(hash.len(), 8);
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()), "{id}");
+        assert_eq!(project_id_for(&first.join("../api")), id);
+        assert_ne!(project_id_for(&second), id);
+    }
+
+    #[test]
+    fn workspace_auto_git_mounts_the_repository_root() {
+        let repo = tempfile::tempdir().unwrap();
+        std::fs::create_dir(repo.path().join(".git")).unwrap();
+        let api = repo.path().join("services/api");
+        std::fs::create_dir_all(&api).unwrap();
+
+        let mut workspace = parse_toml(
+            r#"
+            [workspace]
+            root = "auto-git"
+            workdir = "services/api/"
+            "#,
+        )
+        .workspace;
+        assert_eq!(workspace.mount_root(&api), repo.path());
+        assert_eq!(
+            workspace.workdir_path().unwrap(),
+            Some(Path::new("services/api"))
+        );
+
+        workspace.root = Some(WorkspaceRoot::Project);
+        assert_eq!(workspace.mount_root(&api), api);
+        // Outside a repository, the project root is mounted
+        let plain = tempfile::tempdir().unwrap();
+        workspace.root = Some(WorkspaceRoot::AutoGit);
+        assert_eq!(workspace.mount_root(plain.path()), plain.path());
+    }
+
+    #[test]
+    fn workspace_extra_mounts_sibling_directories() {
+        let parent = tempfile::tempdir().unwrap();
+        let parent = parent.path().canonicalize().unwrap();
+        let project = parent.join("app");
+        for dir in ["app", "shared-ui", "billing"] {
+            std::fs::create_dir(parent.join(dir)).unwrap();
+        }
+
+        let workspace = parse_toml(
+            r#"
+            [[workspace.extra]]
+            source = "../shared-ui"
+
+            [[workspace.extra]]
+            source = "../billing"
+            target = "/srv/billing/"
+            read_only = true
+            "#,
+        )
+        .workspace;
+        assert_eq!(
+            workspace.extra_binds_under(&project).unwrap(),
+            vec![
+                format!(
+                    "{}:/workspace-libs/shared-ui",
+                    parent.join("shared-ui").display()
+                ),
+                format!("{}:/srv/billing:ro", parent.join("billing").display()),
+            ]
+        );
+    }
+
+    #[test]
+    fn workspace_extra_rejects_bad_mounts() {
+        let parent = tempfile::tempdir().unwrap();
+        std::fs::create_dir(parent.path().join("lib")).unwrap();
+        let mount = |source: &str, target: Option<&str>| WorkspaceMount {
+            source: source.to_string(),
+            target: target.map(str::to_string),
+            ..Default::default()
+        };
+        let cases = [
+            (vec![mount("missing", None)], "not found"),
+            (
+                vec![mount("lib", Some("/workspace/lib"))],
+                "outside /workspace",
+            ),
+            (vec![mount("lib", Some("libs/lib"))], "outside /workspace"),
+            (
+                vec![mount("lib", None), mount("lib", None)],
+                "two directories",
+            ),
+        ];
+        for (extra, message) in cases {
+            let workspace = WorkspaceConfig {
+                extra,
+                ..Default::default()
+            };
+            let err = workspace
+                .extra_binds_under(parent.path())
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains(message), "{err}");
+        }
+    }
+
+    #[test]
+    fn workspace_workdir_must_stay_inside() {
+        for workdir in ["/srv/api", "../api", "services/../api", ""] {
+            let workspace = WorkspaceConfig {
+                workdir: Some(workdir.to_string()),
+                ..Default::default()
+            };
+            assert!(workspace.workdir_path().is_err(), "{workdir}");
+        }
+    }
+
+    #[test]
+    fn find_project_dir_stops_at_git_root() {
+        let root = tempfile::tempdir().unwrap();
+        let repo = root.path().join("repo");
+        let nested = repo.join("backend");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir(repo.join(".git")).unwrap();
+        std::fs::write(root.path().join(".bubble-bot.toml"), "").unwrap();
+
+        assert!(find_project_dir(&nested).is_none());
+    }
+
+    #[test]
+    fn explicit_config_replaces_project_and_local() {
+        let files = config_files(Some(Path::new("envs/ci.toml")));
+        let last = files.last().unwrap();
+        assert_eq!(last.layer, Layer::Explicit);
+        assert_eq!(last.path, Path::new("envs/ci.toml"));
+        assert!(files.iter().all(|f| f.layer != Layer::Project));
+    }
+
+    #[test]
+    fn missing_explicit_config_errors() {
+        let err = load_files(Some(Path::new("/nonexistent/ci.toml"))).unwrap_err();
+        assert!(err.to_string().contains("/nonexistent/ci.toml not found"));
+    }
+
+    #[test]
+    fn explicit_config_is_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ci.toml");
+        std::fs::write(&path, "[runtimes]\nnode = \"22\"\n").unwrap();
+        let cli = Cli::parse_from(["bubble-bot", "--config", path.to_str().unwrap()]);
+        let config = Config::load(&cli).unwrap();
+        assert_eq!(config.runtimes.node.as_deref(), Some("22"));
+    }
+
+    #[test]
+    fn missing_config_file_returns_none() {
+        let result = load_from_file(Path::new("/nonexistent/config.toml")).unwrap();
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn expand_home_replaces_tilde() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_home("~/corp-root.pem"), home.join("corp-root.pem"));
+        assert_eq!(
+            expand_home("/etc/ssl/ca.pem"),
+            PathBuf::from("/etc/ssl/ca.pem")
+        );
+        assert_eq!(expand_home("relative.pem"), PathBuf::from("relative.pem"));
+    }
+
+    #[test]
+    fn parse_and_merge_tls_ca_certs() {
+        let mut base = parse_toml(
+            r#"
+            [tls]
+            ca_certs = ["~/corp-root.pem"]
+            "#,
+        );
+        base.merge(parse_toml(""));
+        assert_eq!(base.tls.ca_certs, vec!["~/corp-root.pem"]);
+    }
+
+    #[test]
+    fn merge_mirrors_per_key() {
+        let mut base = parse_toml(
+            r#"
+            [mirrors]
+            apt = "http://mirror.corp/ubuntu/"
+            npm = "https://npm.corp/"
+            "#,
+        );
+        base.merge(parse_toml(
+            r#"
+            [mirrors]
+            npm = "https://npm.project/"
+            go = "https://goproxy.corp"
+            "#,
+        ));
+        assert_eq!(
+            base.mirrors.apt.as_deref(),
+            Some("http://mirror.corp/ubuntu/")
+        );
+        assert_eq!(base.mirrors.npm.as_deref(), Some("https://npm.project/"));
+        assert_eq!(base.mirrors.go.as_deref(), Some("https://goproxy.corp"));
+        assert!(base.mirrors.composer.is_none());
+    }
+
+    #[test]
+    fn parse_workers() {
+        let config = parse_toml(
+            r#"
+            [[workers]]
+            cmd = "php artisan queue:work"
+            depends_on_service = "redis"
+
+            [[workers]]
+            name = "vite"
+            cmd = "npm run dev"
+            restart = "always"
+            "#,
+        );
+        assert_eq!(config.workers.len(), 2);
+        assert_eq!(config.workers[0].display_name(), "php");
+        assert_eq!(config.workers[0].restart, RestartPolicy::OnFailure);
+        assert_eq!(
+            config.workers[0].depends_on_service.as_deref(),
+            Some("redis")
+        );
+        assert_eq!(config.workers[1].display_name(), "vite");
+        assert_eq!(config.workers[1].restart, RestartPolicy::Always);
+    }
+
+    #[test]
+    fn parse_artifacts() {
+        let config = parse_toml(
+            r#"
+            [[artifacts]]
+            from = "coverage/*.xml"
+            to = "build/coverage"
+
+            [[artifacts]]
+            from = "/tmp/agent.log"
+            to = "/var/log/bubble-bot"
+            "#,
+        );
+        let project = Path::new("/home/me/app");
+        assert_eq!(config.artifacts.len(), 2);
+        assert_eq!(config.artifacts[0].from, "coverage/*.xml");
+        assert_eq!(
+            config.artifacts[0].host_dir(project),
+            Path::new("/home/me/app/build/coverage")
+        );
+        assert_eq!(
+            config.artifacts[1].host_dir(project),
+            Path::new("/var/log/bubble-bot")
+        );
+    }
+
+    #[test]
+    fn setup_parses_and_later_list_replaces() {
+        let mut base = parse_toml("setup = [\"npm install -g pnpm\"]\n");
+        assert_eq!(base.setup, vec!["npm install -g pnpm"]);
+
+        base.merge(parse_toml(""));
+        assert_eq!(base.setup.len(), 1);
+        base.merge(parse_toml("setup = [\"pip install poetry\"]\n"));
+        assert_eq!(base.setup, vec!["pip install poetry"]);
+    }
+
+    #[test]
+    fn merge_workers_replaces_list() {
+        let mut base = parse_toml("[[workers]]\ncmd = \"a\"\n\n[[workers]]\ncmd = \"b\"\n");
+        base.merge(parse_toml("[runtimes]\nphp = \"8.3\"\n"));
+        assert_eq!(base.workers.len(), 2);
+
+        base.merge(parse_toml("[[workers]]\ncmd = \"c\"\n"));
+        assert_eq!(base.workers.len(), 1);
+        assert_eq!(base.workers[0].cmd, "c");
+    }
+
+    #[test]
+    fn merge_laravel_manage_env() {
+        let mut base = parse_toml("[laravel]\nmanage_env = true\n");
+        base.merge(parse_toml("[runtimes]\nphp = \"8.3\"\n"));
+        assert_eq!(base.laravel.manage_env, Some(true));
+
+        base.merge(parse_toml("[laravel]\nmanage_env = false\n"));
+        assert_eq!(base.laravel.manage_env, Some(false));
+    }
+
+    #[test]
+    fn merge_registries_per_host() {
+        let mut base = parse_toml(
+            r#"
+            [registry."ghcr.io"]
+            username = "me"
+            password_env = "GHCR_TOKEN"
+
+            [registry."registry.corp:5000"]
+            username = "ci"
+            "#,
+        );
+        base.merge(parse_toml(
+            r#"
+            [registry."ghcr.io"]
+            username = "bot"
+            password = "secret"
+            "#,
+        ));
+        let ghcr = &base.registry["ghcr.io"];
+        assert_eq!(ghcr.username.as_deref(), Some("bot"));
+        assert_eq!(ghcr.password.as_deref(), Some("secret"));
+        assert!(ghcr.password_env.is_none());
+        assert_eq!(
+            base.registry["registry.corp:5000"].username.as_deref(),
+            Some("ci")
+        );
+    }
+
+    #[test]
+    fn parse_mysql_settings_and_init_scripts() {
+        let config = parse_toml(
+            r#"
+            [services.mysql]
+            init_scripts = ["db/schema.sql"]
+
+            [services.mysql.settings]
+            sql_mode = "STRICT_TRANS_TABLES"
+            max_connections = 500
+            "#,
+        );
+        let mysql = config.services.mysql.unwrap();
+        assert_eq!(mysql.version, "8.0");
+        assert_eq!(mysql.settings["max_connections"].as_integer(), Some(500));
+        assert_eq!(mysql.init_scripts, vec!["db/schema.sql"]);
+        assert!(mysql.init_script_paths()[0].ends_with("db/schema.sql"));
+    }
+
+    #[test]
+    fn parse_and_merge_pull_policy() {
+        let mut config = parse_toml("[services]\npull_policy = \"always\"\n");
+        assert_eq!(config.services.pull_policy, Some(PullPolicy::Always));
+
+        config.merge(parse_toml("[services]\nredis = true\n"));
+        assert_eq!(config.services.pull_policy, Some(PullPolicy::Always));
+
+        config.merge(parse_toml("[services]\npull_policy = \"never\"\n"));
+        assert_eq!(config.services.pull_policy, Some(PullPolicy::Never));
+    }
+
+    #[test]
+    fn services_wait_defaults_on_and_merges() {
+        let mut config = Config::default();
+        assert!(config.services.wait());
+
+        config.merge(parse_toml("[services]\nwait = false\n"));
+        assert!(!config.services.wait());
+
+        config.merge(parse_toml("[services]\nredis = true\n"));
+        assert!(!config.services.wait());
+    }
+
+    #[test]
+    fn cli_no_wait_flag_disables_waiting() {
+        let mut config = parse_toml("[services]\nwait = true\n");
+        let cli = Cli::parse_from(["bubble-bot", "--no-wait"]);
+        config.apply_cli(&cli);
+        assert!(!config.services.wait());
+    }
+
+    #[test]
+    fn invalid_pull_policy_is_rejected() {
+        let result: Result<Config, _> = toml::from_str("[services]\npull_policy = \"sometimes\"\n");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn mysql_config_defaults() {
+        let mysql = MysqlConfig::default();
+        assert_eq!(mysql.version, "8.0");
+        assert_eq!(mysql.database, "app");
+        assert_eq!(mysql.username, "root");
+        assert_eq!(mysql.password, "password");
+    }
+
+    #[test]
+    fn postgres_config_defaults() {
+        let pg = PostgresConfig::default();
+        assert_eq!(pg.version, "16");
+        assert_eq!(pg.database, "app");
+        assert_eq!(pg.username, "postgres");
+        assert_eq!(pg.password, "password");
+    }
+
+    #[test]
+    fn service_config_partial_mysql() {
+        let config = parse_toml(
+            r#"
+            [services.mysql]
+            database = "custom_db"
+            "#,
+        );
+        let mysql = config.services.mysql.unwrap();
+        assert_eq!(mysql.database, "custom_db");
+        // Other fields use defaults
+        assert_eq!(mysql.version, "8.0");
+        assert_eq!(mysql.username, "root");
+        assert_eq!(mysql.password, "password");
+    }
+
+    #[test]
+    fn cli_redis_flag_enables_service() {
+        let mut config = Config::default();
+        let cli = Cli::parse_from(["bubble-bot", "--with-redis"]);
+        config.apply_cli(&cli);
+        assert_eq!(config.services.redis, Some(true));
+    }
+
+    #[test]
+    fn cli_rust_flag_enables_runtime() {
+        let mut config = Config::default();
+        let cli = Cli::parse_from(["bubble-bot", "--with-rust"]);
+        config.apply_cli(&cli);
+        assert_eq!(config.runtimes.rust, Some(true));
+    }
+
+    #[test]
+    fn shell_config_from_cli_when_explicit() {
+        let mut config = parse_toml(
+            r#"
+            [container]
+            shell = "fish"
+            "#,
+        );
+        // CLI with explicit --shell zsh overrides config
+        let cli = Cli::parse_from(["bubble-bot", "--shell", "zsh"]);
+        config.apply_cli(&cli);
+        assert_eq!(config.container.shell.as_deref(), Some("zsh"));
+    }
+
+    #[test]
+    fn platform_flag_overrides_config() {
+        let mut config = parse_toml("[container]\nplatform = \"linux/arm64\"\n");
+        config.apply_cli(&Cli::parse_from(["bubble-bot"]));
+        assert_eq!(config.container.platform.as_deref(), Some("linux/arm64"));
+
+        let cli = Cli::parse_from(["bubble-bot", "--platform", "linux/amd64"]);
+        config.apply_cli(&cli);
+        assert_eq!(config.container.platform.as_deref(), Some("linux/amd64"));
+    }
+
+    #[test]
+    fn shell_config_preserved_when_cli_default() {
+        let mut config = parse_toml(
+            r#"
+            [container]
+            shell = "fish"
+            "#,
+        );
+        // CLI with default --shell bash does not override config "fish"
+        let cli = Cli::parse_from(["bubble-bot"]);
+        config.apply_cli(&cli);
+        assert_eq!(config.container.shell.as_deref(), Some("fish"));
+    }
+
+    #[test]
+    fn config_serializes_to_toml() {
+        let config = parse_toml(
+            r#"
+            [runtimes]
+            php = "8.3"
+            node = "22"
+
+            [services.mysql]
+            version = "8.4"
+            database = "mydb"
+            username = "admin"
+            password = "secret"
+
+            [services]
+            redis = true
+
+            [hooks]
+            post_start = ["composer install"]
+
+            [container]
+            shell = "bash"
+            "#,
+        );
+        let output = toml::to_string_pretty(&config).expect("serialize to TOML");
+        assert!(output.contains("php = \"8.3\""));
+        assert!(output.contains("node = \"22\""));
+        assert!(output.contains("version = \"8.4\""));
+        assert!(output.contains("redis = true"));
+        assert!(output.contains("shell = \"bash\""));
+    }
+
+    #[test]
+    fn parse_network_internal() {
+        let config = parse_toml(
+            r#"
+            [network]
+            internal = true
+            "#,
+        );
+        assert_eq!(config.network.internal, Some(true));
+    }
+
+    #[test]
+    fn merge_network_internal() {
+        let mut base = parse_toml(
+            r#"
+            [network]
+            internal = true
+            "#,
+        );
+        base.merge(parse_toml(""));
+        assert_eq!(base.network.internal, Some(true));
+
+        base.merge(parse_toml(
+            r#"
+            [network]
+            internal = false
+            "#,
+        ));
+        assert_eq!(base.network.internal, Some(false));
+    }
+
+    #[test]
+    fn build_timeout_parses_durations() {
+        let mut config = parse_toml(
+            r#"
+            [build]
+            timeout = "1h30m"
+            "#,
+        );
+        assert_eq!(
+            config.build.timeout_duration().unwrap(),
+            Some(Duration::from_secs(5400))
+        );
+        assert_eq!(Config::default().build.timeout_duration().unwrap(), None);
+
+        config.build.timeout = Some("20".to_string());
+        let err = config.build.timeout_duration().unwrap_err().to_string();
+        assert!(err.contains("invalid build.timeout `20`"), "{err}");
+    }
+
+    #[test]
+    fn merge_docker_retries() {
+        let mut base = Config::default();
+        assert_eq!(base.docker.retries(), crate::docker::retry::DEFAULT_RETRIES);
+
+        base.merge(parse_toml(
+            r#"
+            [docker]
+            retries = 0
+            "#,
+        ));
+        base.merge(parse_toml(""));
+        assert_eq!(base.docker.retries(), 0);
+    }
+
+    #[test]
+    fn merge_agent_auto_branch() {
+        let mut base = Config::default();
+        assert!(!base.agent.auto_branch());
+
+        base.merge(parse_toml(
+            r#"
+            [agent]
+            auto_branch = true
+            "#,
+        ));
+        base.merge(parse_toml(""));
+        assert!(base.agent.auto_branch());
+    }
+
+    #[test]
+    fn auth_providers_default_and_merge() {
+        let mut base = parse_toml("");
+        assert_eq!(
+            base.auth.providers(),
+            vec![AuthProvider::Env, AuthProvider::Keychain]
+        );
+
+        base.merge(parse_toml(
+            "[auth]\nproviders = [\"1password\", \"file\"]\nonepassword = \"op://Private/Claude/token\"\n",
+        ));
+        base.merge(parse_toml("[auth]\n"));
+        assert_eq!(
+            base.auth.providers(),
+            vec![AuthProvider::OnePassword, AuthProvider::File]
+        );
+        assert_eq!(
+            base.auth.onepassword.as_deref(),
+            Some("op://Private/Claude/token")
+        );
+        assert!(toml::from_str::<Config>("[auth]\nproviders = [\"vault\"]\n").is_err());
+    }
+
+    #[test]
+    fn auth_refresh_interval_is_parsed() {
+        let auth = parse_toml("[auth]\nrefresh = \"45m\"\n").auth;
+        assert_eq!(
+            auth.refresh_interval().unwrap(),
+            Some(Duration::from_secs(45 * 60))
+        );
+        assert_eq!(parse_toml("").auth.refresh_interval().unwrap(), None);
+        let err = parse_toml("[auth]\nrefresh = \"0s\"\n")
+            .auth
+            .refresh_interval()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid auth.refresh `0s` (expected e.g. `30m`, `1h`)"
+        );
+    }
+
+    #[test]
+    fn merge_claude_per_key() {
+        let mut base = Config::default();
+        assert!(!base.claude.write_env_context());
+
+        base.merge(parse_toml(
+            r#"
+            [claude]
+            write_env_context = true
+            "#,
+        ));
+        base.merge(parse_toml(""));
+        assert!(base.claude.write_env_context());
+        assert!(!base.claude.persist_sessions());
+
+        base.merge(parse_toml("[claude]\npersist_sessions = true\n"));
+        assert!(base.claude.write_env_context());
+        assert!(base.claude.persist_sessions());
+    }
+
+    #[test]
+    fn custom_services_parse_readiness_and_merge_by_name() {
+        let mut base = parse_toml(
+            r#"
+            [services.custom.search]
+            image = "elasticsearch:8.13.0"
+            env = { "discovery.type" = "single-node" }
+            readiness = { http = "http://localhost:9200/_cluster/health" }
+
+            [services.custom.mail]
+            image = "axllent/mailpit"
+            readiness = { tcp = 1025 }
+            "#,
+        );
+        let search = &base.services.custom["search"];
+        assert_eq!(search.env["discovery.type"], "single-node");
+        assert_eq!(
+            search.readiness,
+            Some(Readiness::Http(
+                "http://localhost:9200/_cluster/health".to_string()
+            ))
+        );
+        assert_eq!(
+            base.services.custom["mail"].readiness,
+            Some(Readiness::Tcp(1025))
+        );
+
+        base.merge(parse_toml(
+            r#"
+            [services.custom.mail]
+            image = "mailhog/mailhog"
+            "#,
+        ));
+        assert_eq!(base.services.custom.len(), 2);
+        assert_eq!(base.services.custom["mail"].image, "mailhog/mailhog");
+        assert_eq!(base.services.custom["mail"].readiness, None);
+
+        let err = toml::from_str::<Config>(
+            "[services.custom.x]\nimage = \"x\"\nreadiness = { udp = 53 }\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("udp"), "{err}");
+    }
+
+    #[test]
+    fn merge_notify_per_key() {
+        let mut base = parse_toml(
+            r#"
+            [notify]
+            webhook = "https://hooks.slack.com/services/T0/B0/x"
+            "#,
+        );
+        base.merge(parse_toml("[notify]\ndesktop = true\n"));
+        assert!(base.notify.desktop());
+        assert_eq!(
+            base.notify.webhook_url().unwrap(),
+            Some("https://hooks.slack.com/services/T0/B0/x")
+        );
+    }
+
+    #[test]
+    fn notify_webhook_must_be_http() {
+        let config = parse_toml("[notify]\nwebhook = \"hooks.slack.com/x\"\n");
+        let err = config.notify.webhook_url().unwrap_err().to_string();
+        assert!(err.contains("invalid notify.webhook"), "{err}");
+    }
+
+    #[test]
+    fn network_allowlist_implies_internal() {
+        let config = parse_toml(
+            r#"
+            [network]
+            allow = ["api.anthropic.com", ".github.com"]
+            "#,
+        );
+        assert_eq!(
+            config.network.allow,
+            vec!["api.anthropic.com", ".github.com"]
+        );
+        assert!(config.network.internal.is_none());
+        assert!(config.network.is_internal());
+        assert!(!Config::default().network.is_internal());
+    }
+
+    #[test]
+    fn parse_network_dns_and_extra_hosts() {
+        let config = parse_toml(
+            r#"
+            [network]
+            dns = ["10.0.0.2", "10.0.0.3"]
+            extra_hosts = ["api.internal:10.0.0.5"]
+            "#,
+        );
+        assert_eq!(config.network.dns, vec!["10.0.0.2", "10.0.0.3"]);
+        assert_eq!(config.network.extra_hosts, vec!["api.internal:10.0.0.5"]);
+    }
+
+    #[test]
+    fn container_tmpfs_and_shm_size() {
+        let config = parse_toml(
+            r#"
+            [container]
+            tmpfs = ["/tmp", "/run:size=64m"]
+            shm_size = "1g"
+            "#,
+        );
+        let mounts = config.container.tmpfs_mounts().unwrap();
+        assert_eq!(mounts["/tmp"], "");
+        assert_eq!(mounts["/run"], "size=64m");
+        assert_eq!(config.container.shm_size_bytes().unwrap(), Some(1 << 30));
+        assert_eq!(Config::default().container.shm_size_bytes().unwrap(), None);
+    }
+
+    #[test]
+    fn container_devices_follow_docker_syntax() {
+        let config = parse_toml(
+            r#"
+            [container]
+            groups = ["docker", "992"]
+            devices = ["/dev/kvm", "/dev/fuse:r", "/dev/ttyUSB0:/dev/modem", "/dev/dri:/dev/dri:rw"]
+            "#,
+        );
+        assert_eq!(config.container.groups, vec!["docker", "992"]);
+        let device = |host: &str, container: &str, permissions: &str| Device {
+            host: host.to_string(),
+            container: container.to_string(),
+            permissions: permissions.to_string(),
+        };
+        assert_eq!(
+            config.container.device_mappings().unwrap(),
+            vec![
+                device("/dev/kvm", "/dev/kvm", "rwm"),
+                device("/dev/fuse", "/dev/fuse", "r"),
+                device("/dev/ttyUSB0", "/dev/modem", "rwm"),
+                device("/dev/dri", "/dev/dri", "rw"),
+            ]
+        );
+
+        for bad in ["kvm", "/dev/kvm:rwx:x", "/dev/kvm:/dev/kvm:rwx"] {
+            let container = ContainerConfig {
+                devices: vec![bad.to_string()],
+                ..Default::default()
+            };
+            assert!(container.device_mappings().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn parse_and_merge_selinux_label() {
+        let mut base = parse_toml(
+            r#"
+            [mount]
+            selinux_label = "shared"
+            "#,
+        );
+        assert_eq!(base.mount.selinux_label, Some(SelinuxLabel::Shared));
+        base.merge(parse_toml("[mount]\nselinux_label = \"private\"\n"));
+        assert_eq!(base.mount.selinux_label, Some(SelinuxLabel::Private));
+        base.merge(Config::default());
+        assert_eq!(base.mount.selinux_label, Some(SelinuxLabel::Private));
+    }
+
+    #[test]
+    fn parse_and_merge_persist_history() {
+        let mut base = parse_toml("[shell]\npersist_history = true\n");
+        assert_eq!(base.shell.persist_history, Some(true));
+        base.merge(Config::default());
+        assert_eq!(base.shell.persist_history, Some(true));
+        base.merge(parse_toml("[shell]\npersist_history = false\n"));
+        assert_eq!(base.shell.persist_history, Some(false));
+    }
+
+    #[test]
+    fn parse_and_merge_shell_framework() {
+        let mut base = parse_toml("[shell]\nframework = \"ohmyzsh\"\n");
+        assert_eq!(base.shell.framework, Some(ShellFramework::Ohmyzsh));
+        base.merge(parse_toml("[shell]\nframework = \"none\"\n"));
+        assert_eq!(base.shell.framework, Some(ShellFramework::None));
+
+        let err = toml::from_str::<Config>("[shell]\nframework = \"fish\"\n").unwrap_err();
+        assert!(err.to_string().contains("unknown variant"), "{err}");
+    }
+
+    #[test]
+    fn merge_dotfiles_repo_keeps_unset_keys() {
+        let mut base = parse_toml(
+            "[shell]\ndotfiles_repo = \"git@github.com:me/dotfiles\"\ndotfiles_install = \"make\"\n",
+        );
+        base.merge(parse_toml(
+            "[shell]\ndotfiles_repo = \"https://github.com/me/dotfiles\"\n",
+        ));
+        assert_eq!(
+            base.shell.dotfiles_repo.as_deref(),
+            Some("https://github.com/me/dotfiles")
+        );
+        assert_eq!(base.shell.dotfiles_install.as_deref(), Some("make"));
+    }
+
+    #[test]
+    fn merge_replaces_dotfile_lists() {
+        let mut base = parse_toml(
+            "[shell]\ndotfiles = [\".config/nvim\"]\ndotfiles_exclude = [\".gitconfig\"]\n",
+        );
+        base.merge(parse_toml("[shell]\ndotfiles = [\".config/fish\"]\n"));
+        assert_eq!(base.shell.dotfiles, vec![".config/fish"]);
+        assert_eq!(base.shell.dotfiles_exclude, vec![".gitconfig"]);
+    }
+
+    #[test]
+    fn merge_mount_ssh() {
+        let mut base = parse_toml("[shell]\nmount_ssh = true\n");
+        base.merge(parse_toml("[shell]\npersist_history = true\n"));
+        assert_eq!(base.shell.mount_ssh, Some(true));
+    }
+
+    #[test]
+    fn mount_exclude_maps_to_project_volumes() {
+        let config = parse_toml(
+            r#"
+            [mount]
+            exclude = ["node_modules", "web/vendor/"]
+            "#,
+        );
+        assert_eq!(
+            config.mount.exclude_volumes("shop").unwrap(),
+            vec![
+                (
+                    "bubble-bot-shop-mount-node_modules".to_string(),
+                    "/workspace/node_modules".to_string()
+                ),
+                (
+                    "bubble-bot-shop-mount-web-vendor".to_string(),
+                    "/workspace/web/vendor".to_string()
+                ),
+            ]
+        );
+
+        for entry in ["/abs", "../up", "", "a/../b"] {
+            let mount = MountConfig {
+                exclude: vec![entry.to_string()],
+                ..Default::default()
+            };
+            assert!(mount.exclude_volumes("shop").is_err(), "{entry}");
+        }
+    }
+
+    #[test]
+    fn selinux_label_extends_bind_options() {
+        assert_eq!(
+            SelinuxLabel::Shared.apply("/src/app:/workspace"),
+            "/src/app:/workspace:z"
+        );
+        assert_eq!(
+            SelinuxLabel::Private.apply("/home/u/.gitconfig:/home/dev/.gitconfig:ro"),
+            "/home/u/.gitconfig:/home/dev/.gitconfig:ro,Z"
+        );
+    }
+
+    #[test]
+    fn parse_size_units() {
+        assert_eq!(parse_size("65536").unwrap(), 65536);
+        assert_eq!(parse_size("512m").unwrap(), 512 << 20);
+        assert_eq!(parse_size("2GB").unwrap(), 2 << 30);
+        assert_eq!(parse_size("64k").unwrap(), 64 << 10);
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    #[test]
+    fn relative_tmpfs_path_errors() {
+        let container = ContainerConfig {
+            tmpfs: vec!["tmp".to_string()],
+            ..Default::default()
+        };
+        assert!(container.tmpfs_mounts().is_err());
+    }
+
+    #[test]
+    fn merge_network_dns_keeps_base_when_unset() {
+        let mut base = parse_toml(
+            r#"
+            [network]
+            dns = ["10.0.0.2"]
+            "#,
+        );
+        base.merge(parse_toml(
+            r#"
+            [network]
+            extra_hosts = ["api.internal:10.0.0.5"]
+            "#,
+        ));
+        assert_eq!(base.network.dns, vec!["10.0.0.2"]);
+        assert_eq!(base.network.extra_hosts, vec!["api.internal:10.0.0.5"]);
+    }
+
+    #[test]
+    fn merge_network_proxy() {
+        let mut base = parse_toml(
+            r#"
+            [network]
+            proxy = "http://global-proxy:3128"
+            no_proxy = "localhost"
+            "#,
+        );
+        base.merge(parse_toml(
+            r#"
+            [network]
+            proxy = "http://project-proxy:3128"
+            "#,
+        ));
+        assert_eq!(
+            base.network.proxy.as_deref(),
+            Some("http://project-proxy:3128")
+        );
+        assert_eq!(base.network.no_proxy.as_deref(), Some("localhost"));
+    }
+
+    #[test]
+    fn cli_no_internet_flag_enables_internal_network() {
+        let mut config = Config::default();
+        let cli = Cli::parse_from(["bubble-bot", "--no-internet"]);
+        config.apply_cli(&cli);
+        assert_eq!(config.network.internal, Some(true));
+    }
+
+    #[test]
+    fn cli_build_log_flag_overrides_config() {
+        let mut config: Config = toml::from_str("[build]\nlog = \"~/build.log\"").unwrap();
+        assert_eq!(
+            config.build.log_path(),
+            dirs::home_dir().map(|home| home.join("build.log"))
+        );
+        let cli = Cli::parse_from(["bubble-bot", "--build-log", "/tmp/build.log", "build"]);
+        config.apply_cli(&cli);
+        assert_eq!(
+            config.build.log_path(),
+            Some(PathBuf::from("/tmp/build.log"))
+        );
+    }
+
+    #[test]
+    fn default_config_serializes_to_toml() {
+        let config = Config::default();
+        let output = toml::to_string_pretty(&config).expect("serialize to TOML");
+        // Default config should serialize without error
+        assert!(!output.is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use anyhow::Result;
+use clap::Parser;
+use serde_json::Value as JsonValue;
+
+use super::{Config, ConfigFile, load_files};
+use crate::cli::Cli;
+
+/// Where a resolved config value came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    Default,
+    File(ConfigFile),
+    Cli,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(file) => write!(f, "{} ({})", file.layer, file.path.display()),
+            Origin::Cli => write!(f, "CLI flag"),
+        }
+    }
+}
+
+/// A single resolved config value, keyed by its dotted path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedValue {
+    pub key: String,
+    pub value: toml::Value,
+    pub origin: Origin,
+}
+
+/// Loads config the same way as `Config::load`, recording which layer set
+/// each resolved value.
+pub fn resolve(cli: &Cli) -> Result<Vec<ResolvedValue>> {
+    resolve_layers(load_files(cli.config.as_deref())?, cli)
+}
+
+fn resolve_layers(files: Vec<(ConfigFile, Config)>, cli: &Cli) -> Result<Vec<ResolvedValue>> {
+    let mut origins: BTreeMap<String, Origin> = BTreeMap::new();
+    let mut config = Config::default();
+
+    // Merge takes every set (non-null, non-empty) value from a file, so the
+    // file owns exactly the values it sets
+    for (file, file_config) in files {
+        for key in set_keys(&file_config)? {
+            origins.insert(key, Origin::File(file.clone()));
+        }
+        config.merge(file_config);
+    }
+
+    // CLI flags with clap defaults (e.g., `--shell bash`) apply even when not
+    // passed; compare against a flagless parse to tell defaults from flags
+    let mut implicit = config.clone();
+    implicit.apply_cli(&Cli::parse_from(["bubble-bot"]));
+    let mut resolved = config.clone();
+    resolved.apply_cli(cli);
+
+    let before = leaves(&config)?;
+    let implicit = leaves(&implicit)?;
+    let resolved = leaves(&resolved)?;
+
+    for (key, value) in &resolved {
+        if implicit.get(key) != Some(value) {
+            origins.insert(key.clone(), Origin::Cli);
+        } else if before.get(key) != Some(value) {
+            origins.insert(key.clone(), Origin::Default);
+        }
+    }
+
+    resolved
+        .into_iter()
+        .map(|(key, value)| {
+            let origin = origins.remove(&key).unwrap_or(Origin::Default);
+            Ok(ResolvedValue {
+                value: toml::Value::try_from(value)?,
+                key,
+                origin,
+            })
+        })
+        .collect()
+}
+
+/// Flattens config into dotted keys. Unset values are omitted; arrays are
+/// kept whole.
+fn leaves(config: &Config) -> Result<BTreeMap<String, JsonValue>> {
+    let mut out = BTreeMap::new();
+    flatten(&serde_json::to_value(config)?, "", &mut out);
+    Ok(out)
+}
+
+fn flatten(value: &JsonValue, prefix: &str, out: &mut BTreeMap<String, JsonValue>) {
+    match value {
+        JsonValue::Object(map) => {
+            for (key, value) in map {
+                flatten(value, &format!("{prefix}{key}."), out);
+            }
+        }
+        JsonValue::Null => {}
+        _ => {
+            out.insert(prefix.trim_end_matches('.').to_string(), value.clone());
+        }
+    }
+}
+
+/// Keys a config layer overrides when merged: everything set and non-empty.
+fn set_keys(config: &Config) -> Result<Vec<String>> {
+    Ok(leaves(config)?
+        .into_iter()
+        .filter(|(_, value)| value.as_array().is_none_or(|a| !a.is_empty()))
+        .map(|(key, _)| key)
+        .collect())
+}
+
+/// Formats resolved values as `key = value  # origin`, one per line.
+pub fn format(values: &[ResolvedValue]) -> String {
+    let assignments: Vec<String> = values
+        .iter()
+        .map(|v| format!("{} = {}", v.key, v.value))
+        .collect();
+    let width = assignments.iter().map(String::len).max().unwrap_or(0);
+
+    assignments
+        .iter()
+        .zip(values)
+        .map(|(assignment, v)| format!("{assignment:width$}  # {}\n", v.origin))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::config::Layer;
+
+    fn file(layer: Layer, toml: &str) -> (ConfigFile, Config) {
+        let path = match layer {
+            Layer::Global => PathBuf::from("/home/u/.config/bubble-bot/config.toml"),
+            Layer::Project => PathBuf::from(".bubble-bot.toml"),
+            Layer::Local => PathBuf::from(".bubble-bot.local.toml"),
+            Layer::Explicit => PathBuf::from("envs/ci.toml"),
+        };
+        (ConfigFile { layer, path }, toml::from_str(toml).unwrap())
+    }
+
+    fn origin_of<'a>(values: &'a [ResolvedValue], key: &str) -> &'a Origin {
+        &values.iter().find(|v| v.key == key).unwrap().origin
+    }
+
+    #[test]
+    fn project_overrides_global() {
+        let files = vec![
+            file(Layer::Global, "[runtimes]\nphp = \"8.1\"\nnode = \"20\"\n"),
+            file(Layer::Project, "[runtimes]\nphp = \"8.3\"\n"),
+        ];
+        let cli = Cli::parse_from(["bubble-bot"]);
+        let values = resolve_layers(files, &cli).unwrap();
+
+        let php = values.iter().find(|v| v.key == "runtimes.php").unwrap();
+        assert_eq!(php.value.as_str(), Some("8.3"));
+        assert!(matches!(&php.origin, Origin::File(f) if f.layer == Layer::Project));
+        assert!(
+            matches!(origin_of(&values, "runtimes.node"), Origin::File(f) if f.layer == Layer::Global)
+        );
+    }
+
+    #[test]
+    fn cli_flags_are_attributed_to_cli() {
+        let files = vec![file(Layer::Project, "[runtimes]\nphp = \"8.1\"\n")];
+        let cli = Cli::parse_from(["bubble-bot", "--with-php", "8.3", "--with-redis"]);
+        let values = resolve_layers(files, &cli).unwrap();
+
+        assert_eq!(origin_of(&values, "runtimes.php"), &Origin::Cli);
+        assert_eq!(origin_of(&values, "services.redis"), &Origin::Cli);
+    }
+
+    #[test]
+    fn clap_default_shell_is_a_default() {
+        let cli = Cli::parse_from(["bubble-bot"]);
+        let values = resolve_layers(Vec::new(), &cli).unwrap();
+        assert_eq!(origin_of(&values, "container.shell"), &Origin::Default);
+
+        let cli = Cli::parse_from(["bubble-bot", "--shell", "zsh"]);
+        let values = resolve_layers(Vec::new(), &cli).unwrap();
+        assert_eq!(origin_of(&values, "container.shell"), &Origin::Cli);
+    }
+
+    #[test]
+    fn section_fields_belong_to_the_layer_that_set_the_section() {
+        let files = vec![file(
+            Layer::Project,
+            "[services.mysql]\nversion = \"8.4\"\n",
+        )];
+        let cli = Cli::parse_from(["bubble-bot"]);
+        let values = resolve_layers(files, &cli).unwrap();
+
+        assert!(matches!(
+            origin_of(&values, "services.mysql.database"),
+            Origin::File(f) if f.layer == Layer::Project
+        ));
+    }
+
+    #[test]
+    fn unset_values_are_omitted() {
+        let cli = Cli::parse_from(["bubble-bot"]);
+        let values = resolve_layers(Vec::new(), &cli).unwrap();
+        assert!(values.iter().all(|v| v.key != "runtimes.php"));
+        assert_eq!(origin_of(&values, "hooks.post_start"), &Origin::Default);
+    }
+
+    #[test]
+    fn format_aligns_origin_comments() {
+        let values = vec![
+            ResolvedValue {
+                key: "runtimes.php".to_string(),
+                value: toml::Value::String("8.3".to_string()),
+                origin: Origin::Cli,
+            },
+            ResolvedValue {
+                key: "hooks.pre_stop".to_string(),
+                value: toml::Value::Array(Vec::new()),
+                origin: Origin::Default,
+            },
+        ];
+        assert_eq!(
+            format(&values),
+            "runtimes.php = \"8.3\"  # CLI flag\nhooks.pre_stop = []   # default\n"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+use super::connect::find_in_path;
+
+/// Magic bytes a zstd frame starts with.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Magic bytes a gzip stream starts with.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How an image archive is compressed: chosen by the file name on export,
+/// and by the file's first bytes on import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// The compression a `.tar.zst`, `.tar.gz`, or `.tar` name asks for.
+    pub fn for_path(path: &Path) -> Result<Self> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Ok(Self::Zstd)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Self::Gzip)
+        } else if name.ends_with(".tar") {
+            Ok(Self::None)
+        } else {
+            bail!(
+                "can't tell how to compress {}; name it .tar.zst, .tar.gz, or .tar",
+                path.display()
+            )
+        }
+    }
+
+    /// The compression of an archive starting with `header`.
+    pub fn sniff(header: &[u8]) -> Self {
+        if header.starts_with(&ZSTD_MAGIC) {
+            Self::Zstd
+        } else if header.starts_with(&GZIP_MAGIC) {
+            Self::Gzip
+        } else {
+            Self::None
+        }
+    }
+
+    /// The command line compressing stdin to stdout, if any.
+    pub fn compressor_args(self) -> Option<&'static [&'static str]> {
+        match self {
+            Self::None => None,
+            Self::Gzip => Some(&["gzip", "-c"]),
+            Self::Zstd => Some(&["zstd", "-q", "-T0", "-c"]),
+        }
+    }
+
+    fn compressor(self) -> Option<Command> {
+        let (program, args) = self.compressor_args()?.split_first()?;
+        let mut command = Command::new(program);
+        command.args(args);
+        Some(command)
+    }
+}
+
+/// Fails with an install hint unless `zstd` is on `PATH`. `docker save` and
+/// `docker load` don't handle zstd themselves.
+fn require_zstd() -> Result<()> {
+    if find_in_path("zstd", std::env::var_os("PATH").as_deref()).is_none() {
+        bail!("zstd is not installed (no `zstd` on PATH); install it, or use a .tar.gz file");
+    }
+    Ok(())
+}
+
+/// Writes `images` into `file` with `docker save`, compressed as the file
+/// name says. A failed export removes the partial file.
+pub fn save(images: &[String], file: &Path) -> Result<()> {
+    let compression = Compression::for_path(file)?;
+    if compression == Compression::Zstd {
+        require_zstd()?;
+    }
+    let out = File::create(file).with_context(|| format!("failed to create {}", file.display()))?;
+    let mut save = Command::new("docker");
+    save.arg("save")
+        .args(images)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped());
+
+    let result = match compression.compressor() {
+        None => save
+            .stdout(out)
+            .output()
+            .context("failed to run docker save")
+            .and_then(|saved| check(&saved, "docker save")),
+        Some(mut compressor) => {
+            let mut child = save
+                .stdout(Stdio::piped())
+                .spawn()
+                .context("failed to run docker save")?;
+            let stdout = child.stdout.take().context("docker save has no stdout")?;
+            let compressed = compressor
+                .stdin(stdout)
+                .stdout(out)
+                .stderr(Stdio::piped())
+                .output()
+                .context("failed to run the compressor");
+            let saved = child
+                .wait_with_output()
+                .context("failed to wait for docker save")?;
+            check(&saved, "docker save").and_then(|()| check(&compressed?, "compression"))
+        }
+    };
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(file);
+        return Err(e);
+    }
+    info!(images = images.len(), file = %file.display(), "images exported");
+    Ok(())
+}
+
+/// Loads the images in `file` (an archive from [`save`], or any
+/// `docker save` output) with `docker load`, returning their names.
+pub fn load(file: &Path) -> Result<Vec<String>> {
+    let mut input =
+        File::open(file).with_context(|| format!("failed to open {}", file.display()))?;
+    let mut header = [0u8; 4];
+    let read = input.read(&mut header)?;
+    let compression = Compression::sniff(&header[..read]);
+
+    let mut load = Command::new("docker");
+    load.arg("load")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let loaded = if compression == Compression::Zstd {
+        require_zstd()?;
+        let mut decompress = Command::new("zstd")
+            .args(["-q", "-d", "-c"])
+            .arg(file)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("failed to run zstd")?;
+        let stdout = decompress.stdout.take().context("zstd has no stdout")?;
+        let loaded = load
+            .stdin(stdout)
+            .output()
+            .context("failed to run docker load")?;
+        let decompressed = decompress
+            .wait_with_output()
+            .context("failed to wait for zstd")?;
+        check(&decompressed, "zstd")?;
+        loaded
+    } else {
+        // `docker load` unpacks gzip itself
+        input.rewind()?;
+        load.stdin(input)
+            .output()
+            .context("failed to run docker load")?
+    };
+    check(&loaded, "docker load")?;
+
+    let images = loaded_images(&String::from_utf8_lossy(&loaded.stdout));
+    info!(images = images.len(), file = %file.display(), "images imported");
+    Ok(images)
+}
+
+/// Image names from `docker load` output (`Loaded image: name:tag`).
+fn loaded_images(stdout: &str) -> Vec<String> {
+    stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("Loaded image: "))
+        .map(|image| image.trim().to_string())
+        .collect()
+}
+
+fn check(output: &Output, what: &str) -> Result<()> {
+    if !output.status.success() {
+        bail!(
+            "{what} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compression_follows_the_file_name_and_contents() {
+        let of = |name: &str| Compression::for_path(Path::new(name)).ok();
+        assert_eq!(of("out/bubble.tar.zst"), Some(Compression::Zstd));
+        assert_eq!(of("bubble.tgz"), Some(Compression::Gzip));
+        assert_eq!(of("bubble.tar"), Some(Compression::None));
+        assert_eq!(of("bubble.zip"), None);
+
+        assert_eq!(
+            Compression::sniff(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::sniff(&[0x1f, 0x8b, 0x08]), Compression::Gzip);
+        assert_eq!(Compression::sniff(b"mani"), Compression::None);
+        assert_eq!(Compression::sniff(&[]), Compression::None);
+    }
+
+    #[test]
+    fn loaded_images_are_read_from_docker_load() {
+        let stdout = "Loaded image: bubble-bot:0123456789ab\nLoaded image ID: sha256:abc\nLoaded image: mysql:8.0\n";
+        assert_eq!(
+            loaded_images(stdout),
+            vec!["bubble-bot:0123456789ab", "mysql:8.0"]
+        );
+    }
+}
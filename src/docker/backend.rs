@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use bollard::Docker;
+use bollard::auth::DockerCredentials;
+use bollard::container::{
+    Config, CreateContainerOptions, InspectContainerOptions, ListContainersOptions, LogOutput,
+    LogsOptions, RemoveContainerOptions, StopContainerOptions,
+};
+use bollard::errors::Error;
+use bollard::image::{
+    BuildImageOptions, CreateImageOptions, ListImagesOptions, RemoveImageOptions,
+};
+use bollard::models::{
+    BuildInfo, ContainerCreateResponse, ContainerInspectResponse, ContainerSummary,
+    CreateImageInfo, ImageDeleteResponseItem, ImageInspect, ImageSummary, Network,
+    NetworkCreateResponse, SystemInfo, VolumeListResponse,
+};
+use bollard::network::{ConnectNetworkOptions, CreateNetworkOptions, ListNetworksOptions};
+use bollard::volume::{ListVolumesOptions, RemoveVolumeOptions};
+use futures_util::StreamExt;
+use futures_util::stream::BoxStream;
+
+/// The Docker Engine API calls bubble-bot makes, so the managers can run
+/// against [`super::mock::MockBackend`] in tests instead of a daemon.
+///
+/// Signatures mirror bollard's, with the generic parameters fixed to
+/// `String`. Errors stay bollard errors so callers can keep matching on
+/// status codes (404 for missing, 409 for conflicts).
+pub trait ContainerBackend: Clone + Send + Sync + 'static {
+    /// Daemon-wide information (`GET /info`).
+    fn info(&self) -> impl Future<Output = Result<SystemInfo, Error>> + Send;
+
+    fn list_containers(
+        &self,
+        options: Option<ListContainersOptions<String>>,
+    ) -> impl Future<Output = Result<Vec<ContainerSummary>, Error>> + Send;
+
+    fn inspect_container(
+        &self,
+        id: &str,
+        options: Option<InspectContainerOptions>,
+    ) -> impl Future<Output = Result<ContainerInspectResponse, Error>> + Send;
+
+    fn create_container(
+        &self,
+        options: Option<CreateContainerOptions<String>>,
+        config: Config<String>,
+    ) -> impl Future<Output = Result<ContainerCreateResponse, Error>> + Send;
+
+    fn start_container(&self, id: &str) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn stop_container(
+        &self,
+        id: &str,
+        options: Option<StopContainerOptions>,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn remove_container(
+        &self,
+        id: &str,
+        options: Option<RemoveContainerOptions>,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn connect_network(
+        &self,
+        network: &str,
+        options: ConnectNetworkOptions<String>,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+
+    /// A container's output so far (`docker logs`).
+    fn logs(
+        &self,
+        id: &str,
+        options: Option<LogsOptions<String>>,
+    ) -> BoxStream<'_, Result<LogOutput, Error>>;
+
+    fn inspect_image(
+        &self,
+        image: &str,
+    ) -> impl Future<Output = Result<ImageInspect, Error>> + Send;
+
+    fn list_images(
+        &self,
+        options: Option<ListImagesOptions<String>>,
+    ) -> impl Future<Output = Result<Vec<ImageSummary>, Error>> + Send;
+
+    /// Pulls an image, streaming progress.
+    fn create_image(
+        &self,
+        options: CreateImageOptions<'static, String>,
+        credentials: Option<DockerCredentials>,
+    ) -> BoxStream<'_, Result<CreateImageInfo, Error>>;
+
+    /// Builds an image from a tar `context`, streaming build output.
+    fn build_image(
+        &self,
+        options: BuildImageOptions<String>,
+        credentials: Option<HashMap<String, DockerCredentials>>,
+        context: Vec<u8>,
+    ) -> BoxStream<'_, Result<BuildInfo, Error>>;
+
+    fn remove_image(
+        &self,
+        image: &str,
+        options: Option<RemoveImageOptions>,
+    ) -> impl Future<Output = Result<Vec<ImageDeleteResponseItem>, Error>> + Send;
+
+    fn list_networks(
+        &self,
+        options: Option<ListNetworksOptions<String>>,
+    ) -> impl Future<Output = Result<Vec<Network>, Error>> + Send;
+
+    fn create_network(
+        &self,
+        options: CreateNetworkOptions<String>,
+    ) -> impl Future<Output = Result<NetworkCreateResponse, Error>> + Send;
+
+    fn remove_network(&self, name: &str) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn list_volumes(
+        &self,
+        options: Option<ListVolumesOptions<String>>,
+    ) -> impl Future<Output = Result<VolumeListResponse, Error>> + Send;
+
+    fn remove_volume(
+        &self,
+        name: &str,
+        options: Option<RemoveVolumeOptions>,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+impl ContainerBackend for Docker {
+    async fn info(&self) -> Result<SystemInfo, Error> {
+        Docker::info(self).await
+    }
+
+    async fn list_containers(
+        &self,
+        options: Option<ListContainersOptions<String>>,
+    ) -> Result<Vec<ContainerSummary>, Error> {
+        Docker::list_containers(self, options).await
+    }
+
+    async fn inspect_container(
+        &self,
+        id: &str,
+        options: Option<InspectContainerOptions>,
+    ) -> Result<ContainerInspectResponse, Error> {
+        Docker::inspect_container(self, id, options).await
+    }
+
+    async fn create_container(
+        &self,
+        options: Option<CreateContainerOptions<String>>,
+        config: Config<String>,
+    ) -> Result<ContainerCreateResponse, Error> {
+        Docker::create_container(self, options, config).await
+    }
+
+    async fn start_container(&self, id: &str) -> Result<(), Error> {
+        Docker::start_container::<String>(self, id, None).await
+    }
+
+    async fn stop_container(
+        &self,
+        id: &str,
+        options: Option<StopContainerOptions>,
+    ) -> Result<(), Error> {
+        Docker::stop_container(self, id, options).await
+    }
+
+    async fn remove_container(
+        &self,
+        id: &str,
+        options: Option<RemoveContainerOptions>,
+    ) -> Result<(), Error> {
+        Docker::remove_container(self, id, options).await
+    }
+
+    async fn connect_network(
+        &self,
+        network: &str,
+        options: ConnectNetworkOptions<String>,
+    ) -> Result<(), Error> {
+        Docker::connect_network(self, network, options).await
+    }
+
+    fn logs(
+        &self,
+        id: &str,
+        options: Option<LogsOptions<String>>,
+    ) -> BoxStream<'_, Result<LogOutput, Error>> {
+        Docker::logs(self, id, options).boxed()
+    }
+
+    async fn inspect_image(&self, image: &str) -> Result<ImageInspect, Error> {
+        Docker::inspect_image(self, image).await
+    }
+
+    async fn list_images(
+        &self,
+        options: Option<ListImagesOptions<String>>,
+    ) -> Result<Vec<ImageSummary>, Error> {
+        Docker::list_images(self, options).await
+    }
+
+    fn create_image(
+        &self,
+        options: CreateImageOptions<'static, String>,
+        credentials: Option<DockerCredentials>,
+    ) -> BoxStream<'_, Result<CreateImageInfo, Error>> {
+        Docker::create_image(self, Some(options), None, credentials).boxed()
+    }
+
+    fn build_image(
+        &self,
+        options: BuildImageOptions<String>,
+        credentials: Option<HashMap<String, DockerCredentials>>,
+        context: Vec<u8>,
+    ) -> BoxStream<'_, Result<BuildInfo, Error>> {
+        Docker::build_image(self, options, credentials, Some(context.into())).boxed()
+    }
+
+    async fn remove_image(
+        &self,
+        image: &str,
+        options: Option<RemoveImageOptions>,
+    ) -> Result<Vec<ImageDeleteResponseItem>, Error> {
+        Docker::remove_image(self, image, options, None).await
+    }
+
+    async fn list_networks(
+        &self,
+        options: Option<ListNetworksOptions<String>>,
+    ) -> Result<Vec<Network>, Error> {
+        Docker::list_networks(self, options).await
+    }
+
+    async fn create_network(
+        &self,
+        options: CreateNetworkOptions<String>,
+    ) -> Result<NetworkCreateResponse, Error> {
+        Docker::create_network(self, options).await
+    }
+
+    async fn remove_network(&self, name: &str) -> Result<(), Error> {
+        Docker::remove_network(self, name).await
+    }
+
+    async fn list_volumes(
+        &self,
+        options: Option<ListVolumesOptions<String>>,
+    ) -> Result<VolumeListResponse, Error> {
+        Docker::list_volumes(self, options).await
+    }
+
+    async fn remove_volume(
+        &self,
+        name: &str,
+        options: Option<RemoveVolumeOptions>,
+    ) -> Result<(), Error> {
+        Docker::remove_volume(self, name, options).await
+    }
+}
@@ -7,32 +7,49 @@ use bollard::network::ListNetworksOptions;
 use bollard::volume::ListVolumesOptions;
 use tracing::info;
 
+use super::backend::ContainerBackend;
+use super::ownership::Reaper;
+
 /// Handles cleanup of Bubble Bot Docker resources (images, networks, volumes).
-pub struct Cleaner {
-    docker: Docker,
+pub struct Cleaner<B = Docker> {
+    docker: B,
 }
 
-impl Cleaner {
-    pub fn new(docker: Docker) -> Self {
+impl<B: ContainerBackend> Cleaner<B> {
+    pub fn new(docker: B) -> Self {
         Self { docker }
     }
 
-    /// Removes all `bubble-bot:*` images, `bubble-bot-*` networks, and optionally
+    /// Removes containers left by killed bubble-bot processes, all
+    /// `bubble-bot:*` images, `bubble-bot-*` networks, and optionally
     /// `bubble-bot-*` named volumes. Prints what was removed.
     pub async fn clean(&self, remove_volumes: bool) -> Result<()> {
+        let (removed_containers, mut removed_networks) =
+            Reaper::new(self.docker.clone()).reap().await?;
         let removed_images = self.remove_images().await?;
-        let removed_networks = self.remove_networks().await?;
+        removed_networks.extend(self.remove_networks().await?);
         let removed_volumes = if remove_volumes {
             self.remove_volumes().await?
         } else {
             Vec::new()
         };
 
-        if removed_images.is_empty() && removed_networks.is_empty() && removed_volumes.is_empty() {
+        if removed_containers.is_empty()
+            && removed_images.is_empty()
+            && removed_networks.is_empty()
+            && removed_volumes.is_empty()
+        {
             println!("Nothing to clean.");
             return Ok(());
         }
 
+        if !removed_containers.is_empty() {
+            println!("Removed orphaned containers:");
+            for name in &removed_containers {
+                println!("  {name}");
+            }
+        }
+
         if !removed_images.is_empty() {
             println!("Removed images:");
             for tag in &removed_images {
@@ -97,7 +114,6 @@ impl Cleaner {
                         force: true,
                         ..Default::default()
                     }),
-                    None,
                 )
                 .await
             {
@@ -188,6 +204,7 @@ impl Cleaner {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::docker::mock::MockBackend;
 
     #[test]
     fn cleaner_can_be_constructed() {
@@ -196,4 +213,25 @@ mod tests {
         let docker = Docker::connect_with_local_defaults().unwrap();
         let _cleaner = Cleaner::new(docker);
     }
+
+    #[test]
+    fn clean_removes_only_bubble_bot_resources() {
+        let mock = MockBackend::new();
+        mock.add_image("bubble-bot:0123456789ab");
+        mock.add_image("mysql:8.4");
+        mock.add_network("bubble-bot-app", false, HashMap::new());
+        mock.add_network("other", false, HashMap::new());
+        mock.add_volume("bubble-bot-app-mysql-data");
+        mock.add_volume("other-data");
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let cleaner = Cleaner::new(mock.clone());
+        rt.block_on(cleaner.clean(false)).unwrap();
+        assert_eq!(mock.images(), vec!["mysql:8.4"]);
+        assert_eq!(mock.networks().len(), 1);
+        assert_eq!(mock.volumes().len(), 2);
+
+        rt.block_on(cleaner.clean(true)).unwrap();
+        assert_eq!(mock.volumes(), vec!["other-data"]);
+    }
 }
@@ -0,0 +1,290 @@
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use bollard::{ClientVersion, Docker};
+use tracing::{debug, warn};
+
+use crate::error::Error;
+
+/// Pings before giving up; the daemon may still be starting.
+const PING_ATTEMPTS: u32 = 4;
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(250);
+
+/// Socket used when `DOCKER_HOST` is unset.
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+/// Daemon API of Docker 23, the oldest whose BuildKit builds the cache
+/// mounts of `build.buildkit`.
+pub const BUILDKIT_API: ClientVersion = ClientVersion {
+    major_version: 1,
+    minor_version: 42,
+};
+
+/// Connects to the local Docker daemon and pings it, retrying with backoff
+/// while it looks like it is starting. When it can't be reached, the error
+/// says why (not started, socket permissions, Colima stopped) and how to fix
+/// it, rather than surfacing the raw bollard error.
+pub async fn connect() -> Result<Docker> {
+    let socket = socket_path(std::env::var("DOCKER_HOST").ok().as_deref());
+    let socket = socket.as_deref();
+    let docker =
+        Docker::connect_with_local_defaults().map_err(|e| Error::Docker(failure(socket, &e)))?;
+
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        let error = match docker.ping().await {
+            Ok(_) => return Ok(negotiate(docker).await),
+            Err(e) => e,
+        };
+        if attempt == PING_ATTEMPTS || !is_transient(socket.and_then(probe)) {
+            return Err(Error::Docker(failure(socket, &error)).into());
+        }
+        debug!(attempt, error = %error, "Docker ping failed; retrying");
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// Lowers the client's API version to the daemon's if that is older, so
+/// requests aren't rejected by an old daemon, and logs the versions in use.
+/// A failed negotiation keeps bollard's default.
+async fn negotiate(docker: Docker) -> Docker {
+    let docker = match docker.clone().negotiate_version().await {
+        Ok(negotiated) => negotiated,
+        Err(e) => {
+            warn!(error = %e, "failed to negotiate the Docker API version");
+            docker
+        }
+    };
+    match docker.version().await {
+        Ok(version) => debug!(
+            bubble_bot = env!("CARGO_PKG_VERSION"),
+            docker = version.version.as_deref().unwrap_or("unknown"),
+            daemon_api = version.api_version.as_deref().unwrap_or("unknown"),
+            client_api = %docker.client_version(),
+            "connected to Docker"
+        ),
+        Err(e) => debug!(error = %e, "failed to read the Docker version"),
+    }
+    docker
+}
+
+/// Whether the negotiated API is at least `min`, warning that `feature`
+/// will fail if not. Only warns: the daemon has the final say.
+pub fn supports(docker: &Docker, min: ClientVersion, feature: &str) -> bool {
+    match api_warning(docker.client_version(), min, feature) {
+        Some(warning) => {
+            warn!("{warning}");
+            false
+        }
+        None => true,
+    }
+}
+
+fn api_warning(api: ClientVersion, min: ClientVersion, feature: &str) -> Option<String> {
+    (api < min).then(|| {
+        format!("the Docker daemon's API {api} is older than {min}, which {feature} needs; upgrade Docker")
+    })
+}
+
+/// Checks that the `docker` CLI is on `PATH`. Builds, containers, and
+/// networks go through the daemon API, but commands in containers (the
+/// shell, agents, hooks, workers) still run via `docker exec`, which would
+/// otherwise fail with a bare "No such file or directory".
+pub fn require_cli() -> Result<()> {
+    if find_in_path("docker", std::env::var_os("PATH").as_deref()).is_some() {
+        return Ok(());
+    }
+    Err(Error::Docker(anyhow!(
+        "the docker CLI is not installed (no `docker` on PATH); bubble-bot reaches the \
+         daemon through its socket but runs commands in containers with `docker exec`. \
+         Install just the client, e.g. the `docker-ce-cli` package or the static binary \
+         from https://download.docker.com/linux/static/stable/; it uses the same socket \
+         (or DOCKER_HOST)"
+    ))
+    .into())
+}
+
+/// The first executable `program` in the `PATH`-style list `path`.
+pub(crate) fn find_in_path(program: &str, path: Option<&OsStr>) -> Option<PathBuf> {
+    std::env::split_paths(path?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| {
+            candidate
+                .metadata()
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        })
+}
+
+/// The unix socket bollard connects to for a `DOCKER_HOST` value, or `None`
+/// for a non-socket host (TCP, SSH).
+fn socket_path(docker_host: Option<&str>) -> Option<PathBuf> {
+    match docker_host.filter(|host| !host.is_empty()) {
+        None => Some(PathBuf::from(DEFAULT_SOCKET)),
+        Some(host) => host.strip_prefix("unix://").map(PathBuf::from),
+    }
+}
+
+/// Connects to the socket directly to learn why the daemon is unreachable.
+fn probe(socket: &Path) -> Option<ErrorKind> {
+    UnixStream::connect(socket).err().map(|e| e.kind())
+}
+
+/// Whether a failure may clear up on its own: a refused connection is a
+/// daemon still starting, while a missing socket or denied permission
+/// won't change by waiting.
+fn is_transient(kind: Option<ErrorKind>) -> bool {
+    !matches!(
+        kind,
+        Some(ErrorKind::NotFound | ErrorKind::PermissionDenied)
+    )
+}
+
+fn failure(socket: Option<&Path>, error: &bollard::errors::Error) -> anyhow::Error {
+    let Some(socket) = socket else {
+        return anyhow!("failed to connect to Docker: {error}");
+    };
+    let kind = probe(socket);
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    match diagnose(kind, socket, home.as_deref()) {
+        Some(fix) => {
+            debug!(error = %error, "Docker daemon unreachable");
+            anyhow!("cannot connect to Docker at {}: {fix}", socket.display())
+        }
+        None => anyhow!(
+            "failed to connect to Docker at {}: {error}",
+            socket.display()
+        ),
+    }
+}
+
+/// A specific fix for a socket that can't be connected to, if the cause is
+/// recognizable.
+fn diagnose(kind: Option<ErrorKind>, socket: &Path, home: Option<&Path>) -> Option<String> {
+    let colima = socket.to_string_lossy().contains(".colima")
+        || home.is_some_and(|home| home.join(".colima").is_dir());
+    let desktop = socket.to_string_lossy().contains(".docker/")
+        || cfg!(target_os = "macos") && Path::new("/Applications/Docker.app").exists();
+
+    let not_running = if colima {
+        "Colima is stopped; start it with `colima start`"
+    } else if desktop {
+        "Docker Desktop is not running; start it and wait for the engine to be ready"
+    } else {
+        "the Docker daemon is not running; start it (e.g. `sudo systemctl start docker`)"
+    };
+
+    match kind? {
+        ErrorKind::NotFound | ErrorKind::ConnectionRefused => Some(not_running.to_string()),
+        ErrorKind::PermissionDenied => Some(
+            "permission denied on the socket; add yourself to the `docker` group \
+             (`sudo usermod -aG docker $USER`) and log in again"
+                .to_string(),
+        ),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn socket_path_follows_docker_host() {
+        assert_eq!(socket_path(None), Some(PathBuf::from(DEFAULT_SOCKET)));
+        assert_eq!(socket_path(Some("")), Some(PathBuf::from(DEFAULT_SOCKET)));
+        assert_eq!(
+            socket_path(Some("unix:///home/u/.colima/default/docker.sock")),
+            Some(PathBuf::from("/home/u/.colima/default/docker.sock"))
+        );
+        assert_eq!(socket_path(Some("tcp://10.0.0.1:2375")), None);
+    }
+
+    #[test]
+    fn programs_are_found_only_if_executable() {
+        let dir = tempfile::tempdir().unwrap();
+        let docker = dir.path().join("docker");
+        std::fs::write(&docker, "").unwrap();
+        let path = std::env::join_paths([Path::new("/nonexistent"), dir.path()]).unwrap();
+        assert_eq!(find_in_path("docker", Some(&path)), None);
+
+        std::fs::set_permissions(&docker, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(find_in_path("docker", Some(&path)), Some(docker));
+        assert_eq!(find_in_path("podman", Some(&path)), None);
+        assert_eq!(find_in_path("docker", None), None);
+    }
+
+    #[test]
+    fn old_daemons_are_warned_about() {
+        let old = ClientVersion {
+            major_version: 1,
+            minor_version: 41,
+        };
+        assert_eq!(
+            api_warning(old, BUILDKIT_API, "BuildKit").as_deref(),
+            Some(
+                "the Docker daemon's API 1.41 is older than 1.42, which BuildKit needs; upgrade Docker"
+            )
+        );
+        let current = ClientVersion {
+            major_version: 1,
+            minor_version: 47,
+        };
+        assert_eq!(api_warning(current, BUILDKIT_API, "BuildKit"), None);
+        assert_eq!(api_warning(BUILDKIT_API, BUILDKIT_API, "BuildKit"), None);
+    }
+
+    #[test]
+    fn probe_reports_missing_and_refused_sockets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("docker.sock");
+        assert_eq!(probe(&path), Some(ErrorKind::NotFound));
+
+        // A socket file nobody listens on
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert_eq!(probe(&path), Some(ErrorKind::ConnectionRefused));
+    }
+
+    #[test]
+    fn only_refused_connections_are_retried() {
+        assert!(is_transient(Some(ErrorKind::ConnectionRefused)));
+        assert!(is_transient(None));
+        assert!(!is_transient(Some(ErrorKind::NotFound)));
+        assert!(!is_transient(Some(ErrorKind::PermissionDenied)));
+    }
+
+    #[test]
+    fn diagnosis_names_the_fix() {
+        let home = tempfile::tempdir().unwrap();
+        let socket = Path::new("/run/docker-test.sock");
+
+        let fix = diagnose(Some(ErrorKind::PermissionDenied), socket, Some(home.path())).unwrap();
+        assert!(fix.contains("docker` group"), "{fix}");
+
+        let colima = home.path().join(".colima/default/docker.sock");
+        let fix = diagnose(Some(ErrorKind::NotFound), &colima, Some(home.path())).unwrap();
+        assert!(fix.contains("colima start"), "{fix}");
+
+        let desktop = home.path().join(".docker/run/docker.sock");
+        let fix = diagnose(
+            Some(ErrorKind::ConnectionRefused),
+            &desktop,
+            Some(home.path()),
+        )
+        .unwrap();
+        assert!(fix.contains("Docker Desktop"), "{fix}");
+
+        assert_eq!(diagnose(None, socket, Some(home.path())), None);
+        assert_eq!(
+            diagnose(Some(ErrorKind::TimedOut), socket, Some(home.path())),
+            None
+        );
+    }
+}
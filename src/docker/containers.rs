@@ -1,37 +1,572 @@
 use std::collections::HashMap;
-use std::process::Command;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use anyhow::{Context, Result};
 use bollard::Docker;
+use bollard::auth::DockerCredentials;
 use bollard::container::{
-    Config, CreateContainerOptions, ListContainersOptions, NetworkingConfig,
+    Config, CreateContainerOptions, ListContainersOptions, LogsOptions, NetworkingConfig,
     RemoveContainerOptions, StopContainerOptions,
 };
-use bollard::models::{EndpointSettings, HostConfig, Mount, MountTypeEnum};
+use bollard::image::CreateImageOptions;
+use bollard::models::{
+    ContainerState, ContainerSummary, DeviceMapping, EndpointSettings, HealthConfig,
+    HealthStatusEnum, HostConfig, Mount, MountTypeEnum,
+};
+use bollard::network::ConnectNetworkOptions;
+use indicatif::HumanBytes;
 use tracing::{info, warn};
 
+use super::backend::ContainerBackend;
+use super::images::CONFIG_HASH_LABEL;
+use super::ownership::{KEEP_LABEL, SESSION_ENV};
+use super::registry::RegistryAuth;
+use super::retry::{self, DEFAULT_RETRIES, already_exists};
+use crate::config::{Device, PullPolicy, Readiness, SelinuxLabel, project_id};
+use crate::error::Error;
+use crate::output;
 use crate::services::Service;
+use crate::templates::sha256_hex;
+
+/// PID of the `docker exec` client running the session's main command, or 0.
+static FOREGROUND_PID: AtomicU32 = AtomicU32::new(0);
+
+/// PID of the `docker exec` client for the command currently in the
+/// foreground, if one is running (so signals can be forwarded to it).
+pub fn foreground_pid() -> Option<u32> {
+    match FOREGROUND_PID.load(Ordering::SeqCst) {
+        0 => None,
+        pid => Some(pid),
+    }
+}
+
+/// Runs `command` to completion, publishing its PID via [`foreground_pid`].
+fn run_foreground(command: &mut Command) -> std::io::Result<ExitStatus> {
+    let mut child = command.spawn()?;
+    FOREGROUND_PID.store(child.id(), Ordering::SeqCst);
+    let status = child.wait();
+    FOREGROUND_PID.store(0, Ordering::SeqCst);
+    status
+}
+
+/// Seconds between dev container healthcheck probes.
+const HEALTHCHECK_INTERVAL_SECS: i64 = 10;
+
+/// Label holding a hash of the config a container was created with, so a
+/// container kept with `--keep` is only reused while a new session would
+/// create it the same way.
+pub const DEFINITION_LABEL: &str = "bubble-bot.definition";
+
+/// The create config for the dev container.
+fn dev_container_config(opts: &ContainerOpts) -> Config<String> {
+    let user = match opts.user_mapping {
+        UserMapping::Root => None,
+        UserMapping::HostUser | UserMapping::HostUserns => Some(host_owner()),
+    };
+    let userns_mode = (opts.user_mapping == UserMapping::HostUserns).then(|| "host".to_string());
+
+    let bind = format!("{}:/workspace", opts.project_dir);
+    let mut binds = vec![bind];
+    binds.extend(opts.extra_binds.clone());
+    if let Some(label) = opts.selinux_label {
+        binds = binds.iter().map(|bind| label.apply(bind)).collect();
+    }
+
+    let mounts = opts
+        .volumes
+        .iter()
+        .map(|(volume, target)| Mount {
+            target: Some(target.clone()),
+            source: Some(volume.clone()),
+            typ: Some(MountTypeEnum::VOLUME),
+            ..Default::default()
+        })
+        .collect::<Vec<_>>();
+
+    let host_config = HostConfig {
+        binds: Some(binds),
+        mounts: (!mounts.is_empty()).then_some(mounts),
+        network_mode: opts.network.clone(),
+        dns: non_empty(&opts.dns),
+        extra_hosts: non_empty(&opts.extra_hosts),
+        tmpfs: (!opts.tmpfs.is_empty()).then(|| opts.tmpfs.clone()),
+        shm_size: opts.shm_size,
+        userns_mode,
+        group_add: non_empty(&opts.groups),
+        devices: (!opts.devices.is_empty()).then(|| {
+            opts.devices
+                .iter()
+                .map(|device| DeviceMapping {
+                    path_on_host: Some(device.host.clone()),
+                    path_in_container: Some(device.container.clone()),
+                    cgroup_permissions: Some(device.permissions.clone()),
+                })
+                .collect()
+        }),
+        ..Default::default()
+    };
+
+    let env = non_empty(&opts.env_vars);
+
+    // Attach to network with container name as alias for hostname-based discovery
+    let networking_config = opts.network.as_ref().map(|net| {
+        let endpoint = EndpointSettings {
+            aliases: Some(vec![opts.container_name.clone()]),
+            ..Default::default()
+        };
+        let mut endpoints_config = HashMap::new();
+        endpoints_config.insert(net.clone(), endpoint);
+        NetworkingConfig { endpoints_config }
+    });
+
+    // Probes that the container can still run processes; the session
+    // monitor reports it while it fails
+    let healthcheck = HealthConfig {
+        test: Some(vec!["CMD".to_string(), "true".to_string()]),
+        interval: Some(HEALTHCHECK_INTERVAL_SECS * 1_000_000_000),
+        timeout: Some(5_000_000_000),
+        retries: Some(3),
+        ..Default::default()
+    };
+
+    Config {
+        image: Some(opts.image_tag.clone()),
+        cmd: Some(vec!["sleep".to_string(), "infinity".to_string()]),
+        healthcheck: Some(healthcheck),
+        labels: Some(opts.labels.clone()),
+        user,
+        working_dir: Some(opts.workdir.clone()),
+        host_config: Some(host_config),
+        env,
+        networking_config,
+        ..Default::default()
+    }
+}
+
+/// Hash of a create config, ignoring its labels and [`SESSION_ENV`] (which
+/// carry the owning session) and the session `secrets` in env values (a
+/// `password = "random"` differs every session). serde_json sorts map keys,
+/// so `HashMap` fields hash stably.
+fn definition_hash(config: &Config<String>, secrets: &[&str]) -> String {
+    let session_var = format!("{SESSION_ENV}=");
+    let config = Config {
+        labels: None,
+        env: config
+            .env
+            .as_ref()
+            .map(|env| -> Vec<String> {
+                env.iter()
+                    .filter(|var| !var.starts_with(&session_var))
+                    .map(|var| {
+                        secrets
+                            .iter()
+                            .filter(|secret| !secret.is_empty())
+                            .fold(var.clone(), |var, secret| var.replace(secret, "<secret>"))
+                    })
+                    .collect()
+            })
+            .filter(|env| !env.is_empty()),
+        ..config.clone()
+    };
+    let json = serde_json::to_value(&config)
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+    sha256_hex(&json)[..12].to_string()
+}
+
+/// The create config for `service`'s container on `network`.
+fn service_container_config(
+    service: &dyn Service,
+    network: &str,
+    labels: &HashMap<String, String>,
+) -> Config<String> {
+    let env = Some(service.container_env());
+
+    // Configure volume mount if the service needs persistent storage
+    let mounts = service.volume().map(|vol| {
+        let parts: Vec<&str> = vol.splitn(2, ':').collect();
+        vec![Mount {
+            target: Some(parts[1].to_string()),
+            source: Some(parts[0].to_string()),
+            typ: Some(MountTypeEnum::VOLUME),
+            ..Default::default()
+        }]
+    });
+
+    // Not auto-removed: a service that crashes on startup must stay around
+    // for its logs to be read. The owner labels let the `Reaper` remove it
+    // if bubble-bot is killed before cleanup.
+    let host_config = HostConfig {
+        network_mode: Some(network.to_string()),
+        binds: non_empty(&service.binds()),
+        extra_hosts: non_empty(&service.extra_hosts()),
+        mounts,
+        ..Default::default()
+    };
+
+    // Attach to network with service name as alias for hostname-based discovery
+    let endpoint = EndpointSettings {
+        aliases: Some(vec![service.name().to_string()]),
+        ..Default::default()
+    };
+    let mut endpoints_config = HashMap::new();
+    endpoints_config.insert(network.to_string(), endpoint);
+    let networking_config = Some(NetworkingConfig { endpoints_config });
+
+    Config {
+        image: Some(service.image()),
+        env,
+        entrypoint: service.entrypoint(),
+        labels: Some(labels.clone()),
+        host_config: Some(host_config),
+        networking_config,
+        ..Default::default()
+    }
+}
+
+/// The [`DEFINITION_LABEL`] `service`'s container gets on `network`, to
+/// compare with a running one's.
+pub fn service_definition(service: &dyn Service, network: &str) -> String {
+    definition_hash(
+        &service_container_config(service, network, &HashMap::new()),
+        &service.session_secrets(),
+    )
+}
+
+/// The [`DEFINITION_LABEL`] the dev container gets with `opts`, to compare
+/// with a running one's.
+pub fn dev_definition(opts: &ContainerOpts) -> String {
+    definition_hash(&dev_container_config(opts), &opts.secrets())
+}
+
+/// `config` with [`DEFINITION_LABEL`] set to `definition`.
+fn with_definition(mut config: Config<String>, definition: &str) -> Config<String> {
+    config
+        .labels
+        .get_or_insert_with(HashMap::new)
+        .insert(DEFINITION_LABEL.to_string(), definition.to_string());
+    config
+}
+
+/// State of a container as seen by the session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContainerStatus {
+    Running,
+    /// Running, but its healthcheck is failing.
+    Unhealthy,
+    /// Exited or removed. `exit_code` is `None` if the container is gone.
+    Stopped {
+        exit_code: Option<i64>,
+        oom_killed: bool,
+    },
+}
+
+impl ContainerStatus {
+    fn from_state(state: Option<&ContainerState>) -> Self {
+        let Some(state) = state else {
+            return Self::Stopped {
+                exit_code: None,
+                oom_killed: false,
+            };
+        };
+        if !state.running.unwrap_or(false) {
+            return Self::Stopped {
+                exit_code: state.exit_code,
+                oom_killed: state.oom_killed.unwrap_or(false),
+            };
+        }
+        match state.health.as_ref().and_then(|h| h.status) {
+            Some(HealthStatusEnum::UNHEALTHY) => Self::Unhealthy,
+            _ => Self::Running,
+        }
+    }
+}
+
+impl std::fmt::Display for ContainerStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Running => write!(f, "is running"),
+            Self::Unhealthy => write!(f, "is unhealthy"),
+            Self::Stopped {
+                oom_killed: true, ..
+            } => write!(f, "was killed (out of memory)"),
+            Self::Stopped {
+                exit_code: Some(code),
+                ..
+            } => write!(f, "exited with code {code}"),
+            Self::Stopped {
+                exit_code: None, ..
+            } => write!(f, "was removed"),
+        }
+    }
+}
 
 /// Manages the lifecycle of the dev container: create, start, exec, stop, remove.
-pub struct ContainerManager {
-    docker: Docker,
+#[derive(Clone)]
+pub struct ContainerManager<B = Docker> {
+    docker: B,
+    retries: u32,
+}
+
+/// Extra `docker exec` options for the main command (`exec --workdir/--env`).
+#[derive(Debug, Clone, Default)]
+pub struct ExecOpts {
+    /// Working directory inside the container; relative paths are resolved
+    /// by [`ExecOpts::resolved`].
+    pub workdir: Option<String>,
+    /// `KEY=VALUE` variables set for the command only.
+    pub env: Vec<String>,
+}
+
+impl ExecOpts {
+    /// These options with a relative `workdir` made absolute under `base`,
+    /// since `docker exec -w` only takes absolute paths.
+    pub fn resolved(&self, base: &str) -> Self {
+        let workdir = self.workdir.as_ref().map(|dir| {
+            if dir.starts_with('/') {
+                dir.clone()
+            } else {
+                format!(
+                    "{}/{}",
+                    base.trim_end_matches('/'),
+                    dir.trim_end_matches('/')
+                )
+            }
+        });
+        Self {
+            workdir,
+            env: self.env.clone(),
+        }
+    }
+
+    /// The `docker exec` flags, placed before the container ID.
+    fn args(&self) -> Vec<&str> {
+        let mut args = Vec::new();
+        if let Some(workdir) = &self.workdir {
+            args.extend(["-w", workdir.as_str()]);
+        }
+        for var in &self.env {
+            args.extend(["-e", var.as_str()]);
+        }
+        args
+    }
 }
 
 /// Options for creating a dev container.
+#[derive(Clone, Default)]
 pub struct ContainerOpts {
     pub image_tag: String,
     pub container_name: String,
     pub shell: String,
     pub project_dir: String,
+    /// Working directory inside the container (under `/workspace`).
+    pub workdir: String,
     pub env_vars: Vec<String>,
     pub network: Option<String>,
-    /// Additional read-only bind mounts (e.g., dotfiles) in `host:container:ro` format.
+    /// Additional bind mounts (e.g., dotfiles) in `host:container[:options]` format.
     pub extra_binds: Vec<String>,
+    /// Custom DNS servers.
+    pub dns: Vec<String>,
+    /// Extra `/etc/hosts` entries in `host:ip` format.
+    pub extra_hosts: Vec<String>,
+    /// Ownership labels (see [`super::ownership`]).
+    pub labels: HashMap<String, String>,
+    /// tmpfs mounts: container path to mount options.
+    pub tmpfs: HashMap<String, String>,
+    /// Size of `/dev/shm` in bytes; Docker's default when `None`.
+    pub shm_size: Option<i64>,
+    /// Supplementary groups for the container user, as names or GIDs.
+    pub groups: Vec<String>,
+    /// Host devices exposed to the container.
+    pub devices: Vec<Device>,
+    /// Platform to run the image as (e.g., `linux/amd64`); the daemon's when
+    /// `None`.
+    pub platform: Option<String>,
+    /// How the container user maps to the host user.
+    pub user_mapping: UserMapping,
+    /// SELinux relabeling applied to every bind mount.
+    pub selinux_label: Option<SelinuxLabel>,
+    /// Named volumes mounted over paths under `/workspace`, as
+    /// `(volume, container path)`.
+    pub volumes: Vec<(String, String)>,
+    /// The services' session secrets in `env_vars`, left out of the
+    /// definition (see [`DEFINITION_LABEL`]).
+    pub session_secrets: Vec<String>,
+}
+
+impl ContainerOpts {
+    fn secrets(&self) -> Vec<&str> {
+        self.session_secrets.iter().map(String::as_str).collect()
+    }
+}
+
+/// How the dev container's user maps to the host user, so files created in
+/// `/workspace` stay owned by them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UserMapping {
+    /// Run as the host `uid:gid`.
+    #[default]
+    HostUser,
+    /// Rootless daemon: container root already is the host user, and any
+    /// other UID maps to a subordinate ID, so run as root.
+    Root,
+    /// userns-remap daemon: opt the container out of remapping
+    /// (`--userns=host`) and run as the host `uid:gid`.
+    HostUserns,
+}
+
+impl UserMapping {
+    /// Picks the mapping from the daemon's `SecurityOptions`, whose entries
+    /// look like `name=rootless` or `name=seccomp,profile=builtin`.
+    pub fn from_security_options(options: &[String]) -> Self {
+        let has = |name: &str| {
+            options
+                .iter()
+                .any(|o| o.split(',').next() == Some(&format!("name={name}")))
+        };
+        if has("rootless") {
+            Self::Root
+        } else if has("userns") {
+            Self::HostUserns
+        } else {
+            Self::HostUser
+        }
+    }
 }
 
-impl ContainerManager {
-    pub fn new(docker: Docker) -> Self {
-        Self { docker }
+impl<B: ContainerBackend> ContainerManager<B> {
+    pub fn new(docker: B) -> Self {
+        Self {
+            docker,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+
+    /// Retries of container creates, starts, and network attachments that
+    /// fail transiently (see [`retry::is_transient`]).
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Whether a container with the given name exists and is running.
+    pub async fn is_running(&self, name: &str) -> Result<bool> {
+        match self.docker.inspect_container(name, None).await {
+            Ok(info) => Ok(info.state.and_then(|s| s.running).unwrap_or(false)),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(false),
+            Err(e) => Err(e).context(format!("failed to inspect container {name}")),
+        }
+    }
+
+    /// The env (`KEY=VALUE`) a container was started with, or `None` if it
+    /// isn't running.
+    pub async fn running_env(&self, name: &str) -> Result<Option<Vec<String>>> {
+        match self.docker.inspect_container(name, None).await {
+            Ok(info) if info.state.as_ref().and_then(|s| s.running) == Some(true) => Ok(Some(
+                info.config
+                    .and_then(|config| config.env)
+                    .unwrap_or_default(),
+            )),
+            Ok(_)
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(None),
+            Err(e) => Err(e).context(format!("failed to inspect container {name}")),
+        }
+    }
+
+    /// A container's output so far (stdout and stderr), one entry per line.
+    pub async fn logs(&self, id: &str) -> Result<Vec<String>> {
+        use futures_util::StreamExt;
+
+        let options = LogsOptions::<String> {
+            stdout: true,
+            stderr: true,
+            tail: "all".to_string(),
+            ..Default::default()
+        };
+        let mut stream = self.docker.logs(id, Some(options));
+        let mut text = String::new();
+        while let Some(output) = stream.next().await {
+            let output = output.with_context(|| format!("failed to read logs of {id}"))?;
+            text.push_str(&output.to_string());
+        }
+        Ok(text.lines().map(String::from).collect())
+    }
+
+    /// Writes a container's output (stdout and stderr, each line prefixed
+    /// with its timestamp) to `sink` as it is produced, until the container
+    /// stops.
+    pub async fn follow_logs(&self, id: &str, mut sink: impl std::io::Write) -> Result<()> {
+        use futures_util::StreamExt;
+
+        let options = LogsOptions::<String> {
+            follow: true,
+            stdout: true,
+            stderr: true,
+            timestamps: true,
+            tail: "all".to_string(),
+            ..Default::default()
+        };
+        let mut stream = self.docker.logs(id, Some(options));
+        while let Some(output) = stream.next().await {
+            let output = output.with_context(|| format!("failed to read logs of {id}"))?;
+            sink.write_all(&output.into_bytes())?;
+            sink.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Current status of a container.
+    pub async fn status(&self, id: &str) -> Result<ContainerStatus> {
+        match self.docker.inspect_container(id, None).await {
+            Ok(info) => Ok(ContainerStatus::from_state(info.state.as_ref())),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(ContainerStatus::from_state(None)),
+            Err(e) => Err(e).context(format!("failed to inspect container {id}")),
+        }
+    }
+
+    /// The config hash a container was started with (see
+    /// [`super::images::CONFIG_HASH_LABEL`]), read from its labels, which
+    /// include its image's. `None` if the container doesn't exist or predates
+    /// the label.
+    pub async fn config_hash(&self, name: &str) -> Result<Option<String>> {
+        match self.docker.inspect_container(name, None).await {
+            Ok(info) => Ok(info
+                .config
+                .and_then(|config| config.labels)
+                .and_then(|mut labels| labels.remove(CONFIG_HASH_LABEL))),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => Ok(None),
+            Err(e) => Err(e).context(format!("failed to inspect container {name}")),
+        }
+    }
+
+    /// Polls a container until it stops, warning once if it turns unhealthy.
+    /// Returns the stopped status.
+    pub async fn wait_until_stopped(&self, id: &str, interval_secs: u64) -> ContainerStatus {
+        let mut warned = false;
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(interval_secs)).await;
+            match self.status(id).await {
+                Ok(ContainerStatus::Running) => warned = false,
+                Ok(ContainerStatus::Unhealthy) => {
+                    if !warned {
+                        warn!(id, "dev container is unhealthy");
+                        warned = true;
+                    }
+                }
+                Ok(stopped) => return stopped,
+                Err(e) => warn!(error = %e, "failed to check dev container"),
+            }
+        }
     }
 
     /// Detects and removes an existing container with the given name.
@@ -64,7 +599,8 @@ impl ContainerManager {
                     .stop_container(id, Some(StopContainerOptions { t: 5 }))
                     .await;
 
-                self.docker
+                let removal = self
+                    .docker
                     .remove_container(
                         id,
                         Some(RemoveContainerOptions {
@@ -72,18 +608,75 @@ impl ContainerManager {
                             ..Default::default()
                         }),
                     )
-                    .await
-                    .context("failed to remove existing container")?;
+                    .await;
+                tolerate_removed(removal).context("failed to remove existing container")?;
             }
         }
 
         Ok(())
     }
 
+    /// The ID of the running container `name` if a `--keep` session left
+    /// it with this definition (see [`DEFINITION_LABEL`]).
+    async fn find_kept(&self, name: &str, definition: &str) -> Result<Option<String>> {
+        let filters: HashMap<String, Vec<String>> = HashMap::from([
+            ("name".to_string(), vec![name.to_string()]),
+            ("label".to_string(), vec![KEEP_LABEL.to_string()]),
+        ]);
+        let containers = self
+            .docker
+            .list_containers(Some(ListContainersOptions {
+                filters,
+                ..Default::default()
+            }))
+            .await
+            .context("failed to list kept containers")?;
+
+        let exact_name = format!("/{name}");
+        Ok(containers
+            .into_iter()
+            .find(|c| {
+                c.names.as_deref().unwrap_or_default().contains(&exact_name)
+                    && c.labels
+                        .as_ref()
+                        .and_then(|labels| labels.get(DEFINITION_LABEL))
+                        .is_some_and(|d| d == definition)
+            })
+            .and_then(|c| c.id))
+    }
+
+    /// Containers, running or not, named `project_prefix` or
+    /// `project_prefix-*`: the project's dev and service containers.
+    pub async fn project_containers(&self, project_prefix: &str) -> Result<Vec<ContainerSummary>> {
+        let filters: HashMap<String, Vec<String>> =
+            HashMap::from([("name".to_string(), vec![project_prefix.to_string()])]);
+        let containers = self
+            .docker
+            .list_containers(Some(ListContainersOptions {
+                all: true,
+                filters,
+                ..Default::default()
+            }))
+            .await
+            .context("failed to list project containers")?;
+        Ok(containers
+            .into_iter()
+            .filter(|c| {
+                c.names
+                    .as_deref()
+                    .unwrap_or_default()
+                    .iter()
+                    .any(|n| matches_stale_prefix(n, project_prefix))
+            })
+            .collect())
+    }
+
     /// Detects and removes all stale containers matching the `bubble-bot-<project>` prefix.
     /// This catches dev containers and service containers from crashed sessions.
-    /// Returns the number of containers removed.
-    pub async fn cleanup_stale(&self, project_prefix: &str) -> Result<usize> {
+    /// Containers kept with `--keep` under one of the `reusable` names are
+    /// left for the session to reuse or replace. Returns the number of
+    /// containers removed.
+    pub async fn cleanup_stale(&self, project_prefix: &str, reusable: &[String]) -> Result<usize> {
         let filters: HashMap<String, Vec<String>> =
             [("name".to_string(), vec![project_prefix.to_string()])]
                 .into_iter()
@@ -106,8 +699,16 @@ impl ContainerManager {
             let is_match = names
                 .iter()
                 .any(|n| matches_stale_prefix(n, project_prefix));
-
-            if is_match {
+            let kept = container
+                .labels
+                .as_ref()
+                .is_some_and(|labels| labels.contains_key(KEEP_LABEL));
+            let reused = kept
+                && names
+                    .iter()
+                    .any(|n| reusable.iter().any(|r| n.trim_start_matches('/') == r));
+
+            if is_match && !reused {
                 let id = container.id.as_deref().unwrap_or("unknown");
                 let name = names.first().map(|s| s.as_str()).unwrap_or("unknown");
                 warn!(name, id, "removing stale container from previous session");
@@ -142,66 +743,46 @@ impl ContainerManager {
         Ok(removed)
     }
 
-    /// Creates and starts a container, returning the container ID.
-    pub async fn create_and_start(&self, opts: &ContainerOpts) -> Result<String> {
-        let uid = unsafe { libc::getuid() };
-        let gid = unsafe { libc::getgid() };
-        let user = format!("{uid}:{gid}");
-
-        let bind = format!("{}:/workspace", opts.project_dir);
-        let mut binds = vec![bind];
-        binds.extend(opts.extra_binds.clone());
-
-        let host_config = HostConfig {
-            binds: Some(binds),
-            network_mode: opts.network.clone(),
-            ..Default::default()
-        };
-
-        let env = if opts.env_vars.is_empty() {
-            None
-        } else {
-            Some(opts.env_vars.clone())
-        };
-
-        // Attach to network with container name as alias for hostname-based discovery
-        let networking_config = opts.network.as_ref().map(|net| {
-            let endpoint = EndpointSettings {
-                aliases: Some(vec![opts.container_name.clone()]),
-                ..Default::default()
-            };
-            let mut endpoints_config = HashMap::new();
-            endpoints_config.insert(net.clone(), endpoint);
-            NetworkingConfig { endpoints_config }
-        });
+    /// Detects whether the daemon is rootless or remaps user namespaces. An
+    /// unreadable `/info` falls back to running as the host user.
+    pub async fn user_mapping(&self) -> UserMapping {
+        match self.docker.info().await {
+            Ok(info) => {
+                let mapping =
+                    UserMapping::from_security_options(&info.security_options.unwrap_or_default());
+                if mapping != UserMapping::HostUser {
+                    info!(?mapping, "daemon remaps users");
+                }
+                mapping
+            }
+            Err(e) => {
+                warn!(error = %e, "failed to read daemon info; running as the host user");
+                UserMapping::HostUser
+            }
+        }
+    }
 
-        let config = Config {
-            image: Some(opts.image_tag.clone()),
-            cmd: Some(vec!["sleep".to_string(), "infinity".to_string()]),
-            user: Some(user),
-            working_dir: Some("/workspace".to_string()),
-            host_config: Some(host_config),
-            env,
-            networking_config,
-            ..Default::default()
-        };
+    /// The ID of a dev container a `--keep` session left running that
+    /// `opts` would create the same way, to reattach to instead of
+    /// recreating it.
+    pub async fn reuse_kept(&self, opts: &ContainerOpts) -> Result<Option<String>> {
+        self.find_kept(&opts.container_name, &dev_definition(opts))
+            .await
+    }
 
-        let create_opts = CreateContainerOptions {
-            name: opts.container_name.clone(),
-            ..Default::default()
-        };
+    /// Creates and starts a container, returning the container ID.
+    pub async fn create_and_start(&self, opts: &ContainerOpts) -> Result<String> {
+        let config = dev_container_config(opts);
+        let definition = definition_hash(&config, &opts.secrets());
+        let config = with_definition(config, &definition);
 
-        let response = self
-            .docker
-            .create_container(Some(create_opts), config)
+        let container_id = self
+            .create_container(&opts.container_name, opts.platform.clone(), config)
             .await
             .context("failed to create container")?;
-
-        let container_id = response.id;
         info!(id = %container_id, name = %opts.container_name, "container created");
 
-        self.docker
-            .start_container::<String>(&container_id, None)
+        self.start_container(&container_id)
             .await
             .context("failed to start container")?;
 
@@ -210,39 +791,41 @@ impl ContainerManager {
         Ok(container_id)
     }
 
-    /// Launches an interactive shell inside the container via `docker exec -it`.
-    /// This is a blocking call that inherits stdio.
+    /// Launches an interactive shell inside the container via `docker exec -it`
+    /// (see [`exec_stdio_flag`]). This is a blocking call that inherits stdio.
     pub fn exec_interactive_shell(&self, container_id: &str, shell: &str) -> Result<i32> {
         info!(container = %container_id, shell, "launching interactive shell");
 
-        let status = Command::new("docker")
-            .args(["exec", "-it", container_id, shell])
-            .stdin(std::process::Stdio::inherit())
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .status()
-            .context("failed to exec into container")?;
+        let status = run_foreground(
+            Command::new("docker")
+                .args(["exec", exec_stdio_flag(), container_id, shell])
+                .stdin(std::process::Stdio::inherit())
+                .stdout(std::process::Stdio::inherit())
+                .stderr(std::process::Stdio::inherit()),
+        )
+        .context("failed to exec into container")?;
 
-        Ok(status.code().unwrap_or(1))
+        Ok(exit_code(status))
     }
 
-    /// Launches an interactive command inside the container via `docker exec -it`.
-    /// This is a blocking call that inherits stdio.
+    /// Launches an interactive command inside the container via `docker exec -it`
+    /// (see [`exec_stdio_flag`]). This is a blocking call that inherits stdio.
     pub fn exec_interactive_command(&self, container_id: &str, cmd: &[&str]) -> Result<i32> {
         info!(container = %container_id, ?cmd, "launching interactive command");
 
-        let mut args = vec!["exec", "-it", container_id];
+        let mut args = vec!["exec", exec_stdio_flag(), container_id];
         args.extend(cmd);
 
-        let status = Command::new("docker")
-            .args(&args)
-            .stdin(std::process::Stdio::inherit())
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .status()
-            .context("failed to exec command in container")?;
+        let status = run_foreground(
+            Command::new("docker")
+                .args(&args)
+                .stdin(std::process::Stdio::inherit())
+                .stdout(std::process::Stdio::inherit())
+                .stderr(std::process::Stdio::inherit()),
+        )
+        .context("failed to exec command in container")?;
 
-        Ok(status.code().unwrap_or(1))
+        Ok(exit_code(status))
     }
 
     /// Writes the OAuth credentials file inside the container.
@@ -276,6 +859,36 @@ impl ContainerManager {
         Ok(())
     }
 
+    /// Writes `content` to `path` (relative to the container home), piped via
+    /// stdin.
+    pub fn write_home_file(&self, container_id: &str, path: &str, content: &str) -> Result<()> {
+        use std::io::Write;
+
+        let script = format!("cat > \"${{HOME}}/{path}\"");
+        let mut child = Command::new("docker")
+            .args(["exec", "-i", container_id, "sh", "-c", &script])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to spawn docker exec for ~/{path}"))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(content.as_bytes())?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("failed to wait for ~/{path} write"))?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "failed to write ~/{path} to container: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+
     /// Writes the Claude config file (`~/.claude.json`) inside the container.
     /// Pipes the content via stdin to avoid exposing config in process arguments.
     pub fn write_claude_config(&self, container_id: &str, config: &str) -> Result<()> {
@@ -313,33 +926,135 @@ impl ContainerManager {
 
     /// Runs a command inside the container via `docker exec` (non-interactive).
     /// Inherits stdout and stderr but does not allocate a TTY.
-    pub fn exec_command(&self, container_id: &str, cmd: &[&str]) -> Result<i32> {
-        info!(container = %container_id, ?cmd, "running command");
+    pub fn exec_command(&self, container_id: &str, cmd: &[&str], opts: &ExecOpts) -> Result<i32> {
+        info!(container = %container_id, ?cmd, workdir = ?opts.workdir, "running command");
+
+        let mut args = vec!["exec"];
+        args.extend(opts.args());
+        args.push(container_id);
+        args.extend(cmd);
 
-        let mut args = vec!["exec", container_id];
+        let status = run_foreground(
+            Command::new("docker")
+                .args(&args)
+                .stdin(std::process::Stdio::inherit())
+                .stdout(std::process::Stdio::inherit())
+                .stderr(std::process::Stdio::inherit()),
+        )
+        .context("failed to exec command in container")?;
+
+        Ok(exit_code(status))
+    }
+
+    /// Runs a command inside the container via `docker exec` without a TTY,
+    /// writing its stdout to the host file `output`. Piped stdin is passed
+    /// through; stderr still goes to the terminal.
+    pub fn exec_command_to_file(
+        &self,
+        container_id: &str,
+        cmd: &[&str],
+        output: &Path,
+    ) -> Result<i32> {
+        use std::io::IsTerminal;
+
+        info!(container = %container_id, ?cmd, output = %output.display(), "running command");
+
+        let file = std::fs::File::create(output)
+            .with_context(|| format!("failed to create {}", output.display()))?;
+        let mut args = vec!["exec"];
+        if !std::io::stdin().is_terminal() {
+            args.push("-i");
+        }
+        args.push(container_id);
         args.extend(cmd);
 
-        let status = Command::new("docker")
-            .args(&args)
-            .stdin(std::process::Stdio::inherit())
-            .stdout(std::process::Stdio::inherit())
-            .stderr(std::process::Stdio::inherit())
-            .status()
-            .context("failed to exec command in container")?;
+        let status = run_foreground(
+            Command::new("docker")
+                .args(&args)
+                .stdin(std::process::Stdio::inherit())
+                .stdout(file)
+                .stderr(std::process::Stdio::inherit()),
+        )
+        .context("failed to exec command in container")?;
+
+        Ok(exit_code(status))
+    }
+
+    /// Gives the host user ownership of `paths`, which Docker creates as root
+    /// (e.g., fresh volume mount points).
+    pub fn chown_to_user(&self, container_id: &str, paths: &[String]) -> Result<()> {
+        let owner = host_owner();
+        let mut cmd = vec!["chown", owner.as_str()];
+        cmd.extend(paths.iter().map(String::as_str));
+        self.exec_as_root(container_id, &cmd)
+    }
+
+    /// Gives everything under `/workspace` not owned by `owner` (`uid:gid`,
+    /// as the container runs) back to it, e.g. files a tool running as root
+    /// created, returning how many paths changed owner. `skip`, the volumes
+    /// mounted under `/workspace` (`mount.exclude`), is left alone.
+    pub fn fix_workspace_ownership(
+        &self,
+        container_id: &str,
+        owner: &str,
+        skip: &[String],
+    ) -> Result<usize> {
+        let output = Command::new("docker")
+            .args(["exec", "-u", "0", container_id, "sh", "-c"])
+            .arg(fix_ownership_script(owner, skip.len()))
+            .arg("sh")
+            .args(skip)
+            .stdin(std::process::Stdio::null())
+            .output()
+            .context("failed to spawn docker exec")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "failed to fix workspace ownership: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).lines().count())
+    }
 
-        Ok(status.code().unwrap_or(1))
+    /// Runs a setup command as root via `docker exec`, capturing its output
+    /// for the error if it fails.
+    pub fn exec_as_root(&self, container_id: &str, cmd: &[&str]) -> Result<()> {
+        let output = Command::new("docker")
+            .args(["exec", "-u", "0", container_id])
+            .args(cmd)
+            .output()
+            .context("failed to spawn docker exec")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "`{}` failed in container: {}",
+                cmd.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
     }
 
     /// Stops and removes the container.
     pub async fn stop_and_remove(&self, container_id: &str) -> Result<()> {
+        self.stop(container_id).await;
+        self.remove(container_id).await
+    }
+
+    /// Asks the container to stop, killing it after 5 seconds. Errors (e.g.,
+    /// already stopped) are ignored; [`Self::remove`] forces the rest.
+    pub async fn stop(&self, container_id: &str) {
         info!(id = %container_id, "stopping container");
 
         let _ = self
             .docker
             .stop_container(container_id, Some(StopContainerOptions { t: 5 }))
             .await;
+    }
 
-        self.docker
+    /// Removes the container, killing it if it is still running.
+    pub async fn remove(&self, container_id: &str) -> Result<()> {
+        let removal = self
+            .docker
             .remove_container(
                 container_id,
                 Some(RemoveContainerOptions {
@@ -347,8 +1062,8 @@ impl ContainerManager {
                     ..Default::default()
                 }),
             )
-            .await
-            .context("failed to remove container")?;
+            .await;
+        tolerate_removed(removal).context("failed to remove container")?;
 
         info!(id = %container_id, "container removed");
 
@@ -356,70 +1071,47 @@ impl ContainerManager {
     }
 
     /// Starts a service container (e.g., MySQL, Redis, PostgreSQL) on the given network.
-    /// Returns the container ID.
+    /// Its image must be present; see [`Self::prefetch_images`]. A running
+    /// container a `--keep` session left with the same definition is reused
+    /// as is, data and all. Returns the container ID.
     pub async fn start_service(
         &self,
         service: &dyn Service,
         network: &str,
-        project_name: &str,
+        project: &str,
+        labels: &HashMap<String, String>,
     ) -> Result<String> {
-        let container_name = service.container_name(project_name);
+        let container_name = service.container_name(project);
+        let config = service_container_config(service, network, labels);
+
+        let definition = definition_hash(&config, &service.session_secrets());
+        if let Some(id) = self.find_kept(&container_name, &definition).await? {
+            info!(service = service.name(), id = %id, "reusing kept service container");
+            output::done(format!("Reusing kept {} container", service.name()));
+            return Ok(id);
+        }
+        let config = with_definition(config, &definition);
 
         // Clean up any existing service container
         self.cleanup_existing(&container_name).await?;
 
-        let env = Some(service.container_env());
-
-        // Configure volume mount if the service needs persistent storage
-        let mounts = service.volume().map(|vol| {
-            let parts: Vec<&str> = vol.splitn(2, ':').collect();
-            vec![Mount {
-                target: Some(parts[1].to_string()),
-                source: Some(parts[0].to_string()),
-                typ: Some(MountTypeEnum::VOLUME),
-                ..Default::default()
-            }]
-        });
-
-        let host_config = HostConfig {
-            network_mode: Some(network.to_string()),
-            mounts,
-            ..Default::default()
-        };
-
-        // Attach to network with service name as alias for hostname-based discovery
-        let endpoint = EndpointSettings {
-            aliases: Some(vec![service.name().to_string()]),
-            ..Default::default()
-        };
-        let mut endpoints_config = HashMap::new();
-        endpoints_config.insert(network.to_string(), endpoint);
-        let networking_config = Some(NetworkingConfig { endpoints_config });
-
-        let config = Config {
-            image: Some(service.image()),
-            env,
-            host_config: Some(host_config),
-            networking_config,
-            ..Default::default()
-        };
-
-        let create_opts = CreateContainerOptions {
-            name: container_name.clone(),
-            ..Default::default()
-        };
-
-        let response = self
-            .docker
-            .create_container(Some(create_opts), config)
+        let container_id = self
+            .create_container(&container_name, None, config)
             .await
             .context(format!("failed to create {} container", service.name()))?;
-
-        let container_id = response.id;
         info!(service = service.name(), id = %container_id, "service container created");
 
-        self.docker
-            .start_container::<String>(&container_id, None)
+        // Give egress-capable services a route out of an internal session network
+        if service.needs_egress() {
+            self.connect_network("bridge", &container_id)
+                .await
+                .context(format!(
+                    "failed to attach {} container to bridge network",
+                    service.name()
+                ))?;
+        }
+
+        self.start_container(&container_id)
             .await
             .context(format!("failed to start {} container", service.name()))?;
 
@@ -428,59 +1120,462 @@ impl ContainerManager {
         Ok(container_id)
     }
 
-    /// Waits for a service container to become ready by retrying a readiness command.
-    /// Uses `docker exec` with a retry loop (up to `max_retries` attempts with `interval` seconds between).
-    pub fn wait_for_ready(
+    /// Creates a container, retrying transient failures. Returns its ID.
+    async fn create_container(
         &self,
-        container_id: &str,
-        service: &dyn Service,
-        max_retries: u32,
-        interval_secs: u64,
-    ) -> Result<()> {
-        let cmd = service.readiness_cmd();
-        info!(
-            service = service.name(),
-            container = %container_id,
-            "waiting for service to be ready"
-        );
-
-        for attempt in 1..=max_retries {
-            let mut args = vec!["exec", container_id];
-            let cmd_refs: Vec<&str> = cmd.iter().map(|s| s.as_str()).collect();
-            args.extend(&cmd_refs);
-
-            let status = Command::new("docker")
-                .args(&args)
-                .stdout(std::process::Stdio::null())
-                .stderr(std::process::Stdio::null())
-                .status();
-
-            match status {
-                Ok(s) if s.success() => {
-                    info!(service = service.name(), attempt, "service is ready");
-                    return Ok(());
-                }
-                _ => {
-                    if attempt < max_retries {
-                        info!(
-                            service = service.name(),
-                            attempt, max_retries, "service not ready, retrying..."
-                        );
-                        std::thread::sleep(std::time::Duration::from_secs(interval_secs));
-                    }
+        name: &str,
+        platform: Option<String>,
+        config: Config<String>,
+    ) -> Result<String, bollard::errors::Error> {
+        retry::retry(self.retries, "container create", |attempt| {
+            let options = CreateContainerOptions {
+                name: name.to_string(),
+                platform: platform.clone(),
+            };
+            let config = config.clone();
+            async move {
+                match self.docker.create_container(Some(options), config).await {
+                    Ok(response) => Ok(response.id),
+                    // An earlier attempt created it before its response was lost
+                    Err(e) if attempt > 0 && already_exists(&e) => self
+                        .docker
+                        .inspect_container(name, None)
+                        .await
+                        .map(|info| info.id.unwrap_or_default()),
+                    Err(e) => Err(e),
                 }
             }
-        }
+        })
+        .await
+    }
 
-        anyhow::bail!(
-            "{} service did not become ready after {} attempts",
-            service.name(),
-            max_retries
-        );
+    /// Starts a container, retrying transient failures. Starting a running
+    /// container is a no-op, so retries are safe.
+    async fn start_container(&self, id: &str) -> Result<(), bollard::errors::Error> {
+        retry::retry(self.retries, "container start", |_| {
+            self.docker.start_container(id)
+        })
+        .await
     }
-}
 
-/// Checks whether a container name matches the stale detection prefix.
+    /// Attaches a container to a network, retrying transient failures.
+    async fn connect_network(
+        &self,
+        network: &str,
+        container_id: &str,
+    ) -> Result<(), bollard::errors::Error> {
+        retry::retry(self.retries, "network connect", |attempt| async move {
+            let options = ConnectNetworkOptions {
+                container: container_id.to_string(),
+                ..Default::default()
+            };
+            match self.docker.connect_network(network, options).await {
+                Err(e) if attempt > 0 && already_exists(&e) => Ok(()),
+                result => result,
+            }
+        })
+        .await
+    }
+
+    /// Makes every image available as `policy` dictates, pulling them in
+    /// parallel with a progress line each. Run before any service container is
+    /// created, so a slow or failing pull doesn't leave a half-started
+    /// environment behind.
+    pub async fn prefetch_images(
+        &self,
+        images: &[String],
+        policy: PullPolicy,
+        registry_auth: &RegistryAuth,
+    ) -> Result<()> {
+        let mut unique: Vec<&str> = Vec::new();
+        for image in images {
+            if !unique.contains(&image.as_str()) {
+                unique.push(image);
+            }
+        }
+        let pulls = unique
+            .into_iter()
+            .map(|image| self.ensure_image(image, policy, registry_auth));
+        futures_util::future::try_join_all(pulls).await?;
+        Ok(())
+    }
+
+    /// Makes sure `image` is available locally, pulling it as `policy` dictates.
+    async fn ensure_image(
+        &self,
+        image: &str,
+        policy: PullPolicy,
+        registry_auth: &RegistryAuth,
+    ) -> Result<()> {
+        let present = match self.docker.inspect_image(image).await {
+            Ok(_) => true,
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404, ..
+            }) => false,
+            Err(e) => return Err(e).context(format!("failed to inspect image {image}")),
+        };
+
+        match policy {
+            PullPolicy::Missing if present => Ok(()),
+            PullPolicy::Never if present => Ok(()),
+            PullPolicy::Never => anyhow::bail!(
+                "image {image} is not present locally and services.pull_policy is \"never\""
+            ),
+            PullPolicy::Missing | PullPolicy::Always => {
+                self.pull_image(image, registry_auth.for_image(image)).await
+            }
+        }
+    }
+
+    /// Pulls an image, showing aggregate download progress across layers.
+    async fn pull_image(&self, image: &str, credentials: Option<DockerCredentials>) -> Result<()> {
+        use futures_util::StreamExt;
+
+        let (from_image, tag) = split_image_ref(image);
+        let options = CreateImageOptions {
+            from_image: from_image.to_string(),
+            tag: tag.to_string(),
+            ..Default::default()
+        };
+
+        let pb = output::spinner(format!("Pulling {image}..."));
+
+        // Per-layer (downloaded, total) bytes
+        let mut layers: HashMap<String, (u64, u64)> = HashMap::new();
+        let mut stream = self.docker.create_image(options, credentials);
+
+        while let Some(result) = stream.next().await {
+            let info = match result {
+                Ok(info) => info,
+                Err(e) => {
+                    let detail = if let bollard::errors::Error::DockerStreamError { error } = &e {
+                        error.clone()
+                    } else {
+                        format!("{e}")
+                    };
+                    output::finish(&pb, format!("Pull failed: {image}"));
+                    anyhow::bail!("failed to pull {image}: {detail}");
+                }
+            };
+
+            if let Some(error) = &info.error {
+                output::finish(&pb, format!("Pull failed: {image}"));
+                anyhow::bail!("failed to pull {image}: {error}");
+            }
+
+            if let (Some(id), Some(detail)) = (&info.id, &info.progress_detail) {
+                if info.status.as_deref() == Some("Downloading") {
+                    let current = detail.current.unwrap_or(0).max(0) as u64;
+                    let total = detail.total.unwrap_or(0).max(0) as u64;
+                    layers.insert(id.clone(), (current, total));
+                }
+            }
+
+            let (current, total) = layers
+                .values()
+                .fold((0, 0), |(c, t), (lc, lt)| (c + lc, t + lt));
+            if total > 0 {
+                pb.set_message(format!(
+                    "Pulling {image} ({} / {})",
+                    HumanBytes(current),
+                    HumanBytes(total)
+                ));
+            }
+        }
+
+        output::finish(&pb, format!("Pulled {image}"));
+        info!(image, "image pulled");
+        Ok(())
+    }
+
+    /// Waits for a service container to become ready by retrying its
+    /// readiness check (up to `max_retries` attempts with `interval` seconds
+    /// between). See [`probe_args`] for how each kind of check runs. After
+    /// each failed attempt, the service's new log lines are printed as
+    /// `[name] line`.
+    pub async fn wait_for_ready(
+        &self,
+        container_id: &str,
+        service: &dyn Service,
+        max_retries: u32,
+        interval_secs: u64,
+    ) -> Result<()> {
+        self.wait_until_ready(
+            container_id,
+            service.name(),
+            service.readiness().as_ref(),
+            max_retries,
+            interval_secs,
+            true,
+        )
+        .await
+    }
+
+    /// [`Self::wait_for_ready`] for a service given by name and readiness
+    /// check, so the wait can outlive the `Service` (e.g., in a spawned task).
+    /// Without `echo_logs`, the logs only show up in the timeout error.
+    pub async fn wait_until_ready(
+        &self,
+        container_id: &str,
+        name: &str,
+        readiness: Option<&Readiness>,
+        max_retries: u32,
+        interval_secs: u64,
+        echo_logs: bool,
+    ) -> Result<()> {
+        let Some(readiness) = readiness else {
+            return Ok(());
+        };
+        let args = probe_args(container_id, readiness);
+        info!(
+            service = name,
+            container = %container_id,
+            "waiting for service to be ready"
+        );
+
+        let mut echoed = 0;
+        for attempt in 1..=max_retries {
+            let status = tokio::process::Command::new("docker")
+                .args(&args)
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status()
+                .await;
+
+            match status {
+                Ok(s) if s.success() => {
+                    info!(service = name, attempt, "service is ready");
+                    return Ok(());
+                }
+                _ => {
+                    // A service that crashed won't come up; say so with its logs
+                    if let Ok(stopped @ ContainerStatus::Stopped { .. }) =
+                        self.status(container_id).await
+                    {
+                        let lines = self.logs(container_id).await.unwrap_or_default();
+                        return Err(Error::ServiceTimeout(anyhow::anyhow!(
+                            "{}",
+                            stopped_message(name, &stopped, &lines)
+                        ))
+                        .into());
+                    }
+                    if attempt < max_retries {
+                        info!(
+                            service = name,
+                            attempt, max_retries, "service not ready, retrying..."
+                        );
+                        if echo_logs {
+                            if let Ok(lines) = self.logs(container_id).await {
+                                for line in new_log_lines(&lines, &mut echoed) {
+                                    eprintln!("[{name}] {line}");
+                                }
+                            }
+                        }
+                        tokio::time::sleep(std::time::Duration::from_secs(interval_secs)).await;
+                    }
+                }
+            }
+        }
+
+        let lines = self.logs(container_id).await.unwrap_or_default();
+        Err(Error::ServiceTimeout(anyhow::anyhow!(
+            "{}",
+            timeout_message(name, max_retries, &lines)
+        ))
+        .into())
+    }
+}
+
+/// Log lines echoed per failed readiness attempt; earlier new lines are
+/// skipped so a chatty service doesn't flood the console.
+const ECHOED_LOG_LINES: usize = 10;
+
+/// Log lines quoted in the error of a service that never became ready.
+const TIMEOUT_LOG_LINES: usize = 20;
+
+/// The lines of `lines` past the `echoed` already printed, at most
+/// [`ECHOED_LOG_LINES`] of them, advancing `echoed` past all of them.
+fn new_log_lines<'a>(lines: &'a [String], echoed: &mut usize) -> &'a [String] {
+    let new = &lines[(*echoed).min(lines.len())..];
+    *echoed = lines.len();
+    &new[new.len().saturating_sub(ECHOED_LOG_LINES)..]
+}
+
+/// The readiness timeout error, ending with the service's last log lines.
+fn timeout_message(name: &str, attempts: u32, lines: &[String]) -> String {
+    with_last_lines(
+        format!("{name} service did not become ready after {attempts} attempts"),
+        name,
+        lines,
+    )
+}
+
+/// The error of a service whose container stopped before it became ready,
+/// quoting the last [`TIMEOUT_LOG_LINES`] of `lines`.
+fn stopped_message(name: &str, status: &ContainerStatus, lines: &[String]) -> String {
+    with_last_lines(
+        format!("{name} service {status} before becoming ready"),
+        name,
+        lines,
+    )
+}
+
+fn with_last_lines(mut message: String, name: &str, lines: &[String]) -> String {
+    let last = &lines[lines.len().saturating_sub(TIMEOUT_LOG_LINES)..];
+    if !last.is_empty() {
+        message.push_str(&format!("; last {name} log lines:"));
+        for line in last {
+            message.push_str(&format!("\n  {line}"));
+        }
+    }
+    message
+}
+
+/// Image for TCP and HTTP readiness probes, which run in a throwaway
+/// container sharing the service's network namespace, so `localhost` is the
+/// service and its image needs no client tools.
+pub const PROBE_IMAGE: &str = "busybox:1";
+
+/// `docker` arguments for one readiness attempt against `container_id`.
+pub fn probe_args(container_id: &str, readiness: &Readiness) -> Vec<String> {
+    let probe = |tool: &[&str]| {
+        let mut args: Vec<String> = [
+            "run",
+            "--rm",
+            "--network",
+            &format!("container:{container_id}"),
+            PROBE_IMAGE,
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        args.extend(tool.iter().map(|s| s.to_string()));
+        args
+    };
+    match readiness {
+        Readiness::Cmd(cmd) => {
+            let mut args = vec!["exec".to_string(), container_id.to_string()];
+            args.extend(cmd.iter().cloned());
+            args
+        }
+        Readiness::Tcp(port) => probe(&["nc", "-z", "-w", "2", "127.0.0.1", &port.to_string()]),
+        Readiness::Http(url) => probe(&["wget", "-q", "-O", "/dev/null", "-T", "2", url]),
+    }
+}
+
+/// The `docker exec` flag for an interactive command: `-it` on a terminal,
+/// and `-i` alone when stdin is piped or stdout is redirected, where Docker
+/// refuses a TTY ("the input device is not a TTY") or would mangle the
+/// output with carriage returns.
+fn exec_stdio_flag() -> &'static str {
+    use std::io::IsTerminal;
+
+    stdio_flag(
+        std::io::stdin().is_terminal(),
+        std::io::stdout().is_terminal(),
+    )
+}
+
+fn stdio_flag(stdin_tty: bool, stdout_tty: bool) -> &'static str {
+    if stdin_tty && stdout_tty { "-it" } else { "-i" }
+}
+
+/// Treats "no such container" and "removal already in progress" as success:
+/// a container may disappear before it is removed explicitly (a concurrent
+/// cleanup, or `docker rm` by hand).
+fn tolerate_removed(
+    result: Result<(), bollard::errors::Error>,
+) -> Result<(), bollard::errors::Error> {
+    match result {
+        Err(bollard::errors::Error::DockerResponseServerError {
+            status_code: 404 | 409,
+            ..
+        }) => Ok(()),
+        other => other,
+    }
+}
+
+/// Exit code of a `docker exec` process. A process killed by a signal
+/// reports `128 + signal`, as shells do, so a crash never looks like success.
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+/// Splits an image reference into the name and tag the pull API expects.
+/// A missing tag means `latest` (an empty tag would pull every tag); digest
+/// references are passed through whole.
+fn split_image_ref(image: &str) -> (&str, &str) {
+    if image.contains('@') {
+        return (image, "");
+    }
+    match image.rsplit_once(':') {
+        // A colon followed by a slash belongs to a registry port, not a tag
+        Some((name, tag)) if !tag.contains('/') => (name, tag),
+        _ => (image, "latest"),
+    }
+}
+
+/// Converts a list into the `Option<Vec<_>>` bollard expects, omitting empty lists.
+fn non_empty(values: &[String]) -> Option<Vec<String>> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.to_vec())
+    }
+}
+
+/// Resolves group names to GIDs from the host's `/etc/group`, since Docker
+/// looks names up in the image (which has no `docker` group) and the GID that
+/// matters for a mounted socket is the host's. GIDs and unknown names pass
+/// through.
+pub fn resolve_groups(groups: &[String]) -> Vec<String> {
+    let etc_group = std::fs::read_to_string("/etc/group").unwrap_or_default();
+    groups
+        .iter()
+        .map(|group| resolve_group(group, &etc_group))
+        .collect()
+}
+
+fn resolve_group(group: &str, etc_group: &str) -> String {
+    etc_group
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            Some((fields.next()?, fields.nth(1)?))
+        })
+        .find(|(name, _)| *name == group)
+        .map_or_else(|| group.to_string(), |(_, gid)| gid.to_string())
+}
+
+/// Prints each path under `/workspace` with another owner than `owner`,
+/// chowning them in batches. Symlinks are chowned themselves, not their
+/// targets. The script's first `skipped` arguments are pruned: a volume
+/// isn't always another filesystem to `-xdev` (Docker Desktop's file
+/// sharing), so excluded paths are matched by name.
+fn fix_ownership_script(owner: &str, skipped: usize) -> String {
+    let uid = owner.split(':').next().unwrap_or(owner);
+    let prune = if skipped == 0 {
+        String::new()
+    } else {
+        let paths: Vec<String> = (1..=skipped).map(|n| format!("-path \"${n}\"")).collect();
+        format!("\\( {} \\) -prune -o ", paths.join(" -o "))
+    };
+    format!("find /workspace -xdev {prune}! -user {uid} -print -exec chown -h {owner} {{}} +")
+}
+
+/// The host user as `uid:gid`.
+pub fn host_owner() -> String {
+    let uid = unsafe { libc::getuid() };
+    let gid = unsafe { libc::getgid() };
+    format!("{uid}:{gid}")
+}
+
+/// Checks whether a container name matches the stale detection prefix.
 /// Returns true if the name is exactly the prefix or starts with `prefix-`.
 /// Container names from Docker include a leading `/`.
 pub fn matches_stale_prefix(container_name: &str, prefix: &str) -> bool {
@@ -489,19 +1584,16 @@ pub fn matches_stale_prefix(container_name: &str, prefix: &str) -> bool {
         || container_name.starts_with(&format!("{prefix_with_slash}-"))
 }
 
-/// Derives the default container name from the current working directory.
-/// Returns `bubble-bot-<dir-name>` or `bubble-bot-project` as fallback.
+/// Derives the default container name from the project directory.
+/// Returns `bubble-bot-<dir-name>-<path-hash>` (see [`project_id`]).
 pub fn default_container_name() -> String {
-    std::env::current_dir()
-        .ok()
-        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
-        .map(|name| format!("bubble-bot-{name}"))
-        .unwrap_or_else(|| "bubble-bot-project".to_string())
+    format!("bubble-bot-{}", project_id())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::docker::mock::{MockBackend, MockContainer};
 
     #[test]
     fn default_container_name_has_prefix() {
@@ -516,6 +1608,221 @@ mod tests {
         assert!(!suffix.is_empty());
     }
 
+    #[test]
+    fn exit_code_propagates_status() {
+        use std::os::unix::process::ExitStatusExt;
+
+        assert_eq!(exit_code(std::process::ExitStatus::from_raw(0)), 0);
+        assert_eq!(exit_code(std::process::ExitStatus::from_raw(3 << 8)), 3);
+    }
+
+    #[test]
+    fn exit_code_of_signaled_process() {
+        use std::os::unix::process::ExitStatusExt;
+
+        // SIGKILL
+        assert_eq!(exit_code(std::process::ExitStatus::from_raw(9)), 137);
+    }
+
+    #[test]
+    fn foreground_pid_is_set_only_while_running() {
+        assert_eq!(foreground_pid(), None);
+        let status = run_foreground(Command::new("sh").args(["-c", "exit 3"])).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(foreground_pid(), None);
+    }
+
+    #[test]
+    fn already_removed_containers_are_not_errors() {
+        let error = |status_code| {
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code,
+                message: String::new(),
+            })
+        };
+        assert!(tolerate_removed(error(404)).is_ok());
+        assert!(tolerate_removed(error(409)).is_ok());
+        assert!(tolerate_removed(error(500)).is_err());
+    }
+
+    #[test]
+    fn exec_opts_resolve_relative_workdir() {
+        let opts = ExecOpts {
+            workdir: Some("packages/api/".to_string()),
+            env: vec!["APP_ENV=testing".to_string()],
+        }
+        .resolved("/workspace");
+        assert_eq!(
+            opts.args(),
+            vec!["-w", "/workspace/packages/api", "-e", "APP_ENV=testing"]
+        );
+
+        let opts = ExecOpts {
+            workdir: Some("/tmp".to_string()),
+            env: Vec::new(),
+        }
+        .resolved("/workspace/backend");
+        assert_eq!(opts.args(), vec!["-w", "/tmp"]);
+        assert!(ExecOpts::default().args().is_empty());
+    }
+
+    #[test]
+    fn tty_is_allocated_only_on_a_terminal() {
+        assert_eq!(stdio_flag(true, true), "-it");
+        assert_eq!(stdio_flag(false, true), "-i");
+        assert_eq!(stdio_flag(true, false), "-i");
+        assert_eq!(stdio_flag(false, false), "-i");
+    }
+
+    #[test]
+    fn probes_exec_commands_and_share_the_network_for_tcp_and_http() {
+        let cmd = Readiness::Cmd(vec!["redis-cli".into(), "ping".into()]);
+        assert_eq!(
+            probe_args("abc", &cmd),
+            ["exec", "abc", "redis-cli", "ping"]
+        );
+
+        assert_eq!(
+            probe_args("abc", &Readiness::Tcp(9200)),
+            [
+                "run",
+                "--rm",
+                "--network",
+                "container:abc",
+                "busybox:1",
+                "nc",
+                "-z",
+                "-w",
+                "2",
+                "127.0.0.1",
+                "9200"
+            ]
+        );
+        let http = Readiness::Http("http://localhost:9200/_cluster/health".into());
+        let args = probe_args("abc", &http);
+        assert_eq!(args[3], "container:abc");
+        assert_eq!(
+            args[5..],
+            [
+                "wget",
+                "-q",
+                "-O",
+                "/dev/null",
+                "-T",
+                "2",
+                "http://localhost:9200/_cluster/health"
+            ]
+        );
+    }
+
+    #[test]
+    fn status_of_running_container() {
+        let state = ContainerState {
+            running: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            ContainerStatus::from_state(Some(&state)),
+            ContainerStatus::Running
+        );
+    }
+
+    #[test]
+    fn status_of_unhealthy_container() {
+        let state = ContainerState {
+            running: Some(true),
+            health: Some(bollard::models::Health {
+                status: Some(HealthStatusEnum::UNHEALTHY),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            ContainerStatus::from_state(Some(&state)),
+            ContainerStatus::Unhealthy
+        );
+    }
+
+    #[test]
+    fn status_of_oom_killed_container() {
+        let state = ContainerState {
+            running: Some(false),
+            exit_code: Some(137),
+            oom_killed: Some(true),
+            ..Default::default()
+        };
+        let status = ContainerStatus::from_state(Some(&state));
+        assert_eq!(
+            status,
+            ContainerStatus::Stopped {
+                exit_code: Some(137),
+                oom_killed: true
+            }
+        );
+        assert_eq!(status.to_string(), "was killed (out of memory)");
+    }
+
+    #[test]
+    fn status_of_removed_container() {
+        assert_eq!(ContainerStatus::from_state(None).to_string(), "was removed");
+    }
+
+    #[test]
+    fn split_image_ref_with_tag() {
+        assert_eq!(split_image_ref("mysql:8.4"), ("mysql", "8.4"));
+        assert_eq!(
+            split_image_ref("ubuntu/squid:latest"),
+            ("ubuntu/squid", "latest")
+        );
+    }
+
+    #[test]
+    fn split_image_ref_defaults_to_latest() {
+        assert_eq!(split_image_ref("redis"), ("redis", "latest"));
+        assert_eq!(
+            split_image_ref("registry.corp:5000/team/redis"),
+            ("registry.corp:5000/team/redis", "latest")
+        );
+    }
+
+    #[test]
+    fn split_image_ref_keeps_registry_port() {
+        assert_eq!(
+            split_image_ref("registry.corp:5000/mysql:8.4"),
+            ("registry.corp:5000/mysql", "8.4")
+        );
+    }
+
+    #[test]
+    fn split_image_ref_passes_digests_through() {
+        assert_eq!(
+            split_image_ref("mysql@sha256:abc123"),
+            ("mysql@sha256:abc123", "")
+        );
+    }
+
+    #[test]
+    fn non_empty_omits_empty_lists() {
+        assert!(non_empty(&[]).is_none());
+        assert_eq!(
+            non_empty(&["10.0.0.2".to_string()]),
+            Some(vec!["10.0.0.2".to_string()])
+        );
+    }
+
+    #[test]
+    fn workspace_ownership_fix_skips_the_owner_and_other_mounts() {
+        assert_eq!(
+            fix_ownership_script("1000:1000", 0),
+            "find /workspace -xdev ! -user 1000 -print -exec chown -h 1000:1000 {} +"
+        );
+        assert_eq!(
+            fix_ownership_script("1000:1000", 2),
+            "find /workspace -xdev \\( -path \"$1\" -o -path \"$2\" \\) -prune -o \
+             ! -user 1000 -print -exec chown -h 1000:1000 {} +"
+        );
+    }
+
     #[test]
     fn stale_prefix_matches_exact_container_name() {
         // Docker container names have leading `/`
@@ -562,4 +1869,561 @@ mod tests {
             "bubble-bot-myproject"
         ));
     }
+
+    #[test]
+    fn ensure_image_follows_pull_policy() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("redis:7");
+        let container_mgr = ContainerManager::new(mock.clone());
+        let auth = RegistryAuth::default();
+        let pulls = |mock: &MockBackend| {
+            mock.calls()
+                .into_iter()
+                .filter(|c| c.starts_with("create_image"))
+                .count()
+        };
+
+        rt.block_on(async {
+            // Present images are only pulled with `always`
+            container_mgr
+                .ensure_image("redis:7", PullPolicy::Missing, &auth)
+                .await
+                .unwrap();
+            container_mgr
+                .ensure_image("redis:7", PullPolicy::Never, &auth)
+                .await
+                .unwrap();
+            assert_eq!(pulls(&mock), 0);
+            container_mgr
+                .ensure_image("redis:7", PullPolicy::Always, &auth)
+                .await
+                .unwrap();
+            assert_eq!(pulls(&mock), 1);
+
+            // Missing images are pulled unless pulling is disabled
+            let err = container_mgr
+                .ensure_image("mysql:8.4", PullPolicy::Never, &auth)
+                .await
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains("pull_policy"), "{err}");
+            container_mgr
+                .ensure_image("mysql:8.4", PullPolicy::Missing, &auth)
+                .await
+                .unwrap();
+        });
+        assert!(mock.images().contains(&"mysql:8.4".to_string()));
+    }
+
+    #[test]
+    fn create_and_start_maps_tmpfs_and_shm_size() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("bubble-bot:abc");
+        let opts = ContainerOpts {
+            image_tag: "bubble-bot:abc".to_string(),
+            container_name: "bubble-bot-app".to_string(),
+            tmpfs: HashMap::from([("/tmp".to_string(), String::new())]),
+            shm_size: Some(1 << 30),
+            ..Default::default()
+        };
+        rt.block_on(ContainerManager::new(mock.clone()).create_and_start(&opts))
+            .unwrap();
+
+        let host_config = mock.containers()[0].host_config.clone().unwrap();
+        assert_eq!(host_config.tmpfs, Some(opts.tmpfs));
+        assert_eq!(host_config.shm_size, Some(1 << 30));
+    }
+
+    #[test]
+    fn kept_dev_container_is_reused_only_while_unchanged() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("bubble-bot:abc");
+        mock.add_image("bubble-bot:def");
+        let container_mgr = ContainerManager::new(mock.clone());
+        let opts = ContainerOpts {
+            image_tag: "bubble-bot:abc".to_string(),
+            container_name: "bubble-bot-app".to_string(),
+            labels: HashMap::from([(KEEP_LABEL.to_string(), "true".to_string())]),
+            ..Default::default()
+        };
+
+        rt.block_on(async {
+            // Containers not left by `--keep` are never reused
+            let unkept = ContainerOpts {
+                labels: HashMap::new(),
+                ..opts.clone()
+            };
+            container_mgr.create_and_start(&unkept).await.unwrap();
+            assert_eq!(container_mgr.reuse_kept(&unkept).await.unwrap(), None);
+            container_mgr
+                .cleanup_existing("bubble-bot-app")
+                .await
+                .unwrap();
+
+            let id = container_mgr.create_and_start(&opts).await.unwrap();
+            assert_eq!(
+                container_mgr.reuse_kept(&opts).await.unwrap(),
+                Some(id.clone())
+            );
+
+            // Every session has its own ID; a reused container keeps the
+            // one it was created with
+            let next_session = ContainerOpts {
+                env_vars: vec![format!("{SESSION_ENV}=next")],
+                ..opts.clone()
+            };
+            assert_eq!(
+                container_mgr.reuse_kept(&next_session).await.unwrap(),
+                Some(id)
+            );
+
+            // A runtime bump changes the image, and with it the definition
+            let bumped = ContainerOpts {
+                image_tag: "bubble-bot:def".to_string(),
+                ..opts.clone()
+            };
+            assert_eq!(container_mgr.reuse_kept(&bumped).await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn kept_service_container_is_reused_only_while_unchanged() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("redis:alpine");
+        let container_mgr = ContainerManager::new(mock.clone());
+        let redis = crate::services::redis::RedisService;
+        let kept = HashMap::from([(KEEP_LABEL.to_string(), "true".to_string())]);
+
+        rt.block_on(async {
+            let first = container_mgr
+                .start_service(&redis, "bubble-bot-app", "app", &kept)
+                .await
+                .unwrap();
+            let again = container_mgr
+                .start_service(&redis, "bubble-bot-app", "app", &HashMap::new())
+                .await
+                .unwrap();
+            assert_eq!(again, first);
+
+            // On another network it is a different container
+            let moved = container_mgr
+                .start_service(&redis, "bubble-bot-other", "app", &HashMap::new())
+                .await
+                .unwrap();
+            assert_ne!(moved, first);
+        });
+        let ids: Vec<String> = mock.containers().into_iter().map(|c| c.id).collect();
+        assert_eq!(ids.len(), 1);
+    }
+
+    #[test]
+    fn kept_service_with_a_random_password_is_reused() {
+        use crate::config::MysqlConfig;
+        use crate::services::mysql::MysqlService;
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image(&format!("mysql:{}", MysqlConfig::default().version));
+        let container_mgr = ContainerManager::new(mock.clone());
+        let kept = HashMap::from([(KEEP_LABEL.to_string(), "true".to_string())]);
+        let random = || {
+            MysqlService::new(
+                MysqlConfig {
+                    password: "random".to_string(),
+                    ..Default::default()
+                },
+                "app".to_string(),
+            )
+        };
+
+        rt.block_on(async {
+            let first = container_mgr
+                .start_service(&random(), "bubble-bot-app", "app", &kept)
+                .await
+                .unwrap();
+            // The next session generates a new password, then takes over
+            // the kept container's
+            let mut next = random();
+            let env = container_mgr
+                .running_env("bubble-bot-app-mysql")
+                .await
+                .unwrap()
+                .unwrap();
+            next.reuse_session_secrets(&env);
+            let again = container_mgr
+                .start_service(&next, "bubble-bot-app", "app", &kept)
+                .await
+                .unwrap();
+            assert_eq!(again, first);
+        });
+        // Even without taking it over, the definition doesn't see it
+        assert_eq!(
+            service_definition(&random(), "bubble-bot-app"),
+            service_definition(&random(), "bubble-bot-app")
+        );
+    }
+
+    #[test]
+    fn create_and_start_retries_transient_errors() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("bubble-bot:abc");
+        let opts = ContainerOpts {
+            image_tag: "bubble-bot:abc".to_string(),
+            container_name: "bubble-bot-app".to_string(),
+            ..Default::default()
+        };
+
+        mock.fail_next("create_container", 500, "EOF");
+        let err = rt
+            .block_on(
+                ContainerManager::new(mock.clone())
+                    .with_retries(0)
+                    .create_and_start(&opts),
+            )
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("EOF"), "{err:#}");
+
+        mock.fail_next("create_container", 500, "EOF");
+        mock.fail_next("start_container", 503, "daemon restarting");
+        rt.block_on(ContainerManager::new(mock.clone()).create_and_start(&opts))
+            .unwrap();
+        assert!(mock.containers()[0].running);
+    }
+
+    #[test]
+    fn create_and_start_maps_groups_and_devices() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("bubble-bot:abc");
+        let opts = ContainerOpts {
+            image_tag: "bubble-bot:abc".to_string(),
+            container_name: "bubble-bot-app".to_string(),
+            groups: vec!["998".to_string()],
+            devices: vec![Device {
+                host: "/dev/kvm".to_string(),
+                container: "/dev/kvm".to_string(),
+                permissions: "rwm".to_string(),
+            }],
+            ..Default::default()
+        };
+        rt.block_on(ContainerManager::new(mock.clone()).create_and_start(&opts))
+            .unwrap();
+
+        let host_config = mock.containers()[0].host_config.clone().unwrap();
+        assert_eq!(host_config.group_add, Some(vec!["998".to_string()]));
+        let device = &host_config.devices.unwrap()[0];
+        assert_eq!(device.path_on_host.as_deref(), Some("/dev/kvm"));
+        assert_eq!(device.cgroup_permissions.as_deref(), Some("rwm"));
+    }
+
+    #[test]
+    fn groups_resolve_to_host_gids() {
+        let etc_group = "root:x:0:\ndocker:x:998:ada\nkvm:x:992:\n";
+        assert_eq!(resolve_group("docker", etc_group), "998");
+        assert_eq!(resolve_group("kvm", etc_group), "992");
+        assert_eq!(resolve_group("1234", etc_group), "1234");
+        assert_eq!(resolve_group("video", etc_group), "video");
+    }
+
+    #[test]
+    fn volumes_are_mounted_over_workspace_paths() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("bubble-bot:abc");
+        let opts = ContainerOpts {
+            image_tag: "bubble-bot:abc".to_string(),
+            container_name: "bubble-bot-app".to_string(),
+            volumes: vec![(
+                "bubble-bot-app-mount-node_modules".to_string(),
+                "/workspace/node_modules".to_string(),
+            )],
+            ..Default::default()
+        };
+        rt.block_on(ContainerManager::new(mock.clone()).create_and_start(&opts))
+            .unwrap();
+
+        let mounts = mock.containers()[0]
+            .host_config
+            .clone()
+            .unwrap()
+            .mounts
+            .unwrap();
+        assert_eq!(mounts.len(), 1);
+        assert_eq!(
+            mounts[0].source.as_deref(),
+            Some("bubble-bot-app-mount-node_modules")
+        );
+        assert_eq!(mounts[0].target.as_deref(), Some("/workspace/node_modules"));
+        assert_eq!(mounts[0].typ, Some(MountTypeEnum::VOLUME));
+    }
+
+    #[test]
+    fn selinux_label_applies_to_every_bind() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("bubble-bot:abc");
+        let opts = ContainerOpts {
+            image_tag: "bubble-bot:abc".to_string(),
+            container_name: "bubble-bot-app".to_string(),
+            project_dir: "/src/app".to_string(),
+            extra_binds: vec!["/home/u/.gitconfig:/home/dev/.gitconfig:ro".to_string()],
+            selinux_label: Some(SelinuxLabel::Private),
+            ..Default::default()
+        };
+        rt.block_on(ContainerManager::new(mock.clone()).create_and_start(&opts))
+            .unwrap();
+
+        let host_config = mock.containers()[0].host_config.clone().unwrap();
+        assert_eq!(
+            host_config.binds.unwrap(),
+            vec![
+                "/src/app:/workspace:Z",
+                "/home/u/.gitconfig:/home/dev/.gitconfig:ro,Z"
+            ]
+        );
+    }
+
+    #[test]
+    fn user_mapping_follows_daemon_security_options() {
+        let options = |opts: &[&str]| opts.iter().map(|o| o.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            UserMapping::from_security_options(&options(&["name=seccomp,profile=builtin"])),
+            UserMapping::HostUser
+        );
+        assert_eq!(
+            UserMapping::from_security_options(&options(&[
+                "name=seccomp,profile=builtin",
+                "name=rootless",
+                "name=cgroupns"
+            ])),
+            UserMapping::Root
+        );
+        assert_eq!(
+            UserMapping::from_security_options(&options(&["name=userns"])),
+            UserMapping::HostUserns
+        );
+    }
+
+    #[test]
+    fn remapped_daemons_change_container_user() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("bubble-bot:abc");
+        let container_mgr = ContainerManager::new(mock.clone());
+
+        rt.block_on(async {
+            for (name, security_options) in
+                [("rootless", "name=rootless"), ("remap", "name=userns")]
+            {
+                mock.set_security_options(&[security_options]);
+                let opts = ContainerOpts {
+                    image_tag: "bubble-bot:abc".to_string(),
+                    container_name: name.to_string(),
+                    user_mapping: container_mgr.user_mapping().await,
+                    ..Default::default()
+                };
+                container_mgr.create_and_start(&opts).await.unwrap();
+            }
+        });
+
+        let containers = mock.containers();
+        assert_eq!(containers[0].user, None);
+        assert_eq!(
+            containers[0].host_config.as_ref().unwrap().userns_mode,
+            None
+        );
+        assert!(containers[1].user.is_some());
+        assert_eq!(
+            containers[1]
+                .host_config
+                .as_ref()
+                .unwrap()
+                .userns_mode
+                .as_deref(),
+            Some("host")
+        );
+    }
+
+    #[test]
+    fn prefetch_pulls_each_missing_image_once() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("redis:7");
+        let container_mgr = ContainerManager::new(mock.clone());
+        let auth = RegistryAuth::default();
+        let images = ["redis:7", "mysql:8.0", "postgres:16", "mysql:8.0"].map(String::from);
+
+        rt.block_on(container_mgr.prefetch_images(&images, PullPolicy::Missing, &auth))
+            .unwrap();
+        let mut pulls: Vec<String> = mock
+            .calls()
+            .into_iter()
+            .filter(|c| c.starts_with("create_image"))
+            .collect();
+        pulls.sort();
+        assert_eq!(
+            pulls,
+            vec!["create_image mysql:8.0", "create_image postgres:16"]
+        );
+
+        let err = rt
+            .block_on(container_mgr.prefetch_images(
+                &["valkey:8".to_string()],
+                PullPolicy::Never,
+                &auth,
+            ))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("valkey:8"), "{err}");
+    }
+
+    #[test]
+    fn cleanup_existing_removes_only_exact_name() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        for name in ["bubble-bot-app", "bubble-bot-app-mysql"] {
+            mock.add_container(MockContainer {
+                name: name.to_string(),
+                running: true,
+                ..Default::default()
+            });
+        }
+
+        rt.block_on(ContainerManager::new(mock.clone()).cleanup_existing("bubble-bot-app"))
+            .unwrap();
+        let remaining: Vec<String> = mock.containers().into_iter().map(|c| c.name).collect();
+        assert_eq!(remaining, vec!["bubble-bot-app-mysql"]);
+    }
+
+    #[test]
+    fn status_reports_exited_and_removed_containers() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        let id = mock.add_container(MockContainer {
+            name: "bubble-bot-app".to_string(),
+            running: true,
+            ..Default::default()
+        });
+        let container_mgr = ContainerManager::new(mock.clone());
+
+        rt.block_on(async {
+            assert_eq!(
+                container_mgr.status(&id).await.unwrap(),
+                ContainerStatus::Running
+            );
+            mock.exit_container(&id, 137, true);
+            assert_eq!(
+                container_mgr.status(&id).await.unwrap(),
+                ContainerStatus::Stopped {
+                    exit_code: Some(137),
+                    oom_killed: true
+                }
+            );
+            container_mgr.remove(&id).await.unwrap();
+            assert_eq!(
+                container_mgr.status(&id).await.unwrap().to_string(),
+                "was removed"
+            );
+        });
+    }
+
+    #[test]
+    fn running_env_only_for_running_containers() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        let id = mock.add_container(MockContainer {
+            name: "bubble-bot-app-postgres".to_string(),
+            running: true,
+            env: vec!["POSTGRES_PASSWORD=0123abcd".to_string()],
+            ..Default::default()
+        });
+        let container_mgr = ContainerManager::new(mock.clone());
+
+        rt.block_on(async {
+            assert_eq!(
+                container_mgr
+                    .running_env("bubble-bot-app-postgres")
+                    .await
+                    .unwrap(),
+                Some(vec!["POSTGRES_PASSWORD=0123abcd".to_string()])
+            );
+            mock.exit_container(&id, 0, false);
+            assert_eq!(
+                container_mgr
+                    .running_env("bubble-bot-app-postgres")
+                    .await
+                    .unwrap(),
+                None
+            );
+            assert_eq!(container_mgr.running_env("missing").await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn readiness_failures_show_the_service_logs() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        let lines: Vec<String> = (1..=25).map(|n| format!("line {n}")).collect();
+        let id = mock.add_container(MockContainer {
+            name: "bubble-bot-app-mysql".to_string(),
+            running: true,
+            logs: lines[..3].to_vec(),
+            ..Default::default()
+        });
+        let container_mgr = ContainerManager::new(mock);
+        let logs = rt.block_on(container_mgr.logs(&id)).unwrap();
+        assert_eq!(logs, ["line 1", "line 2", "line 3"]);
+
+        // Each attempt echoes only what's new, and at most the last few lines
+        let mut echoed = 0;
+        assert_eq!(new_log_lines(&lines[..3], &mut echoed), &lines[..3]);
+        assert!(new_log_lines(&lines[..3], &mut echoed).is_empty());
+        assert_eq!(new_log_lines(&lines, &mut echoed), &lines[15..]);
+        assert_eq!(echoed, 25);
+
+        let message = timeout_message("mysql", 30, &lines);
+        assert!(message.starts_with(
+            "mysql service did not become ready after 30 attempts; last mysql log lines:\n  line 6\n"
+        ));
+        assert!(message.ends_with("\n  line 25"), "{message}");
+        assert_eq!(
+            timeout_message("mysql", 30, &[]),
+            "mysql service did not become ready after 30 attempts"
+        );
+
+        let crashed = ContainerStatus::Stopped {
+            exit_code: Some(1),
+            oom_killed: false,
+        };
+        assert_eq!(
+            stopped_message("mysql", &crashed, &lines[..1]),
+            "mysql service exited with code 1 before becoming ready; last mysql log lines:\n  line 1"
+        );
+    }
+
+    #[test]
+    fn followed_logs_are_written_to_the_sink() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        let id = mock.add_container(MockContainer {
+            name: "bubble-bot-app-redis".to_string(),
+            running: true,
+            logs: vec!["Ready to accept connections".to_string()],
+            ..Default::default()
+        });
+        let container_mgr = ContainerManager::new(mock);
+        let mut sink = Vec::new();
+        rt.block_on(container_mgr.follow_logs(&id, &mut sink))
+            .unwrap();
+        assert_eq!(sink, b"Ready to accept connections\n");
+        assert!(
+            rt.block_on(container_mgr.follow_logs("missing", Vec::new()))
+                .is_err()
+        );
+    }
 }
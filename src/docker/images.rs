@@ -1,9 +1,23 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use anyhow::{Context, Result};
 use bollard::Docker;
+use bollard::auth::DockerCredentials;
 use bollard::image::{BuildImageOptions, ListImagesOptions};
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::ProgressBar;
 use sha2::{Digest, Sha256};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tracing::{debug, warn};
 
+use super::backend::ContainerBackend;
+use super::registry::{RegistryAuth, base_images};
+use crate::error::Error;
+use crate::output;
 use crate::templates::ContextFile;
 
 /// Builds Docker images with content-hash caching.
@@ -11,10 +25,40 @@ use crate::templates::ContextFile;
 /// The rendered Dockerfile is SHA-256 hashed (first 12 chars) and used as the
 /// image tag. If an image with that tag already exists, the build is skipped
 /// unless `no_cache` is set.
-pub struct ImageBuilder {
-    docker: Docker,
+pub struct ImageBuilder<B = Docker> {
+    docker: B,
+    buildkit: bool,
+    labels: HashMap<String, String>,
+    timeout: Option<Duration>,
+    log: Option<LogTarget>,
+    /// `host:ip` added to the build containers' `/etc/hosts`.
+    extra_host: Option<String>,
+}
+
+/// Where [`ImageBuilder`] writes the full build output.
+#[derive(Debug, Clone)]
+enum LogTarget {
+    File(PathBuf),
+    /// A new timestamped file per build, in a directory pruned of old logs.
+    Dir(PathBuf),
 }
 
+/// Label holding the name of the project whose config produced an image.
+pub const PROJECT_LABEL: &str = "bubble-bot.project";
+/// Label holding a hash of the resolved config an image was built from.
+pub const CONFIG_HASH_LABEL: &str = "bubble-bot.config-hash";
+/// Label listing the runtimes installed in an image, e.g. `php 8.3, node 22`.
+pub const RUNTIMES_LABEL: &str = "bubble-bot.runtimes";
+/// Label holding the bubble-bot version that built an image.
+pub const VERSION_LABEL: &str = "bubble-bot.version";
+
+/// Build output lines kept for the error when a BuildKit build fails.
+const BUILDKIT_ERROR_LINES: usize = 20;
+
+/// How long logs in the default build log directory are kept; older ones
+/// are removed when the next build starts.
+const BUILD_LOG_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
 /// Result of an image build or cache lookup.
 #[derive(Debug)]
 pub struct BuildResult {
@@ -22,25 +66,76 @@ pub struct BuildResult {
     pub cached: bool,
 }
 
-impl ImageBuilder {
-    pub fn new(docker: Docker) -> Self {
-        Self { docker }
+/// A `bubble-bot:*` image and the metadata recorded by its labels. Images
+/// built before they were labeled have no metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageInfo {
+    pub tag: String,
+    pub project: Option<String>,
+    pub config_hash: Option<String>,
+    pub runtimes: Option<String>,
+    pub version: Option<String>,
+    /// Size in bytes.
+    pub size: u64,
+    /// Build time, as a Unix timestamp.
+    pub created: i64,
+    /// When a session last started from it, if one has on this machine.
+    pub last_used: Option<SystemTime>,
+}
+
+impl<B: ContainerBackend> ImageBuilder<B> {
+    pub fn new(docker: B) -> Self {
+        Self {
+            docker,
+            buildkit: false,
+            labels: HashMap::new(),
+            timeout: None,
+            log: None,
+            extra_host: None,
+        }
     }
 
-    /// Computes the content-hash tag for a rendered Dockerfile.
-    /// Returns `bubble-bot:<first-12-chars-of-sha256>`.
-    pub fn compute_tag(dockerfile_content: &str) -> String {
-        let mut hasher = Sha256::new();
-        hasher.update(dockerfile_content.as_bytes());
-        let hash = hasher.finalize();
-        let hex = format!("{hash:x}");
-        let prefix = &hex[..12];
-        format!("bubble-bot:{prefix}")
+    /// Writes each build's full output to `path`, or to a new timestamped
+    /// file in [`build_log_dir`] when `None`, while the console shows only
+    /// the latest line.
+    pub fn with_log(mut self, path: Option<PathBuf>) -> Self {
+        self.log = path
+            .map(LogTarget::File)
+            .or_else(|| build_log_dir().map(LogTarget::Dir));
+        self
+    }
+
+    /// Aborts builds that take longer than `timeout`; unlimited when `None`.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Labels recorded on built images. They don't affect the tag, so an
+    /// image reused from the cache keeps the labels of the build that made it.
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Adds a `host:ip` entry to `/etc/hosts` in the build containers, such
+    /// as the host gateway a proxy on the host is reached through.
+    pub fn with_extra_host(mut self, extra_host: Option<String>) -> Self {
+        self.extra_host = extra_host;
+        self
+    }
+
+    /// Builds with BuildKit through the docker CLI instead of the API's
+    /// classic builder, which rejects `RUN --mount`. The CLI pulls base
+    /// images with its own `docker login` credentials.
+    pub fn with_buildkit(mut self, buildkit: bool) -> Self {
+        self.buildkit = buildkit;
+        self
     }
 
     /// Checks whether an image with the given tag already exists locally.
     pub async fn image_exists(&self, tag: &str) -> Result<bool> {
-        let filters: std::collections::HashMap<String, Vec<String>> =
+        let filters: HashMap<String, Vec<String>> =
             [("reference".to_string(), vec![tag.to_string()])]
                 .into_iter()
                 .collect();
@@ -63,66 +158,125 @@ impl ImageBuilder {
     /// - `dockerfile_content`: the fully rendered Dockerfile string
     /// - `context_files`: additional files to include in the build context
     /// - `no_cache`: if true, forces a rebuild even if the image tag exists
+    /// - `build_args`: build-time variables (e.g., proxy settings); these do not
+    ///   affect the content-hash tag
+    /// - `registry_auth`: credentials for pulling private base images
+    /// - `platform`: target platform (e.g., `linux/amd64`), the daemon's
+    ///   when `None`
     pub async fn build(
         &self,
         dockerfile_content: &str,
         context_files: &[ContextFile],
         no_cache: bool,
+        build_args: &HashMap<String, String>,
+        registry_auth: &RegistryAuth,
+        platform: Option<&str>,
     ) -> Result<BuildResult> {
-        let tag = Self::compute_tag(dockerfile_content);
+        let tag = ImageBuilder::compute_tag(dockerfile_content);
 
         // Check cache unless --no-cache
         if !no_cache && self.image_exists(&tag).await? {
-            let pb = ProgressBar::new_spinner();
-            pb.set_style(
-                ProgressStyle::default_spinner()
-                    .template("{prefix} {msg}")
-                    .expect("invalid progress template"),
-            );
-            pb.set_prefix("✓");
-            pb.finish_with_message(format!("Image loaded from cache ({tag})"));
+            output::done(format!("Image loaded from cache ({tag})"));
             return Ok(BuildResult { tag, cached: true });
         }
 
         // Create a tar archive with the Dockerfile and context files
-        let tar_bytes = Self::create_build_context(dockerfile_content, context_files)?;
+        let tar_bytes = ImageBuilder::create_build_context(dockerfile_content, context_files)?;
 
-        let options = BuildImageOptions {
-            t: tag.clone(),
-            rm: true,
-            forcerm: true,
-            ..Default::default()
+        let mut log = BuildLog::open(self.log.as_ref(), &tag, SystemTime::now());
+        let log_path = log.path.clone();
+
+        let pb = if self.buildkit {
+            output::spinner(format!("Building image {tag} with BuildKit..."))
+        } else {
+            output::spinner(format!("Building image {tag}..."))
+        };
+        let build = async {
+            if self.buildkit {
+                let command = ImageBuilder::buildkit_command(
+                    &tag,
+                    build_args,
+                    &self.labels,
+                    self.extra_host.as_deref(),
+                    platform,
+                );
+                build_with_cli(command, tar_bytes, &pb, &mut log).await
+            } else {
+                let options = BuildImageOptions {
+                    t: tag.clone(),
+                    rm: true,
+                    forcerm: true,
+                    buildargs: build_args.clone(),
+                    platform: platform.unwrap_or_default().to_string(),
+                    labels: self.labels.clone(),
+                    extrahosts: self.extra_host.clone(),
+                    ..Default::default()
+                };
+                let credentials = registry_auth.for_images(base_images(dockerfile_content));
+                self.build_with_api(options, credentials, tar_bytes, &pb, &mut log)
+                    .await
+            }
         };
+        // Dropping the build on timeout disconnects from the daemon (or kills
+        // `docker build`), which cancels it and removes its intermediate
+        // containers
+        let result = match self.timeout {
+            Some(limit) => match tokio::time::timeout(limit, build).await {
+                Ok(result) => result,
+                Err(_) => {
+                    let limit = format_duration(limit);
+                    output::finish(&pb, format!("Build timed out after {limit}"));
+                    Err(anyhow::anyhow!(
+                        "image build timed out after {limit} (build.timeout); a package mirror or registry may be unreachable"
+                    ))
+                }
+            },
+            None => build.await,
+        };
+        if let Err(e) = result {
+            return Err(Error::Build(match log_path {
+                Some(path) => {
+                    e.context(format!("image build failed (full log: {})", path.display()))
+                }
+                None => e,
+            })
+            .into());
+        }
 
-        let pb = ProgressBar::new_spinner();
-        pb.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.cyan} {msg}")
-                .expect("invalid progress template"),
-        );
-        pb.enable_steady_tick(std::time::Duration::from_millis(120));
-        pb.set_message(format!("Building image {tag}..."));
+        output::finish(&pb, format!("Image built successfully ({tag})"));
+
+        Ok(BuildResult { tag, cached: false })
+    }
 
+    /// Builds through the API's classic builder, streaming its output to `pb`.
+    async fn build_with_api(
+        &self,
+        options: BuildImageOptions<String>,
+        credentials: HashMap<String, DockerCredentials>,
+        tar_bytes: Vec<u8>,
+        pb: &ProgressBar,
+        log: &mut BuildLog,
+    ) -> Result<()> {
         use futures_util::StreamExt;
 
-        let mut stream = self.docker.build_image(
-            options,
-            Some(std::collections::HashMap::new()),
-            Some(tar_bytes.into()),
-        );
+        let mut stream = self
+            .docker
+            .build_image(options, Some(credentials), tar_bytes);
 
         while let Some(result) = stream.next().await {
             match result {
                 Ok(output) => {
                     if let Some(stream_msg) = &output.stream {
                         let clean = console::strip_ansi_codes(stream_msg);
+                        log.write(&clean);
                         let trimmed = clean.trim();
                         if !trimmed.is_empty() {
-                            pb.set_message(trimmed.to_string());
+                            output::update(pb, trimmed.to_string());
                         }
                     }
                     if let Some(error) = &output.error {
-                        pb.finish_with_message(format!("Build failed: {error}"));
+                        log.write(&format!("{error}\n"));
+                        output::finish(pb, format!("Build failed: {error}"));
                         anyhow::bail!("Docker build error: {error}");
                     }
                 }
@@ -132,15 +286,303 @@ impl ImageBuilder {
                     } else {
                         format!("{e}")
                     };
-                    pb.finish_with_message(format!("Build failed: {detail}"));
+                    log.write(&format!("{detail}\n"));
+                    output::finish(pb, format!("Build failed: {detail}"));
                     anyhow::bail!("Docker build error: {detail}");
                 }
             }
         }
+        Ok(())
+    }
 
-        pb.finish_with_message(format!("Image built successfully ({tag})"));
+    /// Lists `bubble-bot:*` images with their metadata, newest first.
+    pub async fn list(&self) -> Result<Vec<ImageInfo>> {
+        let filters: HashMap<String, Vec<String>> =
+            [("reference".to_string(), vec!["bubble-bot".to_string()])]
+                .into_iter()
+                .collect();
+        let images = self
+            .docker
+            .list_images(Some(ListImagesOptions {
+                filters,
+                ..Default::default()
+            }))
+            .await
+            .context("failed to list Docker images")?;
 
-        Ok(BuildResult { tag, cached: false })
+        let mut infos: Vec<ImageInfo> = images
+            .into_iter()
+            .map(|image| {
+                let tag = image
+                    .repo_tags
+                    .first()
+                    .cloned()
+                    .unwrap_or_else(|| image.id.clone());
+                let label = |key: &str| image.labels.get(key).cloned();
+                ImageInfo {
+                    project: label(PROJECT_LABEL),
+                    config_hash: label(CONFIG_HASH_LABEL),
+                    runtimes: label(RUNTIMES_LABEL),
+                    version: label(VERSION_LABEL),
+                    size: image.size.max(0) as u64,
+                    created: image.created,
+                    last_used: last_used(&tag),
+                    tag,
+                }
+            })
+            .collect();
+        infos.sort_by_key(|info| std::cmp::Reverse(info.created));
+        Ok(infos)
+    }
+}
+
+/// The default build log directory, `~/.local/share/bubble-bot/logs` on
+/// Linux (the platform's local data directory elsewhere).
+pub fn build_log_dir() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join("bubble-bot/logs"))
+}
+
+/// One build's output file. A log that can't be written is dropped with a
+/// warning rather than failing the build.
+struct BuildLog {
+    path: Option<PathBuf>,
+    file: Option<std::fs::File>,
+}
+
+impl BuildLog {
+    fn open(target: Option<&LogTarget>, tag: &str, now: SystemTime) -> Self {
+        let path = match target {
+            None => {
+                return Self {
+                    path: None,
+                    file: None,
+                };
+            }
+            Some(LogTarget::File(path)) => path.clone(),
+            Some(LogTarget::Dir(dir)) => {
+                prune_build_logs(dir, now);
+                dir.join(build_log_name(tag, now))
+            }
+        };
+        let file = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::File::create(&path));
+        match file {
+            Ok(file) => Self {
+                path: Some(path),
+                file: Some(file),
+            },
+            Err(e) => {
+                warn!(error = %e, path = %path.display(), "not writing a build log");
+                Self {
+                    path: None,
+                    file: None,
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        use std::io::Write;
+
+        if let Some(file) = &mut self.file {
+            if let Err(e) = file.write_all(text.as_bytes()) {
+                warn!(error = %e, "failed to write build log");
+                self.file = None;
+            }
+        }
+    }
+}
+
+/// `build-<tag hash>-<unix seconds>.log`.
+fn build_log_name(tag: &str, now: SystemTime) -> String {
+    let secs = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let hash = tag.strip_prefix("bubble-bot:").unwrap_or(tag);
+    format!("build-{hash}-{secs}.log")
+}
+
+/// Removes build logs in `dir` older than [`BUILD_LOG_RETENTION`].
+fn prune_build_logs(dir: &Path, now: SystemTime) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with("build-") || !name.ends_with(".log") {
+            continue;
+        }
+        let expired = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .is_ok_and(|modified| {
+                now.duration_since(modified)
+                    .is_ok_and(|age| age > BUILD_LOG_RETENTION)
+            });
+        if expired {
+            if let Err(e) = std::fs::remove_file(entry.path()) {
+                debug!(error = %e, path = %entry.path().display(), "failed to prune build log");
+            }
+        }
+    }
+}
+
+/// Records that a session started from the image `tag`. Docker doesn't track
+/// when an image was last used, so this touches a marker file in the cache
+/// directory.
+pub fn mark_used(tag: &str) {
+    let Some(cache) = dirs::cache_dir() else {
+        return;
+    };
+    let path = usage_file(&cache, tag);
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&path, ""));
+    if let Err(e) = result {
+        debug!(error = %e, path = %path.display(), "failed to record image use");
+    }
+}
+
+/// When a session last started from the image `tag` on this machine.
+pub fn last_used(tag: &str) -> Option<SystemTime> {
+    let path = usage_file(&dirs::cache_dir()?, tag);
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn usage_file(cache: &Path, tag: &str) -> PathBuf {
+    cache
+        .join("bubble-bot/images")
+        .join(tag.strip_prefix("bubble-bot:").unwrap_or(tag))
+}
+
+/// Renders images as the `bubble-bot images` table, with ages relative to
+/// `now`.
+pub fn images_table(images: &[ImageInfo], now: SystemTime) -> String {
+    let now_secs = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let dash = || "-".to_string();
+    let mut rows = vec![[
+        "IMAGE".to_string(),
+        "PROJECT".to_string(),
+        "CONFIG".to_string(),
+        "RUNTIMES".to_string(),
+        "SIZE".to_string(),
+        "CREATED".to_string(),
+        "LAST USED".to_string(),
+        "VERSION".to_string(),
+    ]];
+    for image in images {
+        let last_used = image.last_used.map_or_else(
+            || "never".to_string(),
+            |used| match now.duration_since(used) {
+                Ok(age) => format_age(age.as_secs()),
+                Err(_) => format_age(0),
+            },
+        );
+        rows.push([
+            image.tag.clone(),
+            image.project.clone().unwrap_or_else(dash),
+            image.config_hash.clone().unwrap_or_else(dash),
+            image
+                .runtimes
+                .clone()
+                .filter(|r| !r.is_empty())
+                .unwrap_or_else(dash),
+            format_size(image.size),
+            format_age((now_secs - image.created).max(0) as u64),
+            last_used,
+            image.version.clone().unwrap_or_else(dash),
+        ]);
+    }
+
+    let mut widths = [0; 8];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// Formats a byte count with a decimal unit, as `docker images` does.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "kB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < UNITS.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// Formats a duration as hours, minutes, and seconds, e.g. `1h30m`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let parts = [
+        (secs / 3600, "h"),
+        (secs % 3600 / 60, "m"),
+        (secs % 60, "s"),
+    ];
+    let formatted: String = parts
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, unit)| format!("{n}{unit}"))
+        .collect();
+    if formatted.is_empty() {
+        format!("{}ms", duration.as_millis())
+    } else {
+        formatted
+    }
+}
+
+/// Formats an age in seconds as its largest whole unit, e.g. `3 days ago`.
+fn format_age(secs: u64) -> String {
+    let (n, unit) = match secs {
+        0..60 => return "just now".to_string(),
+        60..3600 => (secs / 60, "minute"),
+        3600..86_400 => (secs / 3600, "hour"),
+        86_400..604_800 => (secs / 86_400, "day"),
+        604_800..2_592_000 => (secs / 604_800, "week"),
+        2_592_000..31_536_000 => (secs / 2_592_000, "month"),
+        _ => (secs / 31_536_000, "year"),
+    };
+    let plural = if n == 1 { "" } else { "s" };
+    format!("{n} {unit}{plural} ago")
+}
+
+/// Docker-independent helpers, callable without a backend.
+impl ImageBuilder {
+    /// Computes the content-hash tag for a rendered Dockerfile.
+    /// Returns `bubble-bot:<first-12-chars-of-sha256>`.
+    pub fn compute_tag(dockerfile_content: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(dockerfile_content.as_bytes());
+        let hash = hasher.finalize();
+        let hex = format!("{hash:x}");
+        let prefix = &hex[..12];
+        format!("bubble-bot:{prefix}")
     }
 
     /// Creates an in-memory tar archive containing the Dockerfile and any
@@ -161,11 +603,12 @@ impl ImageBuilder {
         archive.append(&header, dockerfile_bytes)?;
 
         for file in context_files {
-            let bytes = file.content.as_bytes();
+            let (content, mode) = linux_script(file);
+            let bytes = content.as_bytes();
             let mut file_header = tar::Header::new_gnu();
             file_header.set_path(&file.path)?;
             file_header.set_size(bytes.len() as u64);
-            file_header.set_mode(file.mode);
+            file_header.set_mode(mode);
             file_header.set_cksum();
             archive.append(&file_header, bytes)?;
         }
@@ -174,11 +617,115 @@ impl ImageBuilder {
 
         Ok(archive.into_inner()?)
     }
+
+    /// The `docker build` invocation for a BuildKit build, reading the
+    /// context tar from stdin.
+    fn buildkit_command(
+        tag: &str,
+        build_args: &HashMap<String, String>,
+        labels: &HashMap<String, String>,
+        extra_host: Option<&str>,
+        platform: Option<&str>,
+    ) -> Command {
+        let mut command = Command::new("docker");
+        command
+            .env("DOCKER_BUILDKIT", "1")
+            .args(["build", "--progress=plain", "-t", tag]);
+        if let Some(platform) = platform {
+            command.args(["--platform", platform]);
+        }
+        if let Some(host) = extra_host {
+            command.args(["--add-host", host]);
+        }
+        let mut build_args: Vec<_> = build_args.iter().collect();
+        build_args.sort();
+        for (key, value) in build_args {
+            command.arg("--build-arg").arg(format!("{key}={value}"));
+        }
+        let mut labels: Vec<_> = labels.iter().collect();
+        labels.sort();
+        for (key, value) in labels {
+            command.arg("--label").arg(format!("{key}={value}"));
+        }
+        command.arg("-");
+        command
+    }
+}
+
+/// Runs a `docker build` command, piping it the context tar and showing its
+/// progress on the spinner. On failure, the error carries the last lines of
+/// output, where BuildKit reports the failing step.
+async fn build_with_cli(
+    mut command: Command,
+    tar_bytes: Vec<u8>,
+    pb: &ProgressBar,
+    log: &mut BuildLog,
+) -> Result<()> {
+    let mut child = command
+        .kill_on_drop(true)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run `docker build`; BuildKit builds need the docker CLI")?;
+
+    let mut stdin = child.stdin.take().context("docker build has no stdin")?;
+    let writer = tokio::spawn(async move {
+        stdin.write_all(&tar_bytes).await?;
+        stdin.shutdown().await
+    });
+
+    let stderr = child.stderr.take().context("docker build has no stderr")?;
+    let mut lines = BufReader::new(stderr).lines();
+    let mut tail = VecDeque::with_capacity(BUILDKIT_ERROR_LINES);
+    while let Some(line) = lines.next_line().await? {
+        log.write(&format!("{}\n", console::strip_ansi_codes(&line)));
+        let clean = console::strip_ansi_codes(&line).trim().to_string();
+        if clean.is_empty() {
+            continue;
+        }
+        output::update(pb, clean.clone());
+        if tail.len() == BUILDKIT_ERROR_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(clean);
+    }
+
+    let status = child
+        .wait()
+        .await
+        .context("failed to wait for docker build")?;
+    // A build that exits early closes stdin; its status says why
+    let written = writer.await?;
+    if !status.success() {
+        output::finish(pb, "Build failed".to_string());
+        let tail: Vec<String> = tail.into();
+        anyhow::bail!("Docker build error ({status}):\n{}", tail.join("\n"));
+    }
+    written.context("failed to send the build context to docker build")?;
+    Ok(())
+}
+
+/// A context file's content and mode as they go into the tar. Scripts
+/// (anything starting with `#!`) get LF line endings and the executable bits:
+/// a checkout made on Windows has CRLF, and the kernel then looks for
+/// `/bin/bash\r`, failing with `entrypoint.sh: not found`.
+fn linux_script(file: &ContextFile) -> (Cow<'_, str>, u32) {
+    if !file.content.starts_with("#!") {
+        return (Cow::Borrowed(&file.content), file.mode);
+    }
+    let content = if file.content.contains('\r') {
+        Cow::Owned(file.content.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(file.content.as_str())
+    };
+    (content, file.mode | 0o111)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::docker::mock::MockBackend;
 
     #[test]
     fn compute_tag_uses_first_12_hex_chars() {
@@ -271,4 +818,269 @@ mod tests {
         entry.read_to_string(&mut buf).unwrap();
         assert_eq!(buf, script);
     }
+
+    #[test]
+    fn create_build_context_makes_scripts_runnable_on_linux() {
+        use std::io::Read;
+
+        let context_files = vec![
+            ContextFile {
+                path: "entrypoint.sh".to_string(),
+                content: "#!/bin/bash\r\nexec \"$@\"\r\n".to_string(),
+                mode: 0o644,
+            },
+            ContextFile {
+                path: "motd".to_string(),
+                content: "hello\r\n".to_string(),
+                mode: 0o644,
+            },
+        ];
+        let tar_bytes =
+            ImageBuilder::create_build_context("FROM ubuntu:24.04\n", &context_files).unwrap();
+
+        let mut archive = tar::Archive::new(tar_bytes.as_slice());
+        let mut files = Vec::new();
+        for entry in archive.entries().unwrap().skip(1) {
+            let mut entry = entry.unwrap();
+            let mut buf = String::new();
+            entry.read_to_string(&mut buf).unwrap();
+            files.push((buf, entry.header().mode().unwrap()));
+        }
+        assert_eq!(
+            files,
+            vec![
+                ("#!/bin/bash\nexec \"$@\"\n".to_string(), 0o755),
+                // Only scripts are touched
+                ("hello\r\n".to_string(), 0o644),
+            ]
+        );
+    }
+
+    #[test]
+    fn buildkit_command_reads_context_from_stdin() {
+        let build_args: HashMap<String, String> = [
+            ("NO_PROXY", "localhost"),
+            ("HTTP_PROXY", "http://proxy:3128"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let labels = HashMap::from([(PROJECT_LABEL.to_string(), "app".to_string())]);
+        let command = ImageBuilder::buildkit_command(
+            "bubble-bot:abc",
+            &build_args,
+            &labels,
+            Some("host.docker.internal:host-gateway"),
+            Some("linux/amd64"),
+        );
+        let command = command.as_std();
+        let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+        assert_eq!(
+            args,
+            [
+                "build",
+                "--progress=plain",
+                "-t",
+                "bubble-bot:abc",
+                "--platform",
+                "linux/amd64",
+                "--add-host",
+                "host.docker.internal:host-gateway",
+                "--build-arg",
+                "HTTP_PROXY=http://proxy:3128",
+                "--build-arg",
+                "NO_PROXY=localhost",
+                "--label",
+                "bubble-bot.project=app",
+                "-",
+            ]
+        );
+        assert!(
+            command
+                .get_envs()
+                .any(|(k, v)| k == "DOCKER_BUILDKIT" && v == Some("1".as_ref()))
+        );
+    }
+
+    #[test]
+    fn build_skips_cached_image_unless_no_cache() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let dockerfile = "FROM ubuntu:24.04\n";
+        let tag = ImageBuilder::compute_tag(dockerfile);
+        let mock = MockBackend::new();
+        mock.add_image(&tag);
+        let builder = ImageBuilder::new(mock.clone());
+        let (build_args, auth) = (HashMap::new(), RegistryAuth::default());
+        let build = |no_cache| builder.build(dockerfile, &[], no_cache, &build_args, &auth, None);
+
+        let cached = rt.block_on(build(false)).unwrap();
+        assert!(cached.cached);
+        assert!(!mock.calls().iter().any(|c| c.starts_with("build_image")));
+
+        let rebuilt = rt.block_on(build(true)).unwrap();
+        assert!(!rebuilt.cached);
+        assert_eq!(rebuilt.tag, tag);
+        assert!(mock.calls().contains(&format!("build_image {tag}")));
+    }
+
+    #[test]
+    fn built_images_are_listed_with_their_labels() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("bubble-bot:000000000000");
+        let labels = HashMap::from([
+            (PROJECT_LABEL.to_string(), "app".to_string()),
+            (RUNTIMES_LABEL.to_string(), "php 8.3".to_string()),
+        ]);
+        let builder = ImageBuilder::new(mock.clone()).with_labels(labels);
+        let (build_args, auth) = (HashMap::new(), RegistryAuth::default());
+        let dockerfile = "FROM ubuntu:24.04\n";
+        let built = rt
+            .block_on(builder.build(dockerfile, &[], true, &build_args, &auth, None))
+            .unwrap();
+
+        let images = rt.block_on(builder.list()).unwrap();
+        let image = images.iter().find(|i| i.tag == built.tag).unwrap();
+        assert_eq!(image.project.as_deref(), Some("app"));
+        assert_eq!(image.runtimes.as_deref(), Some("php 8.3"));
+        assert_eq!(image.version, None);
+        let unlabeled = images.iter().find(|i| i.tag != built.tag).unwrap();
+        assert_eq!(unlabeled.project, None);
+    }
+
+    #[test]
+    fn builds_are_aborted_after_the_timeout() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.stall_builds();
+        let builder = ImageBuilder::new(mock.clone())
+            .with_timeout(Some(std::time::Duration::from_millis(50)));
+        let (build_args, auth) = (HashMap::new(), RegistryAuth::default());
+
+        let err = rt
+            .block_on(builder.build("FROM ubuntu:24.04\n", &[], false, &build_args, &auth, None))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("timed out after 50ms"), "{err}");
+    }
+
+    #[test]
+    fn build_output_is_written_to_the_log() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/build.log");
+        let mock = MockBackend::new();
+        let builder = ImageBuilder::new(mock.clone()).with_log(Some(path.clone()));
+        let (build_args, auth) = (HashMap::new(), RegistryAuth::default());
+
+        let built = rt
+            .block_on(builder.build("FROM ubuntu:24.04\n", &[], true, &build_args, &auth, None))
+            .unwrap();
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(log, format!("Successfully tagged {}\n", built.tag));
+
+        // A failed build points at its log
+        mock.stall_builds();
+        let builder = builder.with_timeout(Some(Duration::from_millis(50)));
+        let err = rt
+            .block_on(builder.build("FROM ubuntu:24.04\n", &[], true, &build_args, &auth, None))
+            .unwrap_err();
+        let err = format!("{err:#}");
+        assert!(
+            err.contains(&format!("full log: {}", path.display())),
+            "{err}"
+        );
+        assert!(err.contains("timed out after 50ms"), "{err}");
+    }
+
+    #[test]
+    fn default_logs_are_named_per_build_and_pruned() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_760_000_000);
+        assert_eq!(
+            build_log_name("bubble-bot:a1b2c3d4e5f6", now),
+            "build-a1b2c3d4e5f6-1760000000.log"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("build-000000000000-1.log");
+        let recent = dir.path().join("build-000000000000-2.log");
+        let other = dir.path().join("notes.txt");
+        for path in [&old, &recent, &other] {
+            std::fs::write(path, "").unwrap();
+        }
+        let file = std::fs::File::options().write(true).open(&old).unwrap();
+        file.set_modified(now - BUILD_LOG_RETENTION - Duration::from_secs(1))
+            .unwrap();
+        let file = std::fs::File::options().write(true).open(&recent).unwrap();
+        file.set_modified(now - Duration::from_secs(60)).unwrap();
+
+        let log = BuildLog::open(
+            Some(&LogTarget::Dir(dir.path().to_path_buf())),
+            "bubble-bot:a1b2c3d4e5f6",
+            now,
+        );
+        assert_eq!(
+            log.path,
+            Some(dir.path().join("build-a1b2c3d4e5f6-1760000000.log"))
+        );
+        assert!(!old.exists());
+        assert!(recent.exists());
+        assert!(other.exists());
+    }
+
+    #[test]
+    fn durations_are_formatted_by_unit() {
+        assert_eq!(format_duration(Duration::from_secs(1200)), "20m");
+        assert_eq!(format_duration(Duration::from_secs(5430)), "1h30m30s");
+    }
+
+    #[test]
+    fn images_table_aligns_columns() {
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(10 * 86_400);
+        let images = [
+            ImageInfo {
+                tag: "bubble-bot:a1b2c3d4e5f6".to_string(),
+                project: Some("shop".to_string()),
+                config_hash: Some("0123456789ab".to_string()),
+                runtimes: Some("php 8.3, node 22".to_string()),
+                version: Some("0.1.0".to_string()),
+                size: 1_234_000_000,
+                created: 7 * 86_400,
+                last_used: Some(now - std::time::Duration::from_secs(7200)),
+            },
+            ImageInfo {
+                tag: "bubble-bot:ffffffffffff".to_string(),
+                project: None,
+                config_hash: None,
+                runtimes: None,
+                version: None,
+                size: 512,
+                created: 0,
+                last_used: None,
+            },
+        ];
+        assert_eq!(
+            images_table(&images, now),
+            "IMAGE                    PROJECT  CONFIG        RUNTIMES          SIZE    CREATED     LAST USED    VERSION\n\
+             bubble-bot:a1b2c3d4e5f6  shop     0123456789ab  php 8.3, node 22  1.2 GB  3 days ago  2 hours ago  0.1.0\n\
+             bubble-bot:ffffffffffff  -        -             -                 512 B   1 week ago  never        -\n"
+        );
+    }
+
+    #[test]
+    fn usage_files_are_keyed_by_hash() {
+        let cache = Path::new("/cache");
+        assert_eq!(
+            usage_file(cache, "bubble-bot:a1b2c3d4e5f6"),
+            Path::new("/cache/bubble-bot/images/a1b2c3d4e5f6")
+        );
+    }
+
+    #[test]
+    fn ages_use_the_largest_whole_unit() {
+        assert_eq!(format_age(5), "just now");
+        assert_eq!(format_age(60), "1 minute ago");
+        assert_eq!(format_age(3 * 3600 + 5), "3 hours ago");
+        assert_eq!(format_age(400 * 86_400), "1 year ago");
+    }
 }
@@ -0,0 +1,746 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use bollard::auth::DockerCredentials;
+use bollard::container::{
+    Config, CreateContainerOptions, InspectContainerOptions, ListContainersOptions, LogOutput,
+    LogsOptions, RemoveContainerOptions, StopContainerOptions,
+};
+use bollard::errors::Error;
+use bollard::image::{
+    BuildImageOptions, CreateImageOptions, ListImagesOptions, RemoveImageOptions,
+};
+use bollard::models::{
+    BuildInfo, ContainerConfig, ContainerCreateResponse, ContainerInspectResponse, ContainerState,
+    ContainerSummary, CreateImageInfo, HostConfig, ImageConfig, ImageDeleteResponseItem,
+    ImageInspect, ImageSummary, Network, NetworkCreateResponse, SystemInfo, Volume,
+    VolumeListResponse,
+};
+use bollard::network::{ConnectNetworkOptions, CreateNetworkOptions, ListNetworksOptions};
+use bollard::volume::{ListVolumesOptions, RemoveVolumeOptions};
+use futures_util::StreamExt;
+use futures_util::stream::{self, BoxStream};
+
+use super::backend::ContainerBackend;
+
+/// An in-memory Docker daemon implementing [`ContainerBackend`], for testing
+/// session orchestration without one. Clones share state.
+///
+/// It models what the orchestration depends on: names are unique, missing
+/// resources are 404s, removing a running container needs `force`, a network
+/// with attached containers can't be removed, and containers need their image
+/// present. `name`, `label`, and `reference` list filters are honored. Every
+/// call is recorded (see [`MockBackend::calls`]).
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+}
+
+/// A container known to the mock daemon.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MockContainer {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub labels: HashMap<String, String>,
+    pub running: bool,
+    pub exit_code: Option<i64>,
+    pub oom_killed: bool,
+    /// Networks the container is attached to.
+    pub networks: Vec<String>,
+    /// The env, user, and host config it was created with.
+    pub env: Vec<String>,
+    pub user: Option<String>,
+    pub host_config: Option<HostConfig>,
+    /// Output lines returned by `logs`.
+    pub logs: Vec<String>,
+}
+
+/// A network known to the mock daemon.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MockNetwork {
+    pub name: String,
+    pub internal: bool,
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct MockState {
+    containers: Vec<MockContainer>,
+    networks: Vec<MockNetwork>,
+    images: Vec<String>,
+    /// Labels of images built through the mock.
+    image_labels: HashMap<String, HashMap<String, String>>,
+    volumes: Vec<String>,
+    security_options: Vec<String>,
+    /// Errors the next calls of a method return, as `(method, error)`.
+    failures: Vec<(String, Error)>,
+    /// Whether image builds hang without output, like one stuck on a mirror.
+    stall_builds: bool,
+    calls: Vec<String>,
+    next_id: u64,
+}
+
+impl MockState {
+    fn record(&mut self, method: &str, target: &str) {
+        self.calls.push(format!("{method} {target}"));
+    }
+
+    /// Takes the next error injected for `method` with
+    /// [`MockBackend::fail_next`].
+    fn injected_failure(&mut self, method: &str) -> Result<(), Error> {
+        match self.failures.iter().position(|(m, _)| m == method) {
+            Some(i) => Err(self.failures.remove(i).1),
+            None => Ok(()),
+        }
+    }
+
+    /// Looks up a container by ID or name, as the Docker API does.
+    fn container(&mut self, id_or_name: &str) -> Result<&mut MockContainer, Error> {
+        let name = id_or_name.trim_start_matches('/');
+        self.containers
+            .iter_mut()
+            .find(|c| c.id == id_or_name || c.name == name)
+            .ok_or_else(|| not_found("container", id_or_name))
+    }
+
+    fn has_image(&self, image: &str) -> bool {
+        self.images
+            .iter()
+            .any(|i| i == image || *i == format!("{image}:latest"))
+    }
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds a container, assigning an ID if it has none. Returns the ID.
+    pub fn add_container(&self, mut container: MockContainer) -> String {
+        let mut state = self.state();
+        if container.id.is_empty() {
+            state.next_id += 1;
+            container.id = format!("{:012x}", state.next_id);
+        }
+        let id = container.id.clone();
+        state.containers.push(container);
+        id
+    }
+
+    /// Adds a network.
+    pub fn add_network(&self, name: &str, internal: bool, labels: HashMap<String, String>) {
+        self.state().networks.push(MockNetwork {
+            name: name.to_string(),
+            internal,
+            labels,
+        });
+    }
+
+    /// Adds a local image by tag (`name:tag`).
+    pub fn add_image(&self, tag: &str) {
+        self.state().images.push(tag.to_string());
+    }
+
+    /// Adds a local image by tag with the labels it was built with.
+    pub fn add_labeled_image(&self, tag: &str, labels: HashMap<String, String>) {
+        let mut state = self.state();
+        state.images.push(tag.to_string());
+        state.image_labels.insert(tag.to_string(), labels);
+    }
+
+    /// Adds a named volume.
+    pub fn add_volume(&self, name: &str) {
+        self.state().volumes.push(name.to_string());
+    }
+
+    /// Sets the daemon's reported security options (e.g., `name=rootless`).
+    pub fn set_security_options(&self, options: &[&str]) {
+        self.state().security_options = options.iter().map(|o| o.to_string()).collect();
+    }
+
+    /// Stops a container as if its main process exited (or was OOM-killed).
+    pub fn exit_container(&self, id: &str, exit_code: i64, oom_killed: bool) {
+        if let Ok(container) = self.state().container(id) {
+            container.running = false;
+            container.exit_code = Some(exit_code);
+            container.oom_killed = oom_killed;
+        }
+    }
+
+    /// Makes the next call of `method` (e.g., `create_container`) fail with
+    /// `status_code` before taking effect, as a daemon hiccup would.
+    /// Supported by container create/start and network create/connect.
+    pub fn fail_next(&self, method: &str, status_code: u16, message: &str) {
+        self.state()
+            .failures
+            .push((method.to_string(), error(status_code, message.to_string())));
+    }
+
+    /// Makes image builds hang without finishing.
+    pub fn stall_builds(&self) {
+        self.state().stall_builds = true;
+    }
+
+    pub fn containers(&self) -> Vec<MockContainer> {
+        self.state().containers.clone()
+    }
+
+    pub fn networks(&self) -> Vec<MockNetwork> {
+        self.state().networks.clone()
+    }
+
+    pub fn images(&self) -> Vec<String> {
+        self.state().images.clone()
+    }
+
+    pub fn volumes(&self) -> Vec<String> {
+        self.state().volumes.clone()
+    }
+
+    /// Calls made so far, as `"<method> <target>"` (e.g.,
+    /// `"remove_network bubble-bot-app"`), in order.
+    pub fn calls(&self) -> Vec<String> {
+        self.state().calls.clone()
+    }
+}
+
+fn error(status_code: u16, message: String) -> Error {
+    Error::DockerResponseServerError {
+        status_code,
+        message,
+    }
+}
+
+fn not_found(kind: &str, name: &str) -> Error {
+    error(404, format!("No such {kind}: {name}"))
+}
+
+/// Whether a resource passes Docker list `filters`. `name` matches
+/// substrings, `label` matches `key` or `key=value`, and `reference` matches
+/// an image's full tag or repository.
+fn matches_filters(
+    filters: &HashMap<String, Vec<String>>,
+    name: &str,
+    labels: &HashMap<String, String>,
+) -> bool {
+    filters.iter().all(|(key, values)| match key.as_str() {
+        "name" => values.iter().any(|v| name.contains(v.as_str())),
+        "label" => values.iter().any(|v| match v.split_once('=') {
+            Some((k, value)) => labels.get(k).is_some_and(|l| l == value),
+            None => labels.contains_key(v),
+        }),
+        "reference" => values
+            .iter()
+            .any(|v| name == v || name.split(':').next() == Some(v.as_str())),
+        _ => true,
+    })
+}
+
+impl ContainerBackend for MockBackend {
+    async fn info(&self) -> Result<SystemInfo, Error> {
+        let mut state = self.state();
+        state.record("info", "");
+        Ok(SystemInfo {
+            security_options: Some(state.security_options.clone()),
+            ..Default::default()
+        })
+    }
+
+    async fn list_containers(
+        &self,
+        options: Option<ListContainersOptions<String>>,
+    ) -> Result<Vec<ContainerSummary>, Error> {
+        let mut state = self.state();
+        state.record("list_containers", "");
+        let (all, filters) = options
+            .map(|o| (o.all, o.filters))
+            .unwrap_or((false, HashMap::new()));
+        Ok(state
+            .containers
+            .iter()
+            .filter(|c| all || c.running)
+            .filter(|c| matches_filters(&filters, &c.name, &c.labels))
+            .map(|c| ContainerSummary {
+                id: Some(c.id.clone()),
+                names: Some(vec![format!("/{}", c.name)]),
+                image: Some(c.image.clone()),
+                labels: Some(c.labels.clone()),
+                state: Some(if c.running { "running" } else { "exited" }.to_string()),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    async fn inspect_container(
+        &self,
+        id: &str,
+        _options: Option<InspectContainerOptions>,
+    ) -> Result<ContainerInspectResponse, Error> {
+        let mut state = self.state();
+        state.record("inspect_container", id);
+        let container = state.container(id)?.clone();
+        // Containers inherit their image's labels, under their own
+        let mut labels = state
+            .image_labels
+            .get(&container.image)
+            .cloned()
+            .unwrap_or_default();
+        labels.extend(container.labels.clone());
+        Ok(ContainerInspectResponse {
+            id: Some(container.id.clone()),
+            name: Some(format!("/{}", container.name)),
+            image: Some(container.image.clone()),
+            config: Some(ContainerConfig {
+                labels: Some(labels),
+                env: Some(container.env.clone()),
+                ..Default::default()
+            }),
+            state: Some(ContainerState {
+                running: Some(container.running),
+                exit_code: container.exit_code,
+                oom_killed: Some(container.oom_killed),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    async fn create_container(
+        &self,
+        options: Option<CreateContainerOptions<String>>,
+        config: Config<String>,
+    ) -> Result<ContainerCreateResponse, Error> {
+        let mut state = self.state();
+        let name = options.map(|o| o.name).unwrap_or_default();
+        state.record("create_container", &name);
+        state.injected_failure("create_container")?;
+
+        if !name.is_empty() && state.containers.iter().any(|c| c.name == name) {
+            return Err(error(
+                409,
+                format!("Conflict. The container name \"/{name}\" is already in use"),
+            ));
+        }
+        let image = config.image.unwrap_or_default();
+        if !state.has_image(&image) {
+            return Err(not_found("image", &image));
+        }
+
+        let networks = config
+            .host_config
+            .as_ref()
+            .and_then(|h| h.network_mode.clone())
+            .filter(|mode| state.networks.iter().any(|n| &n.name == mode))
+            .into_iter()
+            .collect();
+        state.next_id += 1;
+        let id = format!("{:012x}", state.next_id);
+        state.containers.push(MockContainer {
+            id: id.clone(),
+            name,
+            image,
+            labels: config.labels.unwrap_or_default(),
+            networks,
+            env: config.env.unwrap_or_default(),
+            user: config.user,
+            host_config: config.host_config,
+            ..Default::default()
+        });
+
+        Ok(ContainerCreateResponse {
+            id,
+            warnings: Vec::new(),
+        })
+    }
+
+    async fn start_container(&self, id: &str) -> Result<(), Error> {
+        let mut state = self.state();
+        state.record("start_container", id);
+        state.injected_failure("start_container")?;
+        let container = state.container(id)?;
+        container.running = true;
+        container.exit_code = None;
+        Ok(())
+    }
+
+    async fn stop_container(
+        &self,
+        id: &str,
+        _options: Option<StopContainerOptions>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.record("stop_container", id);
+        let container = state.container(id)?;
+        if container.running {
+            container.running = false;
+            container.exit_code = Some(143);
+        }
+        Ok(())
+    }
+
+    async fn remove_container(
+        &self,
+        id: &str,
+        options: Option<RemoveContainerOptions>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.record("remove_container", id);
+        let force = options.is_some_and(|o| o.force);
+        let container = state.container(id)?;
+        if container.running && !force {
+            return Err(error(
+                409,
+                format!("cannot remove container {id}: container is running"),
+            ));
+        }
+        let id = container.id.clone();
+        state.containers.retain(|c| c.id != id);
+        Ok(())
+    }
+
+    async fn connect_network(
+        &self,
+        network: &str,
+        options: ConnectNetworkOptions<String>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.record("connect_network", network);
+        state.injected_failure("connect_network")?;
+        // Docker's predefined networks always exist
+        let predefined = matches!(network, "bridge" | "host" | "none");
+        if !predefined && !state.networks.iter().any(|n| n.name == network) {
+            return Err(not_found("network", network));
+        }
+        let container = state.container(&options.container)?;
+        container.networks.push(network.to_string());
+        Ok(())
+    }
+
+    fn logs(
+        &self,
+        id: &str,
+        options: Option<LogsOptions<String>>,
+    ) -> BoxStream<'_, Result<LogOutput, Error>> {
+        let mut state = self.state();
+        state.record("logs", id);
+        let lines = match state.container(id) {
+            Ok(container) => container.logs.clone(),
+            Err(e) => return stream::iter([Err(e)]).boxed(),
+        };
+        let tail = options
+            .and_then(|o| o.tail.parse::<usize>().ok())
+            .unwrap_or(lines.len());
+        let output: Vec<_> = lines[lines.len().saturating_sub(tail)..]
+            .iter()
+            .map(|line| {
+                Ok(LogOutput::StdOut {
+                    message: format!("{line}\n").into(),
+                })
+            })
+            .collect();
+        stream::iter(output).boxed()
+    }
+
+    async fn inspect_image(&self, image: &str) -> Result<ImageInspect, Error> {
+        let mut state = self.state();
+        state.record("inspect_image", image);
+        if !state.has_image(image) {
+            return Err(not_found("image", image));
+        }
+        Ok(ImageInspect {
+            repo_tags: Some(vec![image.to_string()]),
+            config: state.image_labels.get(image).map(|labels| ImageConfig {
+                labels: Some(labels.clone()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    async fn list_images(
+        &self,
+        options: Option<ListImagesOptions<String>>,
+    ) -> Result<Vec<ImageSummary>, Error> {
+        let mut state = self.state();
+        state.record("list_images", "");
+        let filters = options.map(|o| o.filters).unwrap_or_default();
+        let no_labels = HashMap::new();
+        Ok(state
+            .images
+            .iter()
+            .map(|tag| (tag, state.image_labels.get(tag).unwrap_or(&no_labels)))
+            .filter(|(tag, labels)| matches_filters(&filters, tag, labels))
+            .map(|(tag, labels)| ImageSummary {
+                id: format!("sha256:{tag}"),
+                repo_tags: vec![tag.clone()],
+                labels: labels.clone(),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn create_image(
+        &self,
+        options: CreateImageOptions<'static, String>,
+        _credentials: Option<DockerCredentials>,
+    ) -> BoxStream<'_, Result<CreateImageInfo, Error>> {
+        let image = match options.tag.as_str() {
+            "" => options.from_image,
+            tag => format!("{}:{tag}", options.from_image),
+        };
+        let mut state = self.state();
+        state.record("create_image", &image);
+        if !state.images.contains(&image) {
+            state.images.push(image);
+        }
+
+        stream::iter([Ok(CreateImageInfo {
+            status: Some("Pull complete".to_string()),
+            ..Default::default()
+        })])
+        .boxed()
+    }
+
+    fn build_image(
+        &self,
+        options: BuildImageOptions<String>,
+        _credentials: Option<HashMap<String, DockerCredentials>>,
+        _context: Vec<u8>,
+    ) -> BoxStream<'_, Result<BuildInfo, Error>> {
+        let mut state = self.state();
+        state.record("build_image", &options.t);
+        if state.stall_builds {
+            return stream::pending().boxed();
+        }
+        if !state.images.contains(&options.t) {
+            state.images.push(options.t.clone());
+        }
+        state
+            .image_labels
+            .insert(options.t.clone(), options.labels.clone());
+
+        stream::iter([Ok(BuildInfo {
+            stream: Some(format!("Successfully tagged {}\n", options.t)),
+            ..Default::default()
+        })])
+        .boxed()
+    }
+
+    async fn remove_image(
+        &self,
+        image: &str,
+        _options: Option<RemoveImageOptions>,
+    ) -> Result<Vec<ImageDeleteResponseItem>, Error> {
+        let mut state = self.state();
+        state.record("remove_image", image);
+        if !state.has_image(image) {
+            return Err(not_found("image", image));
+        }
+        state
+            .images
+            .retain(|i| i != image && *i != format!("{image}:latest"));
+        Ok(vec![ImageDeleteResponseItem {
+            untagged: Some(image.to_string()),
+            deleted: None,
+        }])
+    }
+
+    async fn list_networks(
+        &self,
+        options: Option<ListNetworksOptions<String>>,
+    ) -> Result<Vec<Network>, Error> {
+        let mut state = self.state();
+        state.record("list_networks", "");
+        let filters = options.map(|o| o.filters).unwrap_or_default();
+        Ok(state
+            .networks
+            .iter()
+            .filter(|n| matches_filters(&filters, &n.name, &n.labels))
+            .map(|n| Network {
+                name: Some(n.name.clone()),
+                internal: Some(n.internal),
+                labels: Some(n.labels.clone()),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    async fn create_network(
+        &self,
+        options: CreateNetworkOptions<String>,
+    ) -> Result<NetworkCreateResponse, Error> {
+        let mut state = self.state();
+        state.record("create_network", &options.name);
+        state.injected_failure("create_network")?;
+        if state.networks.iter().any(|n| n.name == options.name) {
+            return Err(error(
+                409,
+                format!("network with name {} already exists", options.name),
+            ));
+        }
+        state.networks.push(MockNetwork {
+            name: options.name.clone(),
+            internal: options.internal,
+            labels: options.labels,
+        });
+        Ok(NetworkCreateResponse {
+            id: options.name,
+            warning: String::new(),
+        })
+    }
+
+    async fn remove_network(&self, name: &str) -> Result<(), Error> {
+        let mut state = self.state();
+        state.record("remove_network", name);
+        if !state.networks.iter().any(|n| n.name == name) {
+            return Err(not_found("network", name));
+        }
+        if state
+            .containers
+            .iter()
+            .any(|c| c.networks.iter().any(|n| n == name))
+        {
+            return Err(error(
+                403,
+                format!("error while removing network: network {name} has active endpoints"),
+            ));
+        }
+        state.networks.retain(|n| n.name != name);
+        Ok(())
+    }
+
+    async fn list_volumes(
+        &self,
+        options: Option<ListVolumesOptions<String>>,
+    ) -> Result<VolumeListResponse, Error> {
+        let mut state = self.state();
+        state.record("list_volumes", "");
+        let filters = options.map(|o| o.filters).unwrap_or_default();
+        let volumes = state
+            .volumes
+            .iter()
+            .filter(|name| matches_filters(&filters, name, &HashMap::new()))
+            .map(|name| Volume {
+                name: name.clone(),
+                ..Default::default()
+            })
+            .collect();
+        Ok(VolumeListResponse {
+            volumes: Some(volumes),
+            warnings: None,
+        })
+    }
+
+    async fn remove_volume(
+        &self,
+        name: &str,
+        _options: Option<RemoveVolumeOptions>,
+    ) -> Result<(), Error> {
+        let mut state = self.state();
+        state.record("remove_volume", name);
+        if !state.volumes.iter().any(|v| v == name) {
+            return Err(not_found("volume", name));
+        }
+        state.volumes.retain(|v| v != name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_match_like_docker() {
+        let labels = HashMap::from([("bubble-bot.pid".to_string(), "42".to_string())]);
+        let filter =
+            |key: &str, value: &str| HashMap::from([(key.to_string(), vec![value.to_string()])]);
+
+        assert!(matches_filters(
+            &filter("name", "bubble-bot-"),
+            "bubble-bot-app-mysql",
+            &labels
+        ));
+        assert!(!matches_filters(
+            &filter("name", "other"),
+            "bubble-bot-app",
+            &labels
+        ));
+        assert!(matches_filters(
+            &filter("label", "bubble-bot.pid"),
+            "x",
+            &labels
+        ));
+        assert!(matches_filters(
+            &filter("label", "bubble-bot.pid=42"),
+            "x",
+            &labels
+        ));
+        assert!(!matches_filters(
+            &filter("label", "bubble-bot.pid=7"),
+            "x",
+            &labels
+        ));
+        assert!(matches_filters(
+            &filter("reference", "bubble-bot"),
+            "bubble-bot:abc",
+            &labels
+        ));
+        assert!(!matches_filters(
+            &filter("reference", "bubble"),
+            "bubble-bot:abc",
+            &labels
+        ));
+    }
+
+    #[test]
+    fn running_containers_need_force_to_remove() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mock = MockBackend::new();
+            let id = mock.add_container(MockContainer {
+                name: "app".to_string(),
+                running: true,
+                ..Default::default()
+            });
+
+            let err = mock.remove_container(&id, None).await.unwrap_err();
+            assert!(matches!(
+                err,
+                Error::DockerResponseServerError {
+                    status_code: 409,
+                    ..
+                }
+            ));
+
+            let force = RemoveContainerOptions {
+                force: true,
+                ..Default::default()
+            };
+            mock.remove_container("app", Some(force)).await.unwrap();
+            assert!(mock.containers().is_empty());
+        });
+    }
+
+    #[test]
+    fn networks_with_containers_cannot_be_removed() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let mock = MockBackend::new();
+            mock.add_network("net", false, HashMap::new());
+            mock.add_container(MockContainer {
+                name: "app".to_string(),
+                networks: vec!["net".to_string()],
+                ..Default::default()
+            });
+
+            assert!(mock.remove_network("net").await.is_err());
+            assert_eq!(mock.networks().len(), 1);
+        });
+    }
+}
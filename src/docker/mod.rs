@@ -1,4 +1,13 @@
+pub mod archive;
+pub mod backend;
 pub mod clean;
+pub mod connect;
 pub mod containers;
 pub mod images;
+pub mod mock;
 pub mod networks;
+pub mod ownership;
+pub mod platform;
+pub mod registry;
+pub mod retry;
+pub mod volumes;
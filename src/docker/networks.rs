@@ -2,24 +2,54 @@ use std::collections::HashMap;
 
 use anyhow::{Context, Result};
 use bollard::Docker;
+use bollard::models::Network;
 use bollard::network::{CreateNetworkOptions, ListNetworksOptions};
 use tracing::{info, warn};
 
+use super::backend::ContainerBackend;
+use super::ownership::KEEP_LABEL;
+use super::retry::{self, DEFAULT_RETRIES, already_exists};
+use crate::config::project_id;
+
 /// Manages bridge networks for container communication.
-pub struct NetworkManager {
-    docker: Docker,
+pub struct NetworkManager<B = Docker> {
+    docker: B,
+    retries: u32,
 }
 
-impl NetworkManager {
-    pub fn new(docker: Docker) -> Self {
-        Self { docker }
+impl<B: ContainerBackend> NetworkManager<B> {
+    pub fn new(docker: B) -> Self {
+        Self {
+            docker,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+
+    /// Retries of network creation when it fails transiently (see
+    /// [`retry::is_transient`]).
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
     }
 
     /// Creates a bridge network with the given name.
     /// If the network already exists, it is reused.
-    /// Returns the network name.
-    pub async fn ensure_network(&self, name: &str) -> Result<String> {
-        if self.network_exists(name).await? {
+    /// When `internal` is set, the network has no route to the outside world:
+    /// containers can reach each other but not the internet.
+    /// `labels` are attached to a newly created network. Returns the network name.
+    pub async fn ensure_network(
+        &self,
+        name: &str,
+        internal: bool,
+        labels: &HashMap<String, String>,
+    ) -> Result<String> {
+        if let Some(existing) = self.find_network(name).await? {
+            if existing.internal.unwrap_or(false) != internal {
+                anyhow::bail!(
+                    "network '{name}' already exists with internal={} but internal={internal} was requested; remove it with `docker network rm {name}`",
+                    existing.internal.unwrap_or(false)
+                );
+            }
             info!(network = %name, "network already exists — reusing");
             return Ok(name.to_string());
         }
@@ -28,21 +58,44 @@ impl NetworkManager {
             name: name.to_string(),
             driver: "bridge".to_string(),
             check_duplicate: true,
+            internal,
+            labels: labels.clone(),
             ..Default::default()
         };
 
-        self.docker
-            .create_network(options)
-            .await
-            .context("failed to create network")?;
+        retry::retry(self.retries, "network create", |attempt| {
+            let options = options.clone();
+            async move {
+                match self.docker.create_network(options).await {
+                    // An earlier attempt created it before its response was lost
+                    Err(e) if attempt > 0 && already_exists(&e) => Ok(()),
+                    result => result.map(drop),
+                }
+            }
+        })
+        .await
+        .context("failed to create network")?;
 
-        info!(network = %name, "bridge network created");
+        info!(network = %name, internal, "bridge network created");
 
         Ok(name.to_string())
     }
 
     /// Checks whether a network with the given name exists.
     pub async fn network_exists(&self, name: &str) -> Result<bool> {
+        Ok(self.find_network(name).await?.is_some())
+    }
+
+    /// Whether the network is internal, or `None` if it doesn't exist.
+    pub async fn is_internal(&self, name: &str) -> Result<Option<bool>> {
+        Ok(self
+            .find_network(name)
+            .await?
+            .map(|network| network.internal.unwrap_or(false)))
+    }
+
+    /// Looks up a network by exact name.
+    pub async fn find_network(&self, name: &str) -> Result<Option<Network>> {
         let filters: HashMap<String, Vec<String>> = [("name".to_string(), vec![name.to_string()])]
             .into_iter()
             .collect();
@@ -54,12 +107,15 @@ impl NetworkManager {
             .context("failed to list networks")?;
 
         // Docker name filter returns partial matches — check for exact match
-        Ok(networks.iter().any(|n| n.name.as_deref() == Some(name)))
+        Ok(networks
+            .into_iter()
+            .find(|n| n.name.as_deref() == Some(name)))
     }
 
     /// Detects and removes stale networks matching the `bubble-bot-<project>` prefix.
-    /// Returns the number of networks removed.
-    pub async fn cleanup_stale(&self, project_prefix: &str) -> Result<usize> {
+    /// A network kept with `--keep` under one of the `reusable` names is left
+    /// for the session to reuse. Returns the number of networks removed.
+    pub async fn cleanup_stale(&self, project_prefix: &str, reusable: &[String]) -> Result<usize> {
         let filters: HashMap<String, Vec<String>> =
             [("name".to_string(), vec![project_prefix.to_string()])]
                 .into_iter()
@@ -75,6 +131,13 @@ impl NetworkManager {
 
         for network in &networks {
             let name = network.name.as_deref().unwrap_or("");
+            let kept = network
+                .labels
+                .as_ref()
+                .is_some_and(|labels| labels.contains_key(KEEP_LABEL));
+            if kept && reusable.iter().any(|r| r == name) {
+                continue;
+            }
             if matches_stale_prefix(name, project_prefix) {
                 warn!(network = %name, "removing stale network from previous session");
                 match self.docker.remove_network(name).await {
@@ -111,19 +174,17 @@ pub fn matches_stale_prefix(network_name: &str, prefix: &str) -> bool {
     network_name == prefix || network_name.starts_with(&format!("{prefix}-"))
 }
 
-/// Derives the default network name from the current working directory.
-/// Returns `bubble-bot-<dir-name>` matching the container naming convention.
+/// Derives the default network name from the project directory.
+/// Returns `bubble-bot-<dir-name>-<path-hash>` matching the container
+/// naming convention.
 pub fn default_network_name() -> String {
-    std::env::current_dir()
-        .ok()
-        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
-        .map(|name| format!("bubble-bot-{name}"))
-        .unwrap_or_else(|| "bubble-bot-project".to_string())
+    format!("bubble-bot-{}", project_id())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::docker::mock::{MockBackend, MockNetwork};
 
     #[test]
     fn default_network_name_has_prefix() {
@@ -175,4 +236,58 @@ mod tests {
     fn stale_prefix_rejects_non_bubble_boy_network() {
         assert!(!matches_stale_prefix("my-network", "bubble-bot-myproject"));
     }
+
+    #[test]
+    fn ensure_network_creates_labeled_network_then_reuses_it() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        let network_mgr = NetworkManager::new(mock.clone());
+        let labels = HashMap::from([("bubble-bot.pid".to_string(), "42".to_string())]);
+
+        rt.block_on(async {
+            network_mgr
+                .ensure_network("bubble-bot-app", true, &labels)
+                .await
+                .unwrap();
+            network_mgr
+                .ensure_network("bubble-bot-app", true, &labels)
+                .await
+                .unwrap();
+        });
+
+        assert_eq!(
+            mock.networks(),
+            vec![MockNetwork {
+                name: "bubble-bot-app".to_string(),
+                internal: true,
+                labels,
+            }]
+        );
+    }
+
+    #[test]
+    fn ensure_network_retries_transient_errors() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.fail_next("create_network", 500, "EOF");
+        let network_mgr = NetworkManager::new(mock.clone()).with_retries(1);
+
+        rt.block_on(network_mgr.ensure_network("bubble-bot-app", false, &HashMap::new()))
+            .unwrap();
+        assert_eq!(mock.networks().len(), 1);
+    }
+
+    #[test]
+    fn ensure_network_rejects_existing_network_with_other_isolation() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_network("bubble-bot-app", false, HashMap::new());
+        let network_mgr = NetworkManager::new(mock);
+
+        let err = rt
+            .block_on(network_mgr.ensure_network("bubble-bot-app", true, &HashMap::new()))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("docker network rm bubble-bot-app"), "{err}");
+    }
 }
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use bollard::Docker;
+use bollard::container::{ListContainersOptions, RemoveContainerOptions};
+use bollard::network::ListNetworksOptions;
+use tracing::{info, warn};
+
+use super::backend::ContainerBackend;
+
+/// Label holding the ID of the session that created a resource.
+pub const SESSION_LABEL: &str = "bubble-bot.session";
+/// Label holding the PID of the bubble-bot process that owns a resource.
+pub const PID_LABEL: &str = "bubble-bot.pid";
+/// Label holding the hostname of the owning process, since a PID only means
+/// something on the machine it came from.
+pub const HOST_LABEL: &str = "bubble-bot.host";
+/// Label marking the resources of a `--keep` session, which outlive their
+/// owner on purpose and are never reaped.
+pub const KEEP_LABEL: &str = "bubble-bot.keep";
+/// Dev container env var holding the ID of the session that created it,
+/// for shell prompts.
+pub const SESSION_ENV: &str = "BUBBLE_BOT_SESSION";
+
+/// The bubble-bot process that owns a session's containers and network.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    pub session_id: String,
+    pub pid: u32,
+    pub host: String,
+}
+
+impl Owner {
+    /// The current process, with a fresh session ID.
+    pub fn current() -> Self {
+        let pid = std::process::id();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        Self {
+            session_id: format!("{pid:x}-{nanos:x}"),
+            pid,
+            host: hostname(),
+        }
+    }
+
+    /// Labels to attach to every resource the session creates.
+    pub fn labels(&self) -> HashMap<String, String> {
+        HashMap::from([
+            (SESSION_LABEL.to_string(), self.session_id.clone()),
+            (PID_LABEL.to_string(), self.pid.to_string()),
+            (HOST_LABEL.to_string(), self.host.clone()),
+        ])
+    }
+
+    /// Reads the owner back from a resource's labels.
+    pub fn from_labels(labels: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            session_id: labels.get(SESSION_LABEL)?.clone(),
+            pid: labels.get(PID_LABEL)?.parse().ok()?,
+            host: labels.get(HOST_LABEL)?.clone(),
+        })
+    }
+
+    /// Whether the owner was a process on this host that has since exited.
+    /// Owners on other hosts (a shared or remote daemon) are never gone.
+    pub fn is_gone(&self) -> bool {
+        self.host == hostname() && !process_alive(self.pid)
+    }
+}
+
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if rc != 0 {
+        return "unknown".to_string();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Whether a process with this PID exists. `EPERM` means it exists but
+/// belongs to another user.
+fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Removes containers and networks whose owning bubble-bot process is gone,
+/// e.g., after it was `SIGKILL`ed before it could clean up.
+pub struct Reaper<B = Docker> {
+    docker: B,
+}
+
+impl<B: ContainerBackend> Reaper<B> {
+    pub fn new(docker: B) -> Self {
+        Self { docker }
+    }
+
+    /// Reaps orphaned resources. Returns the names of removed containers and
+    /// networks.
+    pub async fn reap(&self) -> Result<(Vec<String>, Vec<String>)> {
+        let containers = self.reap_containers().await?;
+        let networks = self.reap_networks().await?;
+        Ok((containers, networks))
+    }
+
+    async fn reap_containers(&self) -> Result<Vec<String>> {
+        let containers = self
+            .docker
+            .list_containers(Some(ListContainersOptions {
+                all: true,
+                filters: owner_filter(),
+                ..Default::default()
+            }))
+            .await
+            .context("failed to list containers for orphan detection")?;
+
+        let mut removed = Vec::new();
+        for container in &containers {
+            let labels = container.labels.clone().unwrap_or_default();
+            if !is_orphaned(&labels) {
+                continue;
+            }
+
+            let id = container.id.as_deref().unwrap_or("unknown");
+            let name = container
+                .names
+                .as_deref()
+                .and_then(|names| names.first())
+                .map(|n| n.trim_start_matches('/').to_string())
+                .unwrap_or_else(|| id.to_string());
+            warn!(name, id, "removing container left by an exited bubble-bot");
+
+            let options = RemoveContainerOptions {
+                force: true,
+                ..Default::default()
+            };
+            match self.docker.remove_container(id, Some(options)).await {
+                Ok(()) => removed.push(name),
+                Err(e) => warn!(name, error = %e, "failed to remove orphaned container"),
+            }
+        }
+
+        Ok(removed)
+    }
+
+    async fn reap_networks(&self) -> Result<Vec<String>> {
+        let networks = self
+            .docker
+            .list_networks(Some(ListNetworksOptions {
+                filters: owner_filter(),
+            }))
+            .await
+            .context("failed to list networks for orphan detection")?;
+
+        let mut removed = Vec::new();
+        for network in &networks {
+            let labels = network.labels.clone().unwrap_or_default();
+            if !is_orphaned(&labels) {
+                continue;
+            }
+
+            let name = network.name.clone().unwrap_or_default();
+            match self.docker.remove_network(&name).await {
+                Ok(()) => {
+                    info!(network = %name, "removed network left by an exited bubble-bot");
+                    removed.push(name);
+                }
+                Err(e) => warn!(network = %name, error = %e, "failed to remove orphaned network"),
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Whether a resource's owner has exited and it wasn't kept with `--keep`.
+fn is_orphaned(labels: &HashMap<String, String>) -> bool {
+    !labels.contains_key(KEEP_LABEL)
+        && Owner::from_labels(labels).is_some_and(|owner| owner.is_gone())
+}
+
+/// Docker list filter matching resources that carry ownership labels.
+fn owner_filter() -> HashMap<String, Vec<String>> {
+    HashMap::from([("label".to_string(), vec![PID_LABEL.to_string()])])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::mock::{MockBackend, MockContainer};
+
+    #[test]
+    fn labels_round_trip() {
+        let owner = Owner::current();
+        assert_eq!(Owner::from_labels(&owner.labels()), Some(owner));
+    }
+
+    #[test]
+    fn unlabeled_resources_have_no_owner() {
+        let mut labels = Owner::current().labels();
+        labels.remove(PID_LABEL);
+        assert_eq!(Owner::from_labels(&labels), None);
+    }
+
+    #[test]
+    fn current_process_is_not_gone() {
+        assert!(!Owner::current().is_gone());
+    }
+
+    #[test]
+    fn exited_process_is_gone() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+
+        let owner = Owner {
+            pid,
+            ..Owner::current()
+        };
+        assert!(owner.is_gone());
+    }
+
+    #[test]
+    fn owners_on_other_hosts_are_never_gone() {
+        let owner = Owner {
+            pid: u32::MAX,
+            host: "some-other-host".to_string(),
+            ..Owner::current()
+        };
+        assert!(!owner.is_gone());
+    }
+
+    #[test]
+    fn reaper_removes_only_unkept_resources_of_exited_owners() {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let exited = Owner {
+            pid: child.id(),
+            ..Owner::current()
+        };
+        child.wait().unwrap();
+
+        let mock = MockBackend::new();
+        mock.add_network("bubble-bot-dead", false, exited.labels());
+        mock.add_network("bubble-bot-live", false, Owner::current().labels());
+        mock.add_container(MockContainer {
+            name: "bubble-bot-dead".to_string(),
+            running: true,
+            labels: exited.labels(),
+            ..Default::default()
+        });
+        mock.add_container(MockContainer {
+            name: "bubble-bot-live".to_string(),
+            running: true,
+            labels: Owner::current().labels(),
+            ..Default::default()
+        });
+        let mut kept = exited.labels();
+        kept.insert(KEEP_LABEL.to_string(), "true".to_string());
+        mock.add_container(MockContainer {
+            name: "bubble-bot-kept".to_string(),
+            running: true,
+            labels: kept,
+            ..Default::default()
+        });
+        mock.add_container(MockContainer {
+            name: "unrelated".to_string(),
+            running: true,
+            ..Default::default()
+        });
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let (containers, networks) = rt.block_on(Reaper::new(mock.clone()).reap()).unwrap();
+        assert_eq!(containers, vec!["bubble-bot-dead"]);
+        assert_eq!(networks, vec!["bubble-bot-dead"]);
+
+        let remaining: Vec<String> = mock.containers().into_iter().map(|c| c.name).collect();
+        assert_eq!(
+            remaining,
+            vec!["bubble-bot-live", "bubble-bot-kept", "unrelated"]
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use anyhow::{Result, bail};
+use tracing::warn;
+
+use super::backend::ContainerBackend;
+
+/// Rosetta's runtime on macOS; Docker Desktop can use it for amd64 images.
+const ROSETTA_RUNTIME: &str = "/Library/Apple/usr/libexec/oah/libRosettaRuntime";
+
+/// binfmt_misc handlers that run amd64 binaries on a Linux arm64 host.
+const AMD64_BINFMT_HANDLERS: &[&str] = &["qemu-x86_64", "rosetta"];
+
+/// What runs foreign-architecture binaries for the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emulator {
+    Rosetta,
+    Qemu,
+    /// No handler found; containers fail with `exec format error`.
+    Missing,
+}
+
+/// Validates a `--platform` value (`os/arch[/variant]`) and returns its
+/// architecture, normalized to Docker's names.
+pub fn platform_arch(platform: &str) -> Result<&str> {
+    let parts: Vec<&str> = platform.split('/').collect();
+    match parts[..] {
+        [os, arch] | [os, arch, _] if !os.is_empty() && !arch.is_empty() => Ok(normalize(arch)),
+        _ => bail!("invalid platform `{platform}` (expected e.g. `linux/amd64`)"),
+    }
+}
+
+/// Maps kernel architecture names (`uname -m`, `/info`) to Docker's.
+fn normalize(arch: &str) -> &str {
+    match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        other => other,
+    }
+}
+
+/// Checks that the daemon can run `platform`. When it means emulation,
+/// warns that it will be slow and says which emulator will be used, or how
+/// to install one if none is registered.
+pub async fn check<B: ContainerBackend>(docker: &B, platform: &str) -> Result<()> {
+    let target = platform_arch(platform)?;
+    let native = match docker.info().await {
+        Ok(info) => info.architecture.unwrap_or_default(),
+        Err(e) => {
+            warn!(error = %e, "failed to read daemon info; not checking platform emulation");
+            return Ok(());
+        }
+    };
+    let native = normalize(&native);
+    if native.is_empty() || native == target {
+        return Ok(());
+    }
+
+    match detect_emulator(target, Path::new("/")) {
+        Emulator::Rosetta => warn!(
+            platform,
+            native, "running under Rosetta emulation; builds and commands will be slower"
+        ),
+        Emulator::Qemu => warn!(
+            platform,
+            native, "running under QEMU emulation; builds and commands will be much slower"
+        ),
+        Emulator::Missing => warn!(
+            platform,
+            native,
+            "no {target} emulator registered; install one with \
+             `docker run --privileged --rm tonistiigi/binfmt --install {target}`"
+        ),
+    }
+    Ok(())
+}
+
+/// Finds the emulator for `target` binaries, looking under `root` (the
+/// filesystem root, replaceable in tests). macOS always has Docker Desktop's
+/// QEMU, and Rosetta if installed; Linux needs a binfmt_misc handler.
+fn detect_emulator(target: &str, root: &Path) -> Emulator {
+    if cfg!(target_os = "macos") {
+        let rosetta = root.join(ROSETTA_RUNTIME.trim_start_matches('/'));
+        return if target == "amd64" && rosetta.exists() {
+            Emulator::Rosetta
+        } else {
+            Emulator::Qemu
+        };
+    }
+
+    let binfmt = root.join("proc/sys/fs/binfmt_misc");
+    let handlers: &[&str] = match target {
+        "amd64" => AMD64_BINFMT_HANDLERS,
+        "arm64" => &["qemu-aarch64"],
+        _ => &[],
+    };
+    match handlers.iter().find(|h| binfmt.join(h).exists()) {
+        Some(&"rosetta") => Emulator::Rosetta,
+        Some(_) => Emulator::Qemu,
+        None => Emulator::Missing,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::mock::MockBackend;
+
+    #[test]
+    fn platform_arch_is_normalized() {
+        assert_eq!(platform_arch("linux/amd64").unwrap(), "amd64");
+        assert_eq!(platform_arch("linux/x86_64").unwrap(), "amd64");
+        assert_eq!(platform_arch("linux/arm64/v8").unwrap(), "arm64");
+        for bad in ["amd64", "linux/", "linux/amd64/v8/x"] {
+            assert!(platform_arch(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn linux_emulator_comes_from_binfmt_handlers() {
+        let root = tempfile::tempdir().unwrap();
+        assert_eq!(detect_emulator("amd64", root.path()), Emulator::Missing);
+
+        let binfmt = root.path().join("proc/sys/fs/binfmt_misc");
+        std::fs::create_dir_all(&binfmt).unwrap();
+        std::fs::write(binfmt.join("qemu-x86_64"), "enabled\n").unwrap();
+        assert_eq!(detect_emulator("amd64", root.path()), Emulator::Qemu);
+        assert_eq!(detect_emulator("arm64", root.path()), Emulator::Missing);
+
+        std::fs::remove_file(binfmt.join("qemu-x86_64")).unwrap();
+        std::fs::write(binfmt.join("rosetta"), "enabled\n").unwrap();
+        assert_eq!(detect_emulator("amd64", root.path()), Emulator::Rosetta);
+    }
+
+    #[test]
+    fn check_rejects_invalid_platforms_only() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        assert!(rt.block_on(check(&mock, "linux/amd64")).is_ok());
+        assert!(rt.block_on(check(&mock, "amd64")).is_err());
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use bollard::auth::DockerCredentials;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::config::RegistryConfig;
+use crate::redact;
+
+/// Registry host for images without an explicit registry (e.g., `mysql:8.4`).
+const DOCKER_HUB: &str = "docker.io";
+
+/// Server address the Docker CLI stores Docker Hub credentials under.
+const DOCKER_HUB_SERVER: &str = "https://index.docker.io/v1/";
+
+/// Resolves credentials for container registries.
+///
+/// `[registry.<host>]` config entries take precedence; otherwise the Docker
+/// CLI config (`~/.docker/config.json`) is consulted, including its
+/// credential helpers, so `docker login` sessions work unchanged.
+#[derive(Default)]
+pub struct RegistryAuth {
+    config: BTreeMap<String, RegistryConfig>,
+    docker_config: DockerConfigFile,
+}
+
+/// The parts of `~/.docker/config.json` used for authentication.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DockerConfigFile {
+    auths: HashMap<String, AuthEntry>,
+    #[serde(rename = "credsStore")]
+    creds_store: Option<String>,
+    #[serde(rename = "credHelpers")]
+    cred_helpers: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AuthEntry {
+    /// Base64-encoded `username:password`.
+    auth: Option<String>,
+    identitytoken: Option<String>,
+}
+
+/// Output of `docker-credential-<helper> get`.
+#[derive(Debug, Deserialize)]
+struct HelperCredentials {
+    #[serde(rename = "Username")]
+    username: String,
+    #[serde(rename = "Secret")]
+    secret: String,
+}
+
+impl RegistryAuth {
+    /// Loads registry auth from config and the Docker CLI config file. An
+    /// unreadable Docker config is logged and ignored.
+    pub fn load(config: &BTreeMap<String, RegistryConfig>) -> Self {
+        let docker_config = docker_config_path()
+            .and_then(|path| match std::fs::read_to_string(&path) {
+                Ok(contents) => Some((path, contents)),
+                Err(_) => None,
+            })
+            .and_then(|(path, contents)| match serde_json::from_str(&contents) {
+                Ok(parsed) => Some(parsed),
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "ignoring unreadable Docker config");
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        Self {
+            config: config.clone(),
+            docker_config,
+        }
+    }
+
+    /// Returns credentials for pulling `image`, if any are configured for its registry.
+    pub fn for_image(&self, image: &str) -> Option<DockerCredentials> {
+        self.credentials(registry_host(image))
+    }
+
+    /// Returns credentials for each registry referenced by `images`, keyed by
+    /// server address, as the build API expects.
+    pub fn for_images<'a>(
+        &self,
+        images: impl IntoIterator<Item = &'a str>,
+    ) -> HashMap<String, DockerCredentials> {
+        let mut credentials = HashMap::new();
+        for image in images {
+            let host = registry_host(image);
+            if let Some(creds) = self.credentials(host) {
+                credentials.insert(server_address(host).to_string(), creds);
+            }
+        }
+        credentials
+    }
+
+    /// Looks up credentials for `host`, registering their secrets for
+    /// masking in log output.
+    fn credentials(&self, host: &str) -> Option<DockerCredentials> {
+        let creds = self.lookup(host)?;
+        for secret in [&creds.password, &creds.identitytoken]
+            .into_iter()
+            .flatten()
+        {
+            redact::register(secret);
+        }
+        Some(creds)
+    }
+
+    fn lookup(&self, host: &str) -> Option<DockerCredentials> {
+        if let Some(entry) = self.config.get(host) {
+            return config_credentials(host, entry);
+        }
+
+        if let Some(entry) = self.docker_config.auth_entry(host) {
+            if let Some(creds) = auth_entry_credentials(host, entry) {
+                return Some(creds);
+            }
+        }
+
+        let helper = self
+            .docker_config
+            .cred_helpers
+            .get(host)
+            .or(self.docker_config.creds_store.as_ref())?;
+        helper_credentials(helper, host)
+    }
+}
+
+impl DockerConfigFile {
+    /// Finds the `auths` entry for `host`. Keys may be bare hosts or URLs
+    /// (`https://ghcr.io`, `https://index.docker.io/v1/`).
+    fn auth_entry(&self, host: &str) -> Option<&AuthEntry> {
+        self.auths
+            .iter()
+            .find(|(key, _)| normalize_host(key) == host)
+            .map(|(_, entry)| entry)
+    }
+}
+
+/// Extracts the registry host from an image reference. Images without a
+/// registry component (`mysql`, `ubuntu/squid`) come from Docker Hub.
+pub fn registry_host(image: &str) -> &str {
+    match image.split_once('/') {
+        Some((first, _)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+            first
+        }
+        _ => DOCKER_HUB,
+    }
+}
+
+/// Images named in `FROM` lines of a Dockerfile.
+pub fn base_images(dockerfile: &str) -> Vec<&str> {
+    dockerfile
+        .lines()
+        .filter_map(|line| {
+            let rest = line.trim().strip_prefix("FROM ")?;
+            rest.split_whitespace()
+                .find(|token| !token.starts_with("--"))
+        })
+        .collect()
+}
+
+fn server_address(host: &str) -> &str {
+    if host == DOCKER_HUB {
+        DOCKER_HUB_SERVER
+    } else {
+        host
+    }
+}
+
+fn normalize_host(key: &str) -> &str {
+    let host = key.split_once("://").map_or(key, |(_, rest)| rest);
+    let host = host.split('/').next().unwrap_or(host);
+    match host {
+        "index.docker.io" | "registry-1.docker.io" => DOCKER_HUB,
+        _ => host,
+    }
+}
+
+fn config_credentials(host: &str, entry: &RegistryConfig) -> Option<DockerCredentials> {
+    let password = entry.password.clone().or_else(|| {
+        let var = entry.password_env.as_deref()?;
+        let value = std::env::var(var).ok();
+        if value.is_none() {
+            warn!(registry = host, var, "registry password env var is not set");
+        }
+        value
+    });
+
+    Some(DockerCredentials {
+        username: entry.username.clone(),
+        password,
+        serveraddress: Some(server_address(host).to_string()),
+        ..Default::default()
+    })
+}
+
+fn auth_entry_credentials(host: &str, entry: &AuthEntry) -> Option<DockerCredentials> {
+    if let Some(token) = &entry.identitytoken {
+        return Some(DockerCredentials {
+            identitytoken: Some(token.clone()),
+            serveraddress: Some(server_address(host).to_string()),
+            ..Default::default()
+        });
+    }
+
+    let decoded = STANDARD.decode(entry.auth.as_deref()?).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some(DockerCredentials {
+        username: Some(username.to_string()),
+        password: Some(password.to_string()),
+        serveraddress: Some(server_address(host).to_string()),
+        ..Default::default()
+    })
+}
+
+/// Asks a Docker credential helper (`docker-credential-<helper>`) for `host`.
+/// Helpers exit non-zero when they have nothing stored, which is not an error.
+fn helper_credentials(helper: &str, host: &str) -> Option<DockerCredentials> {
+    let program = format!("docker-credential-{helper}");
+    let server = server_address(host);
+
+    let mut child = Command::new(&program)
+        .arg("get")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| warn!(helper = %program, error = %e, "failed to run credential helper"))
+        .ok()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(server.as_bytes());
+    }
+
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        debug!(helper = %program, registry = host, "no credentials from helper");
+        return None;
+    }
+
+    let creds: HelperCredentials = serde_json::from_slice(&output.stdout).ok()?;
+    Some(helper_to_docker(creds, server))
+}
+
+fn helper_to_docker(creds: HelperCredentials, server: &str) -> DockerCredentials {
+    // Helpers report identity tokens with a `<token>` username
+    if creds.username == "<token>" {
+        DockerCredentials {
+            identitytoken: Some(creds.secret),
+            serveraddress: Some(server.to_string()),
+            ..Default::default()
+        }
+    } else {
+        DockerCredentials {
+            username: Some(creds.username),
+            password: Some(creds.secret),
+            serveraddress: Some(server.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+fn docker_config_path() -> Option<PathBuf> {
+    match std::env::var_os("DOCKER_CONFIG") {
+        Some(dir) => Some(PathBuf::from(dir).join("config.json")),
+        None => dirs::home_dir().map(|home| home.join(".docker").join("config.json")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth_with(docker_config: &str, config: BTreeMap<String, RegistryConfig>) -> RegistryAuth {
+        RegistryAuth {
+            config,
+            docker_config: serde_json::from_str(docker_config).unwrap(),
+        }
+    }
+
+    #[test]
+    fn registry_host_of_hub_images() {
+        assert_eq!(registry_host("mysql:8.4"), "docker.io");
+        assert_eq!(registry_host("ubuntu/squid:latest"), "docker.io");
+    }
+
+    #[test]
+    fn registry_host_of_private_images() {
+        assert_eq!(registry_host("ghcr.io/acme/mysql:8.4"), "ghcr.io");
+        assert_eq!(
+            registry_host("registry.corp:5000/redis"),
+            "registry.corp:5000"
+        );
+        assert_eq!(registry_host("localhost/redis"), "localhost");
+    }
+
+    #[test]
+    fn base_images_from_dockerfile() {
+        let dockerfile =
+            "FROM ubuntu:24.04\nRUN true\nFROM --platform=linux/amd64 ghcr.io/acme/base AS build\n";
+        assert_eq!(
+            base_images(dockerfile),
+            vec!["ubuntu:24.04", "ghcr.io/acme/base"]
+        );
+    }
+
+    #[test]
+    fn config_entry_takes_precedence() {
+        let mut config = BTreeMap::new();
+        config.insert(
+            "ghcr.io".to_string(),
+            RegistryConfig {
+                username: Some("bot".to_string()),
+                password: Some("from-config".to_string()),
+                password_env: None,
+            },
+        );
+        let auth = auth_with(
+            r#"{"auths": {"ghcr.io": {"auth": "dXNlcjpmcm9tLWRvY2tlcg=="}}}"#,
+            config,
+        );
+
+        let creds = auth.for_image("ghcr.io/acme/app:1").unwrap();
+        assert_eq!(creds.username.as_deref(), Some("bot"));
+        assert_eq!(creds.password.as_deref(), Some("from-config"));
+        assert_eq!(creds.serveraddress.as_deref(), Some("ghcr.io"));
+    }
+
+    #[test]
+    fn docker_config_auths_are_decoded() {
+        // "user:from-docker"
+        let auth = auth_with(
+            r#"{"auths": {"https://ghcr.io": {"auth": "dXNlcjpmcm9tLWRvY2tlcg=="}}}"#,
+            BTreeMap::new(),
+        );
+
+        let creds = auth.for_image("ghcr.io/acme/app").unwrap();
+        assert_eq!(creds.username.as_deref(), Some("user"));
+        assert_eq!(creds.password.as_deref(), Some("from-docker"));
+    }
+
+    #[test]
+    fn docker_hub_auth_uses_index_server() {
+        let auth = auth_with(
+            r#"{"auths": {"https://index.docker.io/v1/": {"auth": "dXNlcjpmcm9tLWRvY2tlcg=="}}}"#,
+            BTreeMap::new(),
+        );
+
+        let creds = auth.for_images(["mysql:8.4"]);
+        let hub = creds.get("https://index.docker.io/v1/").unwrap();
+        assert_eq!(hub.username.as_deref(), Some("user"));
+    }
+
+    #[test]
+    fn identity_tokens_are_passed_through() {
+        let auth = auth_with(
+            r#"{"auths": {"registry.corp": {"identitytoken": "tok"}}}"#,
+            BTreeMap::new(),
+        );
+        let creds = auth.for_image("registry.corp/app").unwrap();
+        assert_eq!(creds.identitytoken.as_deref(), Some("tok"));
+        assert!(creds.username.is_none());
+    }
+
+    #[test]
+    fn no_credentials_for_unknown_registry() {
+        let auth = auth_with("{}", BTreeMap::new());
+        assert!(auth.for_image("ghcr.io/acme/app").is_none());
+        assert!(auth.for_images(["ubuntu:24.04"]).is_empty());
+    }
+
+    #[test]
+    fn helper_token_username_becomes_identity_token() {
+        let creds = helper_to_docker(
+            HelperCredentials {
+                username: "<token>".to_string(),
+                secret: "abc".to_string(),
+            },
+            "ghcr.io",
+        );
+        assert_eq!(creds.identitytoken.as_deref(), Some("abc"));
+        assert!(creds.password.is_none());
+    }
+}
@@ -0,0 +1,141 @@
+use std::future::Future;
+use std::time::Duration;
+
+use bollard::errors::Error;
+use tracing::warn;
+
+/// Retries when `docker.retries` is unset.
+pub const DEFAULT_RETRIES: u32 = 3;
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Runs `op`, retrying up to `retries` times with backoff while it fails
+/// with a [transient](is_transient) error. `op` gets the attempt number
+/// (0 first), so a retry can recognize an earlier attempt that succeeded
+/// before its response was lost. `what` names the operation in warnings.
+pub async fn retry<T, F, Fut>(retries: u32, what: &str, op: F) -> Result<T, Error>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    retry_after(FIRST_RETRY_DELAY, retries, what, op).await
+}
+
+async fn retry_after<T, F, Fut>(
+    mut delay: Duration,
+    retries: u32,
+    what: &str,
+    mut op: F,
+) -> Result<T, Error>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op(attempt).await {
+            Err(e) if attempt < retries && is_transient(&e) => {
+                attempt += 1;
+                warn!(attempt, retries, error = %e, "{what} failed; retrying");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether an Engine API error may clear up on its own: a dropped
+/// connection, a server error while the daemon restarts, or a network that
+/// isn't visible yet right after it was created.
+pub fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::DockerResponseServerError {
+            status_code,
+            message,
+        } => {
+            matches!(status_code, 500 | 502 | 503 | 504)
+                || *status_code == 404
+                    && message.contains("network")
+                    && message.contains("not found")
+        }
+        Error::IOError { .. }
+        | Error::HyperResponseError { .. }
+        | Error::HyperLegacyError { .. }
+        | Error::RequestTimeoutError => true,
+        _ => false,
+    }
+}
+
+/// Whether `error` says the resource an operation creates already exists.
+/// On a retry, that means an earlier attempt went through.
+pub fn already_exists(error: &Error) -> bool {
+    match error {
+        Error::DockerResponseServerError {
+            status_code,
+            message,
+        } => *status_code == 409 || *status_code == 403 && message.contains("already exists"),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    fn server_error(status_code: u16, message: &str) -> Error {
+        Error::DockerResponseServerError {
+            status_code,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn transient_errors_are_recognized() {
+        assert!(is_transient(&server_error(500, "EOF")));
+        assert!(is_transient(&server_error(503, "daemon restarting")));
+        assert!(is_transient(&server_error(
+            404,
+            "network bubble-bot-app not found"
+        )));
+        assert!(is_transient(&Error::RequestTimeoutError));
+        assert!(!is_transient(&server_error(404, "No such image: php:8.3")));
+        assert!(!is_transient(&server_error(409, "Conflict")));
+    }
+
+    #[test]
+    fn retries_until_success_or_limit() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let calls = AtomicU32::new(0);
+        let result = rt.block_on(retry_after(Duration::ZERO, 3, "create", |attempt| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match attempt {
+                    0 | 1 => Err(server_error(500, "EOF")),
+                    _ => Ok(attempt),
+                }
+            }
+        }));
+        assert_eq!(result.unwrap(), 2);
+
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), Error> =
+            rt.block_on(retry_after(Duration::ZERO, 2, "start", |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Err(server_error(502, "bad gateway")) }
+            }));
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Permanent errors aren't retried
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), Error> =
+            rt.block_on(retry_after(Duration::ZERO, 3, "create", |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Err(server_error(404, "No such image: php:8.3")) }
+            }));
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use bollard::Docker;
+use bollard::volume::ListVolumesOptions;
+use tracing::info;
+
+use super::backend::ContainerBackend;
+
+/// Prefix of every named volume bubble-bot creates.
+pub const VOLUME_PREFIX: &str = "bubble-bot-";
+
+/// Image whose `tar` archives and unpacks volumes.
+const ARCHIVE_IMAGE: &str = "alpine:3";
+
+/// Unpacks a tarball from stdin into a staging directory in the volume, and
+/// only once `tar` succeeds replaces the volume's contents with it. A
+/// truncated or corrupt archive leaves the data as it was.
+const RESTORE_SCRIPT: &str = "set -e
+staging=/data/.bubble-bot-restore
+rm -rf \"$staging\"
+mkdir \"$staging\"
+if ! tar xzf - -C \"$staging\"; then rm -rf \"$staging\"; exit 1; fi
+find /data -mindepth 1 -maxdepth 1 ! -name .bubble-bot-restore -exec rm -rf {} +
+find \"$staging\" -mindepth 1 -maxdepth 1 -exec mv {} /data/ \\;
+rmdir \"$staging\"";
+
+/// A bubble-bot named volume, with the project and contents read back from
+/// its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeInfo {
+    pub name: String,
+    pub project: Option<String>,
+    /// What the volume holds, e.g. `mysql data` or `mount node_modules`.
+    pub contents: String,
+}
+
+impl VolumeInfo {
+    /// Parses `bubble-bot-<project>-<service>-data`,
+    /// `bubble-bot-<project>-mount-<path>`, `bubble-bot-<project>-history`,
+    /// and `bubble-bot-<project>-claude-sessions`.
+    pub fn parse(name: &str) -> Self {
+        let rest = name.strip_prefix(VOLUME_PREFIX).unwrap_or(name);
+        let (project, contents) = if let Some(project) = rest.strip_suffix("-history") {
+            (Some(project), "shell history".to_string())
+        } else if let Some(project) = rest.strip_suffix("-claude-sessions") {
+            (Some(project), "claude sessions".to_string())
+        } else if let Some((project, path)) = rest.split_once("-mount-") {
+            (Some(project), format!("mount {path}"))
+        } else if let Some((project, service)) = rest
+            .strip_suffix("-data")
+            .and_then(|rest| rest.rsplit_once('-'))
+        {
+            (Some(project), format!("{service} data"))
+        } else {
+            (None, "-".to_string())
+        };
+        Self {
+            name: name.to_string(),
+            project: project.map(str::to_string),
+            contents,
+        }
+    }
+}
+
+/// Lists and removes bubble-bot's named volumes.
+pub struct VolumeManager<B = Docker> {
+    docker: B,
+}
+
+impl<B: ContainerBackend> VolumeManager<B> {
+    pub fn new(docker: B) -> Self {
+        Self { docker }
+    }
+
+    /// All `bubble-bot-*` volumes, sorted by name.
+    pub async fn list(&self) -> Result<Vec<VolumeInfo>> {
+        let filters = HashMap::from([("name".to_string(), vec![VOLUME_PREFIX.to_string()])]);
+        let response = self
+            .docker
+            .list_volumes(Some(ListVolumesOptions { filters }))
+            .await
+            .context("failed to list volumes")?;
+
+        let mut volumes: Vec<VolumeInfo> = response
+            .volumes
+            .unwrap_or_default()
+            .iter()
+            .filter(|volume| volume.name.starts_with(VOLUME_PREFIX))
+            .map(|volume| VolumeInfo::parse(&volume.name))
+            .collect();
+        volumes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(volumes)
+    }
+
+    /// Whether the volume exists.
+    pub async fn exists(&self, name: &str) -> Result<bool> {
+        Ok(self.list().await?.iter().any(|volume| volume.name == name))
+    }
+
+    /// Pairs each of `volumes` that doesn't exist yet with its
+    /// [`legacy_name`], when that one does, as `(legacy, volume)`: data kept
+    /// before volumes were named by project ID, to be copied over.
+    pub async fn legacy_sources(
+        &self,
+        volumes: &[String],
+        project_id: &str,
+        project_name: &str,
+    ) -> Result<Vec<(String, String)>> {
+        let existing: Vec<String> = self.list().await?.into_iter().map(|v| v.name).collect();
+        Ok(volumes
+            .iter()
+            .filter(|volume| !existing.contains(volume))
+            .filter_map(|volume| {
+                let legacy = legacy_name(volume, project_id, project_name)?;
+                existing.contains(&legacy).then(|| (legacy, volume.clone()))
+            })
+            .collect())
+    }
+
+    /// Removes a bubble-bot volume. Other volumes are refused, so a typo
+    /// can't delete unrelated data.
+    pub async fn remove(&self, name: &str) -> Result<()> {
+        if !name.starts_with(VOLUME_PREFIX) {
+            bail!("{name} is not a bubble-bot volume (they start with `{VOLUME_PREFIX}`)");
+        }
+        self.docker
+            .remove_volume(name, None)
+            .await
+            .with_context(|| format!("failed to remove volume {name}"))?;
+        info!(volume = name, "volume removed");
+        Ok(())
+    }
+}
+
+/// What `volume` (`bubble-bot-<project ID>-…`) was named before volumes
+/// carried the project path's hash: `bubble-bot-<project name>-…`.
+pub fn legacy_name(volume: &str, project_id: &str, project_name: &str) -> Option<String> {
+    let rest = volume.strip_prefix(&format!("{VOLUME_PREFIX}{project_id}-"))?;
+    (project_id != project_name).then(|| format!("{VOLUME_PREFIX}{project_name}-{rest}"))
+}
+
+/// Formats volumes as an aligned table for `bubble-bot volumes list`.
+pub fn volumes_table(volumes: &[VolumeInfo]) -> String {
+    let mut rows = vec![[
+        "VOLUME".to_string(),
+        "PROJECT".to_string(),
+        "CONTENTS".to_string(),
+    ]];
+    for volume in volumes {
+        rows.push([
+            volume.name.clone(),
+            volume.project.clone().unwrap_or_else(|| "-".to_string()),
+            volume.contents.clone(),
+        ]);
+    }
+
+    let mut widths = [0; 3];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// `docker` arguments that write a gzipped tarball of the volume to stdout.
+pub fn backup_args(volume: &str) -> Vec<String> {
+    vec![
+        "run".to_string(),
+        "--rm".to_string(),
+        "-v".to_string(),
+        format!("{volume}:/data:ro"),
+        ARCHIVE_IMAGE.to_string(),
+        "tar".to_string(),
+        "czf".to_string(),
+        "-".to_string(),
+        "-C".to_string(),
+        "/data".to_string(),
+        ".".to_string(),
+    ]
+}
+
+/// `docker` arguments that replace the volume's contents (creating it if
+/// needed) with a tarball from stdin (see [`RESTORE_SCRIPT`]). `tar` runs as
+/// root, so file owners (e.g. the `mysql` user) are kept.
+pub fn restore_args(volume: &str) -> Vec<String> {
+    vec![
+        "run".to_string(),
+        "--rm".to_string(),
+        "-i".to_string(),
+        "-v".to_string(),
+        format!("{volume}:/data"),
+        ARCHIVE_IMAGE.to_string(),
+        "sh".to_string(),
+        "-c".to_string(),
+        RESTORE_SCRIPT.to_string(),
+    ]
+}
+
+/// `docker` arguments that copy everything in `from` into `to`, keeping
+/// owners and permissions.
+pub fn copy_args(from: &str, to: &str) -> Vec<String> {
+    vec![
+        "run".to_string(),
+        "--rm".to_string(),
+        "-v".to_string(),
+        format!("{from}:/from:ro"),
+        "-v".to_string(),
+        format!("{to}:/to"),
+        ARCHIVE_IMAGE.to_string(),
+        "cp".to_string(),
+        "-a".to_string(),
+        "/from/.".to_string(),
+        "/to/".to_string(),
+    ]
+}
+
+/// Copies the volume `from` into `to`, creating it. A failed copy removes
+/// `to`, so the next attempt starts over rather than finding it half full.
+pub fn copy(from: &str, to: &str) -> Result<()> {
+    let output = Command::new("docker")
+        .args(copy_args(from, to))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .context("failed to spawn docker run")?;
+    if !output.status.success() {
+        let _ = Command::new("docker")
+            .args(["volume", "rm", "-f", to])
+            .output();
+        bail!(
+            "failed to copy {from} into {to}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    info!(from, to, "volume copied");
+    Ok(())
+}
+
+/// Archives the volume into `file` (a `.tar.gz`). A failed backup removes
+/// the partial file.
+pub fn backup(volume: &str, file: &Path) -> Result<()> {
+    let out = File::create(file).with_context(|| format!("failed to create {}", file.display()))?;
+    let output = Command::new("docker")
+        .args(backup_args(volume))
+        .stdin(Stdio::null())
+        .stdout(out)
+        .stderr(Stdio::piped())
+        .output()
+        .context("failed to spawn docker run")?;
+    if !output.status.success() {
+        let _ = std::fs::remove_file(file);
+        bail!(
+            "failed to back up {volume}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    info!(volume, file = %file.display(), "volume backed up");
+    Ok(())
+}
+
+/// Replaces the volume's contents with the tarball `file`.
+pub fn restore(volume: &str, file: &Path) -> Result<()> {
+    let input = File::open(file).with_context(|| format!("failed to open {}", file.display()))?;
+    let output = Command::new("docker")
+        .args(restore_args(volume))
+        .stdin(input)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .context("failed to spawn docker run")?;
+    if !output.status.success() {
+        bail!(
+            "failed to restore {volume}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    info!(volume, file = %file.display(), "volume restored");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::mock::MockBackend;
+
+    #[test]
+    fn volume_names_are_parsed() {
+        assert_eq!(
+            VolumeInfo::parse("bubble-bot-my-app-mysql-data"),
+            VolumeInfo {
+                name: "bubble-bot-my-app-mysql-data".to_string(),
+                project: Some("my-app".to_string()),
+                contents: "mysql data".to_string(),
+            }
+        );
+        let mount = VolumeInfo::parse("bubble-bot-app-mount-node_modules");
+        assert_eq!(mount.project.as_deref(), Some("app"));
+        assert_eq!(mount.contents, "mount node_modules");
+        let history = VolumeInfo::parse("bubble-bot-app-history");
+        assert_eq!(history.project.as_deref(), Some("app"));
+        assert_eq!(history.contents, "shell history");
+        let sessions = VolumeInfo::parse("bubble-bot-app-claude-sessions");
+        assert_eq!(sessions.project.as_deref(), Some("app"));
+        assert_eq!(sessions.contents, "claude sessions");
+        assert_eq!(VolumeInfo::parse("bubble-bot-cache").project, None);
+    }
+
+    #[test]
+    fn lists_and_removes_only_bubble_bot_volumes() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_volume("bubble-bot-app-postgres-data");
+        mock.add_volume("bubble-bot-app-history");
+        mock.add_volume("pgdata");
+        let manager = VolumeManager::new(mock.clone());
+
+        rt.block_on(async {
+            let names: Vec<String> = manager
+                .list()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|v| v.name)
+                .collect();
+            assert_eq!(
+                names,
+                vec!["bubble-bot-app-history", "bubble-bot-app-postgres-data"]
+            );
+
+            let err = manager.remove("pgdata").await.unwrap_err().to_string();
+            assert!(err.contains("not a bubble-bot volume"), "{err}");
+            manager.remove("bubble-bot-app-history").await.unwrap();
+            assert!(!manager.exists("bubble-bot-app-history").await.unwrap());
+        });
+        assert_eq!(
+            mock.volumes(),
+            vec!["bubble-bot-app-postgres-data", "pgdata"]
+        );
+    }
+
+    #[test]
+    fn legacy_volumes_are_found_for_missing_ones() {
+        assert_eq!(
+            legacy_name("bubble-bot-app-1a2b3c4d-mysql-data", "app-1a2b3c4d", "app").as_deref(),
+            Some("bubble-bot-app-mysql-data")
+        );
+        assert_eq!(
+            legacy_name("bubble-bot-other-history", "app-1a2b3c4d", "app"),
+            None
+        );
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_volume("bubble-bot-app-mysql-data");
+        mock.add_volume("bubble-bot-app-history");
+        mock.add_volume("bubble-bot-app-1a2b3c4d-history");
+        let manager = VolumeManager::new(mock);
+        let wanted = [
+            "bubble-bot-app-1a2b3c4d-mysql-data".to_string(),
+            "bubble-bot-app-1a2b3c4d-history".to_string(),
+            "bubble-bot-app-1a2b3c4d-mount-vendor".to_string(),
+        ];
+        let sources = rt
+            .block_on(manager.legacy_sources(&wanted, "app-1a2b3c4d", "app"))
+            .unwrap();
+        assert_eq!(
+            sources,
+            vec![(
+                "bubble-bot-app-mysql-data".to_string(),
+                "bubble-bot-app-1a2b3c4d-mysql-data".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn table_aligns_columns() {
+        let table = volumes_table(&[VolumeInfo::parse("bubble-bot-app-mysql-data")]);
+        assert_eq!(
+            table,
+            "VOLUME                     PROJECT  CONTENTS\n\
+             bubble-bot-app-mysql-data  app      mysql data\n"
+        );
+    }
+
+    #[test]
+    fn restore_deletes_data_only_after_unpacking() {
+        let args = restore_args("bubble-bot-app-mysql-data");
+        assert!(args.contains(&"bubble-bot-app-mysql-data:/data".to_string()));
+        let script = args.last().unwrap();
+        let unpack = script.find("tar xzf").unwrap();
+        let delete = script.find("-exec rm -rf").unwrap();
+        assert!(unpack < delete, "{script}");
+    }
+}
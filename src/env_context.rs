@@ -0,0 +1,215 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::services::Service;
+
+/// Claude Code's personal, uncommitted project memory file.
+pub const CONTEXT_FILE: &str = "CLAUDE.local.md";
+
+const BEGIN_MARKER: &str = "<!-- bubble-bot:begin (rewritten every session) -->";
+const END_MARKER: &str = "<!-- bubble-bot:end -->";
+
+/// The environment facts for Claude Code: how to reach each service and
+/// what not to try. Env var names only, never values.
+pub fn render(services: &[Box<dyn Service>], config: &Config) -> String {
+    let mut text = String::from(
+        "## bubble-bot environment\n\n\
+         This project runs in a bubble-bot dev container.\n",
+    );
+
+    if !services.is_empty() {
+        text.push_str(
+            "\nServices run in their own containers and are reached by hostname, \
+             with connection details in env vars:\n\n",
+        );
+        for service in services {
+            let vars: Vec<String> = service
+                .dev_env()
+                .iter()
+                .filter_map(|var| var.split_once('=').map(|(key, _)| format!("`{key}`")))
+                .collect();
+            let mut line = format!("- {}: host `{}`", service.name(), service.name());
+            if !vars.is_empty() {
+                line.push_str(&format!(", {}", vars.join(", ")));
+            }
+            text.push_str(&line);
+            text.push('\n');
+        }
+    }
+
+    text.push_str("\nDon't:\n\n");
+    if !services.is_empty() {
+        text.push_str(
+            "- connect to services on `localhost` or `127.0.0.1`; use the hostnames or env vars above\n",
+        );
+    }
+    text.push_str("- run `docker` or start services yourself; bubble-bot manages the containers\n");
+    let network = &config.network;
+    if !network.allow.is_empty() {
+        text.push_str(&format!(
+            "- expect internet access beyond {}\n",
+            network.allow.join(", ")
+        ));
+    } else if network.is_internal() {
+        text.push_str("- expect internet access; the container has none\n");
+    }
+    if config.laravel.manage_env == Some(true) {
+        text.push_str(
+            "- edit the service settings in `.env`; bubble-bot sets them for the session and restores them afterwards\n",
+        );
+    }
+    text
+}
+
+/// `existing` with the bubble-bot block replaced by `block`, or with it
+/// appended if there is none. The rest of the file is kept as is.
+fn with_block(existing: &str, block: &str) -> String {
+    let wrapped = format!("{BEGIN_MARKER}\n{block}{END_MARKER}\n");
+    if let Some(begin) = existing.find(BEGIN_MARKER) {
+        if let Some(end) = existing[begin..].find(END_MARKER) {
+            let end = begin + end + END_MARKER.len();
+            let rest = existing[end..]
+                .strip_prefix('\n')
+                .unwrap_or(&existing[end..]);
+            return format!("{}{wrapped}{rest}", &existing[..begin]);
+        }
+    }
+    if existing.is_empty() {
+        wrapped
+    } else if existing.ends_with("\n\n") {
+        format!("{existing}{wrapped}")
+    } else if existing.ends_with('\n') {
+        format!("{existing}\n{wrapped}")
+    } else {
+        format!("{existing}\n\n{wrapped}")
+    }
+}
+
+/// Writes `block` into [`CONTEXT_FILE`] in `project_dir`, then makes sure
+/// git ignores the file.
+pub fn write(project_dir: &Path, block: &str) -> Result<()> {
+    let path = project_dir.join(CONTEXT_FILE);
+    let existing = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    std::fs::write(&path, with_block(&existing, block))
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    info!(path = %path.display(), "environment context written");
+
+    if let Err(e) = ignore(project_dir) {
+        warn!(error = %e, "{CONTEXT_FILE} may show up in git status");
+    }
+    Ok(())
+}
+
+/// Adds [`CONTEXT_FILE`] to `.git/info/exclude` unless git already ignores
+/// it. The repository's own `.gitignore` is left alone. Outside a git
+/// repository this does nothing.
+fn ignore(project_dir: &Path) -> Result<()> {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .arg("-C")
+            .arg(project_dir)
+            .args(args)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .context("failed to run git")
+    };
+    // Exit 0: ignored, 1: not ignored, 128: not a repository
+    let check = git(&["check-ignore", "-q", CONTEXT_FILE])?;
+    if check.status.code() != Some(1) {
+        return Ok(());
+    }
+
+    let output = git(&["rev-parse", "--git-path", "info/exclude"])?;
+    let exclude = project_dir.join(String::from_utf8_lossy(&output.stdout).trim());
+    if let Some(dir) = exclude.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut contents = std::fs::read_to_string(&exclude).unwrap_or_default();
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&format!("/{CONTEXT_FILE}\n"));
+    std::fs::write(&exclude, contents)
+        .with_context(|| format!("failed to write {}", exclude.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::collect_services;
+
+    #[test]
+    fn context_names_services_and_restrictions() {
+        let config: Config = toml::from_str(
+            r#"
+            [services]
+            redis = true
+
+            [network]
+            internal = true
+
+            [laravel]
+            manage_env = true
+            "#,
+        )
+        .unwrap();
+        let services = collect_services(&config, "app");
+        let text = render(&services, &config);
+        assert!(
+            text.contains("- redis: host `redis`, `REDIS_HOST`, `REDIS_PORT`, `REDIS_URL`\n"),
+            "{text}"
+        );
+        assert!(text.contains("`localhost`"), "{text}");
+        assert!(text.contains("the container has none"), "{text}");
+        assert!(text.contains("`.env`"), "{text}");
+
+        let bare = render(&[], &Config::default());
+        assert!(!bare.contains("localhost"), "{bare}");
+        assert!(!bare.contains("internet"), "{bare}");
+    }
+
+    #[test]
+    fn block_is_appended_once_and_then_replaced() {
+        let created = with_block("", "one\n");
+        assert_eq!(created, format!("{BEGIN_MARKER}\none\n{END_MARKER}\n"));
+
+        let appended = with_block("# Notes\nmine", "one\n");
+        assert_eq!(
+            appended,
+            format!("# Notes\nmine\n\n{BEGIN_MARKER}\none\n{END_MARKER}\n")
+        );
+
+        let edited = format!("{appended}more notes\n");
+        assert_eq!(
+            with_block(&edited, "two\n"),
+            format!("# Notes\nmine\n\n{BEGIN_MARKER}\ntwo\n{END_MARKER}\nmore notes\n")
+        );
+    }
+
+    #[test]
+    fn context_file_is_excluded_from_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let status = Command::new("git")
+            .args(["init", "-q"])
+            .current_dir(dir.path())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        write(dir.path(), "facts\n").unwrap();
+        write(dir.path(), "facts\n").unwrap();
+        let exclude = std::fs::read_to_string(dir.path().join(".git/info/exclude")).unwrap();
+        assert_eq!(exclude.matches("/CLAUDE.local.md").count(), 1, "{exclude}");
+        let contents = std::fs::read_to_string(dir.path().join(CONTEXT_FILE)).unwrap();
+        assert_eq!(contents.matches("facts").count(), 1, "{contents}");
+    }
+}
@@ -0,0 +1,73 @@
+//! Failure categories with their own exit codes, so scripts wrapping
+//! bubble-bot can branch on why it failed instead of matching messages.
+//!
+//! Errors are still `anyhow::Error`s: a categorized failure is an [`Error`]
+//! somewhere in the chain, and [`exit_code`] finds it under any context
+//! added on the way up. Uncategorized failures exit with 1.
+
+/// Exit code of a failure that isn't one of the [`Error`] categories.
+pub const GENERIC_EXIT_CODE: i32 = 1;
+
+/// A categorized failure. Each variant wraps the underlying error and
+/// displays as it, so categorizing an error doesn't change its message.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The config files couldn't be read, parsed, or validated.
+    #[error(transparent)]
+    Config(anyhow::Error),
+    /// The Docker daemon or the `docker` CLI isn't available.
+    #[error(transparent)]
+    Docker(anyhow::Error),
+    /// The image build failed or timed out.
+    #[error(transparent)]
+    Build(anyhow::Error),
+    /// A service container didn't pass its readiness check in time.
+    #[error(transparent)]
+    ServiceTimeout(anyhow::Error),
+    /// No credentials for a command that can't log in interactively.
+    #[error(transparent)]
+    AuthMissing(anyhow::Error),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Config(_) => 80,
+            Error::Docker(_) => 81,
+            Error::Build(_) => 82,
+            Error::ServiceTimeout(_) => 83,
+            Error::AuthMissing(_) => 84,
+        }
+    }
+}
+
+/// The exit code for `err`: its first [`Error`] category, or
+/// [`GENERIC_EXIT_CODE`].
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    err.chain()
+        .find_map(|e| e.downcast_ref::<Error>())
+        .map_or(GENERIC_EXIT_CODE, Error::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context, anyhow};
+
+    #[test]
+    fn categories_keep_the_message() {
+        let err: anyhow::Error = Error::Build(anyhow!("step 3 failed")).into();
+        assert_eq!(err.to_string(), "step 3 failed");
+        assert_eq!(exit_code(&err), 82);
+    }
+
+    #[test]
+    fn exit_code_looks_through_context() {
+        let err: Result<(), _> = Err(Error::ServiceTimeout(anyhow!("mysql not ready")));
+        let err = err.context("session failed").unwrap_err();
+        assert_eq!(format!("{err:#}"), "session failed: mysql not ready");
+        assert_eq!(exit_code(&err), 83);
+
+        assert_eq!(exit_code(&anyhow!("something else")), GENERIC_EXIT_CODE);
+    }
+}
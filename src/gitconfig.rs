@@ -0,0 +1,262 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use tracing::{debug, info};
+
+/// Credential helpers that work inside the container; anything else
+/// (`osxkeychain`, `manager`, `libsecret`, host binaries) is dropped.
+const PORTABLE_HELPERS: &[&str] = &["store", "cache"];
+
+/// Keys holding host paths, which don't exist in the container.
+const PATH_KEYS: &[&str] = &[
+    "core.excludesfile",
+    "core.attributesfile",
+    "core.hookspath",
+    "commit.template",
+    "init.templatedir",
+];
+
+/// The host's global git config as it applies to `project_dir`, flattened
+/// into `key`/`value` pairs: `include` and `includeIf` are resolved by git
+/// itself (on the host, where their paths exist), so the result doesn't
+/// depend on files the container can't see. `None` without a global config.
+pub fn effective_global(project_dir: &Path) -> Option<Vec<(String, String)>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_dir)
+        .args(["config", "--global", "--includes", "--list", "-z"])
+        .stdin(Stdio::null())
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            Some(parse_list(&String::from_utf8_lossy(&output.stdout)))
+        }
+        Ok(output) => {
+            debug!(
+                stderr = %String::from_utf8_lossy(&output.stderr).trim(),
+                "no global git config"
+            );
+            None
+        }
+        Err(e) => {
+            debug!(error = %e, "git not available on the host");
+            None
+        }
+    }
+}
+
+/// The sanitized `~/.gitconfig` to write into the container, or `None`
+/// without a host global config.
+pub fn for_container(project_dir: &Path) -> Option<String> {
+    let (kept, changed) = sanitize(effective_global(project_dir)?);
+    if !changed.is_empty() {
+        info!(keys = ?changed, "gitconfig settings dropped or overridden for the container");
+    }
+    Some(render(&kept))
+}
+
+/// Parses `git config --list -z` output: NUL-terminated entries, each a key
+/// and a newline-separated value. A key without a value is boolean true.
+fn parse_list(raw: &str) -> Vec<(String, String)> {
+    raw.split('\0')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('\n') {
+            Some((key, value)) => (key.to_string(), value.to_string()),
+            None => (entry.to_string(), "true".to_string()),
+        })
+        .collect()
+}
+
+/// Drops or overrides settings that break inside the container: host-only
+/// credential helpers, GPG programs and signing (the host's keys aren't
+/// reachable), and host paths. Returns the kept entries and the keys that
+/// were changed.
+pub fn sanitize(entries: Vec<(String, String)>) -> (Vec<(String, String)>, Vec<String>) {
+    let mut kept = Vec::new();
+    let mut changed: Vec<String> = Vec::new();
+    for (key, value) in entries {
+        let (section, name) = key.split_once('.').unwrap_or((&key, ""));
+        let name = name.rsplit('.').next().unwrap_or(name);
+        let drop = match (section, name) {
+            ("include" | "includeif", _) => true,
+            ("credential", "helper") => {
+                let helper = value.split_whitespace().next().unwrap_or("");
+                // An empty helper resets the list and is harmless
+                !value.is_empty() && !PORTABLE_HELPERS.contains(&helper)
+            }
+            ("gpg", "program") => true,
+            _ => PATH_KEYS.contains(&key.as_str()),
+        };
+        if drop {
+            if !changed.contains(&key) {
+                changed.push(key);
+            }
+            continue;
+        }
+
+        let signing = matches!(key.as_str(), "commit.gpgsign" | "tag.gpgsign");
+        if signing && value != "false" {
+            changed.push(key.clone());
+            kept.push((key, "false".to_string()));
+            continue;
+        }
+        kept.push((key, value));
+    }
+    (kept, changed)
+}
+
+/// Renders entries as a git config file, grouping keys by section in
+/// first-seen order. Repeated keys (multi-valued settings) stay repeated.
+pub fn render(entries: &[(String, String)]) -> String {
+    let mut sections: Vec<(String, Vec<String>)> = Vec::new();
+    for (key, value) in entries {
+        let Some((head, name)) = key.rsplit_once('.') else {
+            continue;
+        };
+        let header = match head.split_once('.') {
+            Some((section, subsection)) => format!(
+                "[{section} \"{}\"]",
+                subsection.replace('\\', "\\\\").replace('"', "\\\"")
+            ),
+            None => format!("[{head}]"),
+        };
+        let line = format!("\t{name} = {}", quote(value));
+        match sections.iter_mut().find(|(h, _)| *h == header) {
+            Some((_, lines)) => lines.push(line),
+            None => sections.push((header, vec![line])),
+        }
+    }
+
+    let mut out = String::new();
+    for (header, lines) in sections {
+        out.push_str(&header);
+        out.push('\n');
+        for line in lines {
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Quotes a value so git reads it back verbatim.
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_list_splits_keys_and_values() {
+        let raw = "user.name\nAda Lovelace\0core.bare\0alias.lg\nlog --graph\nline two\0";
+        assert_eq!(
+            parse_list(raw),
+            pairs(&[
+                ("user.name", "Ada Lovelace"),
+                ("core.bare", "true"),
+                ("alias.lg", "log --graph\nline two"),
+            ])
+        );
+    }
+
+    #[test]
+    fn sanitize_drops_host_only_settings() {
+        let (kept, changed) = sanitize(pairs(&[
+            ("user.email", "ada@example.com"),
+            ("credential.helper", "osxkeychain"),
+            (
+                "credential.https://github.com.helper",
+                "!/opt/homebrew/bin/gh auth git-credential",
+            ),
+            ("credential.helper", "cache --timeout=3600"),
+            ("gpg.program", "/opt/homebrew/bin/gpg"),
+            (
+                "gpg.ssh.program",
+                "/Applications/1Password.app/Contents/MacOS/op-ssh-sign",
+            ),
+            ("commit.gpgsign", "true"),
+            ("includeif.gitdir:~/work/.path", "~/.gitconfig-work"),
+            ("core.excludesfile", "/Users/ada/.gitignore_global"),
+        ]));
+        assert_eq!(
+            kept,
+            pairs(&[
+                ("user.email", "ada@example.com"),
+                ("credential.helper", "cache --timeout=3600"),
+                ("commit.gpgsign", "false"),
+            ])
+        );
+        assert_eq!(
+            changed,
+            vec![
+                "credential.helper",
+                "credential.https://github.com.helper",
+                "gpg.program",
+                "gpg.ssh.program",
+                "commit.gpgsign",
+                "includeif.gitdir:~/work/.path",
+                "core.excludesfile",
+            ]
+        );
+    }
+
+    #[test]
+    fn render_groups_sections_and_quotes_values() {
+        let rendered = render(&pairs(&[
+            ("user.name", "Ada \"The Countess\""),
+            ("url.git@github.com:.insteadof", "https://github.com/"),
+            ("user.email", "ada@example.com"),
+            ("remote.origin.fetch", "+refs/heads/*"),
+            ("remote.origin.fetch", "+refs/tags/*"),
+        ]));
+        assert_eq!(
+            rendered,
+            "[user]\n\
+             \tname = \"Ada \\\"The Countess\\\"\"\n\
+             \temail = \"ada@example.com\"\n\
+             [url \"git@github.com:\"]\n\
+             \tinsteadof = \"https://github.com/\"\n\
+             [remote \"origin\"]\n\
+             \tfetch = \"+refs/heads/*\"\n\
+             \tfetch = \"+refs/tags/*\"\n"
+        );
+    }
+
+    #[test]
+    fn rendered_config_reads_back_through_git() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config");
+        let entries = pairs(&[
+            ("user.name", "Ada \"The Countess\""),
+            ("alias.lg", "log --graph\t--oneline"),
+            ("url.git@github.com:.insteadof", "https://github.com/"),
+        ]);
+        std::fs::write(&path, render(&entries)).unwrap();
+
+        let output = Command::new("git")
+            .args(["config", "--file"])
+            .arg(&path)
+            .args(["--list", "-z"])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(
+            parse_list(&String::from_utf8_lossy(&output.stdout)),
+            entries
+        );
+    }
+}
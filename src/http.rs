@@ -0,0 +1,36 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+
+/// POSTs the JSON `body` to `url` with `curl`, giving up after `timeout`.
+/// The body goes over stdin, so it never shows up in the process list; a
+/// failure carries curl's error message.
+pub fn post_json(url: &str, body: &str, timeout: Duration) -> Result<()> {
+    let mut child = Command::new("curl")
+        .args(["-fsS", "-X", "POST", "-m"])
+        .arg(timeout.as_secs().max(1).to_string())
+        .args([
+            "-H",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+        ])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run curl")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body.as_bytes())?;
+    }
+    let output = child
+        .wait_with_output()
+        .context("failed to wait for curl")?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
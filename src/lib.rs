@@ -0,0 +1,45 @@
+//! Ephemeral Docker dev containers: config resolution, Dockerfile rendering,
+//! image builds, service containers, and the session lifecycle behind the
+//! `bubble-bot` CLI, which is a thin wrapper over this crate.
+//!
+//! - [`config`]: layered config loading and merging ([`config::Config`])
+//! - [`templates`]: Dockerfile rendering ([`templates::TemplateRenderer`])
+//! - [`docker`]: image builds, containers, and networks
+//!   ([`docker::images::ImageBuilder`], [`docker::containers::ContainerManager`])
+//! - [`services`]: the `Service` registry ([`services::collect_services`])
+//! - [`session`]: a full session ([`session::run`])
+//! - [`error`]: failure categories and their exit codes
+
+pub mod artifacts;
+pub mod auth;
+pub mod branch;
+pub mod chief;
+pub mod ci;
+pub mod cli;
+pub mod config;
+pub mod docker;
+pub mod env_context;
+pub mod error;
+pub mod gitconfig;
+pub mod hooks;
+pub mod http;
+pub mod laravel;
+pub mod lock;
+pub mod logging;
+pub mod motd;
+pub mod notify;
+pub mod otel;
+pub mod output;
+pub mod plan;
+pub mod proxy;
+pub mod random;
+pub mod redact;
+pub mod runtime;
+pub mod services;
+pub mod session;
+pub mod sessions;
+pub mod shell;
+pub mod telemetry;
+pub mod templates;
+pub mod update;
+pub mod workers;
@@ -0,0 +1,96 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+/// An exclusive lock on a project, held for the lifetime of a session so two
+/// sessions can't remove each other's containers. The kernel releases it when
+/// the process exits, however it exits.
+#[derive(Debug)]
+pub struct ProjectLock {
+    _file: File,
+}
+
+impl ProjectLock {
+    /// Takes the lock for `project_dir`, failing with the PID of the current
+    /// holder if another session has it.
+    pub fn acquire(project_dir: &Path) -> Result<Self> {
+        let path = lock_path(project_dir);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("failed to open lock file {}", path.display()))?;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let error = std::io::Error::last_os_error();
+            if error.raw_os_error() != Some(libc::EWOULDBLOCK) {
+                return Err(error).with_context(|| format!("failed to lock {}", path.display()));
+            }
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            let holder = match holder.trim() {
+                "" => String::new(),
+                pid => format!(" (pid {pid})"),
+            };
+            bail!(
+                "another bubble-bot session{holder} is already running for {}; exit it first",
+                project_dir.display()
+            );
+        }
+
+        // Record the holder for the error above
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+        info!(path = %path.display(), "project lock acquired");
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// Lock file for a project: one per project path, in the temp directory.
+fn lock_path(project_dir: &Path) -> PathBuf {
+    let digest = Sha256::digest(project_dir.to_string_lossy().as_bytes());
+    let hex: String = digest.iter().take(6).map(|b| format!("{b:02x}")).collect();
+    std::env::temp_dir().join(format!("bubble-bot-{hex}.lock"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_lock_on_a_project_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let _lock = ProjectLock::acquire(dir.path()).unwrap();
+
+        let err = ProjectLock::acquire(dir.path()).unwrap_err().to_string();
+        assert!(err.contains("already running"), "{err}");
+        assert!(
+            err.contains(&format!("pid {}", std::process::id())),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn lock_is_released_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        drop(ProjectLock::acquire(dir.path()).unwrap());
+        assert!(ProjectLock::acquire(dir.path()).is_ok());
+    }
+
+    #[test]
+    fn projects_lock_independently() {
+        let a = tempfile::tempdir().unwrap();
+        let b = tempfile::tempdir().unwrap();
+        let _lock = ProjectLock::acquire(a.path()).unwrap();
+        assert!(ProjectLock::acquire(b.path()).is_ok());
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt;
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::{FormatTime, SystemTime};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::layer::{Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+
+use crate::redact::Redacting;
+use crate::{otel, output};
+
+/// How log output is formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Colored, human-oriented output (interactive use).
+    Pretty,
+    /// Lines on stderr at `info`, without spinners (the `run` subcommand).
+    Plain,
+    /// One JSON object per line on stderr at `info` (detected CI).
+    Json,
+}
+
+/// Installs the global tracing subscriber. `RUST_LOG` overrides the level in
+/// every format. Registered secrets are masked in every format (see
+/// [`crate::redact`]). Pretty and plain lines are colored as decided by
+/// [`output::set_color`]. With `otel_endpoint`, spans are also recorded for
+/// [`otel::flush`] to export, whatever the log level.
+pub fn init(format: LogFormat, otel_endpoint: Option<&str>) {
+    let registry = tracing_subscriber::registry().with(otel_endpoint.map(otel::layer));
+    match format {
+        LogFormat::Pretty => {
+            registry
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_ansi(output::color_stdout())
+                        .with_writer(Redacting(std::io::stdout))
+                        .with_filter(EnvFilter::from_default_env()),
+                )
+                .init();
+        }
+        LogFormat::Plain => {
+            registry
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_ansi(output::color_stderr())
+                        .with_writer(Redacting(std::io::stderr))
+                        .with_filter(info_filter()),
+                )
+                .init();
+        }
+        LogFormat::Json => {
+            registry
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_ansi(false)
+                        .with_writer(Redacting(std::io::stderr))
+                        .event_format(JsonFormat)
+                        .with_filter(info_filter()),
+                )
+                .init();
+        }
+    }
+}
+
+fn info_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| "info".into())
+}
+
+/// Formats each event as a JSON object: `timestamp`, `level`, `target`,
+/// `message`, and the event's fields.
+struct JsonFormat;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut timestamp = String::new();
+        SystemTime.format_time(&mut Writer::new(&mut timestamp))?;
+
+        let metadata = event.metadata();
+        let mut object = Map::new();
+        object.insert("timestamp".to_string(), Value::String(timestamp));
+        object.insert(
+            "level".to_string(),
+            Value::String(metadata.level().to_string()),
+        );
+        object.insert(
+            "target".to_string(),
+            Value::String(metadata.target().to_string()),
+        );
+
+        let mut visitor = JsonVisitor(&mut object);
+        event.record(&mut visitor);
+
+        let line = serde_json::to_string(&Value::Object(object)).map_err(|_| fmt::Error)?;
+        writeln!(writer, "{line}")
+    }
+}
+
+/// Collects event fields into a JSON map.
+struct JsonVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for JsonVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0
+            .insert(field.name().to_string(), Value::String(value.to_string()));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::Bool(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            Value::String(format!("{value:?}")),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// Captures subscriber output for assertions.
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_format_emits_one_object_per_event() {
+        let buffer = Buffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .event_format(JsonFormat)
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(
+                tag = "bubble-bot:abc",
+                cached = true,
+                retries = 2,
+                "image ready"
+            );
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line: Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "image ready");
+        assert_eq!(line["tag"], "bubble-bot:abc");
+        assert_eq!(line["cached"], true);
+        assert_eq!(line["retries"], 2);
+        assert!(line["timestamp"].as_str().is_some_and(|t| !t.is_empty()));
+    }
+}
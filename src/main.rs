@@ -1,140 +1,177 @@
-#![allow(dead_code)]
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
 
-mod auth;
-mod cli;
-mod config;
-mod docker;
-mod hooks;
-mod runtime;
-mod services;
-mod templates;
-
-use std::sync::Arc;
-
-use anyhow::Result;
-use bollard::Docker;
+use anyhow::{Context, Result, anyhow};
 use clap::Parser;
-use tokio::sync::Mutex;
-use tracing::{info, warn};
-
-use auth::{resolve_claude_config, resolve_oauth_token};
-use cli::{Cli, Command};
-use config::Config;
-use docker::clean::Cleaner;
-use docker::containers::{ContainerManager, ContainerOpts, default_container_name};
-use docker::images::ImageBuilder;
-use docker::networks::{NetworkManager, default_network_name};
-use hooks::HookRunner;
-use services::{Service, collect_service_env_vars, collect_services};
-use templates::TemplateRenderer;
-
-/// Tracks all Docker resources that need cleanup on shutdown.
-/// Shared between the main task and signal handler.
-#[derive(Default)]
-struct CleanupState {
-    docker: Option<Docker>,
-    dev_container_id: Option<String>,
-    service_container_ids: Vec<String>,
-    network_name: Option<String>,
-}
-
-impl CleanupState {
-    /// Performs cleanup of all tracked Docker resources.
-    /// Safe to call multiple times — resources are cleared after cleanup.
-    async fn cleanup(&mut self) {
-        let Some(docker) = self.docker.take() else {
-            return;
-        };
-
-        let container_mgr = ContainerManager::new(docker.clone());
-        let network_mgr = NetworkManager::new(docker);
-
-        // Stop and remove dev container
-        if let Some(id) = self.dev_container_id.take() {
-            if let Err(e) = container_mgr.stop_and_remove(&id).await {
-                warn!(error = %e, "failed to clean up dev container");
-            }
-        }
+use tracing::{Instrument, info_span};
 
-        // Stop and remove service containers
-        for id in self.service_container_ids.drain(..) {
-            if let Err(e) = container_mgr.stop_and_remove(&id).await {
-                warn!(error = %e, "failed to clean up service container");
-            }
-        }
+use bollard::Docker;
+use bubble_bot::cli::{Cli, Command, ConfigAction, ExportAction, ImportAction, VolumesAction};
+use bubble_bot::config::{self, Config, PullPolicy};
+use bubble_bot::docker::archive;
+use bubble_bot::docker::clean::Cleaner;
+use bubble_bot::docker::connect;
+use bubble_bot::docker::containers::{ContainerManager, ExecOpts, resolve_groups};
+use bubble_bot::docker::images::{self, ImageBuilder};
+use bubble_bot::docker::platform as docker_platform;
+use bubble_bot::docker::registry::RegistryAuth;
+use bubble_bot::docker::volumes::{self, VolumeManager};
+use bubble_bot::error::{self, Error};
+use bubble_bot::logging::{self, LogFormat};
+use bubble_bot::plan::{
+    self, ContainerPlan, Desired, ImagePlan, NetworkPlan, Plan, PlanFormat, ServicePlan,
+};
+use bubble_bot::proxy::ProxySettings;
+use bubble_bot::services::info::{ServiceInfo, info_table};
+use bubble_bot::services::{self, collect_service_env_vars, collect_services};
+use bubble_bot::session::{self, Launch, SessionOpts, project_name};
+use bubble_bot::sessions::{self, Registry};
+use bubble_bot::telemetry::{self, SessionReport};
+use bubble_bot::templates::TemplateRenderer;
+use bubble_bot::{chief, ci, gitconfig, laravel, notify, otel, output, redact, shell, update};
 
-        // Remove network
-        if let Some(name) = self.network_name.take() {
-            if let Err(e) = network_mgr.remove_network(&name).await {
-                warn!(error = %e, "failed to clean up network");
-            }
-        }
+#[tokio::main]
+async fn main() {
+    if let Err(e) = run().await {
+        eprintln!("Error: {e:?}");
+        std::process::exit(error::exit_code(&e));
     }
 }
 
-/// Spawns a background task that listens for SIGINT/SIGTERM and performs
-/// cleanup of all tracked Docker resources. Returns a `JoinHandle` that
-/// should be aborted once the normal cleanup path completes.
-fn spawn_signal_handler(state: Arc<Mutex<CleanupState>>) -> tokio::task::JoinHandle<()> {
-    tokio::spawn(async move {
-        let ctrl_c = tokio::signal::ctrl_c();
-        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
-            .expect("failed to install SIGTERM handler");
-
-        tokio::select! {
-            _ = ctrl_c => {
-                warn!("received SIGINT — cleaning up containers");
-            }
-            _ = sigterm.recv() => {
-                warn!("received SIGTERM — cleaning up containers");
-            }
-        }
+async fn run() -> Result<()> {
+    let cli = Cli::parse();
+    let command = cli.command();
 
-        state.lock().await.cleanup().await;
-        std::process::exit(130); // 128 + 2 (SIGINT convention)
-    })
+    // CI gets JSON logs; `run` gets plain lines. Both drop spinners.
+    let run = matches!(command, Command::Run { .. });
+    let log_format = if ci::detect() {
+        LogFormat::Json
+    } else if run {
+        LogFormat::Plain
+    } else {
+        LogFormat::Pretty
+    };
+    output::set_plain(log_format != LogFormat::Pretty);
+    redact::set_show_secrets(cli.show_secrets);
+    output::set_color(cli.color);
+    logging::init(log_format, cli.otel_endpoint.as_deref());
+
+    // One trace per invocation, exported before the process exits
+    let span = info_span!("bubble-bot", command = command.name());
+    let result = dispatch(&cli, command).instrument(span).await;
+    otel::flush();
+    result
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+async fn dispatch(cli: &Cli, command: Command) -> Result<()> {
+    // Needs no config, so a broken one can't block updating
+    if let Command::SelfUpdate { check } = command {
+        return run_self_update(check || cli.container.dry_run);
+    }
 
-    let cli = Cli::parse();
-    let config = Config::load(&cli)?;
-    let command = cli.command();
+    if let Some(dir) = &cli.project_dir {
+        config::set_project_dir(dir).map_err(Error::Config)?;
+    }
+    // Edits the files without loading them, so a broken config can be fixed
+    if let Command::Config {
+        action: Some(action),
+        ..
+    } = &command
+    {
+        if !cli.container.dry_run {
+            return run_config_edit(cli, action);
+        }
+    }
+    // Checks the files before loading them, so a bad value is reported
+    // against its file and key rather than failing the load
+    if let Command::Config { check: true, .. } = &command {
+        if !cli.container.dry_run {
+            return run_config_check(cli);
+        }
+    }
+    let config = info_span!("config_load")
+        .in_scope(|| Config::load(cli))
+        .map_err(Error::Config)?;
 
     if cli.container.dry_run {
-        return run_dry_run(&config, &command);
+        return run_dry_run(&config, &command, cli.container.format.unwrap_or_default());
     }
 
     match command {
-        Command::Shell => run_shell(&cli, &config).await,
-        Command::Claude { args } => run_claude(&cli, &config, &args).await,
-        Command::Chief { args } => run_chief(&cli, &config, &args).await,
-        Command::Exec { cmd } => run_exec(&cli, &config, &cmd).await,
-        Command::Config => run_config(&config),
+        Command::Shell => run_shell(cli, &config).await,
+        Command::Claude {
+            max_duration,
+            output,
+            branch,
+            args,
+        } => run_claude(cli, &config, max_duration, output, branch, &args).await,
+        Command::Chief {
+            max_duration,
+            branch,
+            tasks,
+            results,
+            args,
+        } => {
+            let files = ChiefFiles { tasks, results };
+            run_chief(cli, &config, max_duration, branch, files, &args).await
+        }
+        Command::Exec { workdir, env, cmd } => {
+            let exec = ExecOpts { workdir, env };
+            run_exec(cli, &config, exec, &cmd).await
+        }
+        Command::Run { cmd } => run_ci(cli, &config, &cmd).await,
+        Command::Db { service } => run_db(&config, service.as_deref()).await,
+        Command::Info => run_info(&config, cli.show_secrets).await,
+        Command::Ps => run_ps().await,
+        Command::Config {
+            action: Some(_), ..
+        }
+        | Command::Config { check: true, .. } => unreachable!("handled before loading the config"),
+        Command::Config { origin: true, .. } => run_config_origin(cli),
+        Command::Config { .. } => run_config(&config),
         Command::Build => run_build(&config).await,
+        Command::Plan => run_plan(&config).await,
+        Command::Images => run_images().await,
+        Command::Volumes { action } => run_volumes(&config, action).await,
+        Command::Export { action } => run_export(&config, action).await,
+        Command::Import { action } => run_import(action).await,
         Command::Clean { volumes } => run_clean(volumes).await,
+        Command::SelfUpdate { .. } => unreachable!("handled before loading the config"),
     }
 }
 
-/// Returns the project directory name used for naming containers and volumes.
-fn project_name() -> String {
-    std::env::current_dir()
-        .ok()
-        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
-        .unwrap_or_else(|| "project".to_string())
-}
-
 /// Prints a dry-run summary: resolved config, generated Dockerfile, and Docker
 /// commands that would be executed — without creating any containers, networks,
 /// or images.
-fn run_dry_run(config: &Config, command: &Command) -> Result<()> {
+fn run_dry_run(config: &Config, command: &Command, format: PlanFormat) -> Result<()> {
+    let json = format == PlanFormat::Json;
+    if json
+        && !matches!(
+            command,
+            Command::Shell
+                | Command::Claude { .. }
+                | Command::Chief { .. }
+                | Command::Exec { .. }
+                | Command::Run { .. }
+                | Command::Build
+        )
+    {
+        anyhow::bail!("--format json plans sessions: shell, claude, chief, exec, run, and build");
+    }
+    // Text output goes out as the plan is worked out; JSON is printed whole
+    macro_rules! say {
+        ($($arg:tt)*) => {
+            if !json {
+                println!($($arg)*);
+            }
+        };
+    }
+    let mut plan = Plan::default();
+
     // Resolved config
-    let config_output = toml::to_string_pretty(config)?;
-    println!("=== Resolved Config ===\n{config_output}");
+    let config_output = redact::config(config)?;
+    say!("=== Resolved Config ===\n{config_output}");
+    plan.config = serde_json::to_value(toml::from_str::<toml::Value>(&config_output)?)?;
 
     // Determine the exec command and whether Chief layer is needed
     let (exec_cmd, install_chief) = match command {
@@ -142,35 +179,147 @@ fn run_dry_run(config: &Config, command: &Command) -> Result<()> {
             let shell = config.container.shell.as_deref().unwrap_or("bash");
             (format!("docker exec -it <container> {shell}"), false)
         }
-        Command::Claude { args } => {
-            let mut parts = vec![
-                "docker exec -it <container> claude --permission-mode bypassPermissions"
-                    .to_string(),
-            ];
+        Command::Claude { output, args, .. } => {
+            let flags = if output.is_some() { "" } else { " -it" };
+            let mut parts = vec![format!(
+                "docker exec{flags} <container> claude --permission-mode bypassPermissions"
+            )];
             for arg in args {
                 parts.push(arg.clone());
             }
+            if let Some(output) = output {
+                parts.push(format!("> {}", output.display()));
+            }
             (parts.join(" "), false)
         }
-        Command::Chief { args } => {
-            let mut parts = vec!["docker exec -it <container> chief".to_string()];
-            for arg in args {
-                parts.push(arg.clone());
-            }
+        Command::Chief { tasks, args, .. } => {
+            let tasks = tasks.as_deref().map(chief::TaskFile::resolve).transpose()?;
+            let mut parts = vec!["docker exec -it <container>".to_string()];
+            parts.extend(chief::command(tasks.as_ref(), args).iter().map(|arg| {
+                if arg.contains(' ') {
+                    format!("'{arg}'")
+                } else {
+                    arg.clone()
+                }
+            }));
             (parts.join(" "), true)
         }
-        Command::Exec { cmd } => {
+        Command::Exec { workdir, env, cmd } => {
+            let mut parts = vec!["docker exec".to_string()];
+            if let Some(workdir) = workdir {
+                parts.push(format!("-w {workdir}"));
+            }
+            for var in env {
+                parts.push(format!("-e {}", redact::env_var(var)));
+            }
+            parts.push("<container>".to_string());
+            parts.extend(cmd.iter().cloned());
+            (parts.join(" "), false)
+        }
+        Command::Run { cmd } => {
             let mut parts = vec!["docker exec <container>".to_string()];
             for c in cmd {
                 parts.push(c.clone());
             }
             (parts.join(" "), false)
         }
+        Command::Db { service } => {
+            let services = collect_services(config, &config::project_id());
+            let service = services::find_client_service(&services, service.as_deref())?;
+            // Quote the `sh -c` script so the line can be pasted into a shell
+            let client: Vec<String> = service
+                .client_cmd()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|arg| {
+                    if arg.contains(' ') {
+                        format!("{arg:?}")
+                    } else {
+                        arg
+                    }
+                })
+                .collect();
+            println!(
+                "docker exec -it {} {}",
+                service.container_name(&config::project_id()),
+                client.join(" ")
+            );
+            return Ok(());
+        }
+        Command::Info => {
+            println!("(info subcommand — inspects running service containers, no changes)");
+            return Ok(());
+        }
+        Command::Ps => {
+            println!("(ps subcommand — lists registered sessions, pruning stale ones)");
+            return Ok(());
+        }
         Command::Build => ("(build only — no container started)".to_string(), false),
-        Command::Config => {
+        Command::Plan => {
+            println!("(plan subcommand — compares the config with running containers, no changes)");
+            return Ok(());
+        }
+        Command::Config { .. } => {
             println!("(config subcommand — no Docker operations)");
             return Ok(());
         }
+        Command::Images => {
+            println!("(images subcommand — lists bubble-bot:* images, no changes)");
+            return Ok(());
+        }
+        Command::Volumes { action } => {
+            match action {
+                Some(VolumesAction::Backup { service, file }) => {
+                    let services = collect_services(config, &config::project_id());
+                    let (_, volume) = services::data_volume(&services, service)?;
+                    println!(
+                        "docker {} > {}",
+                        volumes::backup_args(&volume).join(" "),
+                        file.display()
+                    );
+                }
+                Some(VolumesAction::Restore { service, file }) => {
+                    let services = collect_services(config, &config::project_id());
+                    let (_, volume) = services::data_volume(&services, service)?;
+                    println!(
+                        "docker {} < {}",
+                        volumes::restore_args(&volume).join(" "),
+                        file.display()
+                    );
+                }
+                Some(VolumesAction::Rm { name }) => println!("docker volume rm {name}"),
+                Some(VolumesAction::List) | None => {
+                    println!("(volumes subcommand — lists bubble-bot-* volumes, no changes)")
+                }
+            }
+            return Ok(());
+        }
+        Command::Export {
+            action:
+                ExportAction::Image {
+                    output,
+                    services,
+                    chief,
+                },
+        } => {
+            let images = export_images(config, *services, *chief)?;
+            let compress = archive::Compression::for_path(output)?
+                .compressor_args()
+                .map(|args| format!(" | {}", args.join(" ")))
+                .unwrap_or_default();
+            println!(
+                "docker save {}{compress} > {}",
+                images.join(" "),
+                output.display()
+            );
+            return Ok(());
+        }
+        Command::Import {
+            action: ImportAction::Image { file },
+        } => {
+            println!("docker load < {}", file.display());
+            return Ok(());
+        }
         Command::Clean { volumes } => {
             println!(
                 "(clean subcommand — would remove bubble-bot:* images and bubble-bot-* networks{})",
@@ -178,141 +327,428 @@ fn run_dry_run(config: &Config, command: &Command) -> Result<()> {
             );
             return Ok(());
         }
+        Command::SelfUpdate { .. } => unreachable!("handled before loading the config"),
     };
 
     // Render Dockerfile
     let renderer = TemplateRenderer::new()?;
     let render_result = renderer.render_with_options(config, install_chief)?;
     let image_tag = ImageBuilder::compute_tag(&render_result.dockerfile);
+    plan.command = exec_cmd.clone();
 
-    println!("=== Generated Dockerfile ===\n{}", render_result.dockerfile);
+    say!("=== Generated Dockerfile ===\n{}", render_result.dockerfile);
 
     // Docker commands
-    let container_name = config
-        .container
-        .name
-        .clone()
-        .unwrap_or_else(default_container_name);
-    let network_name = config
-        .container
-        .network
-        .clone()
-        .unwrap_or_else(default_network_name);
-    let project_dir = std::env::current_dir()?.to_string_lossy().to_string();
+    let container_name = session::container_name(config);
+    let network_name = session::network_name(config);
+    let (workspace_root, workdir) = session::workspace(config, &std::env::current_dir()?)?;
+    let project_dir = workspace_root.to_string_lossy().to_string();
     let uid = unsafe { libc::getuid() };
     let gid = unsafe { libc::getgid() };
 
-    println!("=== Docker Commands ===");
-    println!("Image tag: {image_tag}");
-    println!("docker build -t {image_tag} .");
-    println!("docker network create {network_name}");
+    say!("=== Docker Commands ===");
+    say!("Image tag: {image_tag}");
+    let proxy = ProxySettings::resolve(&config.network);
+    let mut build_args: Vec<_> = proxy.build_args().into_iter().collect();
+    build_args.sort();
+    let mut docker_build = if config.build.buildkit == Some(true) {
+        format!("DOCKER_BUILDKIT=1 docker build --progress=plain -t {image_tag}")
+    } else {
+        format!("docker build -t {image_tag}")
+    };
+    let platform_flag = config
+        .container
+        .platform
+        .as_ref()
+        .map(|platform| format!(" --platform {platform}"))
+        .unwrap_or_default();
+    docker_build.push_str(&platform_flag);
+    if let Some(host) = proxy.extra_host() {
+        docker_build.push_str(&format!(" --add-host {host}"));
+    }
+    for (key, value) in &build_args {
+        docker_build.push_str(&format!(" --build-arg {key}={value}"));
+    }
+    say!("{docker_build} .");
+    plan.image = ImagePlan {
+        tag: image_tag.clone(),
+        dockerfile: render_result.dockerfile.clone(),
+        build_args: build_args
+            .iter()
+            .map(|(key, value)| {
+                let masked = redact::env_var(&format!("{key}={value}"));
+                (key.clone(), masked[key.len() + 1..].to_string())
+            })
+            .collect(),
+        platform: config.container.platform.clone(),
+        buildkit: config.build.buildkit == Some(true),
+    };
 
-    // Service containers
-    let project = project_name();
+    // Service images are pulled before the network and containers exist
+    let project = config::project_id();
     let services = collect_services(config, &project);
+    if config.services.pull_policy == Some(PullPolicy::Always) {
+        for image in services::service_images(&services) {
+            say!("docker pull {image}");
+        }
+    }
+    if config.network.is_internal() {
+        say!("docker network create --internal {network_name}");
+    } else {
+        say!("docker network create {network_name}");
+    }
+    plan.network = NetworkPlan {
+        name: network_name.clone(),
+        internal: config.network.is_internal(),
+    };
+
+    // Service containers
     for service in &services {
         let svc_name = service.container_name(&project);
-        println!(
-            "docker run -d --name {svc_name} --network {network_name} {}",
+        let binds: String = service.binds().iter().map(|b| format!(" -v {b}")).collect();
+        say!(
+            "docker run -d --name {svc_name} --network {network_name}{binds} {}",
             service.image()
         );
+        if service.needs_egress() {
+            say!("docker network connect bridge {svc_name}");
+        }
+        plan.services.push(ServicePlan {
+            name: service.name().to_string(),
+            container: svc_name,
+            image: service.image(),
+            env: service
+                .container_env()
+                .iter()
+                .map(|var| redact::env_var(var))
+                .collect(),
+            volume: service.volume(),
+            binds: service.binds(),
+            readiness: service.readiness(),
+            egress: service.needs_egress(),
+        });
     }
 
     // Dev container
-    let mut docker_run = format!(
-        "docker run -d --name {container_name} --user {uid}:{gid} -v {project_dir}:/workspace --network {network_name}"
+    let mut bind = format!("{project_dir}:/workspace");
+    if let Some(label) = config.mount.selinux_label {
+        bind = label.apply(&bind);
+    }
+    let mut container = ContainerPlan {
+        name: container_name.clone(),
+        user: format!("{uid}:{gid}"),
+        workdir: workdir.to_string(),
+        binds: vec![bind],
+        dns: config.network.dns.clone(),
+        extra_hosts: config
+            .network
+            .extra_hosts
+            .iter()
+            .cloned()
+            .chain(proxy.extra_host())
+            .collect(),
+        tmpfs: config.container.tmpfs.clone(),
+        shm_size: config.container.shm_size.clone(),
+        groups: resolve_groups(&config.container.groups),
+        ..Default::default()
+    };
+    for (volume, target) in config.mount.exclude_volumes(&project)? {
+        container.volumes.push(format!("{volume}:{target}"));
+    }
+    if config.shell.persist_history == Some(true) {
+        container.volumes.push(format!(
+            "{}:{}",
+            shell::history_volume(&project),
+            shell::HISTORY_DIR
+        ));
+    }
+    if config.claude.persist_sessions() {
+        container.volumes.push(format!(
+            "{}:{}",
+            shell::claude_sessions_volume(&project),
+            shell::CLAUDE_SESSIONS_DIR
+        ));
+    }
+    // Extra workspace roots, the task file, host dotfiles, and the dotfiles
+    // checkout, relabeled like the project mount
+    let mut extra_binds = config.workspace.extra_binds()?;
+    if let Command::Chief {
+        tasks: Some(tasks), ..
+    } = command
+    {
+        extra_binds.push(chief::TaskFile::resolve(tasks)?.bind);
+    }
+    if let Some(repo) = &config.shell.dotfiles_repo {
+        let checkout = shell::dotfiles_checkout(repo)?;
+        say!("git clone {repo} {}", checkout.display());
+        extra_binds.push(format!("{}:{}", checkout.display(), shell::DOTFILES_DIR));
+    }
+    let mut dotfiles =
+        shell::dotfile_entries(&config.shell.dotfiles, &config.shell.dotfiles_exclude)?;
+    let write_gitconfig = dotfiles.iter().any(|e| e == ".gitconfig");
+    dotfiles.retain(|e| e != ".gitconfig");
+    if let Some(home) = dirs::home_dir() {
+        extra_binds.extend(shell::dotfile_binds(&home, &dotfiles));
+        if let (Some(true), Some(cache)) = (config.shell.mount_ssh, dirs::cache_dir()) {
+            let copy = shell::sanitized_ssh_config(&home, &cache);
+            let copy_path = copy.as_ref().map(|(path, _)| path.as_path());
+            extra_binds.extend(shell::ssh_binds(&home, copy_path));
+        }
+    }
+    container.binds.extend(
+        extra_binds
+            .into_iter()
+            .map(|bind| match config.mount.selinux_label {
+                Some(label) => label.apply(&bind),
+                None => bind,
+            }),
     );
+    for device in config.container.device_mappings()? {
+        container.devices.push(format!(
+            "{}:{}:{}",
+            device.host, device.container, device.permissions
+        ));
+    }
+    container.env = session::bubble_env(&project_name(), "<session>");
 
     // Service env vars
-    let service_envs = collect_service_env_vars(&services);
-    for env in &service_envs {
+    let mut service_envs = collect_service_env_vars(&services)?;
+    service_envs.extend(session::dev_proxy_env(config, &proxy, &services));
+    container
+        .env
+        .extend(service_envs.iter().map(|env| redact::env_var(env)));
+
+    let mut docker_run = format!(
+        "docker run -d --name {container_name}{platform_flag} --user {} -v {} -w {workdir} --network {network_name}",
+        container.user, container.binds[0]
+    );
+    for dns in &container.dns {
+        docker_run.push_str(&format!(" --dns {dns}"));
+    }
+    for host in &container.extra_hosts {
+        docker_run.push_str(&format!(" --add-host {host}"));
+    }
+    for volume in &container.volumes {
+        docker_run.push_str(&format!(" -v {volume}"));
+    }
+    for bind in &container.binds[1..] {
+        docker_run.push_str(&format!(" -v {bind}"));
+    }
+    for tmpfs in &container.tmpfs {
+        docker_run.push_str(&format!(" --tmpfs {tmpfs}"));
+    }
+    if let Some(size) = &container.shm_size {
+        docker_run.push_str(&format!(" --shm-size {size}"));
+    }
+    for group in &container.groups {
+        docker_run.push_str(&format!(" --group-add {group}"));
+    }
+    for device in &container.devices {
+        docker_run.push_str(&format!(" --device {device}"));
+    }
+    for env in &container.env {
         docker_run.push_str(&format!(" -e {env}"));
     }
+    plan.container = container;
 
     docker_run.push_str(&format!(" {image_tag} sleep infinity"));
-    println!("{docker_run}");
+    say!("{docker_run}");
+
+    // Laravel .env wiring
+    if config.laravel.manage_env == Some(true) {
+        let keys: Vec<String> = laravel::laravel_vars(&service_envs)
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        say!("patch .env ({}), restored on exit", keys.join(", "));
+        plan.env_patch = keys;
+    }
+
+    // Agent branch
+    if let Command::Claude { branch, .. } | Command::Chief { branch, .. } = command {
+        let agent = if install_chief { "chief" } else { "claude" };
+        if let Some(name) = agent_branch(config, agent, branch.clone()) {
+            say!("git checkout -b {name}");
+            plan.branch = Some(name);
+        }
+    }
 
     // Exec command
-    println!("{exec_cmd}");
+    say!("{exec_cmd}");
+    if let Command::Claude {
+        max_duration: Some(budget),
+        ..
+    }
+    | Command::Chief {
+        max_duration: Some(budget),
+        ..
+    } = command
+    {
+        say!(
+            "stopped after {}s (exit code {})",
+            budget.as_secs(),
+            session::TIMEOUT_EXIT_CODE
+        );
+    }
+
+    // Workers
+    if !config.workers.is_empty() {
+        say!("\nworkers:");
+        for worker in &config.workers {
+            say!(
+                "  [{}] docker exec <container> sh -c {:?} (restart: {:?})",
+                worker.display_name(),
+                worker.cmd,
+                worker.restart
+            );
+            plan.workers
+                .push(format!("{}: {}", worker.display_name(), worker.cmd));
+        }
+    }
+
+    // Artifacts
+    if !config.artifacts.is_empty() {
+        say!("\nartifacts (copied at teardown):");
+        for artifact in &config.artifacts {
+            let host_dir = artifact.host_dir(&config::project_dir());
+            say!(
+                "  docker cp <container>:{} {}",
+                artifact.from,
+                host_dir.display()
+            );
+            plan.artifacts
+                .push(format!("{} -> {}", artifact.from, host_dir.display()));
+        }
+    }
+
+    // Dotfiles
+    if write_gitconfig {
+        if let Some(entries) = gitconfig::effective_global(&config::project_dir()) {
+            let (kept, changed) = gitconfig::sanitize(entries);
+            say!(
+                "\nwrite ~/.gitconfig ({} settings from the host)",
+                kept.len()
+            );
+            if !changed.is_empty() {
+                say!("  dropped or overridden: {}", changed.join(", "));
+            }
+        }
+    }
+    if config.shell.dotfiles_repo.is_some() {
+        let script = shell::dotfiles_install_script(config.shell.dotfiles_install.as_deref());
+        say!("\ndotfiles:\n  docker exec <container> sh -c {script:?}");
+    }
 
     // Hooks
     if !config.hooks.post_start.is_empty() {
-        println!("\npost_start hooks:");
+        say!("\npost_start hooks:");
         for hook in &config.hooks.post_start {
-            println!("  docker exec <container> sh -c {hook:?}");
+            say!("  docker exec <container> sh -c {hook:?}");
         }
     }
     if !config.hooks.pre_stop.is_empty() {
-        println!("\npre_stop hooks:");
+        say!("\npre_stop hooks:");
         for hook in &config.hooks.pre_stop {
-            println!("  docker exec <container> sh -c {hook:?}");
+            say!("  docker exec <container> sh -c {hook:?}");
         }
     }
+    plan.post_start = config.hooks.post_start.clone();
+    plan.pre_stop = config.hooks.pre_stop.clone();
 
+    if json {
+        println!("{}", serde_json::to_string_pretty(&plan)?);
+    }
     Ok(())
 }
 
-/// Detects and removes stale containers and networks from crashed previous sessions.
-/// Should be called on startup before creating new resources.
-async fn cleanup_stale_resources(docker: &Docker, container_name: &str) -> Result<()> {
-    let container_mgr = ContainerManager::new(docker.clone());
-    let network_mgr = NetworkManager::new(docker.clone());
-
-    let containers_removed = container_mgr.cleanup_stale(container_name).await?;
-    let networks_removed = network_mgr.cleanup_stale(container_name).await?;
+fn run_config(config: &Config) -> Result<()> {
+    let output = redact::config(config)?;
+    print!("{output}");
+    Ok(())
+}
 
-    if containers_removed > 0 || networks_removed > 0 {
-        info!(
-            containers_removed,
-            networks_removed, "cleaned up stale resources from previous session"
-        );
+/// Applies a `config set`/`config unset` edit to the project or global config.
+fn run_config_edit(cli: &Cli, action: &ConfigAction) -> Result<()> {
+    match action {
+        ConfigAction::Set { key, value, global } => {
+            let path = config::edit_target(*global, cli.config.as_deref())?;
+            config::edit::set(&path, key, value)?;
+            println!("set {key} in {}", path.display());
+        }
+        ConfigAction::Unset { key, global } => {
+            let path = config::edit_target(*global, cli.config.as_deref())?;
+            if config::edit::unset(&path, key)? {
+                println!("unset {key} in {}", path.display());
+            } else {
+                println!("{key} is not set in {}", path.display());
+            }
+        }
     }
-
     Ok(())
 }
 
-/// Starts all configured service containers on the given network.
-/// Returns a list of (service_name, container_id) tuples for cleanup.
-async fn start_services(
-    container_mgr: &ContainerManager,
-    services: &[Box<dyn Service>],
-    network: &str,
-) -> Result<Vec<String>> {
-    let project = project_name();
-    let mut service_ids = Vec::new();
-
-    for service in services {
-        let id = container_mgr
-            .start_service(service.as_ref(), network, &project)
-            .await?;
-        container_mgr.wait_for_ready(&id, service.as_ref(), 30, 2)?;
-        service_ids.push(id);
+/// Prints each resolved config value with the layer that set it.
+fn run_config_origin(cli: &Cli) -> Result<()> {
+    let mut values = config::origin::resolve(cli)?;
+    for resolved in &mut values {
+        resolved.value = redact::config_value(&resolved.key, &resolved.value);
     }
-
-    Ok(service_ids)
+    print!("{}", config::origin::format(&values));
+    Ok(())
 }
 
-fn run_config(config: &Config) -> Result<()> {
-    let output = toml::to_string_pretty(config)?;
-    print!("{output}");
-    Ok(())
+/// Validates the config files and merged config, exiting non-zero if any
+/// problems are found.
+fn run_config_check(cli: &Cli) -> Result<()> {
+    let files = config::config_files(cli.config.as_deref());
+    let issues = config::check::check(&files, || Config::load(cli));
+    if issues.is_empty() {
+        println!("config ok");
+        return Ok(());
+    }
+
+    for issue in &issues {
+        eprintln!("error: {issue}");
+    }
+    Err(Error::Config(anyhow!(
+        "{} problem{} found",
+        issues.len(),
+        if issues.len() == 1 { "" } else { "s" }
+    ))
+    .into())
 }
 
 async fn run_build(config: &Config) -> Result<()> {
-    let docker = Docker::connect_with_local_defaults()
-        .map_err(|e| anyhow::anyhow!("failed to connect to Docker: {e}"))?;
+    let docker = connect::connect().await?;
 
     // Render Dockerfile
     let renderer = TemplateRenderer::new()?;
     let render_result = renderer.render(config)?;
 
     // Force build regardless of cache
-    let image_builder = ImageBuilder::new(docker);
+    let proxy = ProxySettings::resolve(&config.network);
+    let platform = config.container.platform.as_deref();
+    if let Some(platform) = platform {
+        docker_platform::check(&docker, platform).await?;
+    }
+    let buildkit = config.build.buildkit == Some(true);
+    if buildkit {
+        connect::supports(&docker, connect::BUILDKIT_API, "BuildKit (build.buildkit)");
+    }
+    let image_builder = ImageBuilder::new(docker)
+        .with_buildkit(buildkit)
+        .with_timeout(config.build.timeout_duration()?)
+        .with_log(config.build.log_path())
+        .with_labels(session::image_labels(config))
+        .with_extra_host(proxy.extra_host());
     let build_result = image_builder
         .build(
             &render_result.dockerfile,
             &render_result.context_files,
             true,
+            &proxy.build_args(),
+            &RegistryAuth::load(&config.registry),
+            platform,
         )
         .await?;
 
@@ -321,397 +757,504 @@ async fn run_build(config: &Config) -> Result<()> {
     Ok(())
 }
 
-async fn run_clean(remove_volumes: bool) -> Result<()> {
-    let docker = Docker::connect_with_local_defaults()
-        .map_err(|e| anyhow::anyhow!("failed to connect to Docker: {e}"))?;
-
-    let cleaner = Cleaner::new(docker);
-    cleaner.clean(remove_volumes).await
-}
-
-async fn run_chief(cli: &Cli, config: &Config, args: &[String]) -> Result<()> {
-    let docker = Docker::connect_with_local_defaults()
-        .map_err(|e| anyhow::anyhow!("failed to connect to Docker: {e}"))?;
-
-    // Resolve container and network names
-    let container_name = config
-        .container
-        .name
-        .clone()
-        .unwrap_or_else(default_container_name);
-    let network_name = config
-        .container
-        .network
-        .clone()
-        .unwrap_or_else(default_network_name);
-
-    // Detect and clean up stale containers/networks from previous sessions
-    cleanup_stale_resources(&docker, &container_name).await?;
-
-    // Render Dockerfile with Chief installation
-    let renderer = TemplateRenderer::new()?;
-    let render_result = renderer.render_with_options(config, true)?;
-
-    // Build or use cached image
-    let image_builder = ImageBuilder::new(docker.clone());
-    let build_result = image_builder
-        .build(
-            &render_result.dockerfile,
-            &render_result.context_files,
-            cli.container.no_cache,
-        )
-        .await?;
-    info!(tag = %build_result.tag, cached = build_result.cached, "image ready");
-
-    // Get project directory
-    let project_dir = std::env::current_dir()?.to_string_lossy().to_string();
-
-    // Resolve auth token and claude config (written to container after start, not via env)
-    let oauth_token = resolve_oauth_token()?;
-    let claude_config = resolve_claude_config()?;
-
-    // Collect service env vars for the dev container
-    let mut env_vars = Vec::new();
-    let project = project_name();
+/// Opens a database client in the project's running service container.
+async fn run_db(config: &Config, service: Option<&str>) -> Result<()> {
+    let project = config::project_id();
     let services = collect_services(config, &project);
-    env_vars.extend(collect_service_env_vars(&services));
-
-    // Set up shared cleanup state and signal handler
-    let cleanup_state = Arc::new(Mutex::new(CleanupState {
-        docker: Some(docker.clone()),
-        network_name: Some(network_name.clone()),
-        ..Default::default()
-    }));
-    let signal_handle = spawn_signal_handler(Arc::clone(&cleanup_state));
+    let service = services::find_client_service(&services, service)?;
+    let container = service.container_name(&project);
 
-    // Create bridge network
-    let network_mgr = NetworkManager::new(docker.clone());
-    network_mgr.ensure_network(&network_name).await?;
-
-    // Container lifecycle
+    connect::require_cli()?;
+    let docker = connect::connect().await?;
+    session::check_config_drift(&docker, config).await?;
     let container_mgr = ContainerManager::new(docker);
-
-    // Start service containers
-    let service_ids = start_services(&container_mgr, &services, &network_name).await?;
-
-    // Register service containers for signal cleanup
-    cleanup_state.lock().await.service_container_ids = service_ids.clone();
-
-    // Clean up any existing dev container with the same name
-    container_mgr.cleanup_existing(&container_name).await?;
-
-    let opts = ContainerOpts {
-        image_tag: build_result.tag,
-        container_name: container_name.clone(),
-        shell: "bash".to_string(),
-        project_dir,
-        env_vars,
-        network: Some(network_name.clone()),
-        extra_binds: Vec::new(),
-    };
-
-    let container_id = container_mgr.create_and_start(&opts).await?;
-
-    // Register dev container for signal cleanup
-    cleanup_state.lock().await.dev_container_id = Some(container_id.clone());
-
-    // Write OAuth credentials into container (avoids exposing token in env)
-    if let Some(ref token) = oauth_token {
-        container_mgr.write_credentials(&container_id, token)?;
+    if !container_mgr.is_running(&container).await? {
+        anyhow::bail!(
+            "{} is not running ({container}); start a session first, e.g. `bubble-bot shell`",
+            service.name()
+        );
     }
 
-    // Write Claude config into container
-    container_mgr.write_claude_config(&container_id, &claude_config)?;
-
-    // Run post_start hooks
-    let hook_runner = HookRunner::new(&container_id, &config.hooks);
-    hook_runner.run_post_start();
-
-    // Build Chief command
-    let mut cmd: Vec<&str> = vec!["chief"];
-    let arg_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-    cmd.extend(&arg_refs);
-
-    // Launch Chief (blocking)
-    let exit_code = container_mgr.exec_interactive_command(&container_id, &cmd)?;
-
-    // Normal exit — cancel signal handler and clean up
-    signal_handle.abort();
-
-    // Run pre_stop hooks
-    hook_runner.run_pre_stop();
-
-    // Cleanup on exit
-    cleanup_state.lock().await.cleanup().await;
-
+    let client = service.client_cmd().unwrap_or_default();
+    let client: Vec<&str> = client.iter().map(String::as_str).collect();
+    let exit_code = container_mgr.exec_interactive_command(&container, &client)?;
     if exit_code != 0 {
+        otel::flush();
         std::process::exit(exit_code);
     }
-
     Ok(())
 }
 
-async fn run_claude(cli: &Cli, config: &Config, args: &[String]) -> Result<()> {
-    let docker = Docker::connect_with_local_defaults()
-        .map_err(|e| anyhow::anyhow!("failed to connect to Docker: {e}"))?;
-
-    // Resolve container and network names
-    let container_name = config
-        .container
-        .name
-        .clone()
-        .unwrap_or_else(default_container_name);
-    let network_name = config
-        .container
-        .network
-        .clone()
-        .unwrap_or_else(default_network_name);
+async fn run_info(config: &Config, show_secrets: bool) -> Result<()> {
+    let docker = connect::connect().await?;
+    let container_mgr = ContainerManager::new(docker);
 
-    // Detect and clean up stale containers/networks from previous sessions
-    cleanup_stale_resources(&docker, &container_name).await?;
+    let project_id = config::project_id();
+    let mut infos = Vec::new();
+    for service in collect_services(config, &project_id) {
+        let Some(connection) = service.connection() else {
+            continue;
+        };
+        let container = service.container_name(&project_id);
+        if let Some(env) = container_mgr.running_env(&container).await? {
+            infos.push(ServiceInfo::new(service.name(), connection, &env));
+        }
+    }
+    if infos.is_empty() {
+        println!("No services running; start a session first, e.g. `bubble-bot shell`.");
+        return Ok(());
+    }
+    print!("{}", info_table(&infos, show_secrets));
+    Ok(())
+}
 
-    // Render Dockerfile
+/// Prints what `shell` would create, replace, or remove, compared with the
+/// project's image, network, and containers.
+async fn run_plan(config: &Config) -> Result<()> {
     let renderer = TemplateRenderer::new()?;
-    let render_result = renderer.render(config)?;
-
-    // Build or use cached image
-    let image_builder = ImageBuilder::new(docker.clone());
-    let build_result = image_builder
-        .build(
-            &render_result.dockerfile,
-            &render_result.context_files,
-            cli.container.no_cache,
-        )
-        .await?;
-    info!(tag = %build_result.tag, cached = build_result.cached, "image ready");
-
-    // Get project directory
-    let project_dir = std::env::current_dir()?.to_string_lossy().to_string();
-
-    // Resolve auth token and claude config (written to container after start, not via env)
-    let oauth_token = resolve_oauth_token()?;
-    let claude_config = resolve_claude_config()?;
-
-    // Collect service env vars for the dev container
-    let mut env_vars = Vec::new();
-    let project = project_name();
-    let services = collect_services(config, &project);
-    env_vars.extend(collect_service_env_vars(&services));
-
-    // Set up shared cleanup state and signal handler
-    let cleanup_state = Arc::new(Mutex::new(CleanupState {
-        docker: Some(docker.clone()),
-        network_name: Some(network_name.clone()),
-        ..Default::default()
-    }));
-    let signal_handle = spawn_signal_handler(Arc::clone(&cleanup_state));
+    let render_result = renderer.render_with_options(config, false)?;
+    let image = ImageBuilder::compute_tag(&render_result.dockerfile);
+    let docker = connect::connect().await?;
+    let user_mapping = ContainerManager::new(docker.clone()).user_mapping().await;
+    let desired = Desired::new(config, image, user_mapping)?;
+    print!("{}", plan::render(&plan::changes(&docker, &desired).await?));
+    Ok(())
+}
 
-    // Create bridge network
-    let network_mgr = NetworkManager::new(docker.clone());
-    network_mgr.ensure_network(&network_name).await?;
+/// Lists registered sessions, first dropping those whose process has exited.
+/// Only containers Docker reports running are shown, so a session still
+/// building its image lists none.
+async fn run_ps() -> Result<()> {
+    let registry = Registry::open().context("no data directory for the session registry")?;
+    let stale: Vec<String> = registry
+        .entries()?
+        .into_iter()
+        .filter(|entry| entry.owner().is_gone())
+        .map(|entry| entry.session_id)
+        .collect();
+    let mut sessions = registry.prune(&stale)?;
+    if sessions.is_empty() {
+        println!("No running sessions.");
+        return Ok(());
+    }
 
-    // Container lifecycle
+    let docker = connect::connect().await?;
     let container_mgr = ContainerManager::new(docker);
+    for session in &mut sessions {
+        let mut running = Vec::new();
+        for container in &session.containers {
+            if container_mgr.is_running(container).await? {
+                running.push(container.clone());
+            }
+        }
+        session.containers = running;
+    }
+    print!("{}", sessions::sessions_table(&sessions, SystemTime::now()));
+    Ok(())
+}
 
-    // Start service containers
-    let service_ids = start_services(&container_mgr, &services, &network_name).await?;
+async fn run_images() -> Result<()> {
+    let docker = connect::connect().await?;
 
-    // Register service containers for signal cleanup
-    cleanup_state.lock().await.service_container_ids = service_ids.clone();
+    let images = ImageBuilder::new(docker).list().await?;
+    if images.is_empty() {
+        println!("No bubble-bot images.");
+        return Ok(());
+    }
+    print!("{}", images::images_table(&images, SystemTime::now()));
+    Ok(())
+}
 
-    // Clean up any existing dev container with the same name
-    container_mgr.cleanup_existing(&container_name).await?;
+async fn run_volumes(config: &Config, action: Option<VolumesAction>) -> Result<()> {
+    let docker = connect::connect().await?;
+    let manager = VolumeManager::new(docker.clone());
+
+    match action.unwrap_or(VolumesAction::List) {
+        VolumesAction::List => {
+            let volumes = manager.list().await?;
+            if volumes.is_empty() {
+                println!("No bubble-bot volumes.");
+            } else {
+                print!("{}", volumes::volumes_table(&volumes));
+            }
+        }
+        VolumesAction::Rm { name } => {
+            manager.remove(&name).await?;
+            output::done(format!("Removed volume {name}"));
+        }
+        VolumesAction::Backup { service, file } => {
+            connect::require_cli()?;
+            let volume = stopped_data_volume(config, &docker, &service).await?;
+            if !manager.exists(&volume).await? {
+                anyhow::bail!("{volume} does not exist; {service} has no data to back up yet");
+            }
+            let pb = output::spinner(format!("Backing up {volume}..."));
+            match volumes::backup(&volume, &file) {
+                Ok(()) => output::finish(&pb, format!("Backed up {volume} to {}", file.display())),
+                Err(e) => {
+                    output::finish(&pb, format!("Failed to back up {volume}"));
+                    return Err(e);
+                }
+            }
+        }
+        VolumesAction::Restore { service, file } => {
+            connect::require_cli()?;
+            let volume = stopped_data_volume(config, &docker, &service).await?;
+            let pb = output::spinner(format!("Restoring {volume}..."));
+            match volumes::restore(&volume, &file) {
+                Ok(()) => output::finish(&pb, format!("Restored {} into {volume}", file.display())),
+                Err(e) => {
+                    output::finish(&pb, format!("Failed to restore {volume}"));
+                    return Err(e);
+                }
+            }
+        }
+    }
+    Ok(())
+}
 
-    let opts = ContainerOpts {
-        image_tag: build_result.tag,
-        container_name: container_name.clone(),
-        shell: "bash".to_string(),
-        project_dir,
-        env_vars,
-        network: Some(network_name.clone()),
-        extra_binds: Vec::new(),
-    };
+/// The data volume of `service`, refusing while its container runs: files
+/// copied from under a live database are inconsistent.
+async fn stopped_data_volume(config: &Config, docker: &Docker, service: &str) -> Result<String> {
+    let project = config::project_id();
+    let services = collect_services(config, &project);
+    let (service, volume) = services::data_volume(&services, service)?;
+    let container = service.container_name(&project);
+    if ContainerManager::new(docker.clone())
+        .is_running(&container)
+        .await?
+    {
+        anyhow::bail!(
+            "{} is running ({container}); end the session first",
+            service.name()
+        );
+    }
+    Ok(volume)
+}
 
-    let container_id = container_mgr.create_and_start(&opts).await?;
+/// The project's image (with Chief for `chief`) and, with `services`, the
+/// enabled services' images, as `export image` saves them.
+fn export_images(config: &Config, services: bool, chief: bool) -> Result<Vec<String>> {
+    let render_result = TemplateRenderer::new()?.render_with_options(config, chief)?;
+    let mut images = vec![ImageBuilder::compute_tag(&render_result.dockerfile)];
+    if services {
+        for image in services::service_images(&collect_services(config, &config::project_id())) {
+            if !images.contains(&image) {
+                images.push(image);
+            }
+        }
+    }
+    Ok(images)
+}
 
-    // Register dev container for signal cleanup
-    cleanup_state.lock().await.dev_container_id = Some(container_id.clone());
+async fn run_export(config: &Config, action: ExportAction) -> Result<()> {
+    let ExportAction::Image {
+        output: file,
+        services,
+        chief,
+    } = action;
+    archive::Compression::for_path(&file)?;
+    connect::require_cli()?;
+    let docker = connect::connect().await?;
+    let builder = ImageBuilder::new(docker);
+
+    let images = export_images(config, services, chief)?;
+    for (i, image) in images.iter().enumerate() {
+        if builder.image_exists(image).await? {
+            continue;
+        }
+        if i == 0 {
+            let how = if chief {
+                "start a `chief` session"
+            } else {
+                "run `bubble-bot build`"
+            };
+            anyhow::bail!("{image} isn't built for the current config; {how} first");
+        }
+        anyhow::bail!("{image} isn't pulled; start a session or `docker pull {image}` first");
+    }
 
-    // Write OAuth credentials into container (avoids exposing token in env)
-    if let Some(ref token) = oauth_token {
-        container_mgr.write_credentials(&container_id, token)?;
+    let pb = output::spinner(format!("Exporting {}...", images.join(", ")));
+    if let Err(e) = archive::save(&images, &file) {
+        output::finish(&pb, "Failed to export images".to_string());
+        return Err(e);
     }
+    let size = std::fs::metadata(&file)
+        .map(|m| m.len())
+        .unwrap_or_default();
+    output::finish(
+        &pb,
+        format!(
+            "Exported {} image(s) to {} ({})",
+            images.len(),
+            file.display(),
+            images::format_size(size)
+        ),
+    );
+    Ok(())
+}
+
+async fn run_import(action: ImportAction) -> Result<()> {
+    let ImportAction::Image { file } = action;
+    connect::require_cli()?;
 
-    // Write Claude config into container
-    container_mgr.write_claude_config(&container_id, &claude_config)?;
+    let pb = output::spinner(format!("Importing {}...", file.display()));
+    match archive::load(&file) {
+        Ok(images) => output::finish(&pb, format!("Imported {}", images.join(", "))),
+        Err(e) => {
+            output::finish(&pb, format!("Failed to import {}", file.display()));
+            return Err(e);
+        }
+    }
+    Ok(())
+}
 
-    // Run post_start hooks
-    let hook_runner = HookRunner::new(&container_id, &config.hooks);
-    hook_runner.run_post_start();
+async fn run_clean(remove_volumes: bool) -> Result<()> {
+    let docker = connect::connect().await?;
 
-    // Build Claude Code command
-    let mut cmd: Vec<&str> = vec!["claude", "--permission-mode", "bypassPermissions"];
-    let arg_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
-    cmd.extend(&arg_refs);
+    let cleaner = Cleaner::new(docker);
+    cleaner.clean(remove_volumes).await
+}
 
-    // Launch Claude Code (blocking)
-    let exit_code = container_mgr.exec_interactive_command(&container_id, &cmd)?;
+/// Installs the latest release over this binary, or with `check` only
+/// reports whether there is one, exiting with
+/// [`update::UPDATE_AVAILABLE_EXIT_CODE`] if so.
+fn run_self_update(check: bool) -> Result<()> {
+    let release = update::latest_release()?;
+    let current = update::CURRENT_VERSION;
+    let latest = release.version();
+    if !update::is_newer(latest, current) {
+        println!("bubble-bot {current} is up to date");
+        return Ok(());
+    }
+    if check {
+        println!("bubble-bot {latest} is available (installed: {current})");
+        otel::flush();
+        std::process::exit(update::UPDATE_AVAILABLE_EXIT_CODE);
+    }
 
-    // Normal exit — cancel signal handler and clean up
-    signal_handle.abort();
+    let path = update::install(&release)?;
+    println!(
+        "updated bubble-bot {current} -> {latest} ({})",
+        path.display()
+    );
+    Ok(())
+}
 
-    // Run pre_stop hooks
-    hook_runner.run_pre_stop();
+/// Runs a session and propagates a non-zero exit code from the main command.
+/// Under CI, every session runs non-interactively.
+async fn run_session(config: &Config, opts: SessionOpts, command: &str) -> Result<()> {
+    run_session_notifying(config, opts, command, false).await
+}
 
-    // Cleanup on exit
-    cleanup_state.lock().await.cleanup().await;
+/// Runs a session for `command`; with `notify`, sends the `[notify]`
+/// notifications once it ends, successfully or not. Either way the session
+/// is reported to `telemetry.endpoint` when one is set.
+async fn run_session_notifying(
+    config: &Config,
+    mut opts: SessionOpts,
+    command: &str,
+    notify: bool,
+) -> Result<()> {
+    if ci::detect() {
+        opts = opts.for_ci();
+    }
+    let started = Instant::now();
+    let result = session::run(config, opts).await;
+    if notify {
+        notify_completion(config, command, started.elapsed(), &result);
+    }
+    if config.telemetry.endpoint.is_some() {
+        let report = SessionReport::new(
+            config,
+            &config::project_id(),
+            command,
+            started.elapsed(),
+            &result,
+        );
+        telemetry::publish(&config.telemetry, &report);
+    }
+    let exit_code = result?;
 
     if exit_code != 0 {
+        otel::flush();
         std::process::exit(exit_code);
     }
 
     Ok(())
 }
 
-async fn run_exec(cli: &Cli, config: &Config, cmd: &[String]) -> Result<()> {
-    let docker = Docker::connect_with_local_defaults()
-        .map_err(|e| anyhow::anyhow!("failed to connect to Docker: {e}"))?;
-
-    // Resolve container and network names
-    let container_name = config
-        .container
-        .name
-        .clone()
-        .unwrap_or_else(default_container_name);
-    let network_name = config
-        .container
-        .network
-        .clone()
-        .unwrap_or_else(default_network_name);
-
-    // Detect and clean up stale containers/networks from previous sessions
-    cleanup_stale_resources(&docker, &container_name).await?;
-
-    // Render Dockerfile
-    let renderer = TemplateRenderer::new()?;
-    let render_result = renderer.render(config)?;
-
-    // Build or use cached image
-    let image_builder = ImageBuilder::new(docker.clone());
-    let build_result = image_builder
-        .build(
-            &render_result.dockerfile,
-            &render_result.context_files,
-            cli.container.no_cache,
-        )
-        .await?;
-    info!(tag = %build_result.tag, cached = build_result.cached, "image ready");
-
-    // Get project directory
-    let project_dir = std::env::current_dir()?.to_string_lossy().to_string();
-
-    // Resolve auth token and claude config (written to container after start, not via env)
-    let oauth_token = resolve_oauth_token()?;
-    let claude_config = resolve_claude_config()?;
-
-    // Collect service env vars for the dev container
-    let mut env_vars = Vec::new();
-    let project = project_name();
-    let services = collect_services(config, &project);
-    env_vars.extend(collect_service_env_vars(&services));
-
-    // Set up shared cleanup state and signal handler
-    let cleanup_state = Arc::new(Mutex::new(CleanupState {
-        docker: Some(docker.clone()),
-        network_name: Some(network_name.clone()),
-        ..Default::default()
-    }));
-    let signal_handle = spawn_signal_handler(Arc::clone(&cleanup_state));
-
-    // Create bridge network
-    let network_mgr = NetworkManager::new(docker.clone());
-    network_mgr.ensure_network(&network_name).await?;
-
-    // Container lifecycle
-    let container_mgr = ContainerManager::new(docker);
-
-    // Start service containers
-    let service_ids = start_services(&container_mgr, &services, &network_name).await?;
-
-    // Register service containers for signal cleanup
-    cleanup_state.lock().await.service_container_ids = service_ids.clone();
-
-    // Clean up any existing dev container with the same name
-    container_mgr.cleanup_existing(&container_name).await?;
-
-    let opts = ContainerOpts {
-        image_tag: build_result.tag,
-        container_name: container_name.clone(),
-        shell: "bash".to_string(),
-        project_dir,
-        env_vars,
-        network: Some(network_name.clone()),
-        extra_binds: Vec::new(),
-    };
-
-    let container_id = container_mgr.create_and_start(&opts).await?;
-
-    // Register dev container for signal cleanup
-    cleanup_state.lock().await.dev_container_id = Some(container_id.clone());
-
-    // Write OAuth credentials into container (avoids exposing token in env)
-    if let Some(ref token) = oauth_token {
-        container_mgr.write_credentials(&container_id, token)?;
+fn notify_completion(config: &Config, agent: &str, duration: Duration, result: &Result<i32>) {
+    if !config.notify.desktop() && config.notify.webhook.is_none() {
+        return;
     }
+    let completion = notify::Completion {
+        project: project_name(),
+        command: agent.to_string(),
+        duration,
+        exit_code: result.as_ref().ok().copied(),
+        error: result.as_ref().err().map(|e| format!("{e:#}")),
+        diff_stat: notify::diff_stat(&config::project_dir()),
+    };
+    notify::send(&config.notify, &completion);
+}
 
-    // Write Claude config into container
-    container_mgr.write_claude_config(&container_id, &claude_config)?;
+/// `chief --tasks` and `--results`.
+struct ChiefFiles {
+    tasks: Option<PathBuf>,
+    results: Option<PathBuf>,
+}
 
-    // Run post_start hooks
-    let hook_runner = HookRunner::new(&container_id, &config.hooks);
-    hook_runner.run_post_start();
+async fn run_chief(
+    cli: &Cli,
+    config: &Config,
+    max_duration: Option<Duration>,
+    branch: Option<String>,
+    files: ChiefFiles,
+    args: &[String],
+) -> Result<()> {
+    // Fail before the branch checkout and image build
+    let tasks = files
+        .tasks
+        .as_deref()
+        .map(chief::TaskFile::resolve)
+        .transpose()?;
+    let results = files
+        .results
+        .as_deref()
+        .map(chief::results_dir)
+        .transpose()?;
+    checkout_agent_branch(config, "chief", branch)?;
+
+    let opts = SessionOpts {
+        launch: Launch::Interactive(chief::command(tasks.as_ref(), args)),
+        exec: ExecOpts::default(),
+        install_chief: true,
+        no_cache: cli.container.no_cache,
+        mount_configs: true,
+        use_keychain: true,
+        auto_restart: cli.container.auto_restart,
+        max_duration,
+        keep: cli.container.keep,
+        binds: tasks.iter().map(|tasks| tasks.bind.clone()).collect(),
+        artifacts: results
+            .iter()
+            .map(|dir| chief::results_artifact(dir))
+            .collect(),
+    };
+    run_session_notifying(config, opts, "chief", true).await
+}
 
-    // Build command
-    let cmd_refs: Vec<&str> = cmd.iter().map(|s| s.as_str()).collect();
+async fn run_claude(
+    cli: &Cli,
+    config: &Config,
+    max_duration: Option<Duration>,
+    output: Option<PathBuf>,
+    branch: Option<String>,
+    args: &[String],
+) -> Result<()> {
+    checkout_agent_branch(config, "claude", branch)?;
+
+    let mut cmd = vec![
+        "claude".to_string(),
+        "--permission-mode".to_string(),
+        "bypassPermissions".to_string(),
+    ];
+    cmd.extend(args.iter().cloned());
+
+    let launch = match output {
+        Some(output) => {
+            // Fail before the image build rather than after it
+            std::fs::File::create(&output)
+                .with_context(|| format!("failed to create {}", output.display()))?;
+            Launch::Capture { cmd, output }
+        }
+        None => Launch::Interactive(cmd),
+    };
 
-    // Run command (non-interactive)
-    let exit_code = container_mgr.exec_command(&container_id, &cmd_refs)?;
+    let opts = SessionOpts {
+        launch,
+        exec: ExecOpts::default(),
+        install_chief: false,
+        no_cache: cli.container.no_cache,
+        mount_configs: true,
+        use_keychain: true,
+        auto_restart: cli.container.auto_restart,
+        max_duration,
+        keep: cli.container.keep,
+        binds: Vec::new(),
+        artifacts: Vec::new(),
+    };
+    run_session_notifying(config, opts, "claude", true).await
+}
 
-    // Normal exit — cancel signal handler and clean up
-    signal_handle.abort();
+/// The branch an agent run starts on: `--branch`, else a generated name with
+/// `agent.auto_branch`, else none (the current branch).
+fn agent_branch(config: &Config, agent: &str, branch: Option<String>) -> Option<String> {
+    branch.or_else(|| {
+        config
+            .agent
+            .auto_branch()
+            .then(|| bubble_bot::branch::auto_name(agent, SystemTime::now()))
+    })
+}
 
-    // Run pre_stop hooks
-    hook_runner.run_pre_stop();
+/// Checks out the agent's branch in the project's repository, so its edits
+/// stay off the branch the user is on.
+fn checkout_agent_branch(config: &Config, agent: &str, branch: Option<String>) -> Result<()> {
+    let Some(name) = agent_branch(config, agent, branch) else {
+        return Ok(());
+    };
+    let project_dir = config::project_dir();
+    let repo = config::git_root(&project_dir).with_context(|| {
+        format!(
+            "--branch needs a git repository, but {} is not in one",
+            project_dir.display()
+        )
+    })?;
+    bubble_bot::branch::checkout_new(&repo, &name)?;
+    output::done(format!("Checked out branch {name}"));
+    Ok(())
+}
 
-    // Cleanup on exit
-    cleanup_state.lock().await.cleanup().await;
+async fn run_exec(cli: &Cli, config: &Config, exec: ExecOpts, cmd: &[String]) -> Result<()> {
+    let opts = SessionOpts {
+        launch: Launch::Exec(cmd.to_vec()),
+        exec,
+        install_chief: false,
+        no_cache: cli.container.no_cache,
+        mount_configs: true,
+        use_keychain: true,
+        auto_restart: cli.container.auto_restart,
+        max_duration: None,
+        keep: false,
+        binds: Vec::new(),
+        artifacts: Vec::new(),
+    };
+    run_session(config, opts, "exec").await
+}
 
-    if exit_code != 0 {
-        std::process::exit(exit_code);
+/// Runs a one-shot command for CI. Teardown happens in `session::run` even
+/// if setup fails; the command's exit code becomes ours.
+async fn run_ci(cli: &Cli, config: &Config, cmd: &[String]) -> Result<()> {
+    let opts = SessionOpts {
+        launch: Launch::Exec(cmd.to_vec()),
+        exec: ExecOpts::default(),
+        install_chief: false,
+        no_cache: cli.container.no_cache,
+        mount_configs: true,
+        use_keychain: true,
+        auto_restart: cli.container.auto_restart,
+        max_duration: None,
+        keep: false,
+        binds: Vec::new(),
+        artifacts: Vec::new(),
     }
-
-    Ok(())
+    .for_ci();
+    run_session(config, opts, "run").await
 }
 
 async fn run_shell(cli: &Cli, config: &Config) -> Result<()> {
-    let docker = Docker::connect_with_local_defaults()
-        .map_err(|e| anyhow::anyhow!("failed to connect to Docker: {e}"))?;
-
-    // Resolve container and network names
-    let container_name = config
-        .container
-        .name
-        .clone()
-        .unwrap_or_else(default_container_name);
-    let network_name = config
-        .container
-        .network
-        .clone()
-        .unwrap_or_else(default_network_name);
-
-    // Detect and clean up stale containers/networks from previous sessions
-    cleanup_stale_resources(&docker, &container_name).await?;
-
     // Resolve shell from config (defaults to "bash" via CLI)
     let shell = config
         .container
@@ -719,100 +1262,18 @@ async fn run_shell(cli: &Cli, config: &Config) -> Result<()> {
         .clone()
         .unwrap_or_else(|| "bash".to_string());
 
-    // Render Dockerfile
-    let renderer = TemplateRenderer::new()?;
-    let render_result = renderer.render(config)?;
-
-    // Build or use cached image
-    let image_builder = ImageBuilder::new(docker.clone());
-    let build_result = image_builder
-        .build(
-            &render_result.dockerfile,
-            &render_result.context_files,
-            cli.container.no_cache,
-        )
-        .await?;
-    info!(tag = %build_result.tag, cached = build_result.cached, "image ready");
-
-    // Get project directory
-    let project_dir = std::env::current_dir()?.to_string_lossy().to_string();
-
-    // Resolve auth token and claude config (written to container after start, not via env)
-    let oauth_token = resolve_oauth_token()?;
-    let claude_config = resolve_claude_config()?;
-
-    // Collect service env vars for the dev container
-    let mut env_vars = Vec::new();
-    let project = project_name();
-    let services = collect_services(config, &project);
-    env_vars.extend(collect_service_env_vars(&services));
-
-    // Set up shared cleanup state and signal handler
-    let cleanup_state = Arc::new(Mutex::new(CleanupState {
-        docker: Some(docker.clone()),
-        network_name: Some(network_name.clone()),
-        ..Default::default()
-    }));
-    let signal_handle = spawn_signal_handler(Arc::clone(&cleanup_state));
-
-    // Create bridge network
-    let network_mgr = NetworkManager::new(docker.clone());
-    network_mgr.ensure_network(&network_name).await?;
-
-    // Container lifecycle
-    let container_mgr = ContainerManager::new(docker);
-
-    // Start service containers
-    let service_ids = start_services(&container_mgr, &services, &network_name).await?;
-
-    // Register service containers for signal cleanup
-    cleanup_state.lock().await.service_container_ids = service_ids.clone();
-
-    // Clean up any existing dev container with the same name
-    container_mgr.cleanup_existing(&container_name).await?;
-
-    let opts = ContainerOpts {
-        image_tag: build_result.tag,
-        container_name: container_name.clone(),
-        shell: shell.clone(),
-        project_dir,
-        env_vars,
-        network: Some(network_name.clone()),
-        extra_binds: Vec::new(),
+    let opts = SessionOpts {
+        launch: Launch::Shell(shell),
+        exec: ExecOpts::default(),
+        install_chief: false,
+        no_cache: cli.container.no_cache,
+        mount_configs: true,
+        use_keychain: true,
+        auto_restart: cli.container.auto_restart,
+        max_duration: None,
+        keep: cli.container.keep,
+        binds: Vec::new(),
+        artifacts: Vec::new(),
     };
-
-    let container_id = container_mgr.create_and_start(&opts).await?;
-
-    // Register dev container for signal cleanup
-    cleanup_state.lock().await.dev_container_id = Some(container_id.clone());
-
-    // Write OAuth credentials into container (avoids exposing token in env)
-    if let Some(ref token) = oauth_token {
-        container_mgr.write_credentials(&container_id, token)?;
-    }
-
-    // Write Claude config into container
-    container_mgr.write_claude_config(&container_id, &claude_config)?;
-
-    // Run post_start hooks
-    let hook_runner = HookRunner::new(&container_id, &config.hooks);
-    hook_runner.run_post_start();
-
-    // Launch interactive shell (blocking)
-    let exit_code = container_mgr.exec_interactive_shell(&container_id, &shell)?;
-
-    // Normal exit — cancel signal handler and clean up
-    signal_handle.abort();
-
-    // Run pre_stop hooks
-    hook_runner.run_pre_stop();
-
-    // Cleanup on shell exit
-    cleanup_state.lock().await.cleanup().await;
-
-    if exit_code != 0 {
-        std::process::exit(exit_code);
-    }
-
-    Ok(())
+    run_session(config, opts, "shell").await
 }
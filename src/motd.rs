@@ -0,0 +1,191 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use tracing::info;
+
+use crate::config::Config;
+use crate::services::Service;
+
+/// Where the MOTD is written, relative to the container home. Interactive
+/// shells print it (see `base.dockerfile`).
+pub const MOTD_PATH: &str = ".bubble-bot/motd.md";
+
+/// Line importing the MOTD into Claude Code's user memory
+/// (`~/.claude/CLAUDE.md`), so the agent knows the environment too.
+const CLAUDE_IMPORT: &str = "@~/.bubble-bot/motd.md";
+
+/// A Markdown summary of the session's environment: runtimes, services with
+/// the env vars that reach them, hooks, workers, and network limits.
+/// Variable names are listed, never values, since the file is readable by
+/// everything in the container.
+pub fn render(
+    project: &str,
+    runtimes: &[String],
+    services: &[Box<dyn Service>],
+    config: &Config,
+) -> String {
+    let mut motd = format!("# 🫧 bubble-bot: {project}\n");
+    if !runtimes.is_empty() {
+        motd.push_str(&format!("\nRuntimes: {}\n", runtimes.join(", ")));
+    }
+
+    if !services.is_empty() {
+        motd.push_str("\nServices (reachable by name on the session network):\n");
+        for service in services {
+            let vars: Vec<String> = service
+                .dev_env()
+                .iter()
+                .filter_map(|var| var.split_once('=').map(|(key, _)| key.to_string()))
+                .collect();
+            let mut line = format!("- {} (`{}`)", service.name(), service.image());
+            if !vars.is_empty() {
+                line.push_str(&format!(": {}", vars.join(", ")));
+            }
+            motd.push_str(&line);
+            motd.push('\n');
+        }
+    }
+
+    let hooks = [
+        ("post_start", &config.hooks.post_start),
+        ("pre_stop", &config.hooks.pre_stop),
+    ];
+    if hooks.iter().any(|(_, cmds)| !cmds.is_empty()) {
+        motd.push_str("\nHooks:\n");
+        for (stage, cmds) in hooks {
+            for cmd in cmds {
+                motd.push_str(&format!("- {stage}: `{cmd}`\n"));
+            }
+        }
+    }
+
+    // Workers tied to a disabled service don't start
+    let workers: Vec<_> = config
+        .workers
+        .iter()
+        .filter(|worker| {
+            worker
+                .depends_on_service
+                .as_ref()
+                .is_none_or(|name| services.iter().any(|s| s.name() == name))
+        })
+        .collect();
+    if !workers.is_empty() {
+        motd.push_str("\nWorkers (started with the session):\n");
+        for worker in workers {
+            motd.push_str(&format!("- {}: `{}`\n", worker.display_name(), worker.cmd));
+        }
+    }
+
+    let network = &config.network;
+    if !network.allow.is_empty() {
+        motd.push_str(&format!(
+            "\nNetwork: internet access only to {}\n",
+            network.allow.join(", ")
+        ));
+    } else if network.is_internal() {
+        motd.push_str("\nNetwork: no internet access\n");
+    }
+    motd
+}
+
+/// Writes `motd` to [`MOTD_PATH`] in the container and imports it into
+/// `~/.claude/CLAUDE.md`, once, keeping whatever else the file holds.
+pub fn install(container_id: &str, motd: &str) -> Result<()> {
+    let script = format!(
+        "mkdir -p \"$HOME/.bubble-bot\" \"$HOME/.claude\" \
+         && cat > \"$HOME/{MOTD_PATH}\" \
+         && {{ grep -qxF '{CLAUDE_IMPORT}' \"$HOME/.claude/CLAUDE.md\" 2>/dev/null \
+         || echo '{CLAUDE_IMPORT}' >> \"$HOME/.claude/CLAUDE.md\"; }}"
+    );
+    let mut child = Command::new("docker")
+        .args(["exec", "-i", container_id, "sh", "-c", &script])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to spawn docker exec for the MOTD")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(motd.as_bytes())?;
+    }
+    let output = child
+        .wait_with_output()
+        .context("failed to wait for the MOTD write")?;
+    if !output.status.success() {
+        bail!(
+            "failed to write the MOTD: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    info!("MOTD written");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::collect_services;
+
+    #[test]
+    fn motd_lists_the_environment_without_secrets() {
+        let config: Config = toml::from_str(
+            r#"
+            [services.mysql]
+            password = "hunter2"
+
+            [services]
+            redis = true
+
+            [hooks]
+            post_start = ["composer install"]
+
+            [[workers]]
+            name = "queue"
+            cmd = "php artisan queue:work"
+
+            [[workers]]
+            cmd = "horizon"
+            depends_on_service = "postgres"
+
+            [network]
+            allow = ["github.com"]
+            "#,
+        )
+        .unwrap();
+        let services = collect_services(&config, "app");
+        let motd = render("app", &["php 8.3".to_string()], &services, &config);
+        assert!(!motd.contains("hunter2"), "{motd}");
+        assert_eq!(
+            motd.lines().take(6).collect::<Vec<_>>(),
+            vec![
+                "# 🫧 bubble-bot: app",
+                "",
+                "Runtimes: php 8.3",
+                "",
+                "Services (reachable by name on the session network):",
+                "- mysql (`mysql:8.0`): DB_HOST, DB_PORT, DB_DATABASE, DB_USERNAME, DB_PASSWORD, DATABASE_URL",
+            ]
+        );
+        assert!(
+            motd.contains("- post_start: `composer install`\n"),
+            "{motd}"
+        );
+        assert!(
+            motd.contains("- queue: `php artisan queue:work`\n"),
+            "{motd}"
+        );
+        // postgres isn't enabled, so its worker doesn't run
+        assert!(!motd.contains("horizon"), "{motd}");
+        assert!(
+            motd.ends_with("Network: internet access only to github.com\n"),
+            "{motd}"
+        );
+    }
+
+    #[test]
+    fn motd_of_a_bare_session() {
+        let config = Config::default();
+        assert_eq!(render("app", &[], &[], &config), "# 🫧 bubble-bot: app\n");
+    }
+}
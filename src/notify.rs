@@ -0,0 +1,207 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::config::NotifyConfig;
+use crate::docker::images::format_duration;
+use crate::http;
+
+/// How long a webhook POST may take before it is abandoned.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How an agent session ended, for `[notify]`.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub project: String,
+    /// The agent that ran (`claude` or `chief`).
+    pub command: String,
+    pub duration: Duration,
+    /// The agent's exit code; `None` if the session failed before it ran.
+    pub exit_code: Option<i32>,
+    /// The session's error, if it failed.
+    pub error: Option<String>,
+    /// `git diff --shortstat` of the project against `HEAD`.
+    pub diff_stat: Option<String>,
+}
+
+impl Completion {
+    /// One line for a notification body or a chat message.
+    pub fn summary(&self) -> String {
+        let outcome = match (self.exit_code, &self.error) {
+            (_, Some(error)) => format!("failed: {error}"),
+            (Some(0), None) => "finished".to_string(),
+            (Some(code), None) => format!("exited with code {code}"),
+            (None, None) => "ended".to_string(),
+        };
+        let mut summary = format!(
+            "{} in {} {outcome} after {}",
+            self.command,
+            self.project,
+            format_duration(self.duration)
+        );
+        if let Some(stat) = &self.diff_stat {
+            summary.push_str(&format!(" ({stat})"));
+        }
+        summary
+    }
+
+    /// The webhook body. `text` makes it postable to Slack as is.
+    pub fn payload(&self) -> serde_json::Value {
+        json!({
+            "text": format!("bubble-bot: {}", self.summary()),
+            "project": self.project,
+            "command": self.command,
+            "duration_secs": self.duration.as_secs(),
+            "exit_code": self.exit_code,
+            "error": self.error,
+            "diff_stat": self.diff_stat,
+        })
+    }
+}
+
+/// The project's uncommitted changes as `git diff --shortstat HEAD`, e.g.
+/// `3 files changed, 10 insertions(+)`. `None` outside a git repository or
+/// without changes.
+pub fn diff_stat(project_dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(project_dir)
+        .args(["diff", "--shortstat", "HEAD"])
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    let stat = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !stat.is_empty()).then_some(stat)
+}
+
+/// Sends the notifications `config` asks for. Failures are logged, never
+/// returned: a missed notification shouldn't change the session's outcome.
+pub fn send(config: &NotifyConfig, completion: &Completion) {
+    if config.desktop() {
+        if let Err(e) = desktop("bubble-bot", &completion.summary()) {
+            warn!(error = %e, "desktop notification failed");
+        }
+    }
+    match config.webhook_url() {
+        Ok(Some(url)) => {
+            if let Err(e) = webhook(url, &completion.payload()) {
+                warn!(error = %e, "webhook notification failed");
+            }
+        }
+        Ok(None) => {}
+        Err(e) => warn!(error = %e, "webhook notification skipped"),
+    }
+}
+
+/// Shows a desktop notification via `osascript` on macOS and `notify-send`
+/// elsewhere.
+fn desktop(title: &str, body: &str) -> Result<()> {
+    let (program, args) = desktop_command(cfg!(target_os = "macos"), title, body);
+    let status = Command::new(program)
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("failed to run {program}"))?;
+    if !status.success() {
+        bail!("{program} exited with {status}");
+    }
+    Ok(())
+}
+
+fn desktop_command(macos: bool, title: &str, body: &str) -> (&'static str, Vec<String>) {
+    if macos {
+        let script = format!(
+            "display notification {} with title {}",
+            applescript_string(body),
+            applescript_string(title)
+        );
+        ("osascript", vec!["-e".to_string(), script])
+    } else {
+        ("notify-send", vec![title.to_string(), body.to_string()])
+    }
+}
+
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// POSTs `payload` as JSON to `url`.
+fn webhook(url: &str, payload: &serde_json::Value) -> Result<()> {
+    http::post_json(url, &payload.to_string(), WEBHOOK_TIMEOUT)?;
+    info!("webhook notification sent");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn completion() -> Completion {
+        Completion {
+            project: "app".to_string(),
+            command: "chief".to_string(),
+            duration: Duration::from_secs(3720),
+            exit_code: Some(0),
+            error: None,
+            diff_stat: Some("3 files changed, 10 insertions(+)".to_string()),
+        }
+    }
+
+    #[test]
+    fn summary_describes_the_outcome() {
+        assert_eq!(
+            completion().summary(),
+            "chief in app finished after 1h2m (3 files changed, 10 insertions(+))"
+        );
+
+        let failed = Completion {
+            exit_code: Some(2),
+            diff_stat: None,
+            ..completion()
+        };
+        assert_eq!(
+            failed.summary(),
+            "chief in app exited with code 2 after 1h2m"
+        );
+    }
+
+    #[test]
+    fn payload_carries_every_field() {
+        let payload = completion().payload();
+        assert_eq!(payload["project"], "app");
+        assert_eq!(payload["command"], "chief");
+        assert_eq!(payload["duration_secs"], 3720);
+        assert_eq!(payload["exit_code"], 0);
+        assert!(payload["error"].is_null());
+        assert_eq!(payload["diff_stat"], "3 files changed, 10 insertions(+)");
+        assert!(
+            payload["text"]
+                .as_str()
+                .unwrap()
+                .starts_with("bubble-bot: chief")
+        );
+    }
+
+    #[test]
+    fn desktop_command_per_platform() {
+        let (program, args) = desktop_command(true, "bubble-bot", "said \"done\"");
+        assert_eq!(program, "osascript");
+        assert_eq!(
+            args,
+            vec![
+                "-e",
+                "display notification \"said \\\"done\\\"\" with title \"bubble-bot\""
+            ]
+        );
+
+        let (program, args) = desktop_command(false, "bubble-bot", "done");
+        assert_eq!(program, "notify-send");
+        assert_eq!(args, vec!["bubble-bot", "done"]);
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{Value, json};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Level, Subscriber, warn};
+use tracing_subscriber::filter::{Filtered, Targets};
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::http;
+
+/// How long the export may take before it is abandoned.
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// OTLP/HTTP path for traces, appended to `--otel-endpoint` unless present.
+const TRACES_PATH: &str = "/v1/traces";
+
+/// Where [`flush`] sends the spans of this process, set by [`layer`].
+static EXPORT: OnceLock<(String, Arc<Mutex<Spans>>)> = OnceLock::new();
+
+/// One span as exported: OTLP field names, IDs in hex.
+#[derive(Debug, Clone)]
+struct SpanData {
+    name: String,
+    span_id: String,
+    parent_span_id: Option<String>,
+    start: SystemTime,
+    end: Option<SystemTime>,
+    attributes: Vec<Value>,
+}
+
+/// Spans of this process, all in one trace.
+#[derive(Debug)]
+struct Spans {
+    trace_id: String,
+    open: HashMap<u64, SpanData>,
+    closed: Vec<SpanData>,
+}
+
+impl Spans {
+    fn new() -> Self {
+        Self {
+            trace_id: crate::random::hex(16),
+            open: HashMap::new(),
+            closed: Vec::new(),
+        }
+    }
+
+    /// Takes every span so far as an OTLP `ExportTraceServiceRequest`.
+    /// Spans still open (the process is exiting inside them) end now.
+    fn drain(&mut self, now: SystemTime) -> Option<Value> {
+        let mut spans: Vec<SpanData> = self.closed.drain(..).collect();
+        spans.extend(self.open.drain().map(|(_, span)| span));
+        if spans.is_empty() {
+            return None;
+        }
+        spans.sort_by_key(|span| span.start);
+        let spans: Vec<Value> = spans
+            .into_iter()
+            .map(|span| {
+                let mut value = json!({
+                    "traceId": self.trace_id,
+                    "spanId": span.span_id,
+                    "name": span.name,
+                    "kind": 1,
+                    "startTimeUnixNano": unix_nanos(span.start),
+                    "endTimeUnixNano": unix_nanos(span.end.unwrap_or(now)),
+                    "attributes": span.attributes,
+                });
+                if let Some(parent) = span.parent_span_id {
+                    value["parentSpanId"] = Value::String(parent);
+                }
+                value
+            })
+            .collect();
+        Some(json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        attribute("service.name", json!({ "stringValue": "bubble-bot" })),
+                        attribute("service.version", json!({ "stringValue": env!("CARGO_PKG_VERSION") })),
+                    ],
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "bubble-bot", "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans,
+                }],
+            }],
+        }))
+    }
+}
+
+/// Records bubble-bot's spans for export to an OTLP collector.
+pub struct OtelLayer {
+    spans: Arc<Mutex<Spans>>,
+}
+
+impl<S> Layer<S> for OtelLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        let mut attributes = Vec::new();
+        attrs.record(&mut AttributeVisitor(&mut attributes));
+        let parent = ctx
+            .span(id)
+            .and_then(|span| span.parent())
+            .map(|parent| parent.id().into_u64());
+        let mut spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        let parent_span_id = parent
+            .and_then(|parent| spans.open.get(&parent))
+            .map(|parent| parent.span_id.clone());
+        spans.open.insert(
+            id.into_u64(),
+            SpanData {
+                name: attrs.metadata().name().to_string(),
+                span_id: crate::random::hex(8),
+                parent_span_id,
+                start: SystemTime::now(),
+                end: None,
+                attributes,
+            },
+        );
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: LayerContext<'_, S>) {
+        let mut spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(span) = spans.open.get_mut(&id.into_u64()) {
+            values.record(&mut AttributeVisitor(&mut span.attributes));
+        }
+    }
+
+    fn on_close(&self, id: Id, _ctx: LayerContext<'_, S>) {
+        let mut spans = self.spans.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(mut span) = spans.open.remove(&id.into_u64()) {
+            span.end = Some(SystemTime::now());
+            spans.closed.push(span);
+        }
+    }
+}
+
+/// The layer recording bubble-bot's `info` spans for [`flush`] to export to
+/// `endpoint`. Spans of dependencies (bollard, hyper) are left out.
+pub fn layer<S>(endpoint: &str) -> Filtered<OtelLayer, Targets, S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let spans = Arc::new(Mutex::new(Spans::new()));
+    let _ = EXPORT.set((traces_url(endpoint), Arc::clone(&spans)));
+    OtelLayer { spans }.with_filter(Targets::new().with_target("bubble_bot", Level::INFO))
+}
+
+/// Exports the spans recorded so far, if `--otel-endpoint` is set. Called
+/// before the process exits; failures are logged, never returned.
+pub fn flush() {
+    let Some((url, spans)) = EXPORT.get() else {
+        return;
+    };
+    let payload = spans
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain(SystemTime::now());
+    if let Some(payload) = payload {
+        if let Err(e) = http::post_json(url, &payload.to_string(), EXPORT_TIMEOUT) {
+            warn!(error = %e, "spans not exported");
+        }
+    }
+}
+
+/// The OTLP/HTTP traces URL for `endpoint`, which may be the collector's
+/// base URL or already end in `/v1/traces`.
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with(TRACES_PATH) {
+        endpoint.to_string()
+    } else {
+        format!("{endpoint}{TRACES_PATH}")
+    }
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+/// Collects span fields as OTLP attributes.
+struct AttributeVisitor<'a>(&'a mut Vec<Value>);
+
+impl Visit for AttributeVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0
+            .push(attribute(field.name(), json!({ "stringValue": value })));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0
+            .push(attribute(field.name(), json!({ "boolValue": value })));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.push(attribute(
+            field.name(),
+            json!({ "intValue": value.to_string() }),
+        ));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.push(attribute(
+            field.name(),
+            json!({ "intValue": value.to_string() }),
+        ));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{value:?}"));
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn subscriber(spans: &Arc<Mutex<Spans>>) -> impl Subscriber {
+        tracing_subscriber::registry().with(
+            OtelLayer {
+                spans: Arc::clone(spans),
+            }
+            .with_filter(Targets::new().with_target("bubble_bot", Level::INFO)),
+        )
+    }
+
+    #[test]
+    fn spans_are_exported_as_one_trace() {
+        let spans = Arc::new(Mutex::new(Spans::new()));
+        tracing::subscriber::with_default(subscriber(&spans), || {
+            let root = tracing::info_span!("bubble-bot", command = "shell");
+            let _root = root.enter();
+            tracing::info_span!("build", cached = true).in_scope(|| {});
+            tracing::debug_span!("too_detailed").in_scope(|| {});
+            let service = tracing::info_span!("service_start", service = "mysql");
+            let _service = service.enter();
+        });
+        let payload = spans.lock().unwrap().drain(SystemTime::now()).unwrap();
+        let exported = &payload["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        let names: Vec<_> = exported
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|span| span["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["bubble-bot", "build", "service_start"]);
+
+        let root = &exported[0];
+        assert!(root.get("parentSpanId").is_none());
+        assert_eq!(root["traceId"].as_str().unwrap().len(), 32);
+        for child in [&exported[1], &exported[2]] {
+            assert_eq!(child["traceId"], root["traceId"]);
+            assert_eq!(child["parentSpanId"], root["spanId"]);
+        }
+        assert_eq!(
+            exported[1]["attributes"][0],
+            json!({ "key": "cached", "value": { "boolValue": true } })
+        );
+        assert!(spans.lock().unwrap().drain(SystemTime::now()).is_none());
+
+        assert_eq!(
+            traces_url("http://otel:4318/"),
+            "http://otel:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("http://otel:4318/v1/traces"),
+            "http://otel:4318/v1/traces"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::{IsTerminal, Write};
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+/// Whether progress is reported as plain log lines instead of spinners.
+static PLAIN: AtomicBool = AtomicBool::new(false);
+
+/// Switches progress reporting to plain lines on stderr, for CI logs.
+pub fn set_plain(plain: bool) {
+    PLAIN.store(plain, Ordering::Relaxed);
+}
+
+pub fn is_plain() -> bool {
+    PLAIN.load(Ordering::Relaxed)
+}
+
+/// When output is styled with colors (`--color`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Style output going to a terminal, unless `NO_COLOR` is set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Whether stdout and stderr output is styled; see [`set_color`].
+static COLOR_STDOUT: AtomicBool = AtomicBool::new(false);
+static COLOR_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Decides per stream whether output is styled, for log lines and for
+/// spinners (via `console`, which indicatif styles with). Call before
+/// [`crate::logging::init`].
+pub fn set_color(choice: ColorChoice) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let stdout = color_enabled(choice, no_color, std::io::stdout().is_terminal());
+    let stderr = color_enabled(choice, no_color, std::io::stderr().is_terminal());
+    COLOR_STDOUT.store(stdout, Ordering::Relaxed);
+    COLOR_STDERR.store(stderr, Ordering::Relaxed);
+    console::set_colors_enabled(stdout);
+    console::set_colors_enabled_stderr(stderr);
+}
+
+pub fn color_stdout() -> bool {
+    COLOR_STDOUT.load(Ordering::Relaxed)
+}
+
+pub fn color_stderr() -> bool {
+    COLOR_STDERR.load(Ordering::Relaxed)
+}
+
+/// `--color always` wins over `NO_COLOR` (set to anything but empty), which
+/// wins over a terminal.
+fn color_enabled(choice: ColorChoice, no_color: bool, terminal: bool) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => terminal && !no_color,
+    }
+}
+
+/// Spinners for concurrent steps (the image build and service startup) each
+/// get their own line.
+static BARS: LazyLock<MultiProgress> = LazyLock::new(MultiProgress::new);
+
+/// Starts a spinner for a long-running step. In plain mode the spinner is
+/// hidden and `message` is printed once instead.
+pub fn spinner(message: String) -> ProgressBar {
+    if is_plain() {
+        eprintln!("{message}");
+        return ProgressBar::hidden();
+    }
+
+    let pb = BARS.add(ProgressBar::new_spinner());
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.cyan} {msg}")
+            .expect("invalid progress template"),
+    );
+    pb.enable_steady_tick(Duration::from_millis(120));
+    pb.set_message(message);
+    pb
+}
+
+/// Updates a spinner's message, or prints it as a line in plain mode (e.g.,
+/// build output).
+pub fn update(pb: &ProgressBar, message: String) {
+    if is_plain() {
+        eprintln!("{message}");
+    } else {
+        pb.set_message(message);
+    }
+}
+
+/// Finishes a spinner with a final message.
+pub fn finish(pb: &ProgressBar, message: String) {
+    if is_plain() {
+        eprintln!("{message}");
+    } else {
+        pb.finish_with_message(message);
+    }
+}
+
+/// Reports a step that completed without work (e.g., a cache hit).
+pub fn done(message: String) {
+    if is_plain() {
+        eprintln!("{message}");
+        return;
+    }
+
+    let pb = BARS.add(ProgressBar::new_spinner());
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{prefix} {msg}")
+            .expect("invalid progress template"),
+    );
+    pb.set_prefix("✓");
+    pb.finish_with_message(message);
+}
+
+/// Whether [`set_title`] changed the title, so [`restore_title`] has
+/// something to undo.
+static TITLE_SET: AtomicBool = AtomicBool::new(false);
+
+/// Sets the terminal title, saving the previous one on the terminal's title
+/// stack. Does nothing unless stdout is a terminal and progress isn't plain
+/// (CI).
+pub fn set_title(title: &str) {
+    if is_plain() || !std::io::stdout().is_terminal() {
+        return;
+    }
+    write_escape(&format!("\x1b[22;0t\x1b]0;{title}\x07"));
+    TITLE_SET.store(true, Ordering::Relaxed);
+}
+
+/// Restores the title saved by [`set_title`]. Terminals without a title
+/// stack keep the session's title.
+pub fn restore_title() {
+    if TITLE_SET.swap(false, Ordering::Relaxed) {
+        write_escape("\x1b[23;0t");
+    }
+}
+
+fn write_escape(sequence: &str) {
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(sequence.as_bytes());
+    let _ = stdout.flush();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_follows_flag_then_no_color_then_terminal() {
+        assert!(color_enabled(ColorChoice::Auto, false, true));
+        assert!(!color_enabled(ColorChoice::Auto, false, false));
+        assert!(!color_enabled(ColorChoice::Auto, true, true));
+        assert!(color_enabled(ColorChoice::Always, true, false));
+        assert!(!color_enabled(ColorChoice::Never, false, true));
+    }
+}
@@ -0,0 +1,460 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use bollard::models::ContainerSummary;
+use serde::Serialize;
+
+use crate::config::{Config, Readiness, project_id};
+use crate::docker::backend::ContainerBackend;
+use crate::docker::containers::{
+    ContainerManager, DEFINITION_LABEL, UserMapping, dev_definition, service_definition,
+};
+use crate::docker::images::ImageBuilder;
+use crate::docker::networks::NetworkManager;
+use crate::docker::ownership::KEEP_LABEL;
+use crate::proxy::ProxySettings;
+use crate::services::collect_services;
+use crate::session::{dev_binds, dev_container_opts, network_name};
+use crate::shell;
+
+/// Output format of `--dry-run`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum PlanFormat {
+    /// The Dockerfile and the Docker commands a session would run.
+    #[default]
+    Text,
+    /// A [`Plan`] as one JSON object.
+    Json,
+}
+
+/// What a session would create and run, printed by `--dry-run --format
+/// json`. Env vars and config values naming secrets are masked unless
+/// `--show-secrets`.
+#[derive(Debug, Default, Serialize)]
+pub struct Plan {
+    /// The resolved config.
+    pub config: serde_json::Value,
+    pub image: ImagePlan,
+    pub network: NetworkPlan,
+    pub services: Vec<ServicePlan>,
+    pub container: ContainerPlan,
+    /// The main command, as a `docker exec` line.
+    pub command: String,
+    /// Branch checked out for the agent before it starts.
+    pub branch: Option<String>,
+    /// Laravel `.env` keys patched for the session.
+    pub env_patch: Vec<String>,
+    pub post_start: Vec<String>,
+    pub pre_stop: Vec<String>,
+    /// `[[workers]]` as `name: cmd`.
+    pub workers: Vec<String>,
+    /// `[[artifacts]]` as `container path -> host directory`.
+    pub artifacts: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImagePlan {
+    pub tag: String,
+    pub dockerfile: String,
+    pub build_args: BTreeMap<String, String>,
+    pub platform: Option<String>,
+    pub buildkit: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct NetworkPlan {
+    pub name: String,
+    pub internal: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ServicePlan {
+    /// Service name, which is also its hostname.
+    pub name: String,
+    pub container: String,
+    pub image: String,
+    /// Service container env as `KEY=VALUE`.
+    pub env: Vec<String>,
+    /// Named volume as `volume:path`.
+    pub volume: Option<String>,
+    pub binds: Vec<String>,
+    pub readiness: Option<Readiness>,
+    /// Also attached to the default bridge network for internet access.
+    pub egress: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ContainerPlan {
+    pub name: String,
+    /// `uid:gid`
+    pub user: String,
+    pub workdir: String,
+    /// Bind mounts (`host:container[:options]`), the project first.
+    pub binds: Vec<String>,
+    /// Named volumes as `volume:path`.
+    pub volumes: Vec<String>,
+    pub tmpfs: Vec<String>,
+    pub shm_size: Option<String>,
+    pub groups: Vec<String>,
+    /// Devices as `host:container:permissions`.
+    pub devices: Vec<String>,
+    pub dns: Vec<String>,
+    pub extra_hosts: Vec<String>,
+    /// Dev container env as `KEY=VALUE`.
+    pub env: Vec<String>,
+}
+
+/// What a session would run for the project, compared with what runs now
+/// by `bubble-bot plan`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Desired {
+    pub image: String,
+    pub network: String,
+    pub internal: bool,
+    /// The dev container, whose name prefixes the project's containers.
+    pub container: String,
+    /// See [`dev_definition`].
+    pub definition: String,
+    pub services: Vec<DesiredService>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesiredService {
+    pub container: String,
+    pub image: String,
+    /// See [`service_definition`].
+    pub definition: String,
+}
+
+impl Desired {
+    /// The resources a `bubble-bot shell` session of the project would use
+    /// with `config`, its dev container running `image` as `user_mapping`.
+    pub fn new(config: &Config, image: String, user_mapping: UserMapping) -> Result<Self> {
+        let network = network_name(config);
+        let id = project_id();
+        let services = collect_services(config, &id);
+        let proxy = ProxySettings::resolve(&config.network);
+        let dotfiles = config
+            .shell
+            .dotfiles_repo
+            .as_deref()
+            .map(shell::dotfiles_checkout)
+            .transpose()?;
+        let binds = dev_binds(config, &[], true, dotfiles.as_deref())?;
+        let mut container =
+            dev_container_opts(config, &proxy, &services, binds.binds, "", user_mapping)?;
+        container.image_tag = image.clone();
+        Ok(Self {
+            image,
+            internal: config.network.is_internal(),
+            network: network.clone(),
+            definition: dev_definition(&container),
+            container: container.container_name,
+            services: services
+                .iter()
+                .map(|service| DesiredService {
+                    container: service.container_name(&id),
+                    image: service.image(),
+                    definition: service_definition(service.as_ref(), &network),
+                })
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Add,
+    Replace,
+    Destroy,
+}
+
+/// One resource a session would create, replace, or remove.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub action: Action,
+    /// Kind and name, e.g. `container bubble-bot-app-mysql`.
+    pub resource: String,
+    pub reasons: Vec<String>,
+}
+
+impl Change {
+    fn new(action: Action, resource: String, reasons: Vec<String>) -> Self {
+        Self {
+            action,
+            resource,
+            reasons,
+        }
+    }
+}
+
+/// The changes a session would make to reach `desired`. Resources a
+/// `--keep` session left as the session would create them are reused, so
+/// they don't appear; other containers of the project are removed.
+pub async fn changes<B: ContainerBackend>(docker: &B, desired: &Desired) -> Result<Vec<Change>> {
+    let mut changes = Vec::new();
+    if !ImageBuilder::new(docker.clone())
+        .image_exists(&desired.image)
+        .await?
+    {
+        changes.push(Change::new(
+            Action::Add,
+            format!("image {}", desired.image),
+            vec!["build".to_string()],
+        ));
+    }
+
+    // Containers can't move to a network with other isolation, so none
+    // are reused when it changes
+    let network_resource = format!("network {}", desired.network);
+    let network_replaced = match NetworkManager::new(docker.clone())
+        .find_network(&desired.network)
+        .await?
+    {
+        None => {
+            changes.push(Change::new(Action::Add, network_resource, Vec::new()));
+            false
+        }
+        Some(network) => {
+            let internal = network.internal.unwrap_or(false);
+            let mut reasons = Vec::new();
+            if internal != desired.internal {
+                reasons.push(format!("internal {internal} -> {}", desired.internal));
+            }
+            if !is_kept(network.labels.as_ref()) {
+                reasons.push("not kept".to_string());
+            }
+            let replaced = internal != desired.internal;
+            if !reasons.is_empty() {
+                changes.push(Change::new(Action::Replace, network_resource, reasons));
+            }
+            replaced
+        }
+    };
+
+    let existing = ContainerManager::new(docker.clone())
+        .project_containers(&desired.container)
+        .await?;
+    let find = |name: &str| {
+        let name = format!("/{name}");
+        existing
+            .iter()
+            .find(|c| c.names.as_deref().unwrap_or_default().contains(&name))
+    };
+    // Services start before the dev container
+    let mut wanted: Vec<_> = desired
+        .services
+        .iter()
+        .map(|service| {
+            (
+                service.container.as_str(),
+                service.image.as_str(),
+                service.definition.as_str(),
+            )
+        })
+        .collect();
+    wanted.push((&desired.container, &desired.image, &desired.definition));
+    for (name, image, definition) in &wanted {
+        let resource = format!("container {name}");
+        let Some(container) = find(name) else {
+            changes.push(Change::new(Action::Add, resource, Vec::new()));
+            continue;
+        };
+        let mut reasons = Vec::new();
+        if network_replaced {
+            reasons.push("network replaced".to_string());
+        }
+        if !is_kept(container.labels.as_ref()) {
+            reasons.push("not kept".to_string());
+        }
+        let running_image = container.image.as_deref().unwrap_or_default();
+        if running_image != *image {
+            reasons.push(format!("image {running_image} -> {image}"));
+        }
+        let running = container
+            .labels
+            .as_ref()
+            .and_then(|l| l.get(DEFINITION_LABEL));
+        if running.is_none_or(|v| v != definition) {
+            reasons.push("definition changed".to_string());
+        }
+        if !reasons.is_empty() {
+            changes.push(Change::new(Action::Replace, resource, reasons));
+        }
+    }
+
+    for container in &existing {
+        let name = container_name_of(container);
+        if !wanted.iter().any(|(wanted, ..)| *wanted == name) {
+            changes.push(Change::new(
+                Action::Destroy,
+                format!("container {name}"),
+                Vec::new(),
+            ));
+        }
+    }
+    Ok(changes)
+}
+
+fn is_kept(labels: Option<&HashMap<String, String>>) -> bool {
+    labels.is_some_and(|labels| labels.contains_key(KEEP_LABEL))
+}
+
+fn container_name_of(container: &ContainerSummary) -> &str {
+    container
+        .names
+        .as_deref()
+        .and_then(|names| names.first())
+        .map(|name| name.trim_start_matches('/'))
+        .unwrap_or_default()
+}
+
+/// Formats `changes` like `terraform plan`: a line per resource marked `+`
+/// (create), `~` (replace), or `-` (remove), then the count of each.
+pub fn render(changes: &[Change]) -> String {
+    if changes.is_empty() {
+        return "No changes: a session would reuse the running containers and network as they are.\n"
+            .to_string();
+    }
+    let mut text = String::from("A session would:\n\n");
+    let mut counts = [0; 3];
+    for change in changes {
+        let (symbol, count) = match change.action {
+            Action::Add => ("+", &mut counts[0]),
+            Action::Replace => ("~", &mut counts[1]),
+            Action::Destroy => ("-", &mut counts[2]),
+        };
+        *count += 1;
+        text.push_str(&format!("  {symbol} {}", change.resource));
+        if !change.reasons.is_empty() {
+            text.push_str(&format!(" ({})", change.reasons.join(", ")));
+        }
+        text.push('\n');
+    }
+    let [add, replace, destroy] = counts;
+    text.push_str(&format!(
+        "\nPlan: {add} to add, {replace} to change, {destroy} to destroy.\n"
+    ));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::docker::mock::{MockBackend, MockContainer};
+
+    fn desired() -> Desired {
+        Desired {
+            image: "bubble-bot:new".to_string(),
+            network: "bubble-bot-app".to_string(),
+            internal: false,
+            container: "bubble-bot-app".to_string(),
+            definition: "c0ffee".to_string(),
+            services: vec![DesiredService {
+                container: "bubble-bot-app-mysql".to_string(),
+                image: "mysql:8.0".to_string(),
+                definition: "d3f".to_string(),
+            }],
+        }
+    }
+
+    fn kept(name: &str, image: &str, definition: &str) -> MockContainer {
+        MockContainer {
+            name: name.to_string(),
+            image: image.to_string(),
+            labels: HashMap::from([
+                (KEEP_LABEL.to_string(), "true".to_string()),
+                (DEFINITION_LABEL.to_string(), definition.to_string()),
+            ]),
+            running: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn dev_definition_matches_any_session() {
+        let config = Config::default();
+        let desired =
+            Desired::new(&config, "bubble-bot:new".to_string(), UserMapping::Root).unwrap();
+
+        let services = collect_services(&config, &project_id());
+        let binds = dev_binds(&config, &[], true, None).unwrap();
+        let mut session = dev_container_opts(
+            &config,
+            &ProxySettings::resolve(&config.network),
+            &services,
+            binds.binds,
+            "4f2a9c",
+            UserMapping::Root,
+        )
+        .unwrap();
+        session.image_tag = "bubble-bot:new".to_string();
+        assert_eq!(desired.definition, dev_definition(&session));
+
+        session.image_tag = "bubble-bot:old".to_string();
+        assert_ne!(desired.definition, dev_definition(&session));
+    }
+
+    #[test]
+    fn nothing_running_plans_everything_as_new() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let changes = rt
+            .block_on(changes(&MockBackend::new(), &desired()))
+            .unwrap();
+        assert_eq!(
+            render(&changes),
+            "A session would:\n\n\
+             \x20 + image bubble-bot:new (build)\n\
+             \x20 + network bubble-bot-app\n\
+             \x20 + container bubble-bot-app-mysql\n\
+             \x20 + container bubble-bot-app\n\
+             \nPlan: 4 to add, 0 to change, 0 to destroy.\n"
+        );
+    }
+
+    #[test]
+    fn kept_resources_are_reused_unless_changed() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_image("bubble-bot:new");
+        let keep = HashMap::from([(KEEP_LABEL.to_string(), "true".to_string())]);
+        mock.add_network("bubble-bot-app", false, keep);
+        mock.add_container(kept("bubble-bot-app-mysql", "mysql:8.0", "d3f"));
+        mock.add_container(kept("bubble-bot-app", "bubble-bot:old", "c0ffee"));
+        // Redis was dropped from the config
+        mock.add_container(kept("bubble-bot-app-redis", "redis:7", "abc"));
+
+        let changes = rt.block_on(changes(&mock, &desired())).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::new(
+                    Action::Replace,
+                    "container bubble-bot-app".to_string(),
+                    vec!["image bubble-bot:old -> bubble-bot:new".to_string()],
+                ),
+                Change::new(
+                    Action::Destroy,
+                    "container bubble-bot-app-redis".to_string(),
+                    Vec::new(),
+                ),
+            ]
+        );
+
+        // An internal network can't be reused, nor anything on it
+        let mut isolated = desired();
+        isolated.internal = true;
+        let replaced = rt.block_on(super::changes(&mock, &isolated)).unwrap();
+        assert_eq!(
+            replaced[0].reasons,
+            vec!["internal false -> true".to_string()]
+        );
+        assert_eq!(replaced[1].reasons, vec!["network replaced".to_string()]);
+        assert!(render(&replaced).ends_with("Plan: 0 to add, 3 to change, 1 to destroy.\n"));
+
+        assert_eq!(
+            render(&[]),
+            "No changes: a session would reuse the running containers and network as they are.\n"
+        );
+    }
+}
@@ -0,0 +1,31 @@
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::io::Read;
+
+/// `bytes` random bytes in hex, from `/dev/urandom`, falling back to the
+/// std hasher's randomly keyed SipHash if it can't be read.
+pub fn hex(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    let read = std::fs::File::open("/dev/urandom").and_then(|mut f| f.read_exact(&mut buf));
+    if read.is_err() {
+        for chunk in buf.chunks_mut(8) {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_usize(std::process::id() as usize);
+            let hash = hasher.finish().to_le_bytes();
+            chunk.copy_from_slice(&hash[..chunk.len()]);
+        }
+    }
+    buf.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_has_two_digits_per_byte_and_differs_per_call() {
+        let first = hex(12);
+        assert_eq!(first.len(), 24);
+        assert!(first.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(first, hex(12));
+    }
+}
@@ -37,6 +37,10 @@ impl Runtime for GoRuntime {
     fn template_context(&self) -> Value {
         context! { go_version => &self.version }
     }
+
+    fn cache_dirs(&self) -> &[&str] {
+        &["/home/dev/.cache/go-build"]
+    }
 }
 
 #[cfg(test)]
@@ -7,12 +7,22 @@ use anyhow::Result;
 use minijinja::value::Value;
 
 use crate::config::Config;
+use crate::templates::ContextFile;
 
 pub trait Runtime {
     fn name(&self) -> &str;
     fn template(&self) -> &str;
     /// Returns the minijinja context values for rendering this runtime's template.
     fn template_context(&self) -> Value;
+    /// Extra build-context files the runtime's layer `COPY`s.
+    fn context_files(&self) -> Vec<ContextFile> {
+        Vec::new()
+    }
+    /// Package-manager cache directories mounted into the `setup` layer
+    /// when building with BuildKit.
+    fn cache_dirs(&self) -> &[&str] {
+        &[]
+    }
 }
 
 /// Builds an ordered list of active runtimes from the resolved config.
@@ -23,7 +33,10 @@ pub fn collect_runtimes(config: &Config) -> Result<Vec<Box<dyn Runtime>>> {
     let mut runtimes: Vec<Box<dyn Runtime>> = Vec::new();
 
     if let Some(ref version) = config.runtimes.php {
-        runtimes.push(Box::new(php::PhpRuntime::new(version)?));
+        let php = php::PhpRuntime::new(version)?
+            .with_extensions(&config.runtimes.php_extensions)?
+            .with_ini(&config.runtimes.php_ini);
+        runtimes.push(Box::new(php));
     }
 
     if let Some(ref version) = config.runtimes.node {
@@ -37,6 +37,10 @@ impl Runtime for NodeRuntime {
     fn template_context(&self) -> Value {
         context! { node_version => &self.version }
     }
+
+    fn cache_dirs(&self) -> &[&str] {
+        &["/home/dev/.npm"]
+    }
 }
 
 #[cfg(test)]
@@ -1,13 +1,27 @@
+use std::collections::BTreeMap;
+
 use minijinja::{context, value::Value};
 
 use super::Runtime;
+use crate::templates::{ContextFile, sha256_hex};
 
 /// Supported PHP versions.
 const SUPPORTED_VERSIONS: &[&str] = &["8.1", "8.2", "8.3"];
 
+/// Build-context path of the rendered `runtimes.php_ini` settings.
+const INI_CONTEXT_PATH: &str = "php/99-bubble-bot.ini";
+
+/// Composer's cache, pinned so BuildKit builds can mount it whatever
+/// `COMPOSER_HOME` is.
+const COMPOSER_CACHE_DIR: &str = "/home/dev/.cache/composer";
+
 #[derive(Debug)]
 pub struct PhpRuntime {
     pub version: String,
+    /// Extensions installed on top of the default set.
+    pub extensions: Vec<String>,
+    /// Rendered php.ini settings, if any are configured.
+    pub ini: Option<String>,
 }
 
 impl PhpRuntime {
@@ -21,8 +35,57 @@ impl PhpRuntime {
         }
         Ok(Self {
             version: version.to_string(),
+            extensions: Vec::new(),
+            ini: None,
         })
     }
+
+    /// Adds extensions, installed as `php<version>-<name>` packages.
+    pub fn with_extensions(mut self, extensions: &[String]) -> anyhow::Result<Self> {
+        for ext in extensions {
+            let valid = !ext.is_empty()
+                && ext
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+            if !valid {
+                anyhow::bail!(
+                    "invalid PHP extension '{ext}': use the package suffix, e.g. 'xdebug' for php{}-xdebug",
+                    self.version
+                );
+            }
+        }
+        self.extensions = extensions.to_vec();
+        Ok(self)
+    }
+
+    /// Sets php.ini overrides for the CLI SAPI.
+    pub fn with_ini(mut self, settings: &BTreeMap<String, toml::Value>) -> Self {
+        self.ini = (!settings.is_empty()).then(|| render_ini(settings));
+        self
+    }
+}
+
+/// Renders settings as php.ini lines. Booleans become `On`/`Off`; strings
+/// are quoted unless they are plain words (`1G`, `debug`).
+fn render_ini(settings: &BTreeMap<String, toml::Value>) -> String {
+    let mut ini = String::new();
+    for (key, value) in settings {
+        let value = match value {
+            toml::Value::Boolean(true) => "On".to_string(),
+            toml::Value::Boolean(false) => "Off".to_string(),
+            toml::Value::String(s)
+                if !s.is_empty()
+                    && s.chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "._-/:,".contains(c)) =>
+            {
+                s.clone()
+            }
+            toml::Value::String(s) => format!("\"{}\"", s.replace('"', "\\\"")),
+            other => other.to_string(),
+        };
+        ini.push_str(&format!("{key} = {value}\n"));
+    }
+    ini
 }
 
 impl Runtime for PhpRuntime {
@@ -35,7 +98,28 @@ impl Runtime for PhpRuntime {
     }
 
     fn template_context(&self) -> Value {
-        context! { php_version => &self.version }
+        context! {
+            php_version => &self.version,
+            php_extensions => &self.extensions,
+            php_ini_path => INI_CONTEXT_PATH,
+            php_ini_digest => self.ini.as_deref().map(sha256_hex),
+            composer_cache_dir => COMPOSER_CACHE_DIR,
+        }
+    }
+
+    fn context_files(&self) -> Vec<ContextFile> {
+        self.ini
+            .iter()
+            .map(|ini| ContextFile {
+                path: INI_CONTEXT_PATH.to_string(),
+                content: ini.clone(),
+                mode: 0o644,
+            })
+            .collect()
+    }
+
+    fn cache_dirs(&self) -> &[&str] {
+        &[COMPOSER_CACHE_DIR]
     }
 }
 
@@ -69,4 +153,55 @@ mod tests {
         assert!(tmpl.contains("composer"));
         assert!(tmpl.contains("ppa:ondrej/php"));
     }
+
+    #[test]
+    fn ini_values_render_as_php_ini() {
+        let settings = BTreeMap::from([
+            ("memory_limit".to_string(), toml::Value::from("1G")),
+            (
+                "xdebug.mode".to_string(),
+                toml::Value::from("debug,develop"),
+            ),
+            ("display_errors".to_string(), toml::Value::from(true)),
+            ("max_execution_time".to_string(), toml::Value::from(0)),
+            (
+                "error_log".to_string(),
+                toml::Value::from("/tmp/php errors.log"),
+            ),
+        ]);
+        assert_eq!(
+            render_ini(&settings),
+            "display_errors = On\n\
+             error_log = \"/tmp/php errors.log\"\n\
+             max_execution_time = 0\n\
+             memory_limit = 1G\n\
+             xdebug.mode = debug,develop\n"
+        );
+    }
+
+    #[test]
+    fn ini_is_a_context_file_only_when_set() {
+        let rt = PhpRuntime::new("8.3").unwrap();
+        assert!(rt.context_files().is_empty());
+
+        let settings = BTreeMap::from([("memory_limit".to_string(), toml::Value::from("1G"))]);
+        let files = rt.with_ini(&settings).context_files();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, INI_CONTEXT_PATH);
+        assert_eq!(files[0].content, "memory_limit = 1G\n");
+    }
+
+    #[test]
+    fn invalid_extension_names_error() {
+        let rt = PhpRuntime::new("8.3").unwrap();
+        let ok = rt.with_extensions(&["xdebug".to_string(), "imagick".to_string()]);
+        assert_eq!(ok.unwrap().extensions, vec!["xdebug", "imagick"]);
+
+        let rt = PhpRuntime::new("8.3").unwrap();
+        let err = rt
+            .with_extensions(&["php8.3-xdebug; rm -rf /".to_string()])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("invalid PHP extension"), "{err}");
+    }
 }
@@ -2,7 +2,7 @@ use minijinja::{context, value::Value};
 
 use super::Runtime;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct RustRuntime;
 
 impl RustRuntime {
@@ -23,6 +23,10 @@ impl Runtime for RustRuntime {
     fn template_context(&self) -> Value {
         context! {}
     }
+
+    fn cache_dirs(&self) -> &[&str] {
+        &["/usr/local/cargo/registry"]
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,110 @@
+use crate::config::{CustomServiceConfig, Readiness};
+use crate::services::Service;
+
+/// A service from `[services.custom.<name>]` (or imported from
+/// `services.from_compose`): any image, with the env, mounts, and readiness
+/// check given in the config.
+pub struct CustomService {
+    name: String,
+    config: CustomServiceConfig,
+    project_id: String,
+}
+
+impl CustomService {
+    pub fn new(name: String, config: CustomServiceConfig, project_id: String) -> Self {
+        Self {
+            name,
+            config,
+            project_id,
+        }
+    }
+}
+
+impl Service for CustomService {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn image(&self) -> String {
+        self.config.image.clone()
+    }
+
+    fn container_env(&self) -> Vec<String> {
+        self.config
+            .env
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect()
+    }
+
+    fn dev_env(&self) -> Vec<String> {
+        self.config
+            .dev_env
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect()
+    }
+
+    fn volume(&self) -> Option<String> {
+        let target = self.config.volume.as_ref()?;
+        Some(format!(
+            "bubble-bot-{}-{}-data:{target}",
+            self.project_id, self.name
+        ))
+    }
+
+    fn binds(&self) -> Vec<String> {
+        self.config.bind_mounts()
+    }
+
+    fn readiness(&self) -> Option<Readiness> {
+        self.config.readiness.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_and_readiness_come_from_the_config() {
+        let config: CustomServiceConfig = toml::from_str(
+            r#"
+            image = "elasticsearch:8.13.0"
+            env = { "discovery.type" = "single-node", ES_JAVA_OPTS = "-Xms512m" }
+            dev_env = { ELASTICSEARCH_URL = "http://search:9200" }
+            readiness = { tcp = 9200 }
+            "#,
+        )
+        .unwrap();
+        let svc = CustomService::new("search".to_string(), config, "shop".to_string());
+        assert_eq!(svc.image(), "elasticsearch:8.13.0");
+        assert_eq!(
+            svc.container_env(),
+            vec!["ES_JAVA_OPTS=-Xms512m", "discovery.type=single-node"]
+        );
+        assert_eq!(svc.dev_env(), vec!["ELASTICSEARCH_URL=http://search:9200"]);
+        assert_eq!(svc.readiness(), Some(Readiness::Tcp(9200)));
+        assert_eq!(svc.container_name("shop"), "bubble-bot-shop-search");
+        assert!(svc.volume().is_none());
+    }
+
+    #[test]
+    fn volume_is_named_after_the_service() {
+        let config = CustomServiceConfig {
+            image: "postgres:16".to_string(),
+            volume: Some("/var/lib/postgresql/data".to_string()),
+            binds: vec!["/src/shop/init:/docker-entrypoint-initdb.d:ro".to_string()],
+            ..Default::default()
+        };
+        let svc = CustomService::new("db".to_string(), config, "shop".to_string());
+        assert_eq!(
+            svc.volume().unwrap(),
+            "bubble-bot-shop-db-data:/var/lib/postgresql/data"
+        );
+        assert_eq!(
+            svc.binds(),
+            vec!["/src/shop/init:/docker-entrypoint-initdb.d:ro"]
+        );
+    }
+}
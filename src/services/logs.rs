@@ -0,0 +1,58 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::docker::images::build_log_dir;
+
+/// Earlier sessions' logs kept per service, as `<service>.log.1` (the
+/// previous session) through `<service>.log.5`.
+const KEPT_LOGS: u32 = 5;
+
+/// Where a project's service logs are written:
+/// `~/.local/share/bubble-bot/logs/<project ID>` on Linux (the platform's
+/// local data directory elsewhere), beside the build logs.
+pub fn log_dir(project: &str) -> Option<PathBuf> {
+    Some(build_log_dir()?.join(project))
+}
+
+/// Creates `<service>.log` in `dir` for this session, first moving the
+/// logs of earlier sessions up one number and dropping the oldest.
+pub fn create(dir: &Path, service: &str) -> Result<File> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{service}.log"));
+    let numbered = |n: u32| dir.join(format!("{service}.log.{n}"));
+    for n in (1..KEPT_LOGS).rev() {
+        if numbered(n).exists() {
+            std::fs::rename(numbered(n), numbered(n + 1))
+                .with_context(|| format!("failed to rotate {}", numbered(n).display()))?;
+        }
+    }
+    if path.exists() {
+        std::fs::rename(&path, numbered(1))
+            .with_context(|| format!("failed to rotate {}", path.display()))?;
+    }
+    File::create(&path).with_context(|| format!("failed to create {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn each_session_rotates_the_previous_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let logs = dir.path().join("shop-1a2b3c4d");
+        for session in 1..=7 {
+            let mut file = create(&logs, "mysql").unwrap();
+            writeln!(file, "session {session}").unwrap();
+        }
+        let read = |name: &str| std::fs::read_to_string(logs.join(name)).unwrap();
+        assert_eq!(read("mysql.log"), "session 7\n");
+        assert_eq!(read("mysql.log.1"), "session 6\n");
+        assert_eq!(read("mysql.log.5"), "session 2\n");
+        assert!(!logs.join("mysql.log.6").exists());
+    }
+}
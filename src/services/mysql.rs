@@ -1,23 +1,52 @@
-use crate::config::MysqlConfig;
-use crate::services::Service;
+use std::collections::BTreeMap;
+
+use crate::config::{MysqlConfig, Readiness};
+use crate::services::{
+    Connection, DEFAULT_ENV_PREFIX, Service, connection_url, env_value, session_password,
+    url_var_name,
+};
+
+/// Where `[services.mysql.settings]` are written; the image includes this
+/// directory from its `my.cnf`.
+const SETTINGS_PATH: &str = "/etc/mysql/conf.d/bubble-bot.cnf";
 
 pub struct MysqlService {
     config: MysqlConfig,
-    project_name: String,
+    project_id: String,
+    /// Whether `config.password` was generated for this session.
+    random_password: bool,
 }
 
 impl MysqlService {
-    pub fn new(config: MysqlConfig, project_name: String) -> Self {
+    pub fn new(mut config: MysqlConfig, project_id: String) -> Self {
+        let (password, random_password) = session_password(&config.password);
+        config.password = password;
         Self {
             config,
-            project_name,
+            project_id,
+            random_password,
         }
     }
 
     /// Volume name for MySQL data persistence.
     fn volume_name(&self) -> String {
-        format!("bubble-bot-{}-mysql-data", self.project_name)
+        format!("bubble-bot-{}-mysql-data", self.project_id)
+    }
+}
+
+/// Renders option-file settings as a `[mysqld]` section.
+fn render_cnf(settings: &BTreeMap<String, toml::Value>) -> String {
+    let mut cnf = "[mysqld]\n".to_string();
+    for (key, value) in settings {
+        let value = match value {
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Boolean(true) => "ON".to_string(),
+            toml::Value::Boolean(false) => "OFF".to_string(),
+            other => other.to_string(),
+        };
+        cnf.push_str(&format!("{key} = {value}\n"));
     }
+    cnf
 }
 
 impl Service for MysqlService {
@@ -43,31 +72,128 @@ impl Service for MysqlService {
     }
 
     fn dev_env(&self) -> Vec<String> {
+        let prefix = self
+            .config
+            .env_prefix
+            .as_deref()
+            .unwrap_or(DEFAULT_ENV_PREFIX);
         vec![
-            "DB_HOST=mysql".to_string(),
-            "DB_PORT=3306".to_string(),
-            format!("DB_DATABASE={}", self.config.database),
-            format!("DB_USERNAME={}", self.config.username),
-            format!("DB_PASSWORD={}", self.config.password),
+            format!("{prefix}_HOST=mysql"),
+            format!("{prefix}_PORT=3306"),
+            format!("{prefix}_DATABASE={}", self.config.database),
+            format!("{prefix}_USERNAME={}", self.config.username),
+            format!("{prefix}_PASSWORD={}", self.config.password),
+            format!(
+                "{}={}",
+                url_var_name(
+                    self.config.env_prefix.as_deref(),
+                    self.config.url_env.as_deref()
+                ),
+                connection_url(
+                    "mysql",
+                    &self.config.username,
+                    &self.config.password,
+                    "mysql",
+                    3306,
+                    &self.config.database
+                )
+            ),
         ]
     }
 
+    /// None with a random password: the data directory keeps the password
+    /// it was initialized with, so each session starts from an empty one.
     fn volume(&self) -> Option<String> {
+        if self.random_password {
+            return None;
+        }
         Some(format!("{}:/var/lib/mysql", self.volume_name()))
     }
 
-    fn readiness_cmd(&self) -> Vec<String> {
-        vec![
+    /// Init scripts, numbered so the entrypoint's alphabetical order is the
+    /// configured order.
+    fn binds(&self) -> Vec<String> {
+        self.config
+            .init_script_paths()
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                format!(
+                    "{}:/docker-entrypoint-initdb.d/{:02}-{name}:ro",
+                    path.display(),
+                    i + 1
+                )
+            })
+            .collect()
+    }
+
+    /// Writes the settings file, then hands off to the image's entrypoint.
+    fn entrypoint(&self) -> Option<Vec<String>> {
+        if self.config.settings.is_empty() {
+            return None;
+        }
+        let script = format!(
+            "cat > {SETTINGS_PATH} <<'EOF'\n{}EOF\nexec docker-entrypoint.sh mysqld",
+            render_cnf(&self.config.settings)
+        );
+        Some(vec!["sh".to_string(), "-c".to_string(), script])
+    }
+
+    fn readiness(&self) -> Option<Readiness> {
+        Some(Readiness::Cmd(vec![
             "mysqladmin".to_string(),
             "ping".to_string(),
             "-h".to_string(),
             "127.0.0.1".to_string(),
             "--silent".to_string(),
-        ]
+        ]))
+    }
+
+    fn client_cmd(&self) -> Option<Vec<String>> {
+        // The root password is only in MYSQL_ROOT_PASSWORD; other users also get MYSQL_PASSWORD
+        let password_var = if self.config.username == "root" {
+            "MYSQL_ROOT_PASSWORD"
+        } else {
+            "MYSQL_PASSWORD"
+        };
+        Some(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            format!("MYSQL_PWD=\"${password_var}\" exec mysql -u \"$1\" \"$2\""),
+            "sh".to_string(),
+            self.config.username.clone(),
+            self.config.database.clone(),
+        ])
+    }
+
+    fn connection(&self) -> Option<Connection> {
+        Some(Connection {
+            scheme: "mysql",
+            port: 3306,
+            database: Some(self.config.database.clone()),
+            username: Some(self.config.username.clone()),
+            password: Some(self.config.password.clone()),
+            password_var: Some("MYSQL_ROOT_PASSWORD"),
+        })
+    }
+
+    fn session_secrets(&self) -> Vec<&str> {
+        if self.random_password {
+            vec![self.config.password.as_str()]
+        } else {
+            Vec::new()
+        }
     }
 
-    fn container_name(&self, _project: &str) -> String {
-        format!("bubble-bot-{}-mysql", self.project_name)
+    fn reuse_session_secrets(&mut self, container_env: &[String]) {
+        if !self.random_password {
+            return;
+        }
+        if let Some(password) = env_value(container_env, "MYSQL_ROOT_PASSWORD") {
+            crate::redact::register(password);
+            self.config.password = password.to_string();
+        }
     }
 }
 
@@ -137,7 +263,7 @@ mod tests {
     }
 
     #[test]
-    fn volume_uses_project_name() {
+    fn volume_uses_project_id() {
         let svc = default_service();
         assert_eq!(
             svc.volume().unwrap(),
@@ -145,6 +271,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn random_password_is_shared_and_not_persisted() {
+        let svc = MysqlService::new(
+            MysqlConfig {
+                password: "random".to_string(),
+                ..Default::default()
+            },
+            "testproject".to_string(),
+        );
+        let secrets = svc.session_secrets();
+        let password = secrets[0];
+        assert_ne!(password, "random");
+        assert!(
+            svc.container_env()
+                .contains(&format!("MYSQL_ROOT_PASSWORD={password}"))
+        );
+        assert!(svc.dev_env().contains(&format!("DB_PASSWORD={password}")));
+        assert!(svc.volume().is_none());
+        assert!(default_service().session_secrets().is_empty());
+    }
+
+    #[test]
+    fn random_password_is_taken_over_from_a_kept_container() {
+        let mut svc = MysqlService::new(
+            MysqlConfig {
+                password: "random".to_string(),
+                ..Default::default()
+            },
+            "testproject".to_string(),
+        );
+        svc.reuse_session_secrets(&["MYSQL_ROOT_PASSWORD=0123abcd".to_string()]);
+        assert_eq!(svc.session_secrets(), vec!["0123abcd"]);
+        assert!(svc.dev_env().contains(&"DB_PASSWORD=0123abcd".to_string()));
+
+        // A configured password is never replaced
+        let mut fixed = default_service();
+        fixed.reuse_session_secrets(&["MYSQL_ROOT_PASSWORD=0123abcd".to_string()]);
+        assert!(
+            fixed
+                .dev_env()
+                .contains(&"DB_PASSWORD=password".to_string())
+        );
+    }
+
     #[test]
     fn container_name_includes_project() {
         let svc = default_service();
@@ -155,10 +325,90 @@ mod tests {
     }
 
     #[test]
-    fn readiness_cmd_is_mysqladmin_ping() {
+    fn readiness_is_mysqladmin_ping() {
         let svc = default_service();
-        let cmd = svc.readiness_cmd();
+        let Some(Readiness::Cmd(cmd)) = svc.readiness() else {
+            panic!("expected a readiness command");
+        };
         assert_eq!(cmd[0], "mysqladmin");
         assert!(cmd.contains(&"ping".to_string()));
     }
+
+    #[test]
+    fn client_cmd_reads_root_password_from_container_env() {
+        let cmd = default_service().client_cmd().unwrap();
+        assert_eq!(cmd[..2], ["sh", "-c"]);
+        assert!(cmd[2].contains("MYSQL_PWD=\"$MYSQL_ROOT_PASSWORD\""));
+        assert_eq!(cmd[4..], ["root", "app"]);
+        assert!(!cmd.iter().any(|arg| arg.contains("password")));
+    }
+
+    #[test]
+    fn settings_are_written_before_the_image_entrypoint() {
+        assert!(default_service().entrypoint().is_none());
+
+        let mut settings = BTreeMap::new();
+        settings.insert(
+            "sql_mode".to_string(),
+            toml::Value::from("STRICT_TRANS_TABLES,NO_ZERO_DATE"),
+        );
+        settings.insert("max_connections".to_string(), toml::Value::from(500));
+        settings.insert("skip_name_resolve".to_string(), toml::Value::from(true));
+        let svc = MysqlService::new(
+            MysqlConfig {
+                settings,
+                ..Default::default()
+            },
+            "proj".to_string(),
+        );
+        let entrypoint = svc.entrypoint().unwrap();
+        assert_eq!(entrypoint[..2], ["sh", "-c"]);
+        assert_eq!(
+            entrypoint[2],
+            "cat > /etc/mysql/conf.d/bubble-bot.cnf <<'EOF'\n\
+             [mysqld]\n\
+             max_connections = 500\n\
+             skip_name_resolve = ON\n\
+             sql_mode = STRICT_TRANS_TABLES,NO_ZERO_DATE\n\
+             EOF\n\
+             exec docker-entrypoint.sh mysqld"
+        );
+    }
+
+    #[test]
+    fn init_scripts_mount_in_configured_order() {
+        let svc = MysqlService::new(
+            MysqlConfig {
+                init_scripts: vec!["db/schema.sql".to_string(), "/abs/seed.sql".to_string()],
+                ..Default::default()
+            },
+            "proj".to_string(),
+        );
+        let binds = svc.binds();
+        assert_eq!(binds.len(), 2);
+        assert!(
+            binds[0].ends_with("/db/schema.sql:/docker-entrypoint-initdb.d/01-schema.sql:ro"),
+            "{binds:?}"
+        );
+        assert_eq!(
+            binds[1],
+            "/abs/seed.sql:/docker-entrypoint-initdb.d/02-seed.sql:ro"
+        );
+        assert!(default_service().binds().is_empty());
+    }
+
+    #[test]
+    fn client_cmd_for_non_root_user() {
+        let svc = MysqlService::new(
+            MysqlConfig {
+                username: "admin".to_string(),
+                database: "shop".to_string(),
+                ..Default::default()
+            },
+            "proj".to_string(),
+        );
+        let cmd = svc.client_cmd().unwrap();
+        assert!(cmd[2].contains("MYSQL_PWD=\"$MYSQL_PASSWORD\""));
+        assert_eq!(cmd[4..], ["admin", "shop"]);
+    }
 }
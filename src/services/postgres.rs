@@ -1,22 +1,30 @@
-use crate::config::PostgresConfig;
-use crate::services::Service;
+use crate::config::{PostgresConfig, Readiness};
+use crate::services::{
+    Connection, DEFAULT_ENV_PREFIX, Service, connection_url, env_value, session_password,
+    url_var_name,
+};
 
 pub struct PostgresService {
     config: PostgresConfig,
-    project_name: String,
+    project_id: String,
+    /// Whether `config.password` was generated for this session.
+    random_password: bool,
 }
 
 impl PostgresService {
-    pub fn new(config: PostgresConfig, project_name: String) -> Self {
+    pub fn new(mut config: PostgresConfig, project_id: String) -> Self {
+        let (password, random_password) = session_password(&config.password);
+        config.password = password;
         Self {
             config,
-            project_name,
+            project_id,
+            random_password,
         }
     }
 
     /// Volume name for PostgreSQL data persistence.
     fn volume_name(&self) -> String {
-        format!("bubble-bot-{}-postgres-data", self.project_name)
+        format!("bubble-bot-{}-postgres-data", self.project_id)
     }
 }
 
@@ -38,29 +46,90 @@ impl Service for PostgresService {
     }
 
     fn dev_env(&self) -> Vec<String> {
+        let prefix = self
+            .config
+            .env_prefix
+            .as_deref()
+            .unwrap_or(DEFAULT_ENV_PREFIX);
         vec![
-            "DB_HOST=postgres".to_string(),
-            "DB_PORT=5432".to_string(),
-            format!("DB_DATABASE={}", self.config.database),
-            format!("DB_USERNAME={}", self.config.username),
-            format!("DB_PASSWORD={}", self.config.password),
+            format!("{prefix}_HOST=postgres"),
+            format!("{prefix}_PORT=5432"),
+            format!("{prefix}_DATABASE={}", self.config.database),
+            format!("{prefix}_USERNAME={}", self.config.username),
+            format!("{prefix}_PASSWORD={}", self.config.password),
+            format!(
+                "{}={}",
+                url_var_name(
+                    self.config.env_prefix.as_deref(),
+                    self.config.url_env.as_deref()
+                ),
+                connection_url(
+                    "postgres",
+                    &self.config.username,
+                    &self.config.password,
+                    "postgres",
+                    5432,
+                    &self.config.database
+                )
+            ),
         ]
     }
 
+    /// None with a random password: the data directory keeps the password
+    /// it was initialized with, so each session starts from an empty one.
     fn volume(&self) -> Option<String> {
+        if self.random_password {
+            return None;
+        }
         Some(format!("{}:/var/lib/postgresql/data", self.volume_name()))
     }
 
-    fn readiness_cmd(&self) -> Vec<String> {
-        vec![
+    fn readiness(&self) -> Option<Readiness> {
+        Some(Readiness::Cmd(vec![
             "pg_isready".to_string(),
             "-U".to_string(),
             self.config.username.clone(),
-        ]
+        ]))
+    }
+
+    fn client_cmd(&self) -> Option<Vec<String>> {
+        Some(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "PGPASSWORD=\"$POSTGRES_PASSWORD\" exec psql -U \"$1\" \"$2\"".to_string(),
+            "sh".to_string(),
+            self.config.username.clone(),
+            self.config.database.clone(),
+        ])
+    }
+
+    fn connection(&self) -> Option<Connection> {
+        Some(Connection {
+            scheme: "postgres",
+            port: 5432,
+            database: Some(self.config.database.clone()),
+            username: Some(self.config.username.clone()),
+            password: Some(self.config.password.clone()),
+            password_var: Some("POSTGRES_PASSWORD"),
+        })
+    }
+
+    fn session_secrets(&self) -> Vec<&str> {
+        if self.random_password {
+            vec![self.config.password.as_str()]
+        } else {
+            Vec::new()
+        }
     }
 
-    fn container_name(&self, _project: &str) -> String {
-        format!("bubble-bot-{}-postgres", self.project_name)
+    fn reuse_session_secrets(&mut self, container_env: &[String]) {
+        if !self.random_password {
+            return;
+        }
+        if let Some(password) = env_value(container_env, "POSTGRES_PASSWORD") {
+            crate::redact::register(password);
+            self.config.password = password.to_string();
+        }
     }
 }
 
@@ -131,7 +200,21 @@ mod tests {
     }
 
     #[test]
-    fn volume_uses_project_name() {
+    fn env_prefix_renames_connection_vars() {
+        let svc = PostgresService::new(
+            PostgresConfig {
+                env_prefix: Some("PG".to_string()),
+                ..Default::default()
+            },
+            "proj".to_string(),
+        );
+        let env = svc.dev_env();
+        assert!(env.contains(&"PG_HOST=postgres".to_string()));
+        assert!(env.iter().all(|var| var.starts_with("PG_")), "{env:?}");
+    }
+
+    #[test]
+    fn volume_uses_project_id() {
         let svc = default_service();
         assert_eq!(
             svc.volume().unwrap(),
@@ -139,6 +222,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn random_password_is_shared_and_not_persisted() {
+        let svc = PostgresService::new(
+            PostgresConfig {
+                password: "random".to_string(),
+                ..Default::default()
+            },
+            "testproject".to_string(),
+        );
+        let secrets = svc.session_secrets();
+        let password = secrets[0];
+        assert_ne!(password, "random");
+        assert!(
+            svc.container_env()
+                .contains(&format!("POSTGRES_PASSWORD={password}"))
+        );
+        assert!(svc.dev_env().contains(&format!("DB_PASSWORD={password}")));
+        assert!(svc.volume().is_none());
+        assert!(default_service().session_secrets().is_empty());
+    }
+
     #[test]
     fn container_name_includes_project() {
         let svc = default_service();
@@ -149,9 +253,19 @@ mod tests {
     }
 
     #[test]
-    fn readiness_cmd_is_pg_isready() {
+    fn readiness_is_pg_isready() {
         let svc = default_service();
-        let cmd = svc.readiness_cmd();
+        let Some(Readiness::Cmd(cmd)) = svc.readiness() else {
+            panic!("expected a readiness command");
+        };
         assert_eq!(cmd, vec!["pg_isready", "-U", "postgres"]);
     }
+
+    #[test]
+    fn client_cmd_is_psql_with_configured_user_and_db() {
+        let cmd = default_service().client_cmd().unwrap();
+        assert!(cmd[2].contains("exec psql"));
+        assert!(cmd[2].contains("PGPASSWORD=\"$POSTGRES_PASSWORD\""));
+        assert_eq!(cmd[4..], ["postgres", "app"]);
+    }
 }
@@ -1,14 +1,7 @@
-use crate::services::Service;
+use crate::config::Readiness;
+use crate::services::{Connection, Service};
 
-pub struct RedisService {
-    project_name: String,
-}
-
-impl RedisService {
-    pub fn new(project_name: String) -> Self {
-        Self { project_name }
-    }
-}
+pub struct RedisService;
 
 impl Service for RedisService {
     fn name(&self) -> &str {
@@ -27,6 +20,7 @@ impl Service for RedisService {
         vec![
             "REDIS_HOST=redis".to_string(),
             "REDIS_PORT=6379".to_string(),
+            "REDIS_URL=redis://redis:6379".to_string(),
         ]
     }
 
@@ -34,12 +28,26 @@ impl Service for RedisService {
         None
     }
 
-    fn readiness_cmd(&self) -> Vec<String> {
-        vec!["redis-cli".to_string(), "ping".to_string()]
+    fn readiness(&self) -> Option<Readiness> {
+        Some(Readiness::Cmd(vec![
+            "redis-cli".to_string(),
+            "ping".to_string(),
+        ]))
+    }
+
+    fn client_cmd(&self) -> Option<Vec<String>> {
+        Some(vec!["redis-cli".to_string()])
     }
 
-    fn container_name(&self, _project: &str) -> String {
-        format!("bubble-bot-{}-redis", self.project_name)
+    fn connection(&self) -> Option<Connection> {
+        Some(Connection {
+            scheme: "redis",
+            port: 6379,
+            database: None,
+            username: None,
+            password: None,
+            password_var: None,
+        })
     }
 }
 
@@ -48,7 +56,7 @@ mod tests {
     use super::*;
 
     fn default_service() -> RedisService {
-        RedisService::new("testproject".to_string())
+        RedisService
     }
 
     #[test]
@@ -71,7 +79,8 @@ mod tests {
         let env = default_service().dev_env();
         assert!(env.contains(&"REDIS_HOST=redis".to_string()));
         assert!(env.contains(&"REDIS_PORT=6379".to_string()));
-        assert_eq!(env.len(), 2);
+        assert!(env.contains(&"REDIS_URL=redis://redis:6379".to_string()));
+        assert_eq!(env.len(), 3);
     }
 
     #[test]
@@ -80,9 +89,16 @@ mod tests {
     }
 
     #[test]
-    fn readiness_cmd_is_redis_cli_ping() {
-        let cmd = default_service().readiness_cmd();
-        assert_eq!(cmd, vec!["redis-cli", "ping"]);
+    fn readiness_is_redis_cli_ping() {
+        assert_eq!(
+            default_service().readiness(),
+            Some(Readiness::Cmd(vec!["redis-cli".into(), "ping".into()]))
+        );
+    }
+
+    #[test]
+    fn client_cmd_is_redis_cli() {
+        assert_eq!(default_service().client_cmd().unwrap(), vec!["redis-cli"]);
     }
 
     #[test]
@@ -0,0 +1,1958 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use bollard::Docker;
+use tokio::sync::{Mutex, watch};
+use tracing::{Instrument, info, info_span, warn};
+
+use crate::artifacts;
+use crate::auth::{resolve_claude_config, resolve_oauth_token, verify_oauth_token};
+use crate::config::{ArtifactConfig, AuthConfig, Config, project_dir, project_id};
+use crate::docker::backend::ContainerBackend;
+use crate::docker::connect;
+use crate::docker::containers::{
+    ContainerManager, ContainerOpts, ContainerStatus, ExecOpts, UserMapping,
+    default_container_name, foreground_pid, host_owner, resolve_groups,
+};
+use crate::docker::images::{self, ImageBuilder};
+use crate::docker::networks::{NetworkManager, default_network_name};
+use crate::docker::ownership::{KEEP_LABEL, Owner, Reaper, SESSION_ENV};
+use crate::docker::platform as docker_platform;
+use crate::docker::registry::RegistryAuth;
+use crate::docker::volumes::{self, VolumeManager};
+use crate::env_context;
+use crate::error::Error;
+use crate::gitconfig;
+use crate::hooks::HookRunner;
+use crate::laravel::{self, EnvPatch, laravel_vars, without_secrets};
+use crate::lock::ProjectLock;
+use crate::motd;
+use crate::output;
+use crate::proxy::ProxySettings;
+use crate::services::{Service, collect_service_env_vars, collect_services, logs, service_images};
+use crate::sessions::{Registry, SessionEntry};
+use crate::shell;
+use crate::templates::{RenderResult, TemplateRenderer, sha256_hex};
+use crate::workers::WorkerSupervisor;
+
+/// Tracks all Docker resources that need cleanup on shutdown.
+/// Shared between the main task and signal handler.
+pub struct CleanupState<B = Docker> {
+    docker: Option<B>,
+    dev_container_id: Option<String>,
+    /// Service containers as `(service name, container ID)`.
+    service_containers: Vec<(String, String)>,
+    network_name: Option<String>,
+    /// Laravel `.env` patched for the session, restored on cleanup.
+    env_patch: Option<EnvPatch>,
+    /// Supervised `[[workers]]`, stopped before the containers are removed.
+    workers: Option<WorkerSupervisor>,
+    /// Background readiness checks (`services.wait = false`), cancelled so
+    /// they don't report on containers being removed.
+    readiness_tasks: Vec<tokio::task::JoinHandle<()>>,
+    /// `[[artifacts]]` copied out of the dev container before it is removed.
+    artifacts: Vec<ArtifactConfig>,
+    /// `uid:gid` given back the files under `/workspace` before the dev
+    /// container is removed (`workspace.fix_ownership`).
+    workspace_owner: Option<String>,
+    /// Volumes under `/workspace` (`mount.exclude`) the ownership fix skips.
+    workspace_volumes: Vec<String>,
+    /// Rewrites the dev container's OAuth credentials (`auth.refresh`).
+    credential_refresh: Option<tokio::task::JoinHandle<()>>,
+    /// Copy service output to their log files, ending as the containers stop.
+    log_tasks: Vec<tokio::task::JoinHandle<()>>,
+    /// `--max-duration` ran out with the main command still running. Its
+    /// exec only ends once the dev container is removed, so `--keep` doesn't
+    /// apply.
+    timed_out: bool,
+}
+
+impl<B> Default for CleanupState<B> {
+    fn default() -> Self {
+        Self {
+            docker: None,
+            dev_container_id: None,
+            service_containers: Vec::new(),
+            network_name: None,
+            env_patch: None,
+            workers: None,
+            readiness_tasks: Vec::new(),
+            artifacts: Vec::new(),
+            workspace_owner: None,
+            workspace_volumes: Vec::new(),
+            credential_refresh: None,
+            log_tasks: Vec::new(),
+            timed_out: false,
+        }
+    }
+}
+
+impl<B: ContainerBackend> CleanupState<B> {
+    /// Tears the session down, or with `keep` releases it instead (see
+    /// [`keep`](Self::keep)) and returns what was kept. A session that ran
+    /// out of time is always torn down.
+    pub async fn finish(&mut self, keep: bool) -> Option<(Vec<String>, Option<String>)> {
+        if keep && !self.timed_out {
+            return Some(self.keep().await);
+        }
+        self.cleanup().instrument(info_span!("cleanup")).await;
+        None
+    }
+
+    /// Performs cleanup of all tracked Docker resources, reporting each one.
+    /// A Ctrl-C during cleanup skips graceful stops and force-removes what's
+    /// left. Safe to call multiple times — resources are cleared after cleanup.
+    pub async fn cleanup(&mut self) {
+        // Restore .env first; it doesn't depend on Docker
+        if let Some(patch) = self.env_patch.take() {
+            if let Err(e) = patch.restore() {
+                warn!(error = %e, "failed to restore .env");
+            }
+        }
+
+        let Some(docker) = self.docker.take() else {
+            return;
+        };
+
+        for task in self
+            .readiness_tasks
+            .drain(..)
+            .chain(self.credential_refresh.take())
+        {
+            task.abort();
+        }
+
+        let (force_tx, mut force) = watch::channel(false);
+        let escalate = tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                eprintln!("forcing cleanup: killing containers without waiting");
+                let _ = force_tx.send(true);
+            }
+            // Keep the sender alive; a closed channel would read as forced
+            std::future::pending::<()>().await;
+        });
+
+        if let Some(workers) = self.workers.take() {
+            tokio::select! {
+                _ = workers.stop() => {}
+                _ = force.wait_for(|forced| *forced) => {}
+            }
+        }
+
+        let container_mgr = ContainerManager::new(docker.clone());
+        let network_mgr = NetworkManager::new(docker);
+
+        // Copy artifacts out and fix workspace ownership while the dev
+        // container still runs, unless forced
+        if let Some(id) = &self.dev_container_id {
+            let artifacts = std::mem::take(&mut self.artifacts);
+            if !artifacts.is_empty() && !*force.borrow() {
+                let pb = output::spinner("Copying artifacts...".to_string());
+                let copied = artifacts::collect(id, &artifacts, &project_dir());
+                output::finish(&pb, format!("Copied {copied} artifact(s)"));
+            }
+            if let Some(owner) = self.workspace_owner.take() {
+                if !*force.borrow() {
+                    let pb = output::spinner("Fixing workspace ownership...".to_string());
+                    let skip = std::mem::take(&mut self.workspace_volumes);
+                    match container_mgr.fix_workspace_ownership(id, &owner, &skip) {
+                        Ok(fixed) => output::finish(
+                            &pb,
+                            format!("Gave {fixed} workspace path(s) back to {owner}"),
+                        ),
+                        Err(e) => {
+                            output::finish(&pb, "Failed to fix workspace ownership".to_string());
+                            warn!(error = %e, "files in the workspace may not be yours");
+                        }
+                    }
+                }
+            }
+        }
+
+        // Stop and remove dev container
+        if let Some(id) = self.dev_container_id.take() {
+            remove_container(&container_mgr, &id, "dev container", &mut force).await;
+        }
+
+        // Stop and remove service containers
+        for (name, id) in self.service_containers.drain(..) {
+            remove_container(
+                &container_mgr,
+                &id,
+                &format!("{name} container"),
+                &mut force,
+            )
+            .await;
+        }
+
+        // Let the last lines of the stopped services reach their log files
+        for task in self.log_tasks.drain(..) {
+            let abort = task.abort_handle();
+            if tokio::time::timeout(LOG_DRAIN_TIMEOUT, task).await.is_err() {
+                abort.abort();
+            }
+        }
+
+        // Remove network
+        if let Some(name) = self.network_name.take() {
+            let pb = output::spinner(format!("Removing network {name}..."));
+            match network_mgr.remove_network(&name).await {
+                Ok(()) => output::finish(&pb, format!("Removed network {name}")),
+                Err(e) => {
+                    output::finish(&pb, format!("Failed to remove network {name}"));
+                    warn!(error = %e, "failed to clean up network");
+                }
+            }
+        }
+
+        escalate.abort();
+    }
+}
+
+impl<B> CleanupState<B> {
+    /// Releases the session's containers and network instead of removing
+    /// them (`--keep`), returning the container IDs (dev container first)
+    /// and the network name. The `.env` patch and workers belong to this
+    /// process, so the `.env` is still restored and workers still stopped.
+    pub async fn keep(&mut self) -> (Vec<String>, Option<String>) {
+        if let Some(patch) = self.env_patch.take() {
+            if let Err(e) = patch.restore() {
+                warn!(error = %e, "failed to restore .env");
+            }
+        }
+        for task in self
+            .readiness_tasks
+            .drain(..)
+            .chain(self.credential_refresh.take())
+            .chain(self.log_tasks.drain(..))
+        {
+            task.abort();
+        }
+        if let Some(workers) = self.workers.take() {
+            workers.stop().await;
+        }
+        self.docker = None;
+
+        let mut containers: Vec<String> = self.dev_container_id.take().into_iter().collect();
+        containers.extend(self.service_containers.drain(..).map(|(_, id)| id));
+        (containers, self.network_name.take())
+    }
+}
+
+/// How to get back into, and later stop, a session kept with `--keep`.
+fn kept_message(
+    container_name: &str,
+    shell: &str,
+    containers: &[String],
+    network: Option<&str>,
+) -> String {
+    let ids: Vec<&str> = containers
+        .iter()
+        .map(|id| &id[..id.len().min(12)])
+        .collect();
+    let mut stop = format!("docker rm -f {}", ids.join(" "));
+    if let Some(network) = network {
+        stop.push_str(&format!(" && docker network rm {network}"));
+    }
+    format!(
+        "Kept the session running. Reattach with:\n  docker exec -it {container_name} {shell}\n\
+         Stop it with:\n  {stop}\n\
+         (the next session in this project reuses whatever hasn't changed)"
+    )
+}
+
+/// Stops and removes one container for [`CleanupState::cleanup`], skipping
+/// (or cutting short) the graceful stop once `force` is set.
+async fn remove_container<B: ContainerBackend>(
+    container_mgr: &ContainerManager<B>,
+    id: &str,
+    label: &str,
+    force: &mut watch::Receiver<bool>,
+) {
+    let pb = output::spinner(format!("Stopping {label}..."));
+    if !*force.borrow() {
+        tokio::select! {
+            _ = container_mgr.stop(id) => {}
+            _ = force.wait_for(|forced| *forced) => {
+                output::update(&pb, format!("Killing {label}..."));
+            }
+        }
+    }
+    match container_mgr.remove(id).await {
+        Ok(()) => output::finish(&pb, format!("Removed {label}")),
+        Err(e) => {
+            output::finish(&pb, format!("Failed to remove {label}"));
+            warn!(error = %e, "failed to clean up {label}");
+        }
+    }
+}
+
+/// Spawns a background task that listens for SIGINT/SIGTERM and performs
+/// cleanup of all tracked Docker resources. Returns a `JoinHandle` that
+/// should be aborted once the normal cleanup path completes.
+///
+/// While the main command runs, the first SIGINT is forwarded to its
+/// `docker exec` instead, so Ctrl-C interrupts the command rather than the
+/// session; the session is torn down when the command exits or on a second
+/// Ctrl-C.
+fn spawn_signal_handler(state: Arc<Mutex<CleanupState>>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler");
+        let mut forwarded = false;
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {
+                    if let Some(pid) = foreground_pid().filter(|_| !forwarded) {
+                        forward_sigint(pid);
+                        forwarded = true;
+                        continue;
+                    }
+                    warn!("received SIGINT — cleaning up containers");
+                }
+                _ = sigterm.recv() => {
+                    warn!("received SIGTERM — cleaning up containers");
+                }
+            }
+            break;
+        }
+
+        state
+            .lock()
+            .await
+            .cleanup()
+            .instrument(info_span!("cleanup"))
+            .await;
+        output::restore_title();
+        crate::otel::flush();
+        std::process::exit(130); // 128 + 2 (SIGINT convention)
+    })
+}
+
+/// Sends SIGINT to the main command's `docker exec` client.
+fn forward_sigint(pid: u32) {
+    info!(pid, "forwarding SIGINT to the running command");
+    eprintln!("interrupting the command; press Ctrl-C again to end the session");
+    // SAFETY: kill(2) has no memory-safety preconditions
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGINT) } != 0 {
+        warn!(pid, error = %std::io::Error::last_os_error(), "failed to forward SIGINT");
+    }
+}
+
+/// How the main command is launched inside the dev container.
+#[derive(Debug, Clone)]
+pub enum Launch {
+    /// Interactive shell via `docker exec -it <shell>`.
+    Shell(String),
+    /// Interactive command via `docker exec -it` (claude, chief).
+    Interactive(Vec<String>),
+    /// Non-interactive command via `docker exec`.
+    Exec(Vec<String>),
+    /// Non-interactive command whose stdout is written to a host file
+    /// (headless `claude --output`).
+    Capture { cmd: Vec<String>, output: PathBuf },
+}
+
+impl Launch {
+    /// The same command without a TTY, for environments that have none.
+    pub fn non_interactive(self) -> Self {
+        match self {
+            Launch::Shell(shell) => Launch::Exec(vec![shell]),
+            Launch::Interactive(cmd) | Launch::Exec(cmd) => Launch::Exec(cmd),
+            capture @ Launch::Capture { .. } => capture,
+        }
+    }
+}
+
+/// Options controlling a single dev container session.
+#[derive(Debug, Clone)]
+pub struct SessionOpts {
+    pub launch: Launch,
+    /// Working directory and env overrides for a [`Launch::Exec`] command.
+    pub exec: ExecOpts,
+    /// Adds the Chief layer to the rendered Dockerfile.
+    pub install_chief: bool,
+    /// Forces an image rebuild even if the tag exists.
+    pub no_cache: bool,
+    /// Copies host config files (`~/.claude.json`) into the dev container and
+    /// mounts the host dotfiles. Off for CI runs, which should not depend on the host's home directory.
+    pub mount_configs: bool,
+    /// Falls back to the macOS Keychain for the OAuth token.
+    pub use_keychain: bool,
+    /// Recreates the dev container (and re-runs post_start hooks) if it stops
+    /// during the session, instead of ending the session.
+    pub auto_restart: bool,
+    /// Stops the main command and ends the session after this long, exiting
+    /// with [`TIMEOUT_EXIT_CODE`].
+    pub max_duration: Option<Duration>,
+    /// Leaves the containers and network running when the main command
+    /// exits, for the next command to reattach to.
+    pub keep: bool,
+    /// Bind mounts for this session only (`host:container[:options]`), on
+    /// top of the configured ones (e.g. `chief --tasks`).
+    pub binds: Vec<String>,
+    /// Copied out at teardown along with `[[artifacts]]` (e.g.
+    /// `chief --results`).
+    pub artifacts: Vec<ArtifactConfig>,
+}
+
+impl SessionOpts {
+    /// Adjusts the session for CI: no TTY, no host config files, and no
+    /// Keychain lookup (its prompt would hang an unattended job).
+    pub fn for_ci(self) -> Self {
+        Self {
+            launch: self.launch.non_interactive(),
+            mount_configs: false,
+            use_keychain: false,
+            keep: false,
+            ..self
+        }
+    }
+}
+
+/// Returns the project directory name used for naming containers and volumes.
+pub fn project_name() -> String {
+    project_dir()
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "project".to_string())
+}
+
+/// The enabled runtimes with their versions, e.g. `php 8.3`.
+pub fn installed_runtimes(config: &Config) -> Vec<String> {
+    let runtimes = &config.runtimes;
+    let mut installed = Vec::new();
+    if let Some(version) = &runtimes.php {
+        installed.push(format!("php {version}"));
+    }
+    if let Some(version) = &runtimes.node {
+        installed.push(format!("node {version}"));
+    }
+    if runtimes.rust == Some(true) {
+        installed.push("rust".to_string());
+    }
+    if let Some(version) = &runtimes.go {
+        installed.push(format!("go {version}"));
+    }
+    installed
+}
+
+/// Labels recorded on the project's image, for `bubble-bot images`.
+pub fn image_labels(config: &Config) -> HashMap<String, String> {
+    HashMap::from([
+        (images::PROJECT_LABEL.to_string(), project_name()),
+        (images::CONFIG_HASH_LABEL.to_string(), config_hash(config)),
+        (
+            images::RUNTIMES_LABEL.to_string(),
+            installed_runtimes(config).join(", "),
+        ),
+        (
+            images::VERSION_LABEL.to_string(),
+            env!("CARGO_PKG_VERSION").to_string(),
+        ),
+    ])
+}
+
+/// Hash of the resolved config, recorded on images as
+/// [`images::CONFIG_HASH_LABEL`].
+pub fn config_hash(config: &Config) -> String {
+    // The resolved config as `config` prints it, so the hash identifies it
+    let resolved = toml::to_string(config).unwrap_or_default();
+    sha256_hex(&resolved)[..12].to_string()
+}
+
+/// Warns if the project's dev container was started from a different config
+/// than `config`, so its runtimes and services may be outdated. Returns
+/// whether it was. Containers from before the config-hash label are not
+/// flagged.
+pub async fn check_config_drift<B: ContainerBackend>(docker: &B, config: &Config) -> Result<bool> {
+    let name = container_name(config);
+    let Some(running) = ContainerManager::new(docker.clone())
+        .config_hash(&name)
+        .await?
+    else {
+        return Ok(false);
+    };
+    let drifted = running != config_hash(config);
+    if drifted {
+        warn!(
+            container = %name,
+            "config changed since this session started; restart it to apply the changes"
+        );
+    }
+    Ok(drifted)
+}
+
+/// Container working directory for `cwd`: its path relative to the project
+/// root, under `/workspace`. Falls back to `/workspace` when `cwd` is outside
+/// the project.
+pub fn container_workdir(project_dir: &Path, cwd: &Path) -> String {
+    let mut workdir = String::from("/workspace");
+    if let Ok(relative) = cwd.strip_prefix(project_dir) {
+        for component in relative.components() {
+            workdir.push('/');
+            workdir.push_str(&component.as_os_str().to_string_lossy());
+        }
+    }
+    workdir
+}
+
+/// The host directory mounted at `/workspace` and the container working
+/// directory: `workspace.workdir` under it, else where `cwd` is in it.
+pub fn workspace(config: &Config, cwd: &Path) -> Result<(PathBuf, String)> {
+    let root = config.workspace.mount_root(&project_dir());
+    let Some(workdir) = config.workspace.workdir_path()? else {
+        let workdir = container_workdir(&root, cwd);
+        return Ok((root, workdir));
+    };
+    if !root.join(workdir).is_dir() {
+        bail!(
+            "workspace.workdir `{}` is not a directory under {}",
+            workdir.display(),
+            root.display()
+        );
+    }
+    let workdir = container_workdir(&root, &root.join(workdir));
+    Ok((root, workdir))
+}
+
+/// Resolves the dev container name from config, falling back to the default.
+pub fn container_name(config: &Config) -> String {
+    config
+        .container
+        .name
+        .clone()
+        .unwrap_or_else(default_container_name)
+}
+
+/// Resolves the session network name from config, falling back to the default.
+pub fn network_name(config: &Config) -> String {
+    config
+        .container
+        .network
+        .clone()
+        .unwrap_or_else(default_network_name)
+}
+
+/// Proxy env vars for the dev container. With an egress allowlist, the egress
+/// sidecar is the dev container's proxy (and uses the host proxy upstream), so
+/// host proxy settings are not passed through.
+pub fn dev_proxy_env(
+    config: &Config,
+    proxy: &ProxySettings,
+    services: &[Box<dyn Service>],
+) -> Vec<String> {
+    if !config.network.allow.is_empty() {
+        return Vec::new();
+    }
+    let hosts: Vec<String> = services.iter().map(|s| s.name().to_string()).collect();
+    proxy.env_vars(&hosts)
+}
+
+/// Names of the resources this session may take over from a `--keep`
+/// session: its service and dev containers and its network. None if the
+/// kept network's isolation differs, since the network can't change under
+/// running containers.
+async fn reusable_names<B: ContainerBackend>(
+    docker: &B,
+    config: &Config,
+    container_name: &str,
+    network_name: &str,
+) -> Result<Vec<String>> {
+    let network_mgr = NetworkManager::new(docker.clone());
+    let internal = network_mgr.is_internal(network_name).await?;
+    if internal.is_some_and(|internal| internal != config.network.is_internal()) {
+        return Ok(Vec::new());
+    }
+
+    let id = project_id();
+    let mut names: Vec<String> = collect_services(config, &project_id())
+        .iter()
+        .map(|s| s.container_name(&id))
+        .collect();
+    names.push(container_name.to_string());
+    names.push(network_name.to_string());
+    Ok(names)
+}
+
+/// Detects and removes stale containers and networks from crashed previous sessions.
+/// Should be called on startup before creating new resources. Resources of
+/// a `--keep` session named in `reusable` are left for the session to reuse
+/// (or replace, if their definition changed).
+async fn cleanup_stale_resources<B: ContainerBackend>(
+    docker: &B,
+    container_name: &str,
+    reusable: &[String],
+) -> Result<()> {
+    let container_mgr = ContainerManager::new(docker.clone());
+    let network_mgr = NetworkManager::new(docker.clone());
+
+    let containers_removed = container_mgr
+        .cleanup_stale(container_name, reusable)
+        .await?;
+    let networks_removed = network_mgr.cleanup_stale(container_name, reusable).await?;
+
+    if containers_removed > 0 || networks_removed > 0 {
+        info!(
+            containers_removed,
+            networks_removed, "cleaned up stale resources from previous session"
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs a full dev container session: build, network, services, dev container,
+/// auth injection, hooks, the main command, and cleanup.
+///
+/// Containers and the network are torn down whether or not the session
+/// succeeds, except with `opts.keep` once the main command has exited (not
+/// when `--max-duration` cut it short). Returns
+/// the exit code of the main command.
+pub async fn run(config: &Config, opts: SessionOpts) -> Result<i32> {
+    // One session per project: stale cleanup below would remove another
+    // live session's containers
+    let _lock = ProjectLock::acquire(&project_dir())?;
+
+    // Fail before building anything if commands can't be run in the container
+    connect::require_cli()?;
+    let docker = connect::connect().await?;
+    if config.build.buildkit == Some(true) {
+        connect::supports(&docker, connect::BUILDKIT_API, "BuildKit (build.buildkit)");
+    }
+
+    // Resolved before anything is built, so a missing or rejected token
+    // fails the session right away
+    let oauth_token = resolve_oauth_token(&config.auth, opts.use_keychain)?;
+    // Headless claude can't fall back to logging in inside the container
+    if oauth_token.is_none() && matches!(opts.launch, Launch::Capture { .. }) {
+        return Err(Error::AuthMissing(anyhow!(
+            "no OAuth token for headless claude (--output); set CLAUDE_CODE_OAUTH_TOKEN, e.g. from `claude setup-token`"
+        ))
+        .into());
+    }
+    if let Some(token) = &oauth_token {
+        if config.auth.verify() {
+            let pb = output::spinner("Checking OAuth token...".to_string());
+            let checked = verify_oauth_token(token);
+            output::finish(&pb, "Checked OAuth token".to_string());
+            checked?;
+        }
+    }
+
+    // Resolve container and network names
+    let container_name = container_name(config);
+    let network_name = network_name(config);
+
+    // Reap resources left by bubble-bot processes that were killed, in any
+    // project, then stale containers/networks from previous sessions here
+    let (reaped_containers, reaped_networks) = Reaper::new(docker.clone()).reap().await?;
+    if !reaped_containers.is_empty() || !reaped_networks.is_empty() {
+        info!(
+            containers = reaped_containers.len(),
+            networks = reaped_networks.len(),
+            "reaped resources from exited sessions"
+        );
+    }
+    let reusable = reusable_names(&docker, config, &container_name, &network_name).await?;
+    cleanup_stale_resources(&docker, &container_name, &reusable).await?;
+
+    // Render Dockerfile
+    let renderer = TemplateRenderer::new()?;
+    let render_result = info_span!("render")
+        .in_scope(|| renderer.render_with_options(config, opts.install_chief))?;
+
+    // Proxy settings apply to both the build and the dev container
+    let proxy = ProxySettings::resolve(&config.network);
+
+    // Private registry credentials for base-image and service pulls
+    let registry_auth = RegistryAuth::load(&config.registry);
+
+    // Set up shared cleanup state and signal handler
+    let cleanup_state = Arc::new(Mutex::new(CleanupState {
+        docker: Some(docker.clone()),
+        network_name: Some(network_name.clone()),
+        artifacts: config
+            .artifacts
+            .iter()
+            .chain(&opts.artifacts)
+            .cloned()
+            .collect(),
+        ..Default::default()
+    }));
+    let signal_handle = spawn_signal_handler(Arc::clone(&cleanup_state));
+
+    // Listed by `bubble-bot ps` while it runs
+    let owner = Owner::current();
+    let registry = Registry::open();
+    if let Some(registry) = &registry {
+        let project = project_name();
+        let id = project_id();
+        let containers = std::iter::once(container_name.clone())
+            .chain(
+                collect_services(config, &id)
+                    .iter()
+                    .map(|service| service.container_name(&id)),
+            )
+            .collect();
+        let entry = SessionEntry::new(&owner, &project, &project_dir(), containers);
+        if let Err(e) = registry.add(entry) {
+            warn!(error = %e, "failed to register the session");
+        }
+    }
+
+    let session = Session {
+        config,
+        opts: &opts,
+        render_result,
+        network_name,
+        proxy,
+        registry_auth,
+        oauth_token,
+        service_log_dir: logs::log_dir(&project_id()),
+        owner: owner.clone(),
+    };
+    // Only a terminal the session takes over gets a title
+    if matches!(opts.launch, Launch::Shell(_) | Launch::Interactive(_)) {
+        output::set_title(&format!("🫧 {} — bubble-bot", project_name()));
+    }
+    let result = session.launch(docker, &cleanup_state).await;
+    output::restore_title();
+
+    // Session over (or failed) — cancel signal handler and clean up, or
+    // with --keep leave a session that ran its command running
+    signal_handle.abort();
+    let mut state = cleanup_state.lock().await;
+    if let Some((containers, network)) = state.finish(opts.keep && result.is_ok()).await {
+        let shell = config.container.shell.as_deref().unwrap_or("bash");
+        eprintln!(
+            "{}",
+            kept_message(&container_name, shell, &containers, network.as_deref())
+        );
+    }
+    if let Some(registry) = &registry {
+        if let Err(e) = registry.remove(&owner.session_id) {
+            warn!(error = %e, "failed to unregister the session");
+        }
+    }
+
+    result
+}
+
+/// Resolves the OAuth token again every `interval` and writes it into the
+/// dev container when it changed (`auth.refresh`), so a long session
+/// outlives the token it started with. Failures are only logged; the
+/// container keeps the last token written.
+fn spawn_credential_refresh<B: ContainerBackend>(
+    container_mgr: ContainerManager<B>,
+    container_id: String,
+    auth: AuthConfig,
+    use_keychain: bool,
+    mut current: String,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        loop {
+            ticker.tick().await;
+            let auth = auth.clone();
+            let resolved =
+                tokio::task::spawn_blocking(move || resolve_oauth_token(&auth, use_keychain)).await;
+            let token = match resolved {
+                Ok(Ok(Some(token))) => token,
+                Ok(Err(e)) => {
+                    warn!(error = %e, "failed to resolve the OAuth token again");
+                    continue;
+                }
+                _ => continue,
+            };
+            if token == current {
+                continue;
+            }
+            match container_mgr.write_credentials(&container_id, &token) {
+                Ok(()) => {
+                    info!("OAuth credentials refreshed in the dev container");
+                    current = token;
+                }
+                Err(e) => warn!(error = %e, "failed to refresh the OAuth credentials"),
+            }
+        }
+    })
+}
+
+/// Exit code when `--max-duration` ends the session (as with `timeout(1)`).
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// How often the dev container is checked while the main command runs.
+const MONITOR_INTERVAL_SECS: u64 = 2;
+
+/// Times `--auto-restart` recreates the dev container before giving up.
+const MAX_RESTARTS: u32 = 3;
+
+/// How long cleanup waits for service logs to be written out once the
+/// containers have stopped.
+const LOG_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How the main command's run ended.
+enum MainOutcome {
+    /// The command exited with this code while the container kept running.
+    Exited(i32),
+    /// The dev container stopped underneath the command.
+    ContainerStopped(ContainerStatus),
+    /// The session's time budget ran out first.
+    TimedOut,
+}
+
+/// Runs the main command in the dev container while polling the container.
+/// If the container stops, the command's `docker exec` may never return, so
+/// the outcome is decided by whichever comes first.
+async fn run_monitored<B: ContainerBackend>(
+    container_mgr: &ContainerManager<B>,
+    container_id: &str,
+    launch: &Launch,
+    exec_opts: &ExecOpts,
+    deadline: Option<tokio::time::Instant>,
+) -> Result<MainOutcome> {
+    let id = container_id.to_string();
+    let launch = launch.clone();
+    let exec_opts = exec_opts.clone();
+    let exec_mgr = container_mgr.clone();
+    let mut main =
+        tokio::task::spawn_blocking(move || run_main_command(&exec_mgr, &id, &launch, &exec_opts));
+
+    tokio::select! {
+        result = &mut main => {
+            let exit_code = result.context("main command panicked")??;
+            // An exec killed by the container dying returns too; tell them apart
+            match container_mgr.status(container_id).await? {
+                ContainerStatus::Running | ContainerStatus::Unhealthy => Ok(MainOutcome::Exited(exit_code)),
+                stopped => Ok(MainOutcome::ContainerStopped(stopped)),
+            }
+        }
+        stopped = container_mgr.wait_until_stopped(container_id, MONITOR_INTERVAL_SECS) => {
+            warn!(id = %container_id, status = %stopped, "dev container stopped during the session");
+            Ok(MainOutcome::ContainerStopped(stopped))
+        }
+        // The exec is left to end when cleanup removes the container, which
+        // a timed-out session always gets (see `CleanupState::finish`)
+        _ = sleep_until(deadline) => Ok(MainOutcome::TimedOut),
+    }
+}
+
+/// Sleeps until `deadline`, or forever without one.
+async fn sleep_until(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Env vars telling prompts (starship, p10k) they are inside a session.
+pub fn bubble_env(project: &str, session_id: &str) -> Vec<String> {
+    vec![
+        "BUBBLE_BOT=1".to_string(),
+        format!("BUBBLE_BOT_PROJECT={project}"),
+        format!("{SESSION_ENV}={session_id}"),
+    ]
+}
+
+/// Binds for the dev container beyond the workspace: extra workspace roots,
+/// the session's `binds`, and the `dotfiles` checkout. With `mount_configs`
+/// the host dotfiles and ssh config are mounted too.
+pub fn dev_binds(
+    config: &Config,
+    binds: &[String],
+    mount_configs: bool,
+    dotfiles: Option<&Path>,
+) -> Result<DevBinds> {
+    let mut extra_binds = config.workspace.extra_binds()?;
+    extra_binds.extend(binds.iter().cloned());
+    extra_binds.extend(
+        dotfiles
+            .iter()
+            .map(|checkout| format!("{}:{}", checkout.display(), shell::DOTFILES_DIR)),
+    );
+    let mut dotfile_entries =
+        shell::dotfile_entries(&config.shell.dotfiles, &config.shell.dotfiles_exclude)?;
+    let mut gitconfig = false;
+    let mut ssh_config = None;
+    if mount_configs {
+        if dotfile_entries.iter().any(|e| e == ".gitconfig") {
+            dotfile_entries.retain(|e| e != ".gitconfig");
+            gitconfig = true;
+        }
+        if let Some(home) = dirs::home_dir() {
+            extra_binds.extend(shell::dotfile_binds(&home, &dotfile_entries));
+            if config.shell.mount_ssh == Some(true) {
+                let cache = dirs::cache_dir().context("no cache directory for ssh config")?;
+                ssh_config = shell::sanitized_ssh_config(&home, &cache);
+                let copy_path = ssh_config.as_ref().map(|(path, _)| path.as_path());
+                extra_binds.extend(shell::ssh_binds(&home, copy_path));
+            }
+        }
+    }
+    Ok(DevBinds {
+        binds: extra_binds,
+        gitconfig,
+        ssh_config,
+    })
+}
+
+/// See [`dev_binds`].
+pub struct DevBinds {
+    pub binds: Vec<String>,
+    /// `.gitconfig` is among the dotfiles but left unmounted, to be written
+    /// sanitized after start.
+    pub gitconfig: bool,
+    /// The sanitized ssh config copy `binds` mounts, as `(path, content)`,
+    /// to be written before the container starts.
+    pub ssh_config: Option<(PathBuf, String)>,
+}
+
+/// The dev container a session of `config` creates, with `extra_binds`
+/// (see [`dev_binds`]) and `session_id` in its env. Shared with `bubble-bot
+/// plan` so both compare kept containers by the same definition. The image
+/// tag, shell, and labels are left for the caller.
+pub fn dev_container_opts(
+    config: &Config,
+    proxy: &ProxySettings,
+    services: &[Box<dyn Service>],
+    extra_binds: Vec<String>,
+    session_id: &str,
+    user_mapping: UserMapping,
+) -> Result<ContainerOpts> {
+    // Mount the workspace root; work in `workspace.workdir` or the
+    // current directory
+    let (workspace_root, workdir) = workspace(config, &std::env::current_dir()?)?;
+
+    let id = project_id();
+    let mut volumes = config.mount.exclude_volumes(&id)?;
+    if config.shell.persist_history == Some(true) {
+        volumes.push((shell::history_volume(&id), shell::HISTORY_DIR.to_string()));
+    }
+    if config.claude.persist_sessions() {
+        volumes.push((
+            shell::claude_sessions_volume(&id),
+            shell::CLAUDE_SESSIONS_DIR.to_string(),
+        ));
+    }
+
+    let mut env_vars = bubble_env(&project_name(), session_id);
+    env_vars.extend(collect_service_env_vars(services)?);
+    env_vars.extend(dev_proxy_env(config, proxy, services));
+
+    Ok(ContainerOpts {
+        container_name: container_name(config),
+        project_dir: workspace_root.to_string_lossy().to_string(),
+        workdir,
+        env_vars,
+        network: Some(network_name(config)),
+        extra_binds,
+        dns: config.network.dns.clone(),
+        extra_hosts: config
+            .network
+            .extra_hosts
+            .iter()
+            .cloned()
+            .chain(proxy.extra_host())
+            .collect(),
+        tmpfs: config.container.tmpfs_mounts()?.into_iter().collect(),
+        shm_size: config.container.shm_size_bytes()?,
+        platform: config.container.platform.clone(),
+        groups: resolve_groups(&config.container.groups),
+        devices: config.container.device_mappings()?,
+        user_mapping,
+        selinux_label: config.mount.selinux_label,
+        volumes,
+        session_secrets: services
+            .iter()
+            .flat_map(|s| s.session_secrets())
+            .map(str::to_string)
+            .collect(),
+        ..Default::default()
+    })
+}
+
+/// Launches the main command (blocking).
+fn run_main_command<B: ContainerBackend>(
+    container_mgr: &ContainerManager<B>,
+    container_id: &str,
+    launch: &Launch,
+    exec_opts: &ExecOpts,
+) -> Result<i32> {
+    match launch {
+        Launch::Shell(shell) => container_mgr.exec_interactive_shell(container_id, shell),
+        Launch::Interactive(cmd) => {
+            let cmd_refs: Vec<&str> = cmd.iter().map(|s| s.as_str()).collect();
+            container_mgr.exec_interactive_command(container_id, &cmd_refs)
+        }
+        Launch::Exec(cmd) => {
+            let cmd_refs: Vec<&str> = cmd.iter().map(|s| s.as_str()).collect();
+            container_mgr.exec_command(container_id, &cmd_refs, exec_opts)
+        }
+        Launch::Capture { cmd, output } => {
+            let cmd_refs: Vec<&str> = cmd.iter().map(|s| s.as_str()).collect();
+            container_mgr.exec_command_to_file(container_id, &cmd_refs, output)
+        }
+    }
+}
+
+/// Everything resolved before any container is created.
+struct Session<'a> {
+    config: &'a Config,
+    opts: &'a SessionOpts,
+    render_result: RenderResult,
+    network_name: String,
+    proxy: ProxySettings,
+    registry_auth: RegistryAuth,
+    oauth_token: Option<String>,
+    /// Where service output is captured (`services::logs`); `None` in tests.
+    service_log_dir: Option<PathBuf>,
+    /// Labels every container and network, so a later run can reap them if
+    /// this process is killed.
+    owner: Owner,
+}
+
+impl Session<'_> {
+    /// Creates the network, services, and dev container, then runs hooks and
+    /// the main command. Every resource is registered in `cleanup_state` as
+    /// soon as it exists, so the caller can tear it down on any error.
+    async fn launch<B: ContainerBackend>(
+        self,
+        docker: B,
+        cleanup_state: &Mutex<CleanupState<B>>,
+    ) -> Result<i32> {
+        let config = self.config;
+        let refresh = config.auth.refresh_interval()?;
+        let project_root = project_dir();
+        let (workspace_root, _) = workspace(config, &std::env::current_dir()?)?;
+
+        // Excluded paths get volumes; their host-side mount points are created
+        // here so Docker doesn't create them as root
+        let excluded = config.mount.exclude_volumes(&project_id())?;
+        for (_, target) in &excluded {
+            let relative = target.trim_start_matches("/workspace/");
+            let path = workspace_root.join(relative);
+            std::fs::create_dir_all(&path)
+                .with_context(|| format!("failed to create {}", path.display()))?;
+        }
+        let exclude_paths: Vec<String> = excluded.iter().map(|(_, t)| t.clone()).collect();
+        let persist_history = config.shell.persist_history == Some(true);
+        let persist_sessions = config.claude.persist_sessions();
+
+        // Dotfiles are cloned with the host's git credentials and mounted
+        let dotfiles = match config.shell.dotfiles_repo.as_deref() {
+            Some(repo) => match shell::sync_dotfiles(repo) {
+                Ok(checkout) => Some(checkout),
+                Err(e) => {
+                    warn!(error = %e, "continuing without dotfiles");
+                    None
+                }
+            },
+            None => None,
+        };
+        let binds = dev_binds(
+            config,
+            &self.opts.binds,
+            self.opts.mount_configs,
+            dotfiles.as_deref(),
+        )?;
+        if let Some((path, content)) = &binds.ssh_config {
+            shell::write_ssh_config(path, content)?;
+        }
+        // `.gitconfig` is written sanitized after start rather than mounted
+        let gitconfig = if binds.gitconfig {
+            gitconfig::for_container(&project_root)
+        } else {
+            None
+        };
+
+        // Auth token and claude config are written to the container after
+        // start, not via env
+        let oauth_token = self.oauth_token.clone();
+        let claude_config = if self.opts.mount_configs {
+            Some(resolve_claude_config()?)
+        } else {
+            None
+        };
+
+        // Collect service env vars for the dev container
+        let project = project_name();
+        let mut services = collect_services(config, &project_id());
+        let container_mgr =
+            ContainerManager::new(docker.clone()).with_retries(config.docker.retries());
+        // A kept service container runs with the passwords generated for the
+        // session that started it; take them over so it can be reused
+        let id = project_id();
+        for service in &mut services {
+            if service.session_secrets().is_empty() {
+                continue;
+            }
+            if let Some(env) = container_mgr
+                .running_env(&service.container_name(&id))
+                .await?
+            {
+                service.reuse_session_secrets(&env);
+            }
+        }
+        let user_mapping = container_mgr.user_mapping().await;
+        let container_opts = dev_container_opts(
+            config,
+            &self.proxy,
+            &services,
+            binds.binds,
+            &self.owner.session_id,
+            user_mapping,
+        )?;
+
+        // Volumes used to be named without the project path's hash; carry
+        // their data over the first time the new ones are needed
+        let wanted: Vec<String> = services
+            .iter()
+            .filter_map(|service| service.volume())
+            .filter_map(|volume| volume.split_once(':').map(|(name, _)| name.to_string()))
+            .chain(container_opts.volumes.iter().map(|(name, _)| name.clone()))
+            .collect();
+        let legacy = VolumeManager::new(docker.clone())
+            .legacy_sources(&wanted, &id, &project)
+            .await?;
+        for (from, to) in legacy {
+            let pb = output::spinner(format!("Copying {from} to {to}..."));
+            if let Err(e) = volumes::copy(&from, &to) {
+                output::finish(&pb, format!("Failed to copy {from}"));
+                return Err(e);
+            }
+            output::finish(&pb, format!("Copied {from} to {to}"));
+        }
+
+        // Laravel reads .env rather than the process env
+        if config.laravel.manage_env == Some(true) {
+            let path = project_root.join(".env");
+            let secrets: Vec<&str> = services.iter().flat_map(|s| s.session_secrets()).collect();
+            let vars = without_secrets(laravel_vars(&container_opts.env_vars), &secrets);
+            let state = laravel::state_path(&path)
+                .context("no local data directory to keep the original .env values in")?;
+            match EnvPatch::apply(&path, &vars, &state)? {
+                Some(patch) => cleanup_state.lock().await.env_patch = Some(patch),
+                None => warn!("laravel.manage_env is set but the project has no .env"),
+            }
+        }
+
+        let mut labels = self.owner.labels();
+        if self.opts.keep {
+            labels.insert(KEEP_LABEL.to_string(), "true".to_string());
+        }
+
+        // The image build and the network + services are independent; run them
+        // concurrently and join before creating the dev container
+        let platform = config.container.platform.as_deref();
+        if let Some(platform) = platform {
+            docker_platform::check(&docker, platform).await?;
+        }
+        let image_builder = ImageBuilder::new(docker.clone())
+            .with_buildkit(config.build.buildkit == Some(true))
+            .with_timeout(config.build.timeout_duration()?)
+            .with_log(config.build.log_path())
+            .with_labels(image_labels(config))
+            .with_extra_host(self.proxy.extra_host());
+        let build_args = self.proxy.build_args();
+        let build = image_builder
+            .build(
+                &self.render_result.dockerfile,
+                &self.render_result.context_files,
+                self.opts.no_cache,
+                &build_args,
+                &self.registry_auth,
+                platform,
+            )
+            .instrument(info_span!("build"));
+        let infrastructure = async {
+            // Pull service images before creating anything
+            let images = service_images(&services);
+            container_mgr
+                .prefetch_images(
+                    &images,
+                    config.services.pull_policy.unwrap_or_default(),
+                    &self.registry_auth,
+                )
+                .await?;
+
+            // Create bridge network
+            let network_mgr =
+                NetworkManager::new(docker.clone()).with_retries(config.docker.retries());
+            network_mgr
+                .ensure_network(&self.network_name, config.network.is_internal(), &labels)
+                .instrument(info_span!("network"))
+                .await?;
+
+            // Start service containers, registering each for cleanup as it starts
+            self.start_services(&container_mgr, &services, &labels, cleanup_state)
+                .await
+        };
+        // A failure in either cancels the other; containers are registered
+        // before they are created, so cleanup removes whatever got started
+        let (build_result, ()) = tokio::try_join!(build, infrastructure)?;
+        info!(tag = %build_result.tag, cached = build_result.cached, "image ready");
+        images::mark_used(&build_result.tag);
+
+        let shell = match &self.opts.launch {
+            Launch::Shell(shell) => shell.clone(),
+            _ => "bash".to_string(),
+        };
+
+        let exec_opts = self.opts.exec.resolved(&container_opts.workdir);
+        let container_opts = ContainerOpts {
+            image_tag: build_result.tag,
+            shell,
+            // The image keeps the hash of the build that made it when a
+            // config change leaves the Dockerfile alone
+            labels: labels
+                .clone()
+                .into_iter()
+                .chain([(images::CONFIG_HASH_LABEL.to_string(), config_hash(config))])
+                .collect(),
+            ..container_opts
+        };
+
+        let service_names: Vec<&str> = services.iter().map(|s| s.name()).collect();
+        let deadline = self
+            .opts
+            .max_duration
+            .map(|budget| tokio::time::Instant::now() + budget);
+        // Reattach to a dev container a `--keep` session left unchanged;
+        // otherwise replace any existing container with the same name
+        let mut kept = container_mgr.reuse_kept(&container_opts).await?;
+        if kept.is_none() {
+            container_mgr
+                .cleanup_existing(&container_opts.container_name)
+                .await?;
+        }
+
+        let mut restarts = 0;
+        loop {
+            // A reused container already ran its post_start hooks and dotfiles install
+            let reused = kept.take();
+            let fresh = reused.is_none();
+            let container_id = match reused {
+                Some(id) => {
+                    info!(id = %id, "reusing kept dev container");
+                    output::done("Reusing kept dev container".to_string());
+                    id
+                }
+                None => {
+                    // Registered by name first, like the service containers
+                    cleanup_state.lock().await.dev_container_id =
+                        Some(container_opts.container_name.clone());
+                    container_mgr.create_and_start(&container_opts).await?
+                }
+            };
+
+            // Register dev container for cleanup
+            cleanup_state.lock().await.dev_container_id = Some(container_id.clone());
+
+            // New volumes are root-owned; under a rootless daemon root is the user
+            let owner = (user_mapping != UserMapping::Root).then(host_owner);
+            if config.workspace.fix_ownership == Some(true) {
+                let mut state = cleanup_state.lock().await;
+                state.workspace_owner = Some(owner.clone().unwrap_or_else(|| "0:0".to_string()));
+                state.workspace_volumes = exclude_paths.clone();
+            }
+            if !exclude_paths.is_empty() && owner.is_some() {
+                if let Err(e) = container_mgr.chown_to_user(&container_id, &exclude_paths) {
+                    warn!(error = %e, "excluded paths may not be writable");
+                }
+            }
+            if persist_history {
+                let script = shell::link_history_script(owner.as_deref());
+                if let Err(e) = container_mgr.exec_as_root(&container_id, &["sh", "-c", &script]) {
+                    warn!(error = %e, "failed to set up persistent history");
+                }
+            }
+            // Docker creates `~/.claude` for the mount root-owned too
+            if persist_sessions && owner.is_some() {
+                let paths = [
+                    "/home/dev/.claude".to_string(),
+                    shell::CLAUDE_SESSIONS_DIR.to_string(),
+                ];
+                if let Err(e) = container_mgr.chown_to_user(&container_id, &paths) {
+                    warn!(error = %e, "Claude sessions may not be saved");
+                }
+            }
+
+            if let Some(ref gitconfig) = gitconfig {
+                if let Err(e) =
+                    container_mgr.write_home_file(&container_id, ".gitconfig", gitconfig)
+                {
+                    warn!(error = %e, "failed to write .gitconfig");
+                }
+            }
+            if dotfiles.is_some() && fresh {
+                shell::apply_dotfiles(&container_id, config.shell.dotfiles_install.as_deref());
+            }
+
+            // Write OAuth credentials into container (avoids exposing token in env)
+            if let Some(ref token) = oauth_token {
+                container_mgr.write_credentials(&container_id, token)?;
+                if let Some(interval) = refresh {
+                    let task = spawn_credential_refresh(
+                        container_mgr.clone(),
+                        container_id.clone(),
+                        config.auth.clone(),
+                        self.opts.use_keychain,
+                        token.clone(),
+                        interval,
+                    );
+                    // A recreated dev container gets its own
+                    if let Some(old) = cleanup_state.lock().await.credential_refresh.replace(task) {
+                        old.abort();
+                    }
+                }
+            }
+
+            // Write Claude config into container
+            if let Some(ref claude_config) = claude_config {
+                container_mgr.write_claude_config(&container_id, claude_config)?;
+            }
+
+            // Rewritten on reuse too, since the config may have changed
+            if config.shell.motd != Some(false) {
+                let text = motd::render(&project, &installed_runtimes(config), &services, config);
+                if let Err(e) = motd::install(&container_id, &text) {
+                    warn!(error = %e, "failed to write the MOTD");
+                }
+            }
+            if config.claude.write_env_context() {
+                let text = env_context::render(&services, config);
+                if let Err(e) = env_context::write(&project_root, &text) {
+                    warn!(error = %e, "failed to write {}", env_context::CONTEXT_FILE);
+                }
+            }
+
+            // Run post_start hooks
+            let hook_runner = HookRunner::new(&container_id, &config.hooks);
+            if fresh {
+                hook_runner.run_post_start();
+            }
+
+            // Start workers; they run until cleanup, so pre_stop hooks still see them
+            cleanup_state.lock().await.workers = Some(WorkerSupervisor::start(
+                &container_id,
+                &config.workers,
+                &service_names,
+            ));
+
+            // Launch the main command, watching for the container dying under it
+            let outcome = run_monitored(
+                &container_mgr,
+                &container_id,
+                &self.opts.launch,
+                &exec_opts,
+                deadline,
+            )
+            .instrument(info_span!("session"))
+            .await?;
+            let status = match outcome {
+                MainOutcome::Exited(exit_code) => {
+                    // Run pre_stop hooks
+                    hook_runner.run_pre_stop();
+                    return Ok(exit_code);
+                }
+                MainOutcome::ContainerStopped(status) => status,
+                MainOutcome::TimedOut => {
+                    cleanup_state.lock().await.timed_out = true;
+                    eprintln!(
+                        "session exceeded --max-duration; stopping the agent and cleaning up"
+                    );
+                    return Ok(TIMEOUT_EXIT_CODE);
+                }
+            };
+
+            if !self.opts.auto_restart {
+                bail!(
+                    "dev container {status} during the session; rerun with --auto-restart to recreate it automatically"
+                );
+            }
+            if restarts == MAX_RESTARTS {
+                bail!(
+                    "dev container {status} during the session; gave up after {MAX_RESTARTS} restarts"
+                );
+            }
+            restarts += 1;
+            eprintln!("dev container {status}; recreating it ({restarts}/{MAX_RESTARTS})");
+
+            // Tear down what belonged to the dead container before recreating it
+            let (workers, dead_id) = {
+                let mut state = cleanup_state.lock().await;
+                (state.workers.take(), state.dev_container_id.take())
+            };
+            if let Some(workers) = workers {
+                workers.stop().await;
+            }
+            if let Some(id) = dead_id {
+                if let Err(e) = container_mgr.stop_and_remove(&id).await {
+                    warn!(error = %e, "failed to remove stopped dev container");
+                }
+            }
+        }
+    }
+}
+
+impl Session<'_> {
+    /// Starts all configured service containers on the session network,
+    /// recording each in `cleanup_state` before it is created, so a failure
+    /// partway through leaves nothing behind once the state is cleaned up.
+    /// With `services.wait = false`, readiness is checked in the background and
+    /// reported as each service comes up.
+    async fn start_services<B: ContainerBackend>(
+        &self,
+        container_mgr: &ContainerManager<B>,
+        services: &[Box<dyn Service>],
+        labels: &HashMap<String, String>,
+        cleanup_state: &Mutex<CleanupState<B>>,
+    ) -> Result<()> {
+        let project = project_id();
+
+        for service in services {
+            // By name until it has an ID, so a container created but never
+            // started is removed too
+            cleanup_state
+                .lock()
+                .await
+                .service_containers
+                .push((service.name().to_string(), service.container_name(&project)));
+            let id = container_mgr
+                .start_service(service.as_ref(), &self.network_name, &project, labels)
+                .instrument(info_span!("service_start", service = service.name()))
+                .await?;
+            if let Some((_, registered)) = cleanup_state.lock().await.service_containers.last_mut()
+            {
+                registered.clone_from(&id);
+            }
+
+            // Captured to a file, which outlives the container
+            if let Some(dir) = &self.service_log_dir {
+                match logs::create(dir, service.name()) {
+                    Ok(file) => {
+                        let container_mgr = container_mgr.clone();
+                        let id = id.clone();
+                        let task = tokio::spawn(async move {
+                            if let Err(e) = container_mgr.follow_logs(&id, file).await {
+                                warn!(error = %e, "stopped capturing service logs");
+                            }
+                        });
+                        cleanup_state.lock().await.log_tasks.push(task);
+                    }
+                    Err(e) => {
+                        warn!(error = %e, service = service.name(), "not capturing service logs")
+                    }
+                }
+            }
+
+            if self.config.services.wait() {
+                container_mgr
+                    .wait_for_ready(&id, service.as_ref(), 30, 2)
+                    .instrument(info_span!("readiness", service = service.name()))
+                    .await?;
+                continue;
+            }
+
+            let container_mgr = container_mgr.clone();
+            let name = service.name().to_string();
+            let readiness = service.readiness();
+            let span = info_span!("readiness", service = service.name());
+            let task = tokio::spawn(async move {
+                match container_mgr
+                    .wait_until_ready(&id, &name, readiness.as_ref(), 30, 2, false)
+                    .instrument(span)
+                    .await
+                {
+                    Ok(()) => output::done(format!("{name} is ready")),
+                    Err(e) => warn!(error = %e, service = %name, "service not ready"),
+                }
+            });
+            cleanup_state.lock().await.readiness_tasks.push(task);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PullPolicy;
+    use crate::docker::mock::{MockBackend, MockContainer};
+
+    #[test]
+    fn project_name_not_empty() {
+        assert!(!project_name().is_empty());
+    }
+
+    #[test]
+    fn bubble_env_identifies_the_session() {
+        assert_eq!(
+            bubble_env("app", "1a-2b"),
+            vec![
+                "BUBBLE_BOT=1",
+                "BUBBLE_BOT_PROJECT=app",
+                "BUBBLE_BOT_SESSION=1a-2b"
+            ]
+        );
+    }
+
+    #[test]
+    fn image_labels_describe_the_config() {
+        let mut config = Config::default();
+        config.runtimes.php = Some("8.3".to_string());
+        config.runtimes.rust = Some(true);
+        let labels = image_labels(&config);
+        assert_eq!(labels[images::RUNTIMES_LABEL], "php 8.3, rust");
+        assert_eq!(labels[images::PROJECT_LABEL], project_name());
+        assert_eq!(labels[images::CONFIG_HASH_LABEL].len(), 12);
+
+        config.runtimes.node = Some("22".to_string());
+        assert_ne!(
+            image_labels(&config)[images::CONFIG_HASH_LABEL],
+            labels[images::CONFIG_HASH_LABEL]
+        );
+    }
+
+    #[test]
+    fn container_workdir_preserves_subdirectory() {
+        assert_eq!(
+            container_workdir(Path::new("/repo"), Path::new("/repo/backend/src")),
+            "/workspace/backend/src"
+        );
+    }
+
+    #[test]
+    fn container_workdir_at_project_root() {
+        assert_eq!(
+            container_workdir(Path::new("/repo"), Path::new("/repo")),
+            "/workspace"
+        );
+        assert_eq!(
+            container_workdir(Path::new("/repo"), Path::new("/elsewhere")),
+            "/workspace"
+        );
+    }
+
+    #[test]
+    fn container_name_prefers_config() {
+        let mut config = Config::default();
+        config.container.name = Some("custom".to_string());
+        assert_eq!(container_name(&config), "custom");
+    }
+
+    #[test]
+    fn container_name_defaults_to_directory() {
+        let config = Config::default();
+        assert_eq!(container_name(&config), default_container_name());
+    }
+
+    #[test]
+    fn network_name_prefers_config() {
+        let mut config = Config::default();
+        config.container.network = Some("custom-net".to_string());
+        assert_eq!(network_name(&config), "custom-net");
+    }
+
+    fn host_proxy() -> ProxySettings {
+        ProxySettings {
+            http: Some("http://proxy:3128".to_string()),
+            https: None,
+            no_proxy: None,
+        }
+    }
+
+    #[test]
+    fn dev_proxy_env_passes_host_proxy_through() {
+        let mut config = Config::default();
+        config.services.redis = Some(true);
+        let services = collect_services(&config, "test");
+        let env = dev_proxy_env(&config, &host_proxy(), &services);
+        assert!(env.contains(&"HTTP_PROXY=http://proxy:3128".to_string()));
+        assert!(env.contains(&"NO_PROXY=redis".to_string()));
+    }
+
+    #[test]
+    fn dev_proxy_env_defers_to_egress_proxy() {
+        let mut config = Config::default();
+        config.network.allow = vec!["github.com".to_string()];
+        let services = collect_services(&config, "test");
+        assert!(dev_proxy_env(&config, &host_proxy(), &services).is_empty());
+    }
+
+    fn opts(launch: Launch) -> SessionOpts {
+        SessionOpts {
+            launch,
+            exec: ExecOpts::default(),
+            install_chief: false,
+            no_cache: false,
+            mount_configs: true,
+            use_keychain: true,
+            auto_restart: false,
+            max_duration: None,
+            keep: false,
+            binds: Vec::new(),
+            artifacts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn ci_sessions_are_non_interactive() {
+        let ci = opts(Launch::Interactive(vec!["claude".to_string()])).for_ci();
+        assert!(matches!(&ci.launch, Launch::Exec(cmd) if cmd == &["claude"]));
+        assert!(!ci.mount_configs);
+        assert!(!ci.use_keychain);
+
+        let ci = opts(Launch::Shell("zsh".to_string())).for_ci();
+        assert!(matches!(&ci.launch, Launch::Exec(cmd) if cmd == &["zsh"]));
+
+        let ci = opts(Launch::Capture {
+            cmd: vec!["claude".to_string()],
+            output: PathBuf::from("result.json"),
+        })
+        .for_ci();
+        assert!(
+            matches!(&ci.launch, Launch::Capture { output, .. } if output == Path::new("result.json"))
+        );
+    }
+
+    #[test]
+    fn cleanup_without_docker_is_noop() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut state: CleanupState = CleanupState::default();
+        rt.block_on(state.cleanup());
+        assert!(state.docker.is_none());
+    }
+
+    fn running(name: &str, network: &str) -> MockContainer {
+        MockContainer {
+            name: name.to_string(),
+            running: true,
+            networks: vec![network.to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cleanup_removes_containers_before_network() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_network("bubble-bot-app", false, HashMap::new());
+        let dev = mock.add_container(running("bubble-bot-app", "bubble-bot-app"));
+        let mysql = mock.add_container(running("bubble-bot-app-mysql", "bubble-bot-app"));
+
+        let mut state = CleanupState {
+            docker: Some(mock.clone()),
+            dev_container_id: Some(dev.clone()),
+            service_containers: vec![("mysql".to_string(), mysql.clone())],
+            network_name: Some("bubble-bot-app".to_string()),
+            ..Default::default()
+        };
+        rt.block_on(state.cleanup());
+
+        assert!(mock.containers().is_empty());
+        assert!(mock.networks().is_empty());
+        let removals: Vec<String> = mock
+            .calls()
+            .into_iter()
+            .filter(|c| c.starts_with("remove_"))
+            .collect();
+        assert_eq!(
+            removals,
+            vec![
+                format!("remove_container {dev}"),
+                format!("remove_container {mysql}"),
+                "remove_network bubble-bot-app".to_string(),
+            ]
+        );
+
+        // Everything was cleared, so a second cleanup does nothing
+        let calls = mock.calls().len();
+        rt.block_on(state.cleanup());
+        assert_eq!(mock.calls().len(), calls);
+    }
+
+    #[test]
+    fn config_drift_compares_the_running_image_hash() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        let mut config = Config::default();
+        config.runtimes.node = Some("22".to_string());
+        let name = container_name(&config);
+        assert!(!rt.block_on(check_config_drift(&mock, &config)).unwrap());
+
+        mock.add_labeled_image("bubble-bot:000000000000", image_labels(&config));
+        mock.add_container(MockContainer {
+            name: name.clone(),
+            image: "bubble-bot:000000000000".to_string(),
+            running: true,
+            ..Default::default()
+        });
+        assert!(!rt.block_on(check_config_drift(&mock, &config)).unwrap());
+
+        config.runtimes.node = Some("20".to_string());
+        assert!(rt.block_on(check_config_drift(&mock, &config)).unwrap());
+
+        // A restarted session labels its container over the reused image's hash
+        config.container.name = Some(format!("{name}-restarted"));
+        mock.add_container(MockContainer {
+            name: container_name(&config),
+            image: "bubble-bot:000000000000".to_string(),
+            labels: HashMap::from([(images::CONFIG_HASH_LABEL.to_string(), config_hash(&config))]),
+            running: true,
+            ..Default::default()
+        });
+        assert!(!rt.block_on(check_config_drift(&mock, &config)).unwrap());
+    }
+
+    #[test]
+    fn stale_resources_of_this_project_are_removed() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_network("bubble-bot-app", false, HashMap::new());
+        mock.add_network("bubble-bot-other", false, HashMap::new());
+        mock.add_container(running("bubble-bot-app", "bubble-bot-app"));
+        mock.add_container(running("bubble-bot-app-redis", "bubble-bot-app"));
+        mock.add_container(running("bubble-bot-other", "bubble-bot-other"));
+
+        rt.block_on(cleanup_stale_resources(&mock, "bubble-bot-app", &[]))
+            .unwrap();
+
+        let containers: Vec<String> = mock.containers().into_iter().map(|c| c.name).collect();
+        assert_eq!(containers, vec!["bubble-bot-other"]);
+        let networks: Vec<String> = mock.networks().into_iter().map(|n| n.name).collect();
+        assert_eq!(networks, vec!["bubble-bot-other"]);
+    }
+
+    #[test]
+    fn kept_resources_survive_stale_cleanup_only_if_reusable() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        let kept = HashMap::from([(KEEP_LABEL.to_string(), "true".to_string())]);
+        mock.add_network("bubble-bot-app", false, kept.clone());
+        for name in ["bubble-bot-app-mysql", "bubble-bot-app-redis"] {
+            mock.add_container(MockContainer {
+                labels: kept.clone(),
+                ..running(name, "bubble-bot-app")
+            });
+        }
+        // Not kept, so stale whatever its name
+        mock.add_container(running("bubble-bot-app", "bubble-bot-app"));
+
+        // Redis was dropped from the config
+        let reusable = vec![
+            "bubble-bot-app-mysql".to_string(),
+            "bubble-bot-app".to_string(),
+        ];
+        rt.block_on(cleanup_stale_resources(&mock, "bubble-bot-app", &reusable))
+            .unwrap();
+
+        let containers: Vec<String> = mock.containers().into_iter().map(|c| c.name).collect();
+        assert_eq!(containers, vec!["bubble-bot-app-mysql"]);
+        let networks: Vec<String> = mock.networks().into_iter().map(|n| n.name).collect();
+        assert_eq!(networks, vec!["bubble-bot-app"]);
+    }
+
+    #[test]
+    fn started_services_are_labeled_and_registered_for_cleanup() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_network("bubble-bot-app", false, HashMap::new());
+
+        let mut config = Config::default();
+        config.services.mysql = Some(Default::default());
+        config.services.redis = Some(true);
+        // Readiness is checked via the docker CLI; keep it in the background
+        config.services.wait = Some(false);
+        let services = collect_services(&config, &project_name());
+
+        let session_opts = opts(Launch::Shell("bash".to_string()));
+        let log_dir = tempfile::tempdir().unwrap();
+        let session = Session {
+            config: &config,
+            opts: &session_opts,
+            render_result: RenderResult {
+                dockerfile: String::new(),
+                context_files: Vec::new(),
+            },
+            network_name: "bubble-bot-app".to_string(),
+            proxy: ProxySettings::default(),
+            registry_auth: RegistryAuth::default(),
+            oauth_token: None,
+            service_log_dir: Some(log_dir.path().to_path_buf()),
+            owner: Owner::current(),
+        };
+        let labels = session.owner.labels();
+        let cleanup_state = Mutex::new(CleanupState {
+            docker: Some(mock.clone()),
+            network_name: Some("bubble-bot-app".to_string()),
+            ..Default::default()
+        });
+
+        rt.block_on(async {
+            let container_mgr = ContainerManager::new(mock.clone());
+            let images = service_images(&services);
+            container_mgr
+                .prefetch_images(&images, PullPolicy::Missing, &session.registry_auth)
+                .await
+                .unwrap();
+            session
+                .start_services(&container_mgr, &services, &labels, &cleanup_state)
+                .await
+                .unwrap();
+
+            let state = cleanup_state.lock().await;
+            let registered: Vec<&str> = state
+                .service_containers
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect();
+            assert_eq!(registered, vec!["mysql", "redis"]);
+            assert_eq!(state.readiness_tasks.len(), 2);
+            assert_eq!(state.log_tasks.len(), 2);
+        });
+
+        // Images were pulled since none were present, and every container
+        // carries the session's ownership labels
+        assert_eq!(mock.images().len(), 2);
+        let containers = mock.containers();
+        assert_eq!(containers.len(), 2);
+        assert!(containers.iter().all(|c| {
+            c.running
+                && labels
+                    .iter()
+                    .all(|(key, value)| c.labels.get(key) == Some(value))
+        }));
+        // Each service's output is captured to its own file
+        assert!(log_dir.path().join("mysql.log").is_file());
+        assert!(log_dir.path().join("redis.log").is_file());
+
+        rt.block_on(async { cleanup_state.lock().await.cleanup().await });
+        assert!(mock.containers().is_empty());
+        assert!(mock.networks().is_empty());
+    }
+
+    #[test]
+    fn services_that_fail_to_start_are_rolled_back() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_network("bubble-bot-app", false, HashMap::new());
+        mock.add_image("redis:alpine");
+        // Created, but never started
+        mock.fail_next("start_container", 400, "port is already allocated");
+
+        let mut config = Config::default();
+        config.services.redis = Some(true);
+        let services = collect_services(&config, &project_name());
+        let session_opts = opts(Launch::Shell("bash".to_string()));
+        let session = Session {
+            config: &config,
+            opts: &session_opts,
+            render_result: RenderResult {
+                dockerfile: String::new(),
+                context_files: Vec::new(),
+            },
+            network_name: "bubble-bot-app".to_string(),
+            proxy: ProxySettings::default(),
+            registry_auth: RegistryAuth::default(),
+            oauth_token: None,
+            service_log_dir: None,
+            owner: Owner::current(),
+        };
+        let labels = session.owner.labels();
+        let cleanup_state = Mutex::new(CleanupState {
+            docker: Some(mock.clone()),
+            network_name: Some("bubble-bot-app".to_string()),
+            ..Default::default()
+        });
+
+        rt.block_on(async {
+            let container_mgr = ContainerManager::new(mock.clone());
+            let err = session
+                .start_services(&container_mgr, &services, &labels, &cleanup_state)
+                .await
+                .unwrap_err();
+            assert!(
+                format!("{err:#}").contains("failed to start redis"),
+                "{err:#}"
+            );
+            assert_eq!(mock.containers().len(), 1);
+            cleanup_state.lock().await.cleanup().await;
+        });
+        assert!(mock.containers().is_empty());
+        assert!(mock.networks().is_empty());
+    }
+
+    #[test]
+    fn kept_sessions_leave_containers_and_network_running() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_network("bubble-bot-app", false, HashMap::new());
+        mock.add_container(MockContainer {
+            id: "0123456789abcdef".to_string(),
+            name: "bubble-bot-app".to_string(),
+            running: true,
+            ..Default::default()
+        });
+        let mut state = CleanupState {
+            docker: Some(mock.clone()),
+            dev_container_id: Some("0123456789abcdef".to_string()),
+            service_containers: vec![("redis".to_string(), "fedcba9876543210".to_string())],
+            network_name: Some("bubble-bot-app".to_string()),
+            ..Default::default()
+        };
+
+        let (containers, network) = rt.block_on(state.keep());
+        assert_eq!(containers, vec!["0123456789abcdef", "fedcba9876543210"]);
+        assert_eq!(network.as_deref(), Some("bubble-bot-app"));
+
+        // Nothing is left for a later cleanup to remove
+        rt.block_on(state.cleanup());
+        assert_eq!(mock.containers().len(), 1);
+        assert_eq!(mock.networks().len(), 1);
+
+        let message = kept_message("bubble-bot-app", "zsh", &containers, network.as_deref());
+        assert!(
+            message.contains("docker exec -it bubble-bot-app zsh"),
+            "{message}"
+        );
+        assert!(
+            message.contains(
+                "docker rm -f 0123456789ab fedcba987654 && docker network rm bubble-bot-app"
+            ),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn timed_out_sessions_are_torn_down_despite_keep() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mock = MockBackend::new();
+        mock.add_network("bubble-bot-app", false, HashMap::new());
+        mock.add_container(MockContainer {
+            id: "0123456789abcdef".to_string(),
+            name: "bubble-bot-app".to_string(),
+            running: true,
+            ..Default::default()
+        });
+        let mut state = CleanupState {
+            docker: Some(mock.clone()),
+            dev_container_id: Some("0123456789abcdef".to_string()),
+            network_name: Some("bubble-bot-app".to_string()),
+            timed_out: true,
+            ..Default::default()
+        };
+
+        // The runaway agent's container goes, --keep or not
+        assert_eq!(rt.block_on(state.finish(true)), None);
+        assert!(mock.containers().is_empty());
+        assert!(mock.networks().is_empty());
+    }
+}
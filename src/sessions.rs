@@ -0,0 +1,258 @@
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::docker::images::format_duration;
+use crate::docker::ownership::Owner;
+
+/// A live session, as recorded in the [`Registry`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SessionEntry {
+    pub session_id: String,
+    pub project: String,
+    pub project_dir: PathBuf,
+    /// Dev container first, then the service containers.
+    pub containers: Vec<String>,
+    /// Unix seconds.
+    pub started: u64,
+    pub pid: u32,
+    pub host: String,
+}
+
+impl SessionEntry {
+    /// An entry for a session starting now, owned by `owner`.
+    pub fn new(owner: &Owner, project: &str, project_dir: &Path, containers: Vec<String>) -> Self {
+        Self {
+            session_id: owner.session_id.clone(),
+            project: project.to_string(),
+            project_dir: project_dir.to_path_buf(),
+            containers,
+            started: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            pid: owner.pid,
+            host: owner.host.clone(),
+        }
+    }
+
+    /// The owning process, for checking whether it still runs.
+    pub fn owner(&self) -> Owner {
+        Owner {
+            session_id: self.session_id.clone(),
+            pid: self.pid,
+            host: self.host.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct State {
+    #[serde(default)]
+    sessions: Vec<SessionEntry>,
+}
+
+/// Sessions running across all projects, kept in a JSON file that every
+/// bubble-bot process updates under an exclusive lock. Entries of sessions
+/// whose process died without removing them are pruned by `bubble-bot ps`.
+pub struct Registry {
+    path: PathBuf,
+}
+
+impl Registry {
+    /// The registry at `~/.local/share/bubble-bot/state.json` on Linux
+    /// (`~/Library/Application Support/bubble-bot` on macOS).
+    pub fn open() -> Option<Self> {
+        Some(Self::at(
+            dirs::data_local_dir()?.join("bubble-bot/state.json"),
+        ))
+    }
+
+    pub fn at(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn add(&self, entry: SessionEntry) -> Result<()> {
+        self.update(|sessions| {
+            sessions.retain(|s| s.session_id != entry.session_id);
+            sessions.push(entry);
+        })
+        .map(drop)
+    }
+
+    pub fn remove(&self, session_id: &str) -> Result<()> {
+        self.update(|sessions| sessions.retain(|s| s.session_id != session_id))
+            .map(drop)
+    }
+
+    /// Drops the entries of `stale` sessions, returning the rest.
+    pub fn prune(&self, stale: &[String]) -> Result<Vec<SessionEntry>> {
+        self.update(|sessions| sessions.retain(|s| !stale.contains(&s.session_id)))
+    }
+
+    pub fn entries(&self) -> Result<Vec<SessionEntry>> {
+        self.update(|_| {})
+    }
+
+    /// Applies `change` to the sessions under the lock and writes them back,
+    /// returning the result.
+    fn update(&self, change: impl FnOnce(&mut Vec<SessionEntry>)) -> Result<Vec<SessionEntry>> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let _lock = lock(&self.path.with_extension("lock"))?;
+
+        let mut state: State = match std::fs::read_to_string(&self.path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                warn!(path = %self.path.display(), error = %e, "ignoring corrupt session registry");
+                State::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to read {}", self.path.display()));
+            }
+        };
+        change(&mut state.sessions);
+
+        // Written beside the registry and renamed over it, so readers never
+        // see a partial file
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&state)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        Ok(state.sessions)
+    }
+}
+
+/// Blocks until the exclusive lock on `path` is ours. The kernel releases it
+/// when the file is closed.
+fn lock(path: &Path) -> Result<File> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .with_context(|| format!("failed to open lock file {}", path.display()))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("failed to lock {}", path.display()));
+    }
+    Ok(file)
+}
+
+/// Formats sessions as an aligned table for `bubble-bot ps`.
+pub fn sessions_table(sessions: &[SessionEntry], now: SystemTime) -> String {
+    let now_secs = now
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let mut rows = vec![[
+        "PROJECT".to_string(),
+        "SESSION".to_string(),
+        "CONTAINERS".to_string(),
+        "UPTIME".to_string(),
+        "PID".to_string(),
+    ]];
+    for session in sessions {
+        let uptime = Duration::from_secs(now_secs.saturating_sub(session.started));
+        rows.push([
+            session.project.clone(),
+            session.session_id.clone(),
+            if session.containers.is_empty() {
+                "-".to_string()
+            } else {
+                session.containers.join(",")
+            },
+            format_duration(uptime.max(Duration::from_secs(1))),
+            session.pid.to_string(),
+        ]);
+    }
+
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        table.push_str(cells.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(session_id: &str, project: &str) -> SessionEntry {
+        SessionEntry {
+            session_id: session_id.to_string(),
+            project: project.to_string(),
+            project_dir: PathBuf::from(format!("/src/{project}")),
+            containers: vec![
+                format!("bubble-bot-{project}"),
+                format!("bubble-bot-{project}-mysql"),
+            ],
+            started: 1_000,
+            pid: 42,
+            host: "laptop".to_string(),
+        }
+    }
+
+    #[test]
+    fn registry_adds_removes_and_prunes() {
+        let dir = tempfile::tempdir().unwrap();
+        let registry = Registry::at(dir.path().join("nested/state.json"));
+        assert!(registry.entries().unwrap().is_empty());
+
+        registry.add(entry("a", "shop")).unwrap();
+        registry.add(entry("b", "blog")).unwrap();
+        registry.add(entry("c", "api")).unwrap();
+        // Re-adding a session replaces its entry
+        registry.add(entry("a", "shop")).unwrap();
+        assert_eq!(registry.entries().unwrap().len(), 3);
+
+        registry.remove("b").unwrap();
+        let left = registry.prune(&["c".to_string()]).unwrap();
+        assert_eq!(left, vec![entry("a", "shop")]);
+
+        // A fresh handle reads the same file
+        let reopened = Registry::at(dir.path().join("nested/state.json"));
+        assert_eq!(reopened.entries().unwrap(), vec![entry("a", "shop")]);
+    }
+
+    #[test]
+    fn corrupt_registry_starts_over() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        std::fs::write(&path, "{not json").unwrap();
+        let registry = Registry::at(path);
+        registry.add(entry("a", "shop")).unwrap();
+        assert_eq!(registry.entries().unwrap(), vec![entry("a", "shop")]);
+    }
+
+    #[test]
+    fn table_lists_sessions_with_uptime() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_000 + 3_725);
+        let table = sessions_table(&[entry("1a2b-3c", "shop")], now);
+        assert_eq!(
+            table,
+            "PROJECT  SESSION  CONTAINERS                             UPTIME  PID\n\
+             shop     1a2b-3c  bubble-bot-shop,bubble-bot-shop-mysql  1h2m5s  42\n"
+        );
+    }
+}
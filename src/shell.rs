@@ -0,0 +1,458 @@
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use tracing::{debug, info, warn};
+
+use crate::templates::sha256_hex;
+
+/// Where the history volume is mounted in the dev container.
+pub const HISTORY_DIR: &str = "/home/dev/.bubble-bot-history";
+
+/// History files kept in the volume, relative to the container home.
+pub const HISTORY_FILES: &[&str] = &[".bash_history", ".zsh_history", ".claude/history.jsonl"];
+
+/// Volume holding shell and Claude prompt history for a project.
+pub fn history_volume(project: &str) -> String {
+    format!("bubble-bot-{project}-history")
+}
+
+/// Where Claude Code keeps its per-project session transcripts, which
+/// `claude --resume` and `--continue` read.
+pub const CLAUDE_SESSIONS_DIR: &str = "/home/dev/.claude/projects";
+
+/// Volume holding Claude Code session transcripts for a project.
+pub fn claude_sessions_volume(project: &str) -> String {
+    format!("bubble-bot-{project}-claude-sessions")
+}
+
+/// Root shell script that links each history file in the home directory to
+/// its copy in the volume, creating it on first use. `owner` (`uid:gid`) is
+/// given the volume, which Docker creates root-owned; `None` when the
+/// container's root is already the host user.
+pub fn link_history_script(owner: Option<&str>) -> String {
+    let mut script = String::from("set -e\n");
+    for file in HISTORY_FILES {
+        let stored = format!(
+            "{HISTORY_DIR}/{}",
+            file.trim_start_matches('.').replace('/', "-")
+        );
+        let link = format!("/home/dev/{file}");
+        script.push_str(&format!(
+            "touch '{stored}'\nmkdir -p \"$(dirname '{link}')\"\nln -sfn '{stored}' '{link}'\n"
+        ));
+    }
+    if let Some(owner) = owner {
+        script.push_str(&format!("chown -R {owner} '{HISTORY_DIR}'\n"));
+    }
+    script
+}
+
+/// Host dotfiles mounted read-only into the container home when present,
+/// before `shell.dotfiles` additions and `shell.dotfiles_exclude`.
+pub const DOTFILES: &[&str] = &[".gitconfig", ".vimrc", ".inputrc", ".tmux.conf"];
+
+/// Dotfile entries to mount: [`DOTFILES`] plus `extra`, less `exclude`.
+/// Entries are paths relative to the home directory, files or directories.
+pub fn dotfile_entries(extra: &[String], exclude: &[String]) -> Result<Vec<String>> {
+    let mut entries: Vec<String> = Vec::new();
+    let candidates = DOTFILES
+        .iter()
+        .copied()
+        .chain(extra.iter().map(String::as_str));
+    for entry in candidates {
+        let entry = entry.trim_end_matches('/');
+        let normal = Path::new(entry)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+        if entry.is_empty() || !normal {
+            bail!("shell.dotfiles entry `{entry}` must be a path relative to the home directory");
+        }
+        let excluded = exclude.iter().any(|e| e.trim_end_matches('/') == entry);
+        if !excluded && !entries.iter().any(|e| e == entry) {
+            entries.push(entry.to_string());
+        }
+    }
+    Ok(entries)
+}
+
+/// Read-only binds for the dotfile entries that exist under `home`.
+pub fn dotfile_binds(home: &Path, entries: &[String]) -> Vec<String> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let host = home.join(entry);
+            if !host.exists() {
+                debug!(path = %host.display(), "dotfile not found; skipping");
+                return None;
+            }
+            Some(format!("{}:/home/dev/{entry}:ro", host.display()))
+        })
+        .collect()
+}
+
+/// `~/.ssh/config` options that only work on the host: the macOS keychain,
+/// host agent sockets and libraries, included host files, and control
+/// sockets (the mounted `~/.ssh` is read-only).
+const HOST_ONLY_SSH_OPTIONS: &[&str] = &[
+    "usekeychain",
+    "identityagent",
+    "include",
+    "controlmaster",
+    "controlpath",
+    "controlpersist",
+    "pkcs11provider",
+    "securitykeyprovider",
+];
+
+/// Strips [`HOST_ONLY_SSH_OPTIONS`] from an ssh config, returning the result
+/// and the options dropped (lowercased).
+pub fn sanitize_ssh_config(config: &str) -> (String, Vec<String>) {
+    let mut kept = String::new();
+    let mut dropped: Vec<String> = Vec::new();
+    for line in config.lines() {
+        let keyword = line
+            .trim_start()
+            .split(|c: char| c.is_whitespace() || c == '=')
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        if HOST_ONLY_SSH_OPTIONS.contains(&keyword.as_str()) {
+            if !dropped.contains(&keyword) {
+                dropped.push(keyword);
+            }
+            continue;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+    }
+    (kept, dropped)
+}
+
+/// The sanitized copy of `~/.ssh/config` to mount, as its path under
+/// `cache` (named by content, so sessions share it) and content. `None`
+/// without a host ssh config.
+pub fn sanitized_ssh_config(home: &Path, cache: &Path) -> Option<(PathBuf, String)> {
+    let config = std::fs::read_to_string(home.join(".ssh/config")).ok()?;
+    let (content, dropped) = sanitize_ssh_config(&config);
+    if !dropped.is_empty() {
+        info!(options = ?dropped, "ssh config options dropped for the container");
+    }
+    let name = format!("config-{}", &sha256_hex(&content)[..12]);
+    Some((cache.join("bubble-bot").join("ssh").join(name), content))
+}
+
+/// Writes the sanitized ssh config copy, owner-only as ssh requires.
+pub fn write_ssh_config(path: &Path, content: &str) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    file.write_all(content.as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+/// Read-only binds for `~/.ssh/known_hosts` (if present) and the sanitized
+/// config copy.
+pub fn ssh_binds(home: &Path, config_copy: Option<&Path>) -> Vec<String> {
+    let mut binds = Vec::new();
+    let known_hosts = home.join(".ssh/known_hosts");
+    if known_hosts.is_file() {
+        binds.push(format!(
+            "{}:/home/dev/.ssh/known_hosts:ro",
+            known_hosts.display()
+        ));
+    }
+    if let Some(copy) = config_copy {
+        binds.push(format!("{}:/home/dev/.ssh/config:ro", copy.display()));
+    }
+    binds
+}
+
+/// Where the dotfiles checkout is mounted in the dev container.
+pub const DOTFILES_DIR: &str = "/home/dev/.dotfiles";
+
+/// Scripts run from a dotfiles repo without `shell.dotfiles_install`, first
+/// match wins (the same list Codespaces uses).
+const INSTALL_SCRIPTS: &[&str] = &[
+    "install.sh",
+    "install",
+    "bootstrap.sh",
+    "bootstrap",
+    "script/bootstrap",
+    "setup.sh",
+    "setup",
+    "script/setup",
+];
+
+/// Body of the loop over [`INSTALL_SCRIPTS`], then the symlink fallback.
+const RUN_INSTALL_SCRIPT: &str = r#"  if [ -f "$script" ]; then
+    if [ -x "$script" ]; then exec "./$script"; else exec sh "$script"; fi
+  fi
+done
+for f in .[!.]*; do
+  [ -e "$f" ] && [ "$f" != .git ] || continue
+  ln -sfn "$PWD/$f" "$HOME/$f"
+done
+"#;
+
+/// Host checkout of a dotfiles repo, cached per URL so sessions share it.
+pub fn dotfiles_checkout(repo: &str) -> Result<PathBuf> {
+    let cache = dirs::cache_dir().context("no cache directory for the dotfiles checkout")?;
+    Ok(cache
+        .join("bubble-bot")
+        .join("dotfiles")
+        .join(&sha256_hex(repo)[..12]))
+}
+
+/// Clones `repo` into its host checkout, or fast-forwards an existing one.
+/// Cloning on the host uses the host's git credentials, so private and SSH
+/// URLs work without forwarding keys. A failed update keeps the previous
+/// checkout.
+pub fn sync_dotfiles(repo: &str) -> Result<PathBuf> {
+    let checkout = dotfiles_checkout(repo)?;
+    if checkout.join(".git").is_dir() {
+        if let Err(e) = git(&["-C", &checkout.to_string_lossy(), "pull", "--ff-only", "-q"]) {
+            warn!(repo, error = %e, "failed to update dotfiles; using the previous checkout");
+        }
+        return Ok(checkout);
+    }
+
+    if let Some(parent) = checkout.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    info!(repo, "cloning dotfiles");
+    let target = checkout.to_string_lossy();
+    if let Err(e) = git(&["clone", "-q", "--depth", "1", repo, &target]) {
+        bail!("failed to clone dotfiles repo {repo}: {e}");
+    }
+    Ok(checkout)
+}
+
+fn git(args: &[&str]) -> Result<()> {
+    let output = Command::new("git")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to spawn git")?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Shell script that applies the mounted dotfiles: `install` if given,
+/// otherwise the repo's first install script, otherwise a symlink in the
+/// home directory for each top-level dotfile.
+pub fn dotfiles_install_script(install: Option<&str>) -> String {
+    let mut script = format!("cd '{DOTFILES_DIR}'\n");
+    if let Some(install) = install {
+        script.push_str(install);
+        script.push('\n');
+        return script;
+    }
+    script.push_str(&format!(
+        "for script in {}; do\n",
+        INSTALL_SCRIPTS.join(" ")
+    ));
+    script.push_str(RUN_INSTALL_SCRIPT);
+    script
+}
+
+/// Applies the mounted dotfiles as the container user, streaming output to
+/// the terminal. Failures are logged but don't stop the session.
+pub fn apply_dotfiles(container_id: &str, install: Option<&str>) {
+    info!("applying dotfiles");
+    let script = dotfiles_install_script(install);
+    let status = Command::new("docker")
+        .args(["exec", container_id, "sh", "-c", &script])
+        .stdin(Stdio::null())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .status();
+    match status {
+        Ok(s) if s.success() => {}
+        Ok(s) => warn!(code = s.code().unwrap_or(-1), "dotfiles install failed"),
+        Err(e) => warn!(error = %e, "dotfiles install error"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn history_volume_is_per_project() {
+        assert_eq!(
+            history_volume("shop-1a2b3c4d"),
+            "bubble-bot-shop-1a2b3c4d-history"
+        );
+        assert_eq!(
+            claude_sessions_volume("shop-1a2b3c4d"),
+            "bubble-bot-shop-1a2b3c4d-claude-sessions"
+        );
+    }
+
+    #[test]
+    fn link_script_links_every_history_file() {
+        let script = link_history_script(Some("1000:1000"));
+        assert!(script.contains(
+            "ln -sfn '/home/dev/.bubble-bot-history/bash_history' '/home/dev/.bash_history'"
+        ));
+        assert!(script.contains(
+            "ln -sfn '/home/dev/.bubble-bot-history/claude-history.jsonl' '/home/dev/.claude/history.jsonl'"
+        ));
+        assert!(script.ends_with("chown -R 1000:1000 '/home/dev/.bubble-bot-history'\n"));
+
+        assert!(!link_history_script(None).contains("chown"));
+    }
+
+    #[test]
+    fn dotfile_entries_extend_and_exclude_defaults() {
+        let extra = vec![".config/nvim/".to_string(), ".vimrc".to_string()];
+        let exclude = vec![".gitconfig".to_string()];
+        assert_eq!(
+            dotfile_entries(&extra, &exclude).unwrap(),
+            vec![".vimrc", ".inputrc", ".tmux.conf", ".config/nvim"]
+        );
+
+        for bad in ["/etc/passwd", "../.ssh", ""] {
+            let err = dotfile_entries(&[bad.to_string()], &[]).unwrap_err();
+            assert!(err.to_string().contains("relative to the home"), "{err}");
+        }
+    }
+
+    #[test]
+    fn dotfile_binds_skip_missing_paths() {
+        let home = tempfile::tempdir().unwrap();
+        std::fs::write(home.path().join(".gitconfig"), "").unwrap();
+        std::fs::create_dir_all(home.path().join(".config/nvim")).unwrap();
+
+        let entries = dotfile_entries(&[".config/nvim".to_string()], &[]).unwrap();
+        let h = home.path().display();
+        assert_eq!(
+            dotfile_binds(home.path(), &entries),
+            vec![
+                format!("{h}/.gitconfig:/home/dev/.gitconfig:ro"),
+                format!("{h}/.config/nvim:/home/dev/.config/nvim:ro"),
+            ]
+        );
+    }
+
+    #[test]
+    fn sanitize_ssh_config_drops_host_only_options() {
+        let config = [
+            "Include ~/.orbstack/ssh/config",
+            "",
+            "Host *",
+            "  UseKeychain yes",
+            "  AddKeysToAgent yes",
+            "  IdentityAgent \"~/Library/Group Containers/2BUA8C4S2C.com.1password/t/agent.sock\"",
+            "  ControlPath=~/.ssh/cm-%r@%h:%p",
+            "Host gh",
+            "  HostName github.com",
+        ]
+        .join("\n");
+        let (sanitized, dropped) = sanitize_ssh_config(&config);
+        assert_eq!(
+            sanitized,
+            "\nHost *\n  AddKeysToAgent yes\nHost gh\n  HostName github.com\n"
+        );
+        assert_eq!(
+            dropped,
+            vec!["include", "usekeychain", "identityagent", "controlpath"]
+        );
+    }
+
+    #[test]
+    fn ssh_binds_mount_known_hosts_and_config_copy() {
+        let home = tempfile::tempdir().unwrap();
+        let cache = tempfile::tempdir().unwrap();
+        assert!(sanitized_ssh_config(home.path(), cache.path()).is_none());
+        assert!(ssh_binds(home.path(), None).is_empty());
+
+        std::fs::create_dir_all(home.path().join(".ssh")).unwrap();
+        std::fs::write(home.path().join(".ssh/known_hosts"), "").unwrap();
+        std::fs::write(
+            home.path().join(".ssh/config"),
+            "UseKeychain yes\nUser git\n",
+        )
+        .unwrap();
+
+        let (path, content) = sanitized_ssh_config(home.path(), cache.path()).unwrap();
+        assert_eq!(content, "User git\n");
+        assert!(path.starts_with(cache.path().join("bubble-bot/ssh")));
+        write_ssh_config(&path, &content).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let h = home.path().display();
+        assert_eq!(
+            ssh_binds(home.path(), Some(&path)),
+            vec![
+                format!("{h}/.ssh/known_hosts:/home/dev/.ssh/known_hosts:ro"),
+                format!("{}:/home/dev/.ssh/config:ro", path.display()),
+            ]
+        );
+    }
+
+    #[test]
+    fn dotfiles_checkout_is_per_repo() {
+        let a = dotfiles_checkout("git@github.com:me/dotfiles").unwrap();
+        let b = dotfiles_checkout("https://github.com/me/dotfiles").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a.file_name().unwrap().len(), 12);
+        assert!(a.parent().unwrap().ends_with("bubble-bot/dotfiles"));
+    }
+
+    #[test]
+    fn install_script_prefers_configured_command() {
+        let script = dotfiles_install_script(Some("chezmoi init --apply --source ."));
+        assert_eq!(
+            script,
+            "cd '/home/dev/.dotfiles'\nchezmoi init --apply --source .\n"
+        );
+
+        let script = dotfiles_install_script(None);
+        assert!(script.contains("for script in install.sh install bootstrap.sh"));
+        assert!(script.contains("ln -sfn \"$PWD/$f\" \"$HOME/$f\""));
+    }
+
+    #[test]
+    fn default_install_script_links_dotfiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = dir.path().join("repo");
+        let home = dir.path().join("home");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::write(repo.join(".vimrc"), "set nu\n").unwrap();
+        std::fs::write(repo.join("README.md"), "").unwrap();
+
+        let script = dotfiles_install_script(None).replace(DOTFILES_DIR, &repo.to_string_lossy());
+        let status = Command::new("sh")
+            .args(["-c", &script])
+            .env("HOME", &home)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert!(home.join(".vimrc").is_symlink());
+        assert!(!home.join(".git").exists());
+        assert!(!home.join("README.md").exists());
+    }
+}
@@ -0,0 +1,198 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::config::{Config, TelemetryConfig};
+use crate::docker::images::ImageBuilder;
+use crate::error;
+use crate::http;
+use crate::session;
+use crate::templates::{TemplateRenderer, sha256_hex};
+
+/// How long publishing a report may take before it is abandoned.
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What `telemetry.endpoint` receives about one session. Anonymized: the
+/// project is a hash of its name and path, and nothing names the user, the
+/// host, files, commands run, or error messages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SessionReport {
+    /// bubble-bot version.
+    pub version: String,
+    /// First 12 hex characters of a SHA-256 of the project ID.
+    pub project: String,
+    /// The subcommand (`shell`, `claude`, `chief`, `exec`, `run`).
+    pub command: String,
+    /// Image tag the config renders to; `None` if it doesn't render.
+    pub image: Option<String>,
+    /// [`session::config_hash`], for grouping sessions by config.
+    pub config_hash: String,
+    pub runtimes: Vec<String>,
+    pub duration_secs: u64,
+    /// The main command's exit code; `None` if the session failed.
+    pub exit_code: Option<i32>,
+    /// The failed session's exit code category (see `error::exit_code`).
+    pub failure: Option<i32>,
+}
+
+impl SessionReport {
+    pub fn new(
+        config: &Config,
+        project_id: &str,
+        command: &str,
+        duration: Duration,
+        result: &Result<i32>,
+    ) -> Self {
+        let image = TemplateRenderer::new()
+            .and_then(|renderer| renderer.render_with_options(config, command == "chief"))
+            .ok()
+            .map(|rendered| ImageBuilder::compute_tag(&rendered.dockerfile));
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            project: sha256_hex(project_id)[..12].to_string(),
+            command: command.to_string(),
+            image,
+            config_hash: session::config_hash(config),
+            runtimes: session::installed_runtimes(config),
+            duration_secs: duration.as_secs(),
+            exit_code: result.as_ref().ok().copied(),
+            failure: result.as_ref().err().map(error::exit_code),
+        }
+    }
+
+    /// The object key the report gets under an `s3://` prefix: unique per
+    /// session, sortable by time.
+    fn key(&self, now: SystemTime) -> String {
+        let secs = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        format!("{secs}-{}-{}.json", self.project, std::process::id())
+    }
+}
+
+/// Publishes `report` to the configured endpoint, if any. Failures are
+/// logged, never returned: telemetry mustn't change a session's outcome.
+pub fn publish(config: &TelemetryConfig, report: &SessionReport) {
+    let url = match config.endpoint_url() {
+        Ok(Some(url)) => url,
+        Ok(None) => return,
+        Err(e) => {
+            warn!(error = %e, "session report not published");
+            return;
+        }
+    };
+    let body = match serde_json::to_string(report) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!(error = %e, "session report not published");
+            return;
+        }
+    };
+    let sent = match url.strip_prefix("s3://") {
+        Some(prefix) => upload(s3_command(prefix, &report.key(SystemTime::now())), &body),
+        None => http::post_json(url, &body, PUBLISH_TIMEOUT),
+    };
+    match sent {
+        Ok(()) => info!("session report published"),
+        Err(e) => warn!(error = %e, "session report not published"),
+    }
+}
+
+/// Uploads stdin as `key` under `s3://prefix` with the AWS CLI, which
+/// brings its own credentials (profile, SSO, instance role). Connecting and
+/// each read are bounded like the `curl` POST, so an unreachable endpoint
+/// can't hold the session open.
+fn s3_command(prefix: &str, key: &str) -> Command {
+    let mut command = Command::new("aws");
+    command
+        .args(["s3", "cp", "-"])
+        .arg(format!("s3://{}/{key}", prefix.trim_end_matches('/')))
+        .args(["--content-type", "application/json", "--only-show-errors"])
+        .arg("--cli-connect-timeout")
+        .arg(PUBLISH_TIMEOUT.as_secs().to_string())
+        .arg("--cli-read-timeout")
+        .arg(PUBLISH_TIMEOUT.as_secs().to_string());
+    command
+}
+
+/// Runs `command` with `body` on stdin.
+fn upload(mut command: Command, body: &str) -> Result<()> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {program}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(body.as_bytes())?;
+    }
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("failed to wait for {program}"))?;
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn report_carries_no_names() {
+        let mut config = Config::default();
+        config.runtimes.node = Some("22".to_string());
+        let failed: Result<i32> = Err(Error::Build(anyhow::anyhow!("secret-path failed")).into());
+        let report = SessionReport::new(
+            &config,
+            "shop-1a2b3c4d",
+            "chief",
+            Duration::from_secs(95),
+            &failed,
+        );
+        assert_eq!(report.project, sha256_hex("shop-1a2b3c4d")[..12]);
+        assert!(report.image.as_deref().unwrap().starts_with("bubble-bot:"));
+        assert_eq!(report.runtimes, vec!["node 22"]);
+        assert_eq!((report.exit_code, report.failure), (None, Some(82)));
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(!json.contains("shop"), "{json}");
+        assert!(!json.contains("secret-path"), "{json}");
+
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        assert_eq!(
+            report.key(now),
+            format!("1700000000-{}-{}.json", report.project, std::process::id())
+        );
+    }
+
+    #[test]
+    fn s3_reports_are_keyed_under_the_prefix() {
+        let command = s3_command("team-bucket/bubble-bot/", "1-abc-2.json");
+        let args: Vec<_> = command.get_args().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(
+            args,
+            vec![
+                "s3",
+                "cp",
+                "-",
+                "s3://team-bucket/bubble-bot/1-abc-2.json",
+                "--content-type",
+                "application/json",
+                "--only-show-errors",
+                "--cli-connect-timeout",
+                "10",
+                "--cli-read-timeout",
+                "10",
+            ]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+
+/// Instructions Docker accepts, upper-cased.
+const INSTRUCTIONS: &[&str] = &[
+    "ADD",
+    "ARG",
+    "CMD",
+    "COPY",
+    "ENTRYPOINT",
+    "ENV",
+    "EXPOSE",
+    "FROM",
+    "HEALTHCHECK",
+    "LABEL",
+    "MAINTAINER",
+    "ONBUILD",
+    "RUN",
+    "SHELL",
+    "STOPSIGNAL",
+    "USER",
+    "VOLUME",
+    "WORKDIR",
+];
+
+/// A malformed instruction, at the line it starts on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Checks a rendered Dockerfile for instructions Docker would reject or
+/// misread: unknown instructions, missing arguments, broken JSON forms, and
+/// anything but `ARG` before the first `FROM`. Values interpolated from the
+/// config (`setup`, `mirrors`, `template.vars`, `build.files`) can produce
+/// them, and Docker only reports them once the build gets there.
+pub fn lint(dockerfile: &str) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let (instructions, dangling) = instructions(dockerfile);
+    let mut seen_from = false;
+    for (line, text) in &instructions {
+        let mut problem = |message: String| {
+            problems.push(Problem {
+                line: *line,
+                message,
+            })
+        };
+        let (keyword, args) = split_keyword(text);
+        let upper = keyword.to_ascii_uppercase();
+        if !seen_from && upper != "FROM" && upper != "ARG" {
+            problem(format!("`{upper}` before `FROM`"));
+        }
+        seen_from |= upper == "FROM";
+        if let Err(message) = check(keyword, args) {
+            problem(message);
+        }
+    }
+    if instructions.is_empty() {
+        problems.push(Problem {
+            line: 1,
+            message: "no instructions".to_string(),
+        });
+    }
+    if let Some(line) = dangling {
+        problems.push(Problem {
+            line,
+            message: "line continuation at the end of the file".to_string(),
+        });
+    }
+    problems
+}
+
+/// Instructions with the line each starts on, continuation lines joined.
+/// Comments and blank lines are dropped, also inside a continuation, as
+/// Docker does. Also returns where a continuation left open at the end of
+/// the file starts.
+fn instructions(dockerfile: &str) -> (Vec<(usize, String)>, Option<usize>) {
+    let mut instructions = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in dockerfile.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let (start, mut text) = current.take().unwrap_or((index + 1, String::new()));
+        match trimmed.strip_suffix('\\') {
+            Some(continued) => {
+                text.push_str(continued);
+                text.push(' ');
+                current = Some((start, text));
+            }
+            None => {
+                text.push_str(trimmed);
+                instructions.push((start, text));
+            }
+        }
+    }
+    (instructions, current.map(|(start, _)| start))
+}
+
+/// The instruction keyword and its trimmed arguments.
+fn split_keyword(text: &str) -> (&str, &str) {
+    let (keyword, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    (keyword, args.trim())
+}
+
+/// Checks one instruction's arguments, describing the first problem.
+fn check(keyword: &str, args: &str) -> Result<(), String> {
+    let upper = keyword.to_ascii_uppercase();
+    if !INSTRUCTIONS.contains(&upper.as_str()) {
+        return Err(format!("unknown instruction `{keyword}`"));
+    }
+    if args.is_empty() {
+        return Err(format!("`{upper}` needs arguments"));
+    }
+    match upper.as_str() {
+        "RUN" | "CMD" | "ENTRYPOINT" => json_form(&upper, args).map(drop),
+        "SHELL" => match json_form(&upper, args)? {
+            Some(_) => Ok(()),
+            None => Err("`SHELL` needs the JSON form, e.g. `[\"/bin/bash\", \"-c\"]`".to_string()),
+        },
+        "COPY" | "ADD" => {
+            let operands = match json_form(&upper, args)? {
+                Some(operands) => operands,
+                None => args
+                    .split_whitespace()
+                    .filter(|arg| !arg.starts_with("--"))
+                    .map(str::to_string)
+                    .collect(),
+            };
+            if operands.len() < 2 {
+                return Err(format!("`{upper}` needs a source and a destination"));
+            }
+            Ok(())
+        }
+        "ENV" | "LABEL" => {
+            // `ENV key value` is the legacy form; without `=` it needs a value
+            let key = args.split_whitespace().next().unwrap_or_default();
+            if !key.contains('=') && args.split_whitespace().count() < 2 {
+                return Err(format!("`{upper} {key}` has no value"));
+            }
+            Ok(())
+        }
+        "ONBUILD" => {
+            let (trigger, trigger_args) = split_keyword(args);
+            let trigger_upper = trigger.to_ascii_uppercase();
+            if matches!(trigger_upper.as_str(), "ONBUILD" | "FROM" | "MAINTAINER") {
+                return Err(format!("`ONBUILD` can't trigger `{trigger_upper}`"));
+            }
+            check(trigger, trigger_args)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The arguments of a JSON-form instruction, `None` in shell form. Docker
+/// runs a broken JSON form as a shell command instead, which is never what
+/// was meant.
+fn json_form(keyword: &str, args: &str) -> Result<Option<Vec<String>>, String> {
+    if !args.starts_with('[') {
+        return Ok(None);
+    }
+    serde_json::from_str(args).map(Some).map_err(|_| {
+        format!("`{keyword}` arguments start with `[` but aren't a JSON array of strings")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(dockerfile: &str) -> Vec<String> {
+        lint(dockerfile).iter().map(Problem::to_string).collect()
+    }
+
+    #[test]
+    fn well_formed_dockerfile_passes() {
+        let dockerfile = "\
+# syntax=docker/dockerfile:1
+ARG BASE=ubuntu:24.04
+FROM $BASE
+
+RUN apt-get update \\
+    # comments inside a continuation are dropped
+    && apt-get install -y curl
+ENV PATH=/opt/bin:$PATH LANG=C.UTF-8
+ENV EDITOR vim
+COPY --chmod=755 entrypoint.sh /usr/local/bin/entrypoint.sh
+SHELL [\"/bin/bash\", \"-o\", \"pipefail\", \"-c\"]
+ENTRYPOINT [\"/usr/local/bin/entrypoint.sh\"]
+CMD [\"sleep\", \"infinity\"]
+";
+        assert_eq!(messages(dockerfile), Vec::<String>::new());
+    }
+
+    #[test]
+    fn malformed_instructions_are_reported_with_their_line() {
+        let dockerfile = "\
+RUN echo too early
+FROM ubuntu:24.04
+RUN apt-get install -y \\
+    curl
+https://mirror.example.com/ubuntu
+COPY entrypoint.sh
+ENV DEBIAN_FRONTEND
+CMD [\"sleep\", infinity]
+SHELL /bin/bash -c
+ONBUILD FROM scratch
+RUN \\
+";
+        assert_eq!(
+            messages(dockerfile),
+            vec![
+                "line 1: `RUN` before `FROM`",
+                "line 5: unknown instruction `https://mirror.example.com/ubuntu`",
+                "line 6: `COPY` needs a source and a destination",
+                "line 7: `ENV DEBIAN_FRONTEND` has no value",
+                "line 8: `CMD` arguments start with `[` but aren't a JSON array of strings",
+                "line 9: `SHELL` needs the JSON form, e.g. `[\"/bin/bash\", \"-c\"]`",
+                "line 10: `ONBUILD` can't trigger `FROM`",
+                "line 11: line continuation at the end of the file",
+            ]
+        );
+        assert_eq!(messages(""), vec!["line 1: no instructions"]);
+    }
+}
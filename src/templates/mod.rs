@@ -1,12 +1,32 @@
-use anyhow::Result;
+use std::path::{Component, Path};
+
+use anyhow::{Context, Result};
 use minijinja::{Environment, context};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
 
-use crate::config::Config;
+use crate::config::{BuildFile, Config, ShellFramework, expand_home};
+use crate::error::Error;
 use crate::runtime;
 
+pub mod lint;
+
 static BASE_TEMPLATE: &str = include_str!("base.dockerfile");
 static CHIEF_TEMPLATE: &str = include_str!("chief.dockerfile");
 static ENTRYPOINT_SCRIPT: &str = include_str!("entrypoint.sh");
+static SHELL_TEMPLATE: &str = include_str!("shell.dockerfile");
+static OHMYZSH_ZSHRC: &str = include_str!("ohmyzsh.zshrc");
+static STARSHIP_BASHRC: &str = include_str!("starship.bashrc");
+static STARSHIP_ZSHRC: &str = include_str!("starship.zshrc");
+static SSH_TEMPLATE: &str = include_str!("ssh.dockerfile");
+static SSH_KNOWN_HOSTS: &str = include_str!("ssh_known_hosts");
+
+/// Cache mounts prefixed to `apt-get` commands in BuildKit builds. `locked`
+/// because apt can't share its lists and archives between concurrent builds.
+const APT_CACHE_MOUNTS: &str = concat!(
+    "--mount=type=cache,target=/var/cache/apt,sharing=locked ",
+    "--mount=type=cache,target=/var/lib/apt/lists,sharing=locked \\\n    ",
+);
 
 /// The result of rendering templates, containing the Dockerfile and any extra
 /// files that must be included in the Docker build context.
@@ -52,6 +72,8 @@ impl<'a> TemplateRenderer<'a> {
     pub fn new() -> Result<Self> {
         let mut env = Environment::new();
         env.add_template("base", BASE_TEMPLATE)?;
+        env.add_template("shell", SHELL_TEMPLATE)?;
+        env.add_template("ssh", SSH_TEMPLATE)?;
         Ok(Self { env })
     }
 
@@ -67,8 +89,62 @@ impl<'a> TemplateRenderer<'a> {
         config: &Config,
         install_chief: bool,
     ) -> Result<RenderResult> {
+        let mut context_files = Vec::new();
+
+        // Custom CA certificates are copied into the build context and their
+        // digests recorded in the Dockerfile, so a changed cert changes the tag
+        let ca_certs = load_ca_certs(&config.tls.ca_certs)?;
+        let ca_cert_entries = digest_entries(&ca_certs);
+        context_files.extend(ca_certs);
+
+        // Values shared by the base template and every layer after it
+        let buildkit = config.build.buildkit == Some(true);
+        let shared = context! {
+            mirrors => &config.mirrors,
+            vars => &config.template.vars,
+            buildkit => buildkit,
+            minimal => config.base.minimal(),
+            apt_mounts => if buildkit { APT_CACHE_MOUNTS } else { "" },
+        };
+
         let tmpl = self.env.get_template("base")?;
-        let mut rendered = tmpl.render(context! {})?;
+        let mut rendered = tmpl.render(context! {
+            ca_certs => ca_cert_entries,
+            platform => &config.container.platform,
+            ..shared.clone()
+        })?;
+
+        // Shell framework, before the runtimes so changing one doesn't
+        // reinstall the other
+        let rc_files = shell_rc_files(config.shell.framework);
+        if !rc_files.is_empty() {
+            let layer = self.env.get_template("shell")?.render(context! {
+                framework => config.shell.framework,
+                rc_files => digest_entries(&rc_files),
+                ..shared.clone()
+            })?;
+            rendered.push('\n');
+            rendered.push_str(&layer);
+        }
+        context_files.extend(rc_files);
+
+        // SSH client and host keys, so the first clone over SSH doesn't stop
+        // at host verification
+        if config.shell.mount_ssh == Some(true) {
+            let known_hosts = ContextFile {
+                path: "ssh/ssh_known_hosts".to_string(),
+                content: SSH_KNOWN_HOSTS.to_string(),
+                mode: 0o644,
+            };
+            let layer = self.env.get_template("ssh")?.render(context! {
+                known_hosts_path => &known_hosts.path,
+                known_hosts_digest => sha256_hex(&known_hosts.content),
+                ..shared.clone()
+            })?;
+            rendered.push('\n');
+            rendered.push_str(&layer);
+            context_files.push(known_hosts);
+        }
 
         // Collect runtimes via the registry (deterministic order: PHP, Node, Rust, Go)
         let runtimes = runtime::collect_runtimes(config)?;
@@ -77,9 +153,35 @@ impl<'a> TemplateRenderer<'a> {
             let mut rt_env = Environment::new();
             rt_env.add_template(rt.name(), rt.template())?;
             let rt_tmpl = rt_env.get_template(rt.name())?;
-            let layer = rt_tmpl.render(rt.template_context())?;
+            let layer = rt_tmpl.render(context! { ..rt.template_context(), ..shared.clone() })?;
             rendered.push('\n');
             rendered.push_str(&layer);
+            context_files.extend(rt.context_files());
+        }
+
+        // Project build files, copied in before the setup commands that may
+        // use them. Digests are recorded so a changed file changes the tag
+        let build_files = load_build_files(&config.build.files)?;
+        if !build_files.is_empty() {
+            rendered.push('\n');
+            rendered.push_str(&build_files_layer(&config.build.files, &build_files));
+        }
+        context_files.extend(build_files);
+
+        // Project setup commands, after the runtimes they typically use, with
+        // those runtimes' package caches mounted under BuildKit
+        if !config.setup.is_empty() {
+            let cache_dirs: Vec<&str> = if buildkit {
+                runtimes
+                    .iter()
+                    .flat_map(|rt| rt.cache_dirs())
+                    .copied()
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            rendered.push('\n');
+            rendered.push_str(&setup_layer(&config.setup, &cache_dirs)?);
         }
 
         // Install Chief binary from GitHub releases when requested
@@ -94,11 +196,25 @@ impl<'a> TemplateRenderer<'a> {
         rendered.push_str("ENTRYPOINT [\"/usr/local/bin/entrypoint.sh\"]\n");
         rendered.push_str("CMD [\"sleep\", \"infinity\"]\n");
 
-        let context_files = vec![ContextFile {
-            path: "entrypoint.sh".to_string(),
-            content: ENTRYPOINT_SCRIPT.to_string(),
-            mode: 0o755,
-        }];
+        // Caught here rather than minutes into the build
+        let problems = lint::lint(&rendered);
+        if !problems.is_empty() {
+            let problems: Vec<String> = problems.iter().map(|p| format!("  {p}")).collect();
+            return Err(Error::Config(anyhow::anyhow!(
+                "the rendered Dockerfile is malformed; check `setup`, `mirrors`, `template.vars`, and `build.files`:\n{}",
+                problems.join("\n")
+            ))
+            .into());
+        }
+
+        context_files.insert(
+            0,
+            ContextFile {
+                path: "entrypoint.sh".to_string(),
+                content: ENTRYPOINT_SCRIPT.to_string(),
+                mode: 0o755,
+            },
+        );
 
         Ok(RenderResult {
             dockerfile: rendered,
@@ -107,6 +223,159 @@ impl<'a> TemplateRenderer<'a> {
     }
 }
 
+/// Reads `[[build.files]]` into build-context files.
+fn load_build_files(files: &[BuildFile]) -> Result<Vec<ContextFile>> {
+    let mut loaded: Vec<ContextFile> = Vec::new();
+    for file in files {
+        let path = file.context_path();
+        validate_context_path(&path)?;
+        if loaded.iter().any(|f| f.path == path) {
+            anyhow::bail!("build.files: more than one file uses context path `{path}`");
+        }
+
+        let host_path = file.host_path();
+        let content = std::fs::read_to_string(&host_path)
+            .with_context(|| format!("failed to read build file {}", host_path.display()))?;
+        loaded.push(ContextFile {
+            path,
+            content,
+            mode: file.mode.unwrap_or(0o644),
+        });
+    }
+    Ok(loaded)
+}
+
+/// Rejects context paths that escape the context or clash with the files
+/// bubble-bot adds itself.
+fn validate_context_path(path: &str) -> Result<()> {
+    let relative = Path::new(path);
+    let escapes = relative.is_absolute()
+        || relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)));
+    if path.is_empty() || escapes {
+        anyhow::bail!(
+            "build.files: context path `{path}` must be relative and stay inside the build context"
+        );
+    }
+    let reserved = ["Dockerfile", "entrypoint.sh", "ca-certs", "shell", "ssh"];
+    if let Some(Component::Normal(first)) = relative.components().next() {
+        if reserved.iter().any(|r| first == *r) {
+            anyhow::bail!("build.files: context path `{path}` is reserved by bubble-bot");
+        }
+    }
+    Ok(())
+}
+
+/// Renders the digest of every build file, and a `COPY` for each one with a
+/// `dest`.
+fn build_files_layer(files: &[BuildFile], loaded: &[ContextFile]) -> String {
+    let mut layer = String::from("# Project build files\n");
+    for loaded in loaded {
+        layer.push_str(&format!(
+            "# {} sha256:{}\n",
+            loaded.path,
+            sha256_hex(&loaded.content)
+        ));
+    }
+    for (file, loaded) in files.iter().zip(loaded) {
+        if let Some(dest) = &file.dest {
+            layer.push_str(&format!("COPY {} {dest}\n", loaded.path));
+        }
+    }
+    layer
+}
+
+/// Renders `setup` commands as a single `RUN` layer, so they're cached (and
+/// invalidated) together, with a cache mount for each of `cache_dirs`.
+fn setup_layer(commands: &[String], cache_dirs: &[&str]) -> Result<String> {
+    if let Some(command) = commands.iter().find(|c| c.contains('\n')) {
+        anyhow::bail!(
+            "setup command {command:?} spans multiple lines; split it into separate entries"
+        );
+    }
+    let commands: Vec<&str> = commands.iter().map(|c| c.trim()).collect();
+    let mounts: String = cache_dirs
+        .iter()
+        .map(|dir| format!("--mount=type=cache,target={dir} \\\n    "))
+        .collect();
+    Ok(format!(
+        "# Project setup commands\nRUN {mounts}{}\n",
+        commands.join(" \\\n    && ")
+    ))
+}
+
+/// Default rc files for a shell framework, as build-context files under
+/// `shell/` that are copied into the home directory. An rc file bind-mounted
+/// over them still takes precedence.
+fn shell_rc_files(framework: Option<ShellFramework>) -> Vec<ContextFile> {
+    let files: &[(&str, &str)] = match framework {
+        Some(ShellFramework::Ohmyzsh) => &[(".zshrc", OHMYZSH_ZSHRC)],
+        Some(ShellFramework::Starship) => {
+            &[(".bashrc", STARSHIP_BASHRC), (".zshrc", STARSHIP_ZSHRC)]
+        }
+        Some(ShellFramework::None) | None => &[],
+    };
+    files
+        .iter()
+        .map(|(name, content)| ContextFile {
+            path: format!("shell/{name}"),
+            content: content.to_string(),
+            // The home directory is writable by whichever UID runs the container
+            mode: 0o666,
+        })
+        .collect()
+}
+
+/// A context file's digest, as exposed to templates.
+#[derive(Debug, Serialize)]
+struct DigestEntry {
+    path: String,
+    digest: String,
+}
+
+fn digest_entries(files: &[ContextFile]) -> Vec<DigestEntry> {
+    files
+        .iter()
+        .map(|file| DigestEntry {
+            path: file.path.clone(),
+            digest: sha256_hex(&file.content),
+        })
+        .collect()
+}
+
+/// Reads the configured CA certificates into build-context files under
+/// `ca-certs/`. Files get a `.crt` extension, which `update-ca-certificates`
+/// requires.
+fn load_ca_certs(paths: &[String]) -> Result<Vec<ContextFile>> {
+    paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let host_path = expand_home(path);
+            let content = std::fs::read_to_string(&host_path).with_context(|| {
+                format!("failed to read CA certificate {}", host_path.display())
+            })?;
+            if !content.contains("-----BEGIN CERTIFICATE-----") {
+                anyhow::bail!("CA certificate {} is not PEM-encoded", host_path.display());
+            }
+            let stem = host_path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "cert".to_string());
+            Ok(ContextFile {
+                path: format!("ca-certs/{i}-{stem}.crt"),
+                content,
+                mode: 0o644,
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn sha256_hex(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -149,6 +418,174 @@ mod tests {
         assert!(output.contains("WORKDIR /workspace"));
     }
 
+    #[test]
+    fn template_vars_reach_base_and_runtime_templates() {
+        let mut env = Environment::new();
+        env.add_template("base", "FROM ubuntu:{{ vars.ubuntu }}")
+            .unwrap();
+        let renderer = TemplateRenderer { env };
+
+        let mut config = config_with_runtimes(None, Some("22"), false, None);
+        config
+            .template
+            .vars
+            .insert("ubuntu".to_string(), toml::Value::from("24.04"));
+        let output = renderer.render(&config).unwrap().dockerfile;
+        assert!(output.starts_with("FROM ubuntu:24.04"), "{output}");
+
+        // A var no template uses leaves the Dockerfile (and tag) unchanged
+        let mut with_unused = config.clone();
+        with_unused
+            .template
+            .vars
+            .insert("unused".to_string(), toml::Value::from(true));
+        assert_eq!(renderer.render(&with_unused).unwrap().dockerfile, output);
+    }
+
+    #[test]
+    fn php_extensions_and_ini_render_into_php_layer() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = config_with_runtimes(Some("8.3"), None, false, None);
+        config.runtimes.php_extensions = vec!["xdebug".to_string(), "imagick".to_string()];
+        config
+            .runtimes
+            .php_ini
+            .insert("memory_limit".to_string(), toml::Value::from("1G"));
+        let result = renderer.render(&config).unwrap();
+        let output = &result.dockerfile;
+
+        assert!(output.contains("    php8.3-xdebug \\\n    php8.3-imagick \\\n    && rm -rf"));
+        assert!(
+            output.contains("COPY php/99-bubble-bot.ini /etc/php/8.3/cli/conf.d/99-bubble-bot.ini")
+        );
+        let ini = result
+            .context_files
+            .iter()
+            .find(|f| f.path == "php/99-bubble-bot.ini")
+            .unwrap();
+        assert_eq!(ini.content, "memory_limit = 1G\n");
+
+        // Changed settings change the tag
+        config
+            .runtimes
+            .php_ini
+            .insert("memory_limit".to_string(), toml::Value::from("2G"));
+        assert_ne!(renderer.render(&config).unwrap().dockerfile, *output);
+    }
+
+    #[test]
+    fn shell_framework_adds_install_layer_and_rc_files() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = Config::default();
+        config.shell.framework = Some(ShellFramework::Ohmyzsh);
+        let result = renderer.render(&config).unwrap();
+        assert!(
+            result
+                .dockerfile
+                .contains("install -y --no-install-recommends zsh")
+        );
+        assert!(
+            result
+                .dockerfile
+                .contains("ohmyzsh.git /home/dev/.oh-my-zsh")
+        );
+        assert!(result.dockerfile.contains("COPY shell/ /home/dev/"));
+        let paths: Vec<&str> = result
+            .context_files
+            .iter()
+            .map(|f| f.path.as_str())
+            .collect();
+        assert!(paths.contains(&"shell/.zshrc"), "{paths:?}");
+
+        config.shell.framework = Some(ShellFramework::Starship);
+        let result = renderer.render(&config).unwrap();
+        assert!(result.dockerfile.contains("starship.rs/install.sh"));
+        assert!(result.dockerfile.contains("# shell/.bashrc sha256:"));
+        let bashrc = result
+            .context_files
+            .iter()
+            .find(|f| f.path == "shell/.bashrc")
+            .unwrap();
+        assert!(bashrc.content.contains("starship init bash"));
+
+        // Runtime layers come after, so switching frameworks keeps them cached
+        config.runtimes.node = Some("22".to_string());
+        let output = renderer.render(&config).unwrap().dockerfile;
+        assert!(output.find("starship.rs").unwrap() < output.find("Node.js").unwrap());
+    }
+
+    #[test]
+    fn mount_ssh_installs_client_and_host_keys() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = Config::default();
+        assert!(
+            !renderer
+                .render(&config)
+                .unwrap()
+                .dockerfile
+                .contains("openssh-client")
+        );
+
+        config.shell.mount_ssh = Some(true);
+        let result = renderer.render(&config).unwrap();
+        assert!(
+            result
+                .dockerfile
+                .contains("--no-install-recommends openssh-client")
+        );
+        assert!(result.dockerfile.contains("# ssh/ssh_known_hosts sha256:"));
+        assert!(
+            result
+                .dockerfile
+                .contains("COPY ssh/ssh_known_hosts /etc/ssh/ssh_known_hosts")
+        );
+        let known_hosts = result
+            .context_files
+            .iter()
+            .find(|f| f.path == "ssh/ssh_known_hosts")
+            .unwrap();
+        assert!(known_hosts.content.contains("github.com ssh-ed25519 "));
+        assert!(known_hosts.content.contains("gitlab.com ssh-ed25519 "));
+    }
+
+    #[test]
+    fn platform_pins_base_image_and_changes_tag() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = Config::default();
+        let native = renderer.render(&config).unwrap().dockerfile;
+        config.container.platform = Some("linux/amd64".to_string());
+        let amd64 = renderer.render(&config).unwrap().dockerfile;
+        assert_eq!(
+            amd64.lines().next().unwrap(),
+            "FROM --platform=linux/amd64 ubuntu:24.04"
+        );
+        assert_ne!(amd64, native);
+    }
+
+    #[test]
+    fn no_shell_framework_leaves_image_unchanged() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = Config::default();
+        let plain = renderer.render(&config).unwrap();
+        config.shell.framework = Some(ShellFramework::None);
+        let none = renderer.render(&config).unwrap();
+        assert_eq!(none.dockerfile, plain.dockerfile);
+        assert!(
+            none.context_files
+                .iter()
+                .all(|f| !f.path.starts_with("shell/"))
+        );
+    }
+
+    #[test]
+    fn php_layer_unchanged_without_extensions_or_ini() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let config = config_with_runtimes(Some("8.3"), None, false, None);
+        let output = renderer.render(&config).unwrap().dockerfile;
+        assert!(output.contains("    php8.3-tokenizer \\\n    && rm -rf"));
+        assert!(!output.contains("99-bubble-bot.ini"));
+    }
+
     #[test]
     fn render_is_deterministic() {
         let renderer = TemplateRenderer::new().unwrap();
@@ -562,6 +999,267 @@ mod tests {
         assert!(!entrypoint.contains("credentials"));
     }
 
+    const TEST_PEM: &str = "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n";
+
+    fn write_cert(dir: &tempfile::TempDir, name: &str, content: &str) -> String {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn render_without_ca_certs_has_no_cert_layer() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let result = renderer.render(&Config::default()).unwrap();
+        assert!(!result.dockerfile.contains("update-ca-certificates"));
+        assert!(!result.dockerfile.contains("NODE_EXTRA_CA_CERTS"));
+    }
+
+    #[test]
+    fn render_with_ca_certs_installs_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.tls.ca_certs = vec![write_cert(&dir, "corp-root.pem", TEST_PEM)];
+
+        let renderer = TemplateRenderer::new().unwrap();
+        let result = renderer.render(&config).unwrap();
+        let output = &result.dockerfile;
+
+        assert!(output.contains("COPY ca-certs/ /usr/local/share/ca-certificates/bubble-bot/"));
+        assert!(output.contains("RUN update-ca-certificates"));
+        assert!(output.contains("NODE_EXTRA_CA_CERTS"));
+        assert!(output.contains("# ca-certs/0-corp-root.crt sha256:"));
+
+        let cert = result
+            .context_files
+            .iter()
+            .find(|f| f.path == "ca-certs/0-corp-root.crt")
+            .unwrap();
+        assert_eq!(cert.content, TEST_PEM);
+        assert_eq!(result.context_files[0].path, "entrypoint.sh");
+    }
+
+    #[test]
+    fn render_ca_certs_before_claude_install() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.tls.ca_certs = vec![write_cert(&dir, "corp.pem", TEST_PEM)];
+
+        let renderer = TemplateRenderer::new().unwrap();
+        let output = renderer.render(&config).unwrap().dockerfile;
+        let cert_pos = output.find("update-ca-certificates").unwrap();
+        let claude_pos = output.find("claude.ai/install.sh").unwrap();
+        assert!(
+            cert_pos < claude_pos,
+            "CA certs must be trusted before downloading over TLS"
+        );
+    }
+
+    #[test]
+    fn ca_cert_content_changes_dockerfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_cert(&dir, "corp.pem", TEST_PEM);
+        let mut config = Config::default();
+        config.tls.ca_certs = vec![path.clone()];
+
+        let renderer = TemplateRenderer::new().unwrap();
+        let before = renderer.render(&config).unwrap().dockerfile;
+        std::fs::write(&path, TEST_PEM.replace("MIIB", "MIIC")).unwrap();
+        let after = renderer.render(&config).unwrap().dockerfile;
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn missing_ca_cert_errors() {
+        let mut config = Config::default();
+        config.tls.ca_certs = vec!["/nonexistent/corp.pem".to_string()];
+        let renderer = TemplateRenderer::new().unwrap();
+        let err = renderer.render(&config).unwrap_err().to_string();
+        assert!(err.contains("failed to read CA certificate"));
+    }
+
+    fn build_file(dir: &tempfile::TempDir, name: &str, dest: Option<&str>) -> BuildFile {
+        let source = dir.path().join(name);
+        std::fs::write(&source, format!("contents of {name}\n")).unwrap();
+        BuildFile {
+            source: source.to_string_lossy().to_string(),
+            dest: dest.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn build_files_are_added_to_context_and_copied_before_setup() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.build.files = vec![
+            build_file(
+                &dir,
+                "php.ini",
+                Some("/usr/local/etc/php/conf.d/zz-project.ini"),
+            ),
+            BuildFile {
+                path: Some("config/npmrc".to_string()),
+                mode: Some(0o600),
+                ..build_file(&dir, ".npmrc", None)
+            },
+        ];
+        config.setup = vec!["true".to_string()];
+
+        let result = TemplateRenderer::new().unwrap().render(&config).unwrap();
+        let npmrc = result
+            .context_files
+            .iter()
+            .find(|f| f.path == "config/npmrc")
+            .unwrap();
+        assert_eq!(npmrc.mode, 0o600);
+        assert!(result.context_files.iter().any(|f| f.path == "php.ini"));
+
+        let output = &result.dockerfile;
+        let copy_pos = output
+            .find("COPY php.ini /usr/local/etc/php/conf.d/zz-project.ini\n")
+            .unwrap();
+        assert!(copy_pos < output.find("# Project setup commands").unwrap());
+        assert!(!output.contains("COPY config/npmrc"));
+        assert!(output.contains(&format!(
+            "# config/npmrc sha256:{}",
+            sha256_hex("contents of .npmrc\n")
+        )));
+    }
+
+    #[test]
+    fn build_file_content_changes_dockerfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.build.files = vec![build_file(&dir, "php.ini", None)];
+
+        let renderer = TemplateRenderer::new().unwrap();
+        let before = renderer.render(&config).unwrap().dockerfile;
+        std::fs::write(&config.build.files[0].source, "memory_limit = 1G\n").unwrap();
+        let after = renderer.render(&config).unwrap().dockerfile;
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn build_file_context_paths_are_validated() {
+        assert!(validate_context_path("config/php.ini").is_ok());
+        for path in [
+            "",
+            "/etc/passwd",
+            "../secret",
+            "a/../../b",
+            "ca-certs/x.crt",
+            "Dockerfile",
+        ] {
+            assert!(validate_context_path(path).is_err(), "{path}");
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.build.files = vec![
+            build_file(&dir, "a.ini", None),
+            build_file(&dir, "a.ini", None),
+        ];
+        let err = TemplateRenderer::new()
+            .unwrap()
+            .render(&config)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("more than one file"), "{err}");
+    }
+
+    #[test]
+    fn non_pem_ca_cert_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.tls.ca_certs = vec![write_cert(&dir, "corp.der", "not a cert")];
+        let renderer = TemplateRenderer::new().unwrap();
+        let err = renderer.render(&config).unwrap_err().to_string();
+        assert!(err.contains("not PEM-encoded"));
+    }
+
+    #[test]
+    fn render_without_mirrors_uses_upstream_sources() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let config = config_with_runtimes(Some("8.3"), Some("22"), true, Some("1.23"));
+        let output = renderer.render(&config).unwrap().dockerfile;
+        assert!(!output.contains("ubuntu.sources"));
+        assert!(!output.contains("NPM_CONFIG_REGISTRY"));
+        assert!(!output.contains("repos.packagist"));
+        assert!(!output.contains("replace-with"));
+        assert!(!output.contains("GOPROXY"));
+    }
+
+    #[test]
+    fn render_apt_mirror_before_first_apt_update() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = Config::default();
+        config.mirrors.apt = Some("http://mirror.corp/ubuntu/".to_string());
+        let output = renderer.render(&config).unwrap().dockerfile;
+
+        assert!(
+            output.contains("s|http://archive.ubuntu.com/ubuntu/\\?|http://mirror.corp/ubuntu/|g")
+        );
+        // An archive mirror doesn't carry arm64 and other ports
+        assert!(!output.contains("ports.ubuntu.com"));
+        let mirror_pos = output.find("ubuntu.sources").unwrap();
+        let update_pos = output.find("apt-get update").unwrap();
+        assert!(mirror_pos < update_pos);
+    }
+
+    #[test]
+    fn render_apt_ports_mirror_on_its_own() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = Config::default();
+        config.mirrors.apt_ports = Some("http://mirror.corp/ubuntu-ports/".to_string());
+        let output = renderer.render(&config).unwrap().dockerfile;
+
+        assert!(output.contains(
+            "s|http://ports.ubuntu.com/ubuntu-ports/\\?|http://mirror.corp/ubuntu-ports/|g"
+        ));
+        assert!(!output.contains("archive.ubuntu.com"));
+    }
+
+    #[test]
+    fn render_runtime_mirrors() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = config_with_runtimes(Some("8.3"), Some("22"), true, Some("1.23"));
+        config.mirrors.npm = Some("https://npm.corp/".to_string());
+        config.mirrors.composer = Some("https://packagist.corp".to_string());
+        config.mirrors.crates = Some("sparse+https://crates.corp/index/".to_string());
+        config.mirrors.go = Some("https://goproxy.corp".to_string());
+        let output = renderer.render(&config).unwrap().dockerfile;
+
+        assert!(output.contains("ENV NPM_CONFIG_REGISTRY=https://npm.corp/"));
+        assert!(output.contains("repos.packagist composer https://packagist.corp"));
+        assert!(output.contains("sparse+https://crates.corp/index/"));
+        assert!(output.contains("$CARGO_HOME/config.toml"));
+        assert!(output.contains("ENV GOPROXY=https://goproxy.corp"));
+    }
+
+    #[test]
+    fn render_mirror_without_runtime_is_ignored() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = Config::default();
+        config.mirrors.npm = Some("https://npm.corp/".to_string());
+        let output = renderer.render(&config).unwrap().dockerfile;
+        assert!(!output.contains("NPM_CONFIG_REGISTRY"));
+    }
+
+    #[test]
+    fn render_rejects_a_malformed_dockerfile() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = config_with_runtimes(None, None, false, Some("1.23"));
+        config.mirrors.go = Some("https://goproxy.corp\nproxy.golang.org".to_string());
+        let err = renderer.render(&config).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(": unknown instruction `proxy.golang.org`"),
+            "{err}"
+        );
+        assert_eq!(crate::error::exit_code(&err), 80);
+    }
+
     #[test]
     fn render_without_chief_has_no_chief_layer() {
         let renderer = TemplateRenderer::new().unwrap();
@@ -599,6 +1297,106 @@ mod tests {
         );
     }
 
+    #[test]
+    fn setup_commands_render_one_layer_after_runtimes() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = config_with_runtimes(None, Some("22"), false, None);
+        config.setup = vec![
+            "npm install -g pnpm".to_string(),
+            "pip install poetry ".to_string(),
+        ];
+        let output = renderer.render(&config).unwrap().dockerfile;
+
+        assert!(output.contains("RUN npm install -g pnpm \\\n    && pip install poetry\n"));
+        let setup_pos = output.find("# Project setup commands").unwrap();
+        assert!(output.find("nodesource").unwrap() < setup_pos);
+        assert!(setup_pos < output.find("ENTRYPOINT").unwrap());
+    }
+
+    #[test]
+    fn multiline_setup_command_errors() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let config = Config {
+            setup: vec!["apt-get update\napt-get install -y jq".to_string()],
+            ..Default::default()
+        };
+        let err = renderer.render(&config).unwrap_err().to_string();
+        assert!(err.contains("multiple lines"), "{err}");
+    }
+
+    #[test]
+    fn render_without_buildkit_has_no_cache_mounts() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = config_with_runtimes(Some("8.3"), Some("22"), true, Some("1.23"));
+        config.setup = vec!["npm install -g pnpm".to_string()];
+        let output = renderer.render(&config).unwrap().dockerfile;
+
+        assert!(!output.contains("--mount"));
+        assert!(!output.contains("COMPOSER_CACHE_DIR"));
+        assert_eq!(output.matches("rm -rf /var/lib/apt/lists/*").count(), 3);
+    }
+
+    #[test]
+    fn render_with_buildkit_mounts_package_caches() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = config_with_runtimes(Some("8.3"), Some("22"), true, Some("1.23"));
+        config.build.buildkit = Some(true);
+        config.shell.mount_ssh = Some(true);
+        config.shell.framework = Some(ShellFramework::Ohmyzsh);
+        config.container.shell = Some("zsh".to_string());
+        config.setup = vec!["npm install -g pnpm".to_string()];
+        let output = renderer.render(&config).unwrap().dockerfile;
+
+        // Every apt layer (base, shell, ssh, PHP, Node) keeps its lists and
+        // archives in the cache instead of deleting them
+        assert!(output.contains("rm -f /etc/apt/apt.conf.d/docker-clean"));
+        assert_eq!(output.matches(APT_CACHE_MOUNTS).count(), 5);
+        assert!(!output.contains("rm -rf /var/lib/apt/lists/*"));
+
+        assert!(output.contains("ENV COMPOSER_CACHE_DIR=/home/dev/.cache/composer"));
+        assert!(output.contains(
+            "RUN --mount=type=cache,target=/home/dev/.cache/composer \\\n    \
+             --mount=type=cache,target=/home/dev/.npm \\\n    \
+             --mount=type=cache,target=/usr/local/cargo/registry \\\n    \
+             --mount=type=cache,target=/home/dev/.cache/go-build \\\n    \
+             npm install -g pnpm\n"
+        ));
+    }
+
+    #[test]
+    fn render_with_buildkit_mounts_only_enabled_runtime_caches() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = config_with_runtimes(None, Some("22"), false, None);
+        config.build.buildkit = Some(true);
+        config.setup = vec!["npm install -g pnpm".to_string()];
+        let output = renderer.render(&config).unwrap().dockerfile;
+
+        assert!(output.contains(
+            "RUN --mount=type=cache,target=/home/dev/.npm \\\n    npm install -g pnpm\n"
+        ));
+        assert!(!output.contains("go-build"));
+    }
+
+    #[test]
+    fn render_minimal_base_leaves_out_the_toolchain() {
+        let renderer = TemplateRenderer::new().unwrap();
+        let mut config = config_with_runtimes(Some("8.3"), Some("22"), true, None);
+        let full = renderer.render(&config).unwrap().dockerfile;
+        assert!(full.contains("build-essential"));
+        assert!(!full.contains("libc6-dev"));
+
+        config.base.minimal = Some(true);
+        let output = renderer.render(&config).unwrap().dockerfile;
+        assert!(!output.contains("build-essential"));
+        assert!(!output.contains("wget"));
+        assert!(output.contains("    ca-certificates \\\n    && rm -rf /var/lib/apt/lists/*\n"));
+        assert!(output.contains("apt-get purge -y --auto-remove software-properties-common"));
+        // Rust still needs a linker
+        assert!(output.contains("apt-get install -y --no-install-recommends gcc libc6-dev"));
+        // Every apt layer drops its lists: base, PHP, Node, Rust's linker
+        assert_eq!(output.matches("rm -rf /var/lib/apt/lists/*").count(), 4);
+    }
+
     #[test]
     fn render_chief_with_runtimes() {
         let renderer = TemplateRenderer::new().unwrap();
@@ -0,0 +1,339 @@
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+/// Repository whose GitHub releases `self-update` installs from.
+const REPO: &str = "hostingaccessories/bubble-bot";
+
+/// Release asset listing `<sha256>  <archive>` for every archive.
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+/// The running binary's version.
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Exit code of `self-update --check` when a newer release exists.
+pub const UPDATE_AVAILABLE_EXIT_CODE: i32 = 10;
+
+/// A GitHub release, as returned by the releases API.
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// The release's version, without the tag's `v` prefix.
+    pub fn version(&self) -> &str {
+        self.tag_name.strip_prefix('v').unwrap_or(&self.tag_name)
+    }
+
+    fn asset_url(&self, name: &str) -> Result<&str> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .map(|asset| asset.browser_download_url.as_str())
+            .with_context(|| format!("release {} has no {name}", self.tag_name))
+    }
+}
+
+/// Fetches the latest release. `GITHUB_TOKEN` is sent if set, so CI runners
+/// sharing an IP don't hit the anonymous rate limit.
+pub fn latest_release() -> Result<Release> {
+    let mut headers = vec!["Accept: application/vnd.github+json".to_string()];
+    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
+        if !token.is_empty() {
+            headers.push(format!("Authorization: Bearer {token}"));
+        }
+    }
+    let body = fetch(
+        &format!("https://api.github.com/repos/{REPO}/releases/latest"),
+        &headers,
+    )?;
+    serde_json::from_slice(&body).context("unexpected response from the GitHub releases API")
+}
+
+/// Whether `candidate` is a newer `major.minor.patch` than `current`.
+/// Pre-release and build suffixes are ignored.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    fn parse(version: &str) -> [u64; 3] {
+        let core = version.split(['-', '+']).next().unwrap_or_default();
+        let mut parts = core.split('.').map(|part| part.parse().unwrap_or(0));
+        [0; 3].map(|_| parts.next().unwrap_or(0))
+    }
+    parse(candidate) > parse(current)
+}
+
+/// The release target triple for this platform, matching the release
+/// workflow's build matrix.
+pub fn release_target(arch: &str, os: &str) -> Option<String> {
+    let os = match os {
+        "linux" => "unknown-linux-gnu",
+        "macos" => "apple-darwin",
+        _ => return None,
+    };
+    matches!(arch, "x86_64" | "aarch64").then(|| format!("{arch}-{os}"))
+}
+
+fn archive_name(target: &str) -> String {
+    format!("bubble-bot-{target}.tar.gz")
+}
+
+/// Downloads `release`'s archive for this platform, verifies it against the
+/// release's checksums, and replaces the running executable with its
+/// binary. Returns the replaced path.
+pub fn install(release: &Release) -> Result<PathBuf> {
+    let exe = std::env::current_exe().context("failed to locate the running executable")?;
+    // Replace the file a symlink (e.g. in ~/.local/bin) points to
+    let exe = exe.canonicalize().unwrap_or(exe);
+    if exe.components().any(|c| c.as_os_str() == "Cellar") {
+        bail!(
+            "{} is managed by Homebrew; update it with `brew upgrade bubble-bot`",
+            exe.display()
+        );
+    }
+    let target =
+        release_target(std::env::consts::ARCH, std::env::consts::OS).with_context(|| {
+            format!(
+                "no release binary for {}-{}",
+                std::env::consts::ARCH,
+                std::env::consts::OS
+            )
+        })?;
+
+    let name = archive_name(&target);
+    info!(version = release.version(), archive = %name, "downloading release");
+    let archive = fetch(release.asset_url(&name)?, &[])?;
+    let checksums = fetch(release.asset_url(CHECKSUMS_ASSET)?, &[])?;
+    verify_checksum(&archive, &String::from_utf8_lossy(&checksums), &name)?;
+
+    // Unpack next to the executable so the final rename stays on one
+    // filesystem and replaces it atomically
+    let dir = exe.parent().context("executable has no parent directory")?;
+    let staging = tempfile::Builder::new()
+        .prefix(".bubble-bot-update")
+        .tempdir_in(dir)
+        .with_context(|| {
+            format!(
+                "cannot write to {}; rerun with permission to replace {}",
+                dir.display(),
+                exe.display()
+            )
+        })?;
+    let binary = unpack(&archive, staging.path())?;
+    std::fs::rename(&binary, &exe)
+        .with_context(|| format!("failed to replace {}", exe.display()))?;
+    Ok(exe)
+}
+
+/// Checks `data` against the `file` line of a `sha256sum` listing.
+fn verify_checksum(data: &[u8], checksums: &str, file: &str) -> Result<()> {
+    let expected = checksums
+        .lines()
+        .find_map(|line| {
+            let (hash, name) = line.split_once(char::is_whitespace)?;
+            // `sha256sum` marks binary-mode entries with `*`
+            (name.trim_start().trim_start_matches('*') == file).then_some(hash)
+        })
+        .with_context(|| format!("{CHECKSUMS_ASSET} has no entry for {file}"))?;
+    let actual = format!("{:x}", Sha256::digest(data));
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!("checksum mismatch for {file}: expected {expected}, got {actual}");
+    }
+    Ok(())
+}
+
+/// Extracts the release archive into `dir`, returning the executable
+/// `bubble-bot` binary in it.
+fn unpack(archive: &[u8], dir: &Path) -> Result<PathBuf> {
+    let path = dir.join("release.tar.gz");
+    std::fs::write(&path, archive)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    let output = Command::new("tar")
+        .arg("xzf")
+        .arg(&path)
+        .arg("-C")
+        .arg(dir)
+        .stdin(Stdio::null())
+        .output()
+        .context("failed to run tar")?;
+    if !output.status.success() {
+        bail!(
+            "failed to unpack release: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let binary = dir.join("bubble-bot");
+    if !binary.is_file() {
+        bail!("release archive has no bubble-bot binary");
+    }
+    std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755))?;
+    Ok(binary)
+}
+
+/// GETs `url` with `curl`, following redirects (release assets redirect to
+/// a CDN).
+fn fetch(url: &str, headers: &[String]) -> Result<Vec<u8>> {
+    let mut child = fetch_command(url, headers)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run curl")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        for header in headers {
+            writeln!(stdin, "{header}")?;
+        }
+    }
+    let output = child
+        .wait_with_output()
+        .context("failed to wait for curl")?;
+    if !output.status.success() {
+        // With retries, curl repeats the error once per attempt
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!(
+            "failed to fetch {url}: {}",
+            stderr.trim().lines().last().unwrap_or_default()
+        );
+    }
+    Ok(output.stdout)
+}
+
+/// The `curl` command for [`fetch`]. Headers are read from stdin rather
+/// than passed as arguments, where other users could read a token from `ps`.
+fn fetch_command(url: &str, headers: &[String]) -> Command {
+    let mut command = Command::new("curl");
+    command.args(["-fsSL", "--retry", "2"]);
+    if headers.is_empty() {
+        command.stdin(Stdio::null());
+    } else {
+        command.args(["-H", "@-"]).stdin(Stdio::piped());
+    }
+    command.arg(url);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_are_not_passed_as_arguments() {
+        let headers = vec!["Authorization: Bearer ghp_secret".to_string()];
+        let command = fetch_command("https://api.github.com/x", &headers);
+        let args: Vec<_> = command.get_args().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(
+            args,
+            vec![
+                "-fsSL",
+                "--retry",
+                "2",
+                "-H",
+                "@-",
+                "https://api.github.com/x"
+            ]
+        );
+        let command = fetch_command("https://example.com/a.tar.gz", &[]);
+        assert!(!command.get_args().any(|a| a == "@-"));
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert!(is_newer("0.10.0", "0.9.3"));
+        assert!(is_newer("1.0.0", "0.99.99"));
+        assert!(is_newer("0.2", "0.1.9"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.1.0-rc.1", "0.1.0"));
+        assert!(!is_newer("0.0.9", "0.1.0"));
+    }
+
+    #[test]
+    fn release_targets_match_the_build_matrix() {
+        assert_eq!(
+            release_target("aarch64", "macos").as_deref(),
+            Some("aarch64-apple-darwin")
+        );
+        assert_eq!(
+            release_target("x86_64", "linux").as_deref(),
+            Some("x86_64-unknown-linux-gnu")
+        );
+        assert_eq!(release_target("riscv64", "linux"), None);
+        assert_eq!(release_target("x86_64", "windows"), None);
+    }
+
+    #[test]
+    fn release_parses_from_the_api() {
+        let release: Release = serde_json::from_str(
+            r#"{
+                "tag_name": "v0.4.0",
+                "name": "v0.4.0",
+                "assets": [{
+                    "name": "SHA256SUMS",
+                    "browser_download_url": "https://example.com/SHA256SUMS",
+                    "size": 420
+                }]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(release.version(), "0.4.0");
+        assert_eq!(
+            release.asset_url("SHA256SUMS").unwrap(),
+            "https://example.com/SHA256SUMS"
+        );
+        let err = release.asset_url("bubble-bot-x.tar.gz").unwrap_err();
+        assert_eq!(err.to_string(), "release v0.4.0 has no bubble-bot-x.tar.gz");
+    }
+
+    #[test]
+    fn checksums_are_verified() {
+        let data = b"release";
+        let hash = format!("{:x}", Sha256::digest(data));
+        let sums = format!(
+            "{}  other.tar.gz\n{hash} *bubble-bot.tar.gz\n",
+            "0".repeat(64)
+        );
+        verify_checksum(data, &sums, "bubble-bot.tar.gz").unwrap();
+
+        let err = verify_checksum(b"tampered", &sums, "bubble-bot.tar.gz").unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"), "{err}");
+        let err = verify_checksum(data, &sums, "missing.tar.gz").unwrap_err();
+        assert!(
+            err.to_string().contains("no entry for missing.tar.gz"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn unpack_finds_the_binary() {
+        let src = tempfile::tempdir().unwrap();
+        std::fs::write(src.path().join("bubble-bot"), "#!/bin/sh\n").unwrap();
+        let archive = src.path().join("a.tar.gz");
+        let status = Command::new("tar")
+            .arg("czf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(src.path())
+            .arg("./bubble-bot")
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let dir = tempfile::tempdir().unwrap();
+        let binary = unpack(&std::fs::read(&archive).unwrap(), dir.path()).unwrap();
+        assert_eq!(binary, dir.path().join("bubble-bot"));
+        let mode = binary.metadata().unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+}
@@ -0,0 +1,250 @@
+use std::process::Stdio;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::config::{RestartPolicy, WorkerConfig};
+
+/// Pause before restarting an exited worker, so a crash loop doesn't spin.
+const RESTART_DELAY: Duration = Duration::from_secs(2);
+
+/// Directory in the dev container holding each worker's PID.
+const PID_DIR: &str = "/tmp/bubble-bot-workers";
+
+/// Runs `[[workers]]` in the dev container via `docker exec`, restarting them
+/// per their restart policy until `stop` is called. Output is printed to
+/// stderr with a `[name]` prefix per line.
+pub struct WorkerSupervisor {
+    container_id: String,
+    shutdown: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl WorkerSupervisor {
+    /// Starts every worker whose `depends_on_service` (if any) is among the
+    /// session's `services`.
+    pub fn start(container_id: &str, workers: &[WorkerConfig], services: &[&str]) -> Self {
+        let (shutdown, _) = watch::channel(false);
+        let tasks = active_workers(workers, services)
+            .into_iter()
+            .map(|(index, worker)| {
+                tokio::spawn(supervise(
+                    container_id.to_string(),
+                    index,
+                    worker.clone(),
+                    shutdown.subscribe(),
+                ))
+            })
+            .collect();
+
+        Self {
+            container_id: container_id.to_string(),
+            shutdown,
+            tasks,
+        }
+    }
+
+    /// Stops supervision and terminates the worker processes in the container.
+    pub async fn stop(self) {
+        if self.tasks.is_empty() {
+            return;
+        }
+
+        let _ = self.shutdown.send(true);
+        for task in self.tasks {
+            let _ = task.await;
+        }
+
+        // Killing the `docker exec` clients leaves the processes running in
+        // the container; signal them through their PID files
+        let script =
+            format!("kill -TERM $(cat {PID_DIR}/*.pid 2>/dev/null) 2>/dev/null; rm -rf {PID_DIR}");
+        let status = Command::new("docker")
+            .args(["exec", &self.container_id, "sh", "-c", &script])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .await;
+        match status {
+            Ok(_) => info!("workers stopped"),
+            Err(e) => warn!(error = %e, "failed to stop workers"),
+        }
+    }
+}
+
+/// Workers to run, with their index in the config. Workers depending on a
+/// service that isn't enabled are skipped.
+fn active_workers<'a>(
+    workers: &'a [WorkerConfig],
+    services: &[&str],
+) -> Vec<(usize, &'a WorkerConfig)> {
+    workers
+        .iter()
+        .enumerate()
+        .filter(|(_, worker)| match worker.depends_on_service.as_deref() {
+            Some(service) if !services.contains(&service) => {
+                warn!(
+                    worker = worker.display_name(),
+                    service, "skipping worker: service is not enabled"
+                );
+                false
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+fn should_restart(policy: RestartPolicy, success: bool) -> bool {
+    match policy {
+        RestartPolicy::Never => false,
+        RestartPolicy::OnFailure => !success,
+        RestartPolicy::Always => true,
+    }
+}
+
+/// Shell script that records its PID, then replaces itself with the worker
+/// command (passed as `$1`).
+fn worker_script(index: usize) -> String {
+    format!("mkdir -p {PID_DIR} && echo $$ > {PID_DIR}/{index}.pid && exec sh -c \"$1\"")
+}
+
+async fn supervise(
+    container_id: String,
+    index: usize,
+    worker: WorkerConfig,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let name = worker.display_name().to_string();
+    let script = worker_script(index);
+
+    loop {
+        let spawned = Command::new("docker")
+            .args([
+                "exec",
+                &container_id,
+                "sh",
+                "-c",
+                &script,
+                "sh",
+                &worker.cmd,
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+        let mut child = match spawned {
+            Ok(child) => child,
+            Err(e) => {
+                warn!(worker = %name, error = %e, "failed to start worker");
+                return;
+            }
+        };
+        info!(worker = %name, cmd = %worker.cmd, "worker started");
+
+        let readers: Vec<JoinHandle<()>> = [
+            child
+                .stdout
+                .take()
+                .map(|s| tokio::spawn(print_prefixed(name.clone(), s))),
+            child
+                .stderr
+                .take()
+                .map(|s| tokio::spawn(print_prefixed(name.clone(), s))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        let status = tokio::select! {
+            status = child.wait() => status,
+            _ = shutdown.changed() => {
+                let _ = child.kill().await;
+                return;
+            }
+        };
+        for reader in readers {
+            let _ = reader.await;
+        }
+
+        let success = status.as_ref().is_ok_and(|s| s.success());
+        let code = status.ok().and_then(|s| s.code());
+        if success {
+            info!(worker = %name, "worker exited");
+        } else {
+            warn!(worker = %name, ?code, "worker failed");
+        }
+
+        if !should_restart(worker.restart, success) {
+            return;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(RESTART_DELAY) => {}
+            _ = shutdown.changed() => return,
+        }
+        info!(worker = %name, "restarting worker");
+    }
+}
+
+/// Prints each line of `stream` to stderr as `[name] line`.
+async fn print_prefixed(name: String, stream: impl AsyncRead + Unpin) {
+    let mut lines = BufReader::new(stream).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        eprintln!("[{name}] {line}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worker(cmd: &str, depends_on_service: Option<&str>) -> WorkerConfig {
+        WorkerConfig {
+            cmd: cmd.to_string(),
+            depends_on_service: depends_on_service.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn workers_without_their_service_are_skipped() {
+        let workers = vec![
+            worker("php artisan queue:work", Some("redis")),
+            worker("npm run dev", None),
+            worker("php artisan horizon", Some("mysql")),
+        ];
+        let active: Vec<usize> = active_workers(&workers, &["mysql"])
+            .into_iter()
+            .map(|(index, _)| index)
+            .collect();
+        assert_eq!(active, vec![1, 2]);
+    }
+
+    #[test]
+    fn restart_policies() {
+        assert!(!should_restart(RestartPolicy::Never, false));
+        assert!(should_restart(RestartPolicy::OnFailure, false));
+        assert!(!should_restart(RestartPolicy::OnFailure, true));
+        assert!(should_restart(RestartPolicy::Always, true));
+    }
+
+    #[test]
+    fn worker_script_records_pid_and_execs_command() {
+        let script = worker_script(3);
+        assert!(script.contains("echo $$ > /tmp/bubble-bot-workers/3.pid"));
+        assert!(script.ends_with("exec sh -c \"$1\""));
+    }
+
+    #[test]
+    fn stop_without_workers_is_noop() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let supervisor = WorkerSupervisor::start("unused", &[], &[]);
+            supervisor.stop().await;
+        });
+    }
+}